@@ -0,0 +1,157 @@
+//! Timer Fuzz Target
+//!
+//! Drives the timer with an arbitrary sequence of register reads, writes
+//! and ticks, checking TIMA against a reference model of the delayed
+//! reload after every operation and that `TimerOverflow` is only raised
+//! when the reload from TMA happens.
+
+#![no_main]
+
+use gbemu::events::{EventQueue, HardwareEvent};
+use gbemu::timer::Timer;
+use libfuzzer_sys::arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+/// Timer register addresses, indexed by `kind % 4`
+const REGISTERS: [u16; 4] = [0xFF04, 0xFF05, 0xFF06, 0xFF07];
+/// Upper bound on cycles per tick operation, so inputs cannot hang
+const MAX_TICK_CYCLES: u32 = 512;
+/// T-cycles TIMA reads 0 after overflowing, and then spends reloading
+const RELOAD_CYCLES: u8 = 4;
+
+/// One fuzzer-chosen operation
+#[derive(Debug, Arbitrary)]
+struct TimerOp {
+    /// 0-3: write DIV/TIMA/TMA/TAC, 4-7: read them, anything else: tick
+    kind: u8,
+    /// Value for writes
+    value: u8,
+    /// T-cycles for ticks
+    cycles: u8,
+}
+
+/// Reference model of the reload after a TIMA overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// No overflow in progress
+    Idle,
+    /// TIMA overflowed and reads 0 until the count reaches 0
+    Delay(u8),
+    /// TIMA was just loaded from TMA and ignores writes
+    Reloading(u8),
+}
+
+/// Signal TIMA counts on for a given TAC and internal divider
+fn timer_input(tac: u8, div: u16) -> bool {
+    let bit = [9, 3, 5, 7][(tac & 0x03) as usize];
+    tac & 0x04 != 0 && (div >> bit) & 1 != 0
+}
+
+/// Apply a falling edge of the timer input to the expected TIMA
+fn clock(tima: u8, phase: Phase, falling: bool) -> (u8, Phase) {
+    if !falling {
+        return (tima, phase);
+    }
+    match tima.checked_add(1) {
+        Some(tima) => (tima, phase),
+        None => (0, Phase::Delay(RELOAD_CYCLES)),
+    }
+}
+
+/// Check the register invariants that hold between operations
+fn check_registers(timer: &Timer) {
+    let state = timer.inspect();
+    assert_eq!(timer.read(0xFF04), (timer.div_internal() >> 8) as u8);
+    assert_eq!(timer.read(0xFF05), state.tima);
+    assert_eq!(timer.read(0xFF07) & !0x07, 0);
+    assert_eq!(state.tac, timer.read(0xFF07));
+}
+
+/// Write a register and check TIMA against the model
+fn write_checked(timer: &mut Timer, phase: &mut Phase, address: u16, value: u8) {
+    let before = timer.inspect();
+    timer.write(address, value);
+    let after = timer.inspect();
+
+    let (mut tima, mut next) = (before.tima, *phase);
+    match address {
+        0xFF04 => {
+            assert_eq!(after.div_internal, 0);
+            let falling = timer_input(before.tac, before.div_internal);
+            (tima, next) = clock(tima, next, falling);
+        }
+        0xFF05 => match next {
+            Phase::Reloading(_) => {}
+            Phase::Delay(_) => (tima, next) = (value, Phase::Idle),
+            Phase::Idle => tima = value,
+        },
+        0xFF06 => {
+            assert_eq!(after.tma, value);
+            if let Phase::Reloading(_) = next {
+                tima = value;
+            }
+        }
+        _ => {
+            assert_eq!(after.tac, value & 0x07);
+            let falling = timer_input(before.tac, before.div_internal)
+                && !timer_input(after.tac, after.div_internal);
+            (tima, next) = clock(tima, next, falling);
+        }
+    }
+    assert_eq!(after.tima, tima);
+    *phase = next;
+}
+
+/// Tick once and check TIMA and the overflow event against the model
+fn tick_checked(timer: &mut Timer, events: &mut EventQueue, phase: &mut Phase) {
+    let before = timer.inspect();
+    timer.tick(events);
+    let after = timer.inspect();
+
+    let overflowed = events.pop() == Some(HardwareEvent::TimerOverflow);
+    assert!(events.is_empty());
+    assert_eq!(after.div_internal, before.div_internal.wrapping_add(1));
+
+    let mut tima = before.tima;
+    let next = match *phase {
+        Phase::Idle => Phase::Idle,
+        Phase::Delay(1) => {
+            assert_eq!(before.tima, 0);
+            tima = before.tma;
+            Phase::Reloading(RELOAD_CYCLES)
+        }
+        Phase::Delay(cycles) => Phase::Delay(cycles - 1),
+        Phase::Reloading(1) => Phase::Idle,
+        Phase::Reloading(cycles) => Phase::Reloading(cycles - 1),
+    };
+    assert_eq!(overflowed, *phase == Phase::Delay(1));
+
+    let falling = timer_input(before.tac, before.div_internal)
+        && !timer_input(after.tac, after.div_internal);
+    let (tima, next) = clock(tima, next, falling);
+    assert_eq!(after.tima, tima);
+    *phase = next;
+}
+
+fuzz_target!(|ops: Vec<TimerOp>| {
+    let mut timer = Timer::new();
+    let mut events = EventQueue::new();
+    let mut phase = Phase::Idle;
+
+    for op in ops {
+        match op.kind {
+            0..=3 => {
+                write_checked(&mut timer, &mut phase, REGISTERS[op.kind as usize], op.value);
+            }
+            4..=7 => {
+                let _ = timer.read(REGISTERS[(op.kind - 4) as usize]);
+            }
+            _ => {
+                for _ in 0..(op.cycles as u32).min(MAX_TICK_CYCLES) {
+                    tick_checked(&mut timer, &mut events, &mut phase);
+                }
+            }
+        }
+        check_registers(&timer);
+    }
+});
@@ -0,0 +1,35 @@
+#!/bin/sh
+# Build the WebAssembly package into web/pkg.
+#
+#   scripts/build-wasm.sh          # release build
+#   scripts/build-wasm.sh --dev    # unoptimized build
+#
+# The library is built as a cdylib only here (`cargo rustc --crate-type`),
+# so the native and no_std builds keep a plain rlib. Requires the
+# wasm32-unknown-unknown target and wasm-bindgen-cli.
+#
+# Serve web/ over HTTP (e.g. `python3 -m http.server -d web`) to try it out.
+set -e
+
+cd "$(dirname "$0")/.."
+
+PROFILE=release
+CARGO_PROFILE=--release
+if [ "$1" = "--dev" ]; then
+    PROFILE=debug
+    CARGO_PROFILE=
+fi
+
+cargo rustc --lib $CARGO_PROFILE --target wasm32-unknown-unknown \
+    --no-default-features --features wasm --crate-type cdylib
+wasm-bindgen --target web --out-dir web/pkg --out-name gbemu \
+    "target/wasm32-unknown-unknown/$PROFILE/gbemu.wasm"
+
+# Keep the download small enough for a web page
+MAX_BYTES=2097152
+size=$(wc -c < web/pkg/gbemu_bg.wasm)
+echo "gbemu_bg.wasm: $size bytes"
+if [ "$PROFILE" = "release" ] && [ "$size" -gt "$MAX_BYTES" ]; then
+    echo "wasm binary exceeds 2 MB" >&2
+    exit 1
+fi
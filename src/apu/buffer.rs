@@ -0,0 +1,172 @@
+//! APU Sample Buffer
+//!
+//! This module implements the ring buffer between the APU and the host's
+//! audio output. The APU pushes interleaved stereo samples as it runs and
+//! the host pulls them at its own pace; when the host falls behind, the
+//! oldest frames are overwritten so the newest audio is always kept.
+
+use alloc::vec::Vec;
+
+/// Ring buffer of interleaved stereo i16 samples
+///
+/// Queued samples stay contiguous so they can be borrowed as one slice;
+/// consumed space at the front is reclaimed once it reaches the capacity.
+#[derive(Debug, Clone, Default)]
+pub struct SampleBuffer {
+    /// Consumed samples followed by the queued ones, oldest first
+    samples: Vec<i16>,
+    /// Index of the oldest queued sample
+    start: usize,
+    /// Maximum queued samples (always a whole number of frames)
+    capacity: usize,
+    /// Samples handed out by the last `drain`
+    drained: Vec<i16>,
+}
+
+impl SampleBuffer {
+    /// Create a buffer holding up to `capacity` samples
+    ///
+    /// The capacity is rounded down to whole stereo frames, with a minimum
+    /// of one frame.
+    pub fn new(capacity: usize) -> Self {
+        let mut buffer = Self::default();
+        buffer.set_capacity(capacity);
+        buffer
+    }
+
+    /// Maximum number of queued samples
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the capacity, dropping the oldest frames if it shrinks below
+    /// the queued amount
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = (capacity & !1).max(2);
+        if self.len() > self.capacity {
+            self.consume(self.len() - self.capacity);
+        }
+    }
+
+    /// Number of queued samples
+    pub fn len(&self) -> usize {
+        self.samples.len() - self.start
+    }
+
+    /// Whether no samples are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Queue one stereo frame, overwriting the oldest frame when full
+    pub fn push(&mut self, left: i16, right: i16) {
+        if self.len() + 2 > self.capacity {
+            self.consume(2);
+        }
+        self.samples.push(left);
+        self.samples.push(right);
+    }
+
+    /// Move up to `out.len()` of the oldest samples into `out`
+    ///
+    /// Returns the number of samples written.
+    pub fn read(&mut self, out: &mut [i16]) -> usize {
+        let count = out.len().min(self.len());
+        out[..count].copy_from_slice(&self.samples[self.start..self.start + count]);
+        self.consume(count);
+        count
+    }
+
+    /// Queued samples without consuming them
+    pub fn pending(&self) -> &[i16] {
+        &self.samples[self.start..]
+    }
+
+    /// Take every queued sample
+    pub fn drain(&mut self) -> &[i16] {
+        self.drained.clear();
+        self.drained.extend_from_slice(&self.samples[self.start..]);
+        self.clear();
+        &self.drained
+    }
+
+    /// Drop every queued sample
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.start = 0;
+    }
+
+    /// Drop the `count` oldest samples, reclaiming consumed space once it
+    /// reaches the capacity
+    fn consume(&mut self, count: usize) {
+        self.start += count;
+        if self.start == self.samples.len() {
+            self.clear();
+        } else if self.start >= self.capacity {
+            self.samples.drain(..self.start);
+            self.start = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_in_chunks() {
+        let mut buffer = SampleBuffer::new(16);
+        for i in 0..4 {
+            buffer.push(i, -i);
+        }
+
+        let mut out = [0; 3];
+        assert_eq!(buffer.read(&mut out), 3);
+        assert_eq!(out, [0, 0, 1]);
+        assert_eq!(buffer.len(), 5);
+
+        let mut out = [0; 8];
+        assert_eq!(buffer.read(&mut out), 5);
+        assert_eq!(out[..5], [-1, 2, -2, 3, -3]);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.read(&mut out), 0);
+    }
+
+    #[test]
+    fn test_overflow_overwrites_oldest_frames() {
+        let mut buffer = SampleBuffer::new(6);
+        for i in 0..5 {
+            buffer.push(i, 100 + i);
+        }
+
+        assert_eq!(buffer.len(), 6);
+        assert_eq!(buffer.pending(), [2, 102, 3, 103, 4, 104]);
+        assert_eq!(buffer.drain(), [2, 102, 3, 103, 4, 104]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_set_capacity() {
+        let mut buffer = SampleBuffer::new(7);
+        assert_eq!(buffer.capacity(), 6);
+        for i in 0..3 {
+            buffer.push(i, i);
+        }
+
+        buffer.set_capacity(3);
+        assert_eq!(buffer.capacity(), 2);
+        assert_eq!(buffer.pending(), [2, 2]);
+        assert_eq!(SampleBuffer::new(0).capacity(), 2);
+    }
+
+    #[test]
+    fn test_storage_stays_bounded() {
+        let mut buffer = SampleBuffer::new(8);
+        for i in 0..100 {
+            buffer.push(i, -i);
+        }
+
+        assert_eq!(buffer.pending(), [96, -96, 97, -97, 98, -98, 99, -99]);
+        assert!(buffer.samples.len() < 2 * buffer.capacity());
+    }
+}
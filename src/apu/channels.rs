@@ -3,6 +3,7 @@
 //! This module implements the 4 audio channels of the Game Boy APU.
 
 use crate::common::Byte;
+use super::HardwareModel;
 
 /// Duty cycle patterns (8 steps each)
 const DUTY_PATTERNS: [[u8; 8]; 4] = [
@@ -12,8 +13,40 @@ const DUTY_PATTERNS: [[u8; 8]; 4] = [
     [0, 1, 1, 1, 1, 1, 1, 0], // 75%
 ];
 
+/// Convert a channel's 4-bit digital output to its DAC's analog level
+///
+/// Digital 0 maps to -1.0 and 15 to 1.0. A powered-off DAC outputs 0.0.
+pub fn dac_output(digital: u8, dac_enabled: bool) -> f32 {
+    if !dac_enabled {
+        return 0.0;
+    }
+    (digital & 0x0F) as f32 / 7.5 - 1.0
+}
+
+/// Snapshot of one channel for debug visualisation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelState {
+    /// Channel is playing (NR52 status bit)
+    pub enabled: bool,
+    /// DAC is powered
+    pub dac_enabled: bool,
+    /// Current envelope volume (0-15); for channel 3 the NR32 output level code (0-3)
+    pub volume: u8,
+    /// 11-bit period value; for channel 4 the NR43 polynomial counter setting
+    pub frequency: u16,
+    /// Duty cycle index (square channels only)
+    pub duty: Option<u8>,
+    /// Position in wave RAM, 0-31 (channel 3 only)
+    pub wave_position: Option<u8>,
+    /// Envelope ticks until the next volume step (0 for channel 3)
+    pub envelope_timer: u8,
+    /// Remaining length counter
+    pub length_counter: u16,
+}
+
 /// Channel 1 - Square wave with sweep
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Channel1 {
     pub enabled: bool,
     pub dac_enabled: bool,
@@ -139,8 +172,13 @@ impl Channel1 {
         new_freq
     }
 
+    /// Status reported in NR52: triggered, not yet stopped and DAC on
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.dac_enabled
+    }
+
     pub fn output(&self) -> u8 {
-        if !self.enabled || !self.dac_enabled {
+        if !self.is_active() {
             return 0;
         }
         DUTY_PATTERNS[self.duty as usize][self.duty_position as usize] * self.volume
@@ -195,11 +233,25 @@ impl Channel1 {
         self.frequency = (self.frequency & 0xFF) | ((value as u16 & 0x07) << 8);
         if (value & 0x80) != 0 { self.trigger(); }
     }
+
+    pub fn state(&self) -> ChannelState {
+        ChannelState {
+            enabled: self.enabled,
+            dac_enabled: self.dac_enabled,
+            volume: self.volume,
+            frequency: self.frequency,
+            duty: Some(self.duty),
+            wave_position: None,
+            envelope_timer: self.envelope_timer,
+            length_counter: self.length_counter,
+        }
+    }
 }
 
 
 /// Channel 2 - Square wave (no sweep)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Channel2 {
     pub enabled: bool,
     pub dac_enabled: bool,
@@ -254,8 +306,13 @@ impl Channel2 {
         }
     }
 
+    /// Status reported in NR52: triggered, not yet stopped and DAC on
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.dac_enabled
+    }
+
     pub fn output(&self) -> u8 {
-        if !self.enabled || !self.dac_enabled { return 0; }
+        if !self.is_active() { return 0; }
         DUTY_PATTERNS[self.duty as usize][self.duty_position as usize] * self.volume
     }
 
@@ -289,11 +346,25 @@ impl Channel2 {
         self.frequency = (self.frequency & 0xFF) | ((value as u16 & 0x07) << 8);
         if (value & 0x80) != 0 { self.trigger(); }
     }
+
+    pub fn state(&self) -> ChannelState {
+        ChannelState {
+            enabled: self.enabled,
+            dac_enabled: self.dac_enabled,
+            volume: self.volume,
+            frequency: self.frequency,
+            duty: Some(self.duty),
+            wave_position: None,
+            envelope_timer: self.envelope_timer,
+            length_counter: self.length_counter,
+        }
+    }
 }
 
 
 /// Channel 3 - Wave
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Channel3 {
     pub enabled: bool,
     pub dac_enabled: bool,
@@ -333,15 +404,28 @@ impl Channel3 {
         }
     }
 
+    /// Status reported in NR52: triggered, not yet stopped and DAC on
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.dac_enabled
+    }
+
     pub fn output(&self) -> u8 {
-        if !self.enabled || !self.dac_enabled { return 0; }
+        if !self.is_active() { return 0; }
         let sample = self.wave_ram[(self.wave_position / 2) as usize];
         let sample = if self.wave_position & 1 == 0 { sample >> 4 } else { sample & 0x0F };
         let shift = match self.volume_code { 0 => 4, 1 => 0, 2 => 1, 3 => 2, _ => 4 };
         sample >> shift
     }
 
-    fn trigger(&mut self) {
+    fn trigger(&mut self, model: HardwareModel) {
+        // DMG bug: retriggering while active corrupts the first byte of wave RAM
+        if self.enabled && model == HardwareModel::Dmg {
+            if self.wave_position < 8 {
+                self.wave_ram[0] = self.wave_ram[(self.wave_position / 2) as usize];
+            } else {
+                self.wave_ram[0] = 0xFF;
+            }
+        }
         self.enabled = self.dac_enabled;
         if self.length_counter == 0 { self.length_counter = 256; }
         self.timer = (2048 - self.frequency) * 2;
@@ -358,18 +442,32 @@ impl Channel3 {
     pub fn write_nr32(&mut self, value: Byte) { self.volume_code = (value >> 5) & 0x03; }
     pub fn write_nr33(&mut self, value: Byte) { self.frequency = (self.frequency & 0x700) | value as u16; }
     pub fn read_nr34(&self) -> Byte { (if self.length_enabled { 0x40 } else { 0 }) | 0xBF }
-    pub fn write_nr34(&mut self, value: Byte) {
+    pub fn write_nr34(&mut self, value: Byte, model: HardwareModel) {
         self.length_enabled = (value & 0x40) != 0;
         self.frequency = (self.frequency & 0xFF) | ((value as u16 & 0x07) << 8);
-        if (value & 0x80) != 0 { self.trigger(); }
+        if (value & 0x80) != 0 { self.trigger(model); }
     }
     pub fn read_wave_ram(&self, address: u16) -> Byte { self.wave_ram[(address - 0xFF30) as usize] }
     pub fn write_wave_ram(&mut self, address: u16, value: Byte) { self.wave_ram[(address - 0xFF30) as usize] = value; }
+
+    pub fn state(&self) -> ChannelState {
+        ChannelState {
+            enabled: self.enabled,
+            dac_enabled: self.dac_enabled,
+            volume: self.volume_code,
+            frequency: self.frequency,
+            duty: None,
+            wave_position: Some(self.wave_position),
+            envelope_timer: 0,
+            length_counter: self.length_counter,
+        }
+    }
 }
 
 
 /// Channel 4 - Noise
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Channel4 {
     pub enabled: bool,
     pub dac_enabled: bool,
@@ -436,8 +534,13 @@ impl Channel4 {
         }
     }
 
+    /// Status reported in NR52: triggered, not yet stopped and DAC on
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.dac_enabled
+    }
+
     pub fn output(&self) -> u8 {
-        if !self.enabled || !self.dac_enabled { return 0; }
+        if !self.is_active() { return 0; }
         if (self.lfsr & 1) == 0 { self.volume } else { 0 }
     }
 
@@ -474,6 +577,19 @@ impl Channel4 {
         self.length_enabled = (value & 0x40) != 0;
         if (value & 0x80) != 0 { self.trigger(); }
     }
+
+    pub fn state(&self) -> ChannelState {
+        ChannelState {
+            enabled: self.enabled,
+            dac_enabled: self.dac_enabled,
+            volume: self.volume,
+            frequency: self.read_nr43() as u16,
+            duty: None,
+            wave_position: None,
+            envelope_timer: self.envelope_timer,
+            length_counter: self.length_counter,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -499,6 +615,75 @@ mod tests {
         assert!(!ch.dac_enabled);
     }
 
+    fn active_channel3(wave_position: u8) -> Channel3 {
+        let mut ch = Channel3::new();
+        ch.write_nr30(0x80);
+        for (i, byte) in ch.wave_ram.iter_mut().enumerate() {
+            *byte = 0x10 + i as u8;
+        }
+        ch.enabled = true;
+        ch.wave_position = wave_position;
+        ch
+    }
+
+    #[test]
+    fn test_channel3_retrigger_corrupts_wave_ram_dmg() {
+        // Reading byte 2 (nibble 5): byte 2 is copied to byte 0
+        let mut ch = active_channel3(5);
+        ch.write_nr34(0x80, HardwareModel::Dmg);
+        assert_eq!(ch.wave_ram[0], 0x12);
+        assert_eq!(ch.read_wave_ram(0xFF30), 0x12);
+        assert_eq!(ch.wave_ram[2], 0x12);
+        assert_eq!(ch.wave_position, 0);
+
+        // Past the first 4 bytes: byte 0 becomes 0xFF
+        let mut ch = active_channel3(20);
+        ch.write_nr34(0x80, HardwareModel::Dmg);
+        assert_eq!(ch.wave_ram[0], 0xFF);
+        assert_eq!(ch.wave_ram[1], 0x11);
+        assert_eq!(ch.wave_ram[10], 0x1A);
+    }
+
+    #[test]
+    fn test_channel3_retrigger_no_corruption() {
+        // CGB doesn't have the bug
+        let mut ch = active_channel3(20);
+        ch.write_nr34(0x80, HardwareModel::Cgb);
+        assert_eq!(ch.wave_ram[0], 0x10);
+
+        // Triggering an inactive channel leaves wave RAM intact
+        let mut ch = active_channel3(20);
+        ch.enabled = false;
+        ch.write_nr34(0x80, HardwareModel::Dmg);
+        assert_eq!(ch.wave_ram[0], 0x10);
+    }
+
+    #[test]
+    fn test_dac_output() {
+        assert_eq!(dac_output(0, true), -1.0);
+        assert_eq!(dac_output(15, true), 1.0);
+        assert!((dac_output(7, true) + 1.0 / 15.0).abs() < 1e-6);
+        assert_eq!(dac_output(15, false), 0.0);
+    }
+
+    #[test]
+    fn test_channel3_volume_codes() {
+        let mut ch = Channel3::new();
+        ch.write_nr30(0x80);
+        ch.wave_ram[0] = 0xF7;
+        ch.wave_ram[1] = 0x31;
+        ch.enabled = true;
+
+        // Nibbles 0xF, 0x7, 0x3, 0x1 at 100%, 50%, 25% and muted
+        for (code, expected) in [(1, [15, 7, 3, 1]), (2, [7, 3, 1, 0]), (3, [3, 1, 0, 0]), (0, [0; 4])] {
+            ch.write_nr32(code << 5);
+            for (position, &sample) in expected.iter().enumerate() {
+                ch.wave_position = position as u8;
+                assert_eq!(ch.output(), sample, "code {} position {}", code, position);
+            }
+        }
+    }
+
     #[test]
     fn test_channel4_lfsr() {
         let mut ch = Channel4::new();
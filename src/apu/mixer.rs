@@ -1,6 +1,212 @@
 //! APU Mixer
 //!
-//! This module handles audio mixing and output.
+//! This module post-processes the stereo samples produced by the APU:
+//! DC blocking like the hardware's output capacitors, downmixing for mono
+//! backends and crossfeed for headphone listening.
 
-// Mixer functionality is implemented in mod.rs
-// This file is kept for future expansion (e.g., high-pass filter, DC offset removal)
+/// Channel layout written to the audio buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioOutput {
+    /// Left and right as panned by NR51
+    #[default]
+    Stereo,
+    /// Average of both channels on left and right
+    Mono,
+    /// Left channel on both sides
+    MonoLeft,
+    /// Right channel on both sides
+    MonoRight,
+}
+
+/// Raise `base` to an integer power (no_std has no `f32::powi`)
+fn powi(mut base: f32, mut exponent: u32) -> f32 {
+    let mut result = 1.0;
+    while exponent > 0 {
+        if exponent & 1 != 0 {
+            result *= base;
+        }
+        base *= base;
+        exponent >>= 1;
+    }
+    result
+}
+
+/// One-pole DC-blocking high-pass filter
+///
+/// Models the capacitor between the APU mixer and the amplifier on each
+/// side. A constant input (e.g. a DAC switched on with no waveform)
+/// decays toward zero instead of leaving a bias in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct HighPassFilter {
+    /// Capacitor charge (left, right)
+    capacitor: [f32; 2],
+}
+
+impl HighPassFilter {
+    /// Create a filter with discharged capacitors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capacitor charge (left, right)
+    pub fn capacitor(&self) -> [f32; 2] {
+        self.capacitor
+    }
+
+    /// Discharge both capacitors
+    pub fn reset(&mut self) {
+        self.capacitor = [0.0; 2];
+    }
+
+    /// Share of the charge kept per output sample
+    ///
+    /// `charge_per_cycle` is the share kept per T-cycle, so the factor is
+    /// `charge_per_cycle ^ cycles_per_sample`.
+    pub fn charge_factor(charge_per_cycle: f32, cycles_per_sample: u32) -> f32 {
+        powi(charge_per_cycle, cycles_per_sample)
+    }
+
+    /// Filter one stereo sample, keeping `charge` of the capacitor charge
+    pub fn process(&mut self, left: f32, right: f32, charge: f32) -> (f32, f32) {
+        let mut filter = |side: usize, input: f32| {
+            let output = input - self.capacitor[side];
+            self.capacitor[side] = input - output * charge;
+            output
+        };
+        (filter(0, left), filter(1, right))
+    }
+}
+
+/// Stereo sample post-processor
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Mixer {
+    /// Output channel layout
+    output_mode: AudioOutput,
+    /// Share of each channel blended into the other (0.0-1.0)
+    crossfeed: f32,
+}
+
+impl Mixer {
+    /// Create a stereo mixer without crossfeed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current output channel layout
+    pub fn output_mode(&self) -> AudioOutput {
+        self.output_mode
+    }
+
+    /// Select the output channel layout
+    pub fn set_output_mode(&mut self, mode: AudioOutput) {
+        self.output_mode = mode;
+    }
+
+    /// Current crossfeed amount
+    pub fn crossfeed(&self) -> f32 {
+        self.crossfeed
+    }
+
+    /// Blend `amount` of each channel into the other (Bauer crossfeed)
+    ///
+    /// Hard-panned Game Boy audio is tiring on headphones; feeding part of
+    /// the opposite channel in mimics listening on speakers. The result is
+    /// normalised so the overall level is unchanged. `amount` is clamped to
+    /// 0.0-1.0 (0.0 disables crossfeed, 1.0 is mono); non-finite values are
+    /// ignored.
+    pub fn apply_crossfeed(&mut self, amount: f32) {
+        if amount.is_finite() {
+            self.crossfeed = amount.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Process one stereo sample
+    pub fn mix(&self, left: i16, right: i16) -> (i16, i16) {
+        let (left, right) = if self.crossfeed > 0.0 {
+            let (l, r) = (left as f32, right as f32);
+            let scale = 1.0 / (1.0 + self.crossfeed);
+            (
+                ((l + self.crossfeed * r) * scale) as i16,
+                ((r + self.crossfeed * l) * scale) as i16,
+            )
+        } else {
+            (left, right)
+        };
+
+        match self.output_mode {
+            AudioOutput::Stereo => (left, right),
+            AudioOutput::Mono => {
+                let mono = ((left as i32 + right as i32) / 2) as i16;
+                (mono, mono)
+            }
+            AudioOutput::MonoLeft => (left, left),
+            AudioOutput::MonoRight => (right, right),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stereo_passthrough() {
+        let mixer = Mixer::new();
+        assert_eq!(mixer.mix(1000, -2000), (1000, -2000));
+    }
+
+    #[test]
+    fn test_mono_modes() {
+        let mut mixer = Mixer::new();
+        mixer.set_output_mode(AudioOutput::Mono);
+        assert_eq!(mixer.mix(1000, -2000), (-500, -500));
+        assert_eq!(mixer.mix(i16::MAX, i16::MAX), (i16::MAX, i16::MAX));
+
+        mixer.set_output_mode(AudioOutput::MonoLeft);
+        assert_eq!(mixer.mix(1000, -2000), (1000, 1000));
+
+        mixer.set_output_mode(AudioOutput::MonoRight);
+        assert_eq!(mixer.mix(1000, -2000), (-2000, -2000));
+    }
+
+    #[test]
+    fn test_high_pass_decays_constant_input() {
+        let mut filter = HighPassFilter::new();
+        // DMG coefficient at 44100 Hz
+        let charge = HighPassFilter::charge_factor(0.999958, 4194304 / 44100);
+        assert!((charge - 0.996018).abs() < 1e-5);
+
+        let (left, right) = filter.process(1.0, -0.5, charge);
+        assert_eq!((left, right), (1.0, -0.5));
+        let mut previous = left;
+        for _ in 0..4000 {
+            let (left, _) = filter.process(1.0, -0.5, charge);
+            assert!(left <= previous);
+            previous = left;
+        }
+        assert!(previous.abs() < 1e-5);
+        assert!((filter.capacitor()[1] + 0.5).abs() < 1e-4);
+
+        filter.reset();
+        assert_eq!(filter.capacitor(), [0.0; 2]);
+    }
+
+    #[test]
+    fn test_crossfeed() {
+        let mut mixer = Mixer::new();
+        mixer.apply_crossfeed(0.5);
+        // A hard-left sample leaks a third into the right channel
+        assert_eq!(mixer.mix(3000, 0), (2000, 1000));
+        // Centred audio keeps its level
+        assert_eq!(mixer.mix(3000, 3000), (3000, 3000));
+
+        mixer.apply_crossfeed(f32::NAN);
+        assert_eq!(mixer.crossfeed(), 0.5);
+        mixer.apply_crossfeed(4.0);
+        assert_eq!(mixer.crossfeed(), 1.0);
+        assert_eq!(mixer.mix(3000, -1000), (1000, 1000));
+        mixer.apply_crossfeed(0.0);
+        assert_eq!(mixer.mix(3000, -1000), (3000, -1000));
+    }
+}
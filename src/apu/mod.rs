@@ -7,21 +7,49 @@
 //! - Channel 3: Wave
 //! - Channel 4: Noise
 
+pub mod buffer;
 pub mod channels;
 pub mod mixer;
 
 use crate::common::Byte;
-use channels::{Channel1, Channel2, Channel3, Channel4};
+use buffer::SampleBuffer;
+use channels::{dac_output, Channel1, Channel2, Channel3, Channel4, ChannelState};
+use mixer::{HighPassFilter, Mixer};
 
 /// Audio sample rate
 pub const SAMPLE_RATE: u32 = 44100;
 /// CPU clock frequency
 pub const CPU_CLOCK: u32 = 4194304;
-/// Samples per frame sequencer tick (512 Hz)
+/// T-cycles per internal mix sample (the APU's 1.048576 MHz rate)
+const MIX_INTERVAL: u8 = 4;
+/// T-cycles per frame sequencer tick (512 Hz)
 pub const FRAME_SEQUENCER_RATE: u32 = 8192;
+/// Internal DIV counter bit whose falling edge clocks the frame sequencer
+/// (DIV register bit 4, falling every `FRAME_SEQUENCER_RATE` T-cycles)
+const FRAME_SEQUENCER_DIV_BIT: u16 = 12;
+/// Default audio buffer capacity (interleaved stereo i16 samples)
+pub const AUDIO_BUFFER_SIZE: usize = 4096;
+/// Sample level of one channel at full DAC swing and master volume 7
+const CHANNEL_AMPLITUDE: f32 = 8192.0;
+/// Share of the output capacitor's charge kept per T-cycle (DMG)
+const HPF_CHARGE_DMG: f32 = 0.999958;
+/// Share of the output capacitor's charge kept per T-cycle (CGB)
+const HPF_CHARGE_CGB: f32 = 0.998943;
+
+/// Hardware revision, for model-specific APU quirks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub enum HardwareModel {
+    /// Original Game Boy
+    #[default]
+    Dmg,
+    /// Game Boy Color
+    Cgb,
+}
 
 /// Audio Processing Unit
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Apu {
     /// Channel 1 (square wave with sweep)
     pub ch1: Channel1,
@@ -37,29 +65,53 @@ pub struct Apu {
     pub nr51: Byte,
     /// NR52 - Sound on/off
     pub nr52: Byte,
-    /// Frame sequencer timer
-    frame_sequencer_timer: u32,
+    /// Internal DIV counter seen on the previous tick
+    prev_div: u16,
     /// Frame sequencer step (0-7)
     frame_sequencer_step: u8,
     /// Sample timer for audio output
     sample_timer: u32,
-    /// Audio buffer
-    pub audio_buffer: Vec<i16>,
-    /// Buffer write position
-    buffer_pos: usize,
+    /// T-cycles since the last internal mix sample
+    mix_phase: u8,
+    /// Mixed level (left, right) at the previous internal sample
+    prev_level: [f32; 2],
+    /// Mixed level (left, right) at the latest internal sample
+    level: [f32; 2],
+    /// DC-blocking filter on the mixed output
+    pub high_pass: HighPassFilter,
+    /// Generated samples waiting for the host
+    #[cfg_attr(feature = "save-state", serde(skip, default = "empty_sample_buffer"))]
+    samples: SampleBuffer,
     /// APU enabled
     enabled: bool,
+    /// Emulated hardware revision
+    pub hardware_model: HardwareModel,
+    /// Output samples per second (per channel)
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    sample_rate: u32,
+    /// Mono downmix and crossfeed applied to each sample
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    pub mixer: Mixer,
+    /// Channels muted at the output stage (bit 0 = channel 1)
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    muted_channels: u8,
+}
+
+/// Empty audio buffer for APUs restored from a save state
+#[cfg(feature = "save-state")]
+fn empty_sample_buffer() -> SampleBuffer {
+    SampleBuffer::new(AUDIO_BUFFER_SIZE)
 }
 
 impl Default for Apu {
     fn default() -> Self {
-        Self::new()
+        Self::new(SAMPLE_RATE)
     }
 }
 
 impl Apu {
-    /// Create a new APU
-    pub fn new() -> Self {
+    /// Create a new APU producing `sample_rate` stereo frames per second
+    pub fn new(sample_rate: u32) -> Self {
         Self {
             ch1: Channel1::new(),
             ch2: Channel2::new(),
@@ -68,15 +120,38 @@ impl Apu {
             nr50: 0x77,
             nr51: 0xF3,
             nr52: 0xF1,
-            frame_sequencer_timer: 0,
+            prev_div: 0,
             frame_sequencer_step: 0,
             sample_timer: 0,
-            audio_buffer: vec![0; 4096],
-            buffer_pos: 0,
+            mix_phase: 0,
+            prev_level: [0.0; 2],
+            level: [0.0; 2],
+            high_pass: HighPassFilter::new(),
+            samples: SampleBuffer::new(AUDIO_BUFFER_SIZE),
             enabled: true,
+            hardware_model: HardwareModel::Dmg,
+            sample_rate,
+            mixer: Mixer::new(),
+            muted_channels: 0,
         }
     }
 
+    /// Replace the sound state with one from a save state
+    ///
+    /// The output sample rate, mixer, channel mutes and buffer capacity
+    /// belong to the host and are kept; samples already buffered are dropped.
+    #[cfg(feature = "save-state")]
+    pub(crate) fn load_state(&mut self, saved: Apu) {
+        let (sample_rate, mixer, muted_channels) = (self.sample_rate, self.mixer, self.muted_channels);
+        let mut samples = core::mem::take(&mut self.samples);
+        samples.clear();
+        *self = saved;
+        self.sample_rate = sample_rate;
+        self.mixer = mixer;
+        self.muted_channels = muted_channels;
+        self.samples = samples;
+    }
+
     /// Initialize APU
     pub fn init(&mut self) {
         self.ch1 = Channel1::new();
@@ -86,23 +161,67 @@ impl Apu {
         self.nr50 = 0x77;
         self.nr51 = 0xF3;
         self.nr52 = 0xF1;
-        self.frame_sequencer_timer = 0;
+        self.prev_div = 0;
         self.frame_sequencer_step = 0;
         self.sample_timer = 0;
-        self.buffer_pos = 0;
+        self.mix_phase = 0;
+        self.prev_level = [0.0; 2];
+        self.level = [0.0; 2];
+        self.high_pass.reset();
+        self.samples.clear();
         self.enabled = true;
     }
 
+    /// Output samples per second
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Change the output sample rate (defaults to `SAMPLE_RATE`)
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_rate = rate;
+        self.sample_timer = 0;
+    }
+
+    /// Whether channel `channel` (1-4) is heard in the mixed output
+    pub fn channel_enabled(&self, channel: u8) -> bool {
+        match channel {
+            1..=4 => self.muted_channels & (1 << (channel - 1)) == 0,
+            _ => false,
+        }
+    }
+
+    /// Mute or unmute channel `channel` (1-4) in the mixed output
+    ///
+    /// A muted channel keeps running (length, envelope and NR52 status are
+    /// unaffected) but contributes silence to the mix. Other channel
+    /// numbers are ignored.
+    pub fn set_channel_enabled(&mut self, channel: u8, enabled: bool) {
+        if let 1..=4 = channel {
+            let bit = 1 << (channel - 1);
+            if enabled {
+                self.muted_channels &= !bit;
+            } else {
+                self.muted_channels |= bit;
+            }
+        }
+    }
+
     /// Tick APU by one T-cycle
-    pub fn tick(&mut self) {
+    ///
+    /// `div` is the timer's internal 16-bit divider after this cycle; the
+    /// frame sequencer advances on falling edges of its bit 12, so a DIV
+    /// reset can clock it early.
+    pub fn tick(&mut self, div: u16) {
+        let falling_edge = (self.prev_div & !div) >> FRAME_SEQUENCER_DIV_BIT & 1 != 0;
+        self.prev_div = div;
+
         if !self.enabled {
             return;
         }
 
         // Tick frame sequencer
-        self.frame_sequencer_timer += 1;
-        if self.frame_sequencer_timer >= FRAME_SEQUENCER_RATE {
-            self.frame_sequencer_timer = 0;
+        if falling_edge {
             self.tick_frame_sequencer();
         }
 
@@ -112,8 +231,15 @@ impl Apu {
         self.ch3.tick();
         self.ch4.tick();
 
+        // Sample the mix at the internal rate
+        self.mix_phase = (self.mix_phase + 1) % MIX_INTERVAL;
+        if self.mix_phase == 0 {
+            self.prev_level = self.level;
+            self.level = self.mix_levels();
+        }
+
         // Generate sample
-        self.sample_timer += SAMPLE_RATE;
+        self.sample_timer += self.sample_rate;
         if self.sample_timer >= CPU_CLOCK {
             self.sample_timer -= CPU_CLOCK;
             self.generate_sample();
@@ -165,55 +291,121 @@ impl Apu {
         self.frame_sequencer_step = (self.frame_sequencer_step + 1) & 7;
     }
 
-    /// Generate audio sample
-    fn generate_sample(&mut self) {
-        if self.buffer_pos >= self.audio_buffer.len() {
-            return;
-        }
+    /// Mixed analog level (left, right) of the unmuted channels
+    ///
+    /// NR51 panning and NR50 master volume are applied; the result lies in
+    /// -4.0..=4.0 with every channel at full swing and master volume 7.
+    fn mix_levels(&self) -> [f32; 2] {
+        let mut left = 0.0;
+        let mut right = 0.0;
 
-        let mut left: i32 = 0;
-        let mut right: i32 = 0;
-
-        // Get channel outputs
-        let ch1_out = self.ch1.output() as i32;
-        let ch2_out = self.ch2.output() as i32;
-        let ch3_out = self.ch3.output() as i32;
-        let ch4_out = self.ch4.output() as i32;
-
-        // Mix channels based on NR51 panning
-        if self.nr51 & 0x10 != 0 { left += ch1_out; }
-        if self.nr51 & 0x20 != 0 { left += ch2_out; }
-        if self.nr51 & 0x40 != 0 { left += ch3_out; }
-        if self.nr51 & 0x80 != 0 { left += ch4_out; }
-        if self.nr51 & 0x01 != 0 { right += ch1_out; }
-        if self.nr51 & 0x02 != 0 { right += ch2_out; }
-        if self.nr51 & 0x04 != 0 { right += ch3_out; }
-        if self.nr51 & 0x08 != 0 { right += ch4_out; }
+        // Convert channel outputs to analog levels (-1.0 to 1.0)
+        let analog = [
+            dac_output(self.ch1.output(), self.ch1.dac_enabled),
+            dac_output(self.ch2.output(), self.ch2.dac_enabled),
+            dac_output(self.ch3.output(), self.ch3.dac_enabled),
+            dac_output(self.ch4.output(), self.ch4.dac_enabled),
+        ];
+
+        // Mix unmuted channels based on NR51 panning
+        for (i, &level) in analog.iter().enumerate() {
+            if self.muted_channels & (1 << i) != 0 {
+                continue;
+            }
+            if self.nr51 & (0x10 << i) != 0 { left += level; }
+            if self.nr51 & (0x01 << i) != 0 { right += level; }
+        }
 
         // Apply master volume
-        let left_vol = ((self.nr50 >> 4) & 0x07) as i32 + 1;
-        let right_vol = (self.nr50 & 0x07) as i32 + 1;
+        left *= ((self.nr50 >> 4) & 0x07) as f32 + 1.0;
+        right *= (self.nr50 & 0x07) as f32 + 1.0;
+        [left / 8.0, right / 8.0]
+    }
+
+    /// Generate audio sample
+    ///
+    /// The output instant falls between two internal mix samples, so the
+    /// level is linearly interpolated between them. This delays the output
+    /// by one internal sample (under a microsecond).
+    fn generate_sample(&mut self) {
+        // Position of this sample between the previous and latest mix
+        // samples; `sample_timer` holds how far past the instant we are
+        let overshoot = self.sample_timer as f32 / self.sample_rate as f32;
+        let position = (self.mix_phase as f32 + 1.0 - overshoot) / MIX_INTERVAL as f32;
+        let left = self.prev_level[0] + (self.level[0] - self.prev_level[0]) * position;
+        let right = self.prev_level[1] + (self.level[1] - self.prev_level[1]) * position;
 
-        left = (left * left_vol) / 4;
-        right = (right * right_vol) / 4;
+        // Remove the DC offset; with every DAC off the output is silent and
+        // the capacitors keep their charge
+        let dacs_on = self.ch1.dac_enabled || self.ch2.dac_enabled || self.ch3.dac_enabled || self.ch4.dac_enabled;
+        let (left, right) = if dacs_on {
+            let charge_per_cycle = match self.hardware_model {
+                HardwareModel::Dmg => HPF_CHARGE_DMG,
+                HardwareModel::Cgb => HPF_CHARGE_CGB,
+            };
+            let charge = HighPassFilter::charge_factor(charge_per_cycle, CPU_CLOCK / self.sample_rate);
+            self.high_pass.process(left, right, charge)
+        } else {
+            (0.0, 0.0)
+        };
 
         // Scale to i16 range
-        left = (left * 256).clamp(-32768, 32767);
-        right = (right * 256).clamp(-32768, 32767);
-
-        // Write stereo sample
-        if self.buffer_pos + 1 < self.audio_buffer.len() {
-            self.audio_buffer[self.buffer_pos] = left as i16;
-            self.audio_buffer[self.buffer_pos + 1] = right as i16;
-            self.buffer_pos += 2;
-        }
+        let left = (left * CHANNEL_AMPLITUDE).clamp(-32768.0, 32767.0) as i16;
+        let right = (right * CHANNEL_AMPLITUDE).clamp(-32768.0, 32767.0) as i16;
+
+        // Downmix / crossfeed, then write stereo sample
+        let (left, right) = self.mixer.mix(left, right);
+        self.samples.push(left, right);
+    }
+
+    /// Get samples generated since the buffer was last drained
+    pub fn pending_samples(&self) -> &[i16] {
+        self.samples.pending()
+    }
+
+    /// Samples generated since the buffer was last drained
+    ///
+    /// Replaces the former fixed-size `audio_buffer` field, whose filled
+    /// part this slice corresponds to.
+    #[deprecated(note = "use `pending_samples`, or `read_samples` to pull them")]
+    pub fn audio_buffer(&self) -> &[i16] {
+        self.pending_samples()
     }
 
     /// Get audio buffer and reset position
     pub fn get_audio_buffer(&mut self) -> &[i16] {
-        let len = self.buffer_pos;
-        self.buffer_pos = 0;
-        &self.audio_buffer[..len]
+        self.samples.drain()
+    }
+
+    /// Number of samples waiting to be read
+    pub fn available_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Pull up to `out.len()` of the oldest interleaved stereo samples
+    ///
+    /// Meant for host audio callbacks that request a fixed amount at a
+    /// time. Returns the number of samples written.
+    pub fn read_samples(&mut self, out: &mut [i16]) -> usize {
+        self.samples.read(out)
+    }
+
+    /// Maximum number of buffered samples
+    pub fn sample_capacity(&self) -> usize {
+        self.samples.capacity()
+    }
+
+    /// Change the buffer capacity (defaults to `AUDIO_BUFFER_SIZE`)
+    ///
+    /// Once full, each new frame overwrites the oldest one, so a host that
+    /// falls behind hears at most `capacity` samples of latency.
+    pub fn set_sample_capacity(&mut self, capacity: usize) {
+        self.samples.set_capacity(capacity);
+    }
+
+    /// Per-channel state (channels 1-4) for audio visualisers
+    pub fn get_channel_state(&self) -> [ChannelState; 4] {
+        [self.ch1.state(), self.ch2.state(), self.ch3.state(), self.ch4.state()]
     }
 
     /// Read APU register
@@ -248,10 +440,10 @@ impl Apu {
             0xFF25 => self.nr51,
             0xFF26 => {
                 let mut result = self.nr52 & 0x80;
-                if self.ch1.enabled { result |= 0x01; }
-                if self.ch2.enabled { result |= 0x02; }
-                if self.ch3.enabled { result |= 0x04; }
-                if self.ch4.enabled { result |= 0x08; }
+                if self.ch1.is_active() { result |= 0x01; }
+                if self.ch2.is_active() { result |= 0x02; }
+                if self.ch3.is_active() { result |= 0x04; }
+                if self.ch4.is_active() { result |= 0x08; }
                 result | 0x70 // Bits 4-6 always read as 1
             }
             _ => 0xFF,
@@ -282,7 +474,7 @@ impl Apu {
             0xFF1B => self.ch3.write_nr31(value),
             0xFF1C => self.ch3.write_nr32(value),
             0xFF1D => self.ch3.write_nr33(value),
-            0xFF1E => self.ch3.write_nr34(value),
+            0xFF1E => self.ch3.write_nr34(value, self.hardware_model),
             // Wave RAM
             0xFF30..=0xFF3F => self.ch3.write_wave_ram(address, value),
             // Channel 4
@@ -319,7 +511,7 @@ mod tests {
 
     #[test]
     fn test_apu_new() {
-        let apu = Apu::new();
+        let apu = Apu::new(SAMPLE_RATE);
         assert!(apu.enabled);
         assert_eq!(apu.nr50, 0x77);
         assert_eq!(apu.nr51, 0xF3);
@@ -327,7 +519,7 @@ mod tests {
 
     #[test]
     fn test_nr52_read() {
-        let apu = Apu::new();
+        let apu = Apu::new(SAMPLE_RATE);
         let nr52 = apu.read(0xFF26);
         // Bits 4-6 always 1, bit 7 = enabled
         assert_eq!(nr52 & 0xF0, 0xF0);
@@ -335,7 +527,7 @@ mod tests {
 
     #[test]
     fn test_apu_disable() {
-        let mut apu = Apu::new();
+        let mut apu = Apu::new(SAMPLE_RATE);
         apu.nr50 = 0x77;
         apu.nr51 = 0xF3;
         
@@ -346,4 +538,149 @@ mod tests {
         assert_eq!(apu.nr50, 0);
         assert_eq!(apu.nr51, 0);
     }
+
+    #[test]
+    fn test_mono_output_mode() {
+        let mut apu = Apu::new(SAMPLE_RATE);
+        apu.mixer.set_output_mode(mixer::AudioOutput::Mono);
+        // Channel 1 at full volume, panned hard left
+        apu.nr51 = 0x10;
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF14, 0x87);
+        for div in 0..20_000u16 {
+            apu.tick(div);
+        }
+
+        let samples = apu.pending_samples();
+        assert!(samples.iter().any(|&sample| sample != 0));
+        for frame in samples.chunks(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+    }
+
+    #[test]
+    fn test_get_channel_state() {
+        let mut apu = Apu::new(SAMPLE_RATE);
+        // Channel 1: 75% duty, length 64-0x30, volume 12 decreasing every 3 ticks, period 0x5A3
+        apu.write(0xFF11, 0xF0);
+        apu.write(0xFF12, 0xC3);
+        apu.write(0xFF13, 0xA3);
+        apu.write(0xFF14, 0x85);
+        // Channel 3: DAC on, 50% output level, period 0x123
+        apu.write(0xFF1A, 0x80);
+        apu.write(0xFF1C, 0x40);
+        apu.write(0xFF1D, 0x23);
+        apu.write(0xFF1E, 0x81);
+        // Channel 4: DAC off
+        apu.write(0xFF21, 0x00);
+
+        let [ch1, ch2, ch3, ch4] = apu.get_channel_state();
+        assert_eq!(ch1, ChannelState {
+            enabled: true,
+            dac_enabled: true,
+            volume: 12,
+            frequency: 0x5A3,
+            duty: Some(3),
+            wave_position: None,
+            envelope_timer: 3,
+            length_counter: 16,
+        });
+        assert!(!ch2.enabled);
+        assert_eq!(ch2.duty, Some(0));
+        assert!(ch3.enabled);
+        assert_eq!(ch3.volume, 2);
+        assert_eq!(ch3.frequency, 0x123);
+        assert_eq!(ch3.wave_position, Some(0));
+        assert_eq!(ch3.length_counter, 256);
+        assert_eq!(ch3.duty, None);
+        assert!(!ch4.enabled && !ch4.dac_enabled);
+    }
+
+    #[test]
+    fn test_frame_sequencer_follows_div() {
+        let mut apu = Apu::new(SAMPLE_RATE);
+        let mut div = 0u16;
+        for _ in 0..FRAME_SEQUENCER_RATE * 2 {
+            div = div.wrapping_add(1);
+            apu.tick(div);
+        }
+        assert_eq!(apu.frame_sequencer_step, 2);
+
+        // Run half a period, then reset DIV while bit 12 is set
+        for _ in 0..FRAME_SEQUENCER_RATE / 2 {
+            div = div.wrapping_add(1);
+            apu.tick(div);
+        }
+        assert_eq!(apu.frame_sequencer_step, 2);
+        apu.tick(0);
+        assert_eq!(apu.frame_sequencer_step, 3);
+
+        // Resetting while bit 12 is clear has no effect
+        apu.tick(0);
+        assert_eq!(apu.frame_sequencer_step, 3);
+    }
+
+    #[test]
+    fn test_channel3_volume_code_samples() {
+        // Wave RAM full of 0xF: the first sample is the raw DAC level
+        // because the output capacitor starts uncharged
+        for (code, expected) in [(1u8, 8192i16), (2, -546), (3, -4915), (0, -8192)] {
+            let mut apu = Apu::new(SAMPLE_RATE);
+            apu.nr51 = 0x04;
+            for address in 0xFF30..=0xFF3F {
+                apu.write(address, 0xFF);
+            }
+            apu.write(0xFF1A, 0x80);
+            apu.write(0xFF1C, code << 5);
+            apu.write(0xFF1E, 0x80);
+            while apu.pending_samples().is_empty() {
+                apu.tick(0);
+            }
+
+            let samples = apu.pending_samples();
+            assert_eq!(samples[0], 0, "code {}", code);
+            assert!((samples[1] - expected).abs() <= 1, "code {}: {}", code, samples[1]);
+        }
+    }
+
+    #[test]
+    fn test_high_pass_removes_dc_offset() {
+        let mut apu = Apu::new(SAMPLE_RATE);
+        apu.nr51 = 0x04;
+        apu.write(0xFF1A, 0x80);
+        apu.write(0xFF1C, 0x20);
+        apu.write(0xFF1E, 0x80);
+        // Wave RAM is all zero, a constant -1.0 DAC level
+        let mut first = None;
+        let mut last = 0;
+        for _ in 0..CPU_CLOCK / 8 {
+            apu.tick(0);
+            if let [.., right] = *apu.get_audio_buffer() {
+                first.get_or_insert(right);
+                last = right;
+            }
+        }
+
+        assert!(first.unwrap() < -8000);
+        assert!(last.abs() < 64);
+        assert!(apu.high_pass.capacitor()[1] < -0.9);
+
+        apu.init();
+        assert_eq!(apu.high_pass.capacitor(), [0.0; 2]);
+    }
+
+    #[test]
+    fn test_sample_interpolates_between_mix_samples() {
+        let mut apu = Apu::new(48000);
+        apu.ch1.dac_enabled = true;
+        apu.prev_level = [0.0, 1.0];
+        apu.level = [1.0, -1.0];
+        // Two T-cycles after the previous mix sample, half a cycle ago
+        apu.mix_phase = 1;
+        apu.sample_timer = 24000;
+        apu.generate_sample();
+
+        // 0.375 of the way from the previous level to the latest
+        assert_eq!(apu.get_audio_buffer(), [3072, 2048]);
+    }
 }
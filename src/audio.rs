@@ -0,0 +1,183 @@
+//! Audio Output
+//!
+//! This module defines audio output sinks for samples produced by the APU,
+//! including a WAV file recorder.
+
+use crate::error::EmulatorError;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Size of the RIFF/WAVE header written before the sample data
+const WAV_HEADER_SIZE: u32 = 44;
+/// Bits per sample (signed 16-bit PCM)
+const BITS_PER_SAMPLE: u16 = 16;
+/// Largest data chunk whose RIFF size still fits in 32 bits, in whole
+/// stereo frames
+const MAX_DATA_SIZE: u32 = (u32::MAX - (WAV_HEADER_SIZE - 8)) & !3;
+
+/// Sink for interleaved stereo i16 samples
+pub trait AudioOutput {
+    /// Consume a block of interleaved samples
+    fn write_samples(&mut self, samples: &[i16]);
+}
+
+/// Records interleaved i16 samples to a PCM WAV file
+#[derive(Debug)]
+pub struct WavRecorder {
+    /// Output file
+    file: BufWriter<File>,
+    /// Sample rate in Hz
+    sample_rate: u32,
+    /// Number of interleaved channels
+    channel_count: u16,
+    /// Number of i16 samples written (across all channels)
+    sample_count: u32,
+    /// Data chunk size at which recording stops
+    max_data_size: u32,
+    /// Samples were dropped because the data chunk is full
+    truncated: bool,
+    /// First write error, reported by `finish`
+    error: Option<io::Error>,
+}
+
+impl WavRecorder {
+    /// Create a WAV file and write a header with a placeholder data length
+    pub fn open(path: impl AsRef<Path>, sample_rate: u32) -> Result<WavRecorder, EmulatorError> {
+        let mut recorder = WavRecorder {
+            file: BufWriter::new(File::create(path)?),
+            sample_rate,
+            channel_count: 2,
+            sample_count: 0,
+            max_data_size: MAX_DATA_SIZE,
+            truncated: false,
+            error: None,
+        };
+        recorder.write_header(0)?;
+        Ok(recorder)
+    }
+
+    /// Append interleaved samples to the data chunk
+    ///
+    /// After a write error nothing more is written; `finish` reports it.
+    /// Recording also stops once the data chunk reaches 4 GiB, the most a
+    /// WAV header can describe.
+    pub fn write_samples(&mut self, samples: &[i16]) {
+        if self.error.is_some() {
+            return;
+        }
+        let room = (self.max_data_size / (BITS_PER_SAMPLE as u32 / 8)).saturating_sub(self.sample_count) as usize;
+        if samples.len() > room {
+            self.truncated = true;
+        }
+        for &sample in &samples[..samples.len().min(room)] {
+            if let Err(e) = self.file.write_all(&sample.to_le_bytes()) {
+                self.error = Some(e);
+                return;
+            }
+            self.sample_count = self.sample_count.saturating_add(1);
+        }
+    }
+
+    /// Patch the header with the final data length and close the file
+    ///
+    /// Fails with the first error from `write_samples`, if any. A recording
+    /// cut off at the 4 GiB limit is still finalized, then reported as an
+    /// error.
+    pub fn finish(mut self) -> Result<(), EmulatorError> {
+        if let Some(e) = self.error.take() {
+            return Err(e.into());
+        }
+        let data_size = self.sample_count * (BITS_PER_SAMPLE as u32 / 8);
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header(data_size)?;
+        self.file.flush()?;
+        if self.truncated {
+            return Err(io::Error::other("WAV recording stopped at the 4 GiB size limit").into());
+        }
+        Ok(())
+    }
+
+    /// Write the 44-byte canonical WAV header
+    fn write_header(&mut self, data_size: u32) -> Result<(), EmulatorError> {
+        let block_align = self.channel_count * (BITS_PER_SAMPLE / 8);
+        let byte_rate = self.sample_rate * block_align as u32;
+
+        let f = &mut self.file;
+        f.write_all(b"RIFF")?;
+        f.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        f.write_all(b"WAVE")?;
+        f.write_all(b"fmt ")?;
+        f.write_all(&16u32.to_le_bytes())?;
+        f.write_all(&1u16.to_le_bytes())?; // PCM
+        f.write_all(&self.channel_count.to_le_bytes())?;
+        f.write_all(&self.sample_rate.to_le_bytes())?;
+        f.write_all(&byte_rate.to_le_bytes())?;
+        f.write_all(&block_align.to_le_bytes())?;
+        f.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+        f.write_all(b"data")?;
+        f.write_all(&data_size.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl AudioOutput for WavRecorder {
+    fn write_samples(&mut self, samples: &[i16]) {
+        WavRecorder::write_samples(self, samples);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_header_after_finish() {
+        let path = std::env::temp_dir().join(format!("rgbe_wav_{}.wav", std::process::id()));
+        let mut recorder = WavRecorder::open(&path, 44100).unwrap();
+        recorder.write_samples(&[0, 1, -1, 2]);
+        recorder.finish().unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(data.len(), 44 + 8);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 36 + 8);
+        assert_eq!(&data[8..12], b"WAVE");
+        assert_eq!(u16::from_le_bytes(data[22..24].try_into().unwrap()), 2);
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 44100);
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 8);
+        assert_eq!(&data[46..48], &1i16.to_le_bytes());
+    }
+
+    #[test]
+    fn test_recording_stops_at_size_limit() {
+        let path = std::env::temp_dir().join(format!("rgbe_wav_limit_{}.wav", std::process::id()));
+        let mut recorder = WavRecorder::open(&path, 44100).unwrap();
+        recorder.max_data_size = 8;
+        recorder.write_samples(&[1, 2, 3]);
+        recorder.write_samples(&[4, 5, 6]);
+        assert!(matches!(recorder.finish(), Err(EmulatorError::Io(_))));
+
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // The header still describes exactly the samples that were kept
+        assert_eq!(data.len(), 44 + 8);
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 36 + 8);
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 8);
+        assert_eq!(&data[50..52], &4i16.to_le_bytes());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_write_error_reported_by_finish() {
+        // Writes to /dev/full fail once the buffer is flushed
+        let mut recorder = WavRecorder::open("/dev/full", 44100).unwrap();
+        recorder.write_samples(&[0; 0x4000]);
+        assert!(recorder.error.is_some());
+        assert!(matches!(recorder.finish(), Err(EmulatorError::Io(_))));
+    }
+}
@@ -0,0 +1,79 @@
+//! Headless Benchmark
+//!
+//! Runs a ROM for a fixed number of frames without rendering and reports
+//! emulation speed. Built without SDL2:
+//!
+//! ```text
+//! cargo run --release --no-default-features --features std --bin benchmark -- <rom_file> <frame_count> [--profile]
+//! ```
+
+use gbemu::emu::Emulator;
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Instant;
+
+/// Game Boy frame rate (Hz)
+const GB_FPS: f64 = 59.7275;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let profile = args.iter().any(|arg| arg == "--profile");
+    let positional: Vec<&String> = args.iter().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+
+    if positional.len() < 2 {
+        eprintln!("Usage: {} <rom_file> <frame_count> [--profile]", args[0]);
+        process::exit(1);
+    }
+
+    let rom = match fs::read(positional[0]) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Failed to read ROM: {}", e);
+            process::exit(1);
+        }
+    };
+    let frame_count: u32 = match positional[1].parse() {
+        Ok(count) => count,
+        Err(_) => {
+            eprintln!("Invalid frame count: {}", positional[1]);
+            process::exit(1);
+        }
+    };
+
+    let mut emulator = match Emulator::from_bytes(rom) {
+        Ok(emu) => emu,
+        Err(e) => {
+            eprintln!("Failed to initialize emulator: {}", e);
+            process::exit(1);
+        }
+    };
+    if profile {
+        emulator.cpu.enable_cycle_histogram();
+    }
+
+    let start = Instant::now();
+    for _ in 0..frame_count {
+        emulator.run_frame();
+        // Drain audio like a frontend would so the buffer doesn't saturate
+        emulator.get_audio_buffer();
+    }
+    let elapsed = start.elapsed();
+
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    let fps = frame_count as f64 / secs;
+    println!(
+        "Emulated {} frames in {}ms ({:.1} fps, {:.2}x realtime)",
+        frame_count,
+        elapsed.as_millis(),
+        fps,
+        fps / GB_FPS
+    );
+
+    if profile {
+        println!("Hottest addresses (M-cycles):");
+        for (addr, cycles) in emulator.cpu.dump_cycle_histogram().into_iter().take(10) {
+            println!("  {:04X}: {}", addr, cycles);
+        }
+    }
+}
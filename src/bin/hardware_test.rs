@@ -0,0 +1,480 @@
+//! Closed-Loop Hardware Tests
+//!
+//! Assembles small test ROMs with `gbemu::cpu::asm`, runs each one in the
+//! emulator and checks the value left in register A. Every ROM carries a
+//! valid header and loads its own starting registers, so the same images can
+//! be written out with `--write-roms` and run on real hardware, where the
+//! result sits in A at the end loop:
+//!
+//! ```text
+//! cargo run --no-default-features --features std --bin hardware_test -- [--write-roms <dir>]
+//! ```
+
+use gbemu::cart::Cartridge;
+use gbemu::cpu::asm;
+use gbemu::cpu::CpuInitState;
+use gbemu::emu::Emulator;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+/// Logo bitmap the boot ROM checks before starting a cartridge
+const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+/// Test ROMs are a single 32KB ROM-only image
+const ROM_SIZE: usize = 0x8000;
+/// Address the test program is assembled at (just past the header)
+const CODE_ORIGIN: u16 = 0x0150;
+/// Timer interrupt vector
+const TIMER_VECTOR: u16 = 0x0050;
+/// T-cycle budget for the register setup that runs before each program
+const SETUP_CYCLES: u64 = 256;
+/// Appended to every program; the ROM parks here when finished
+const END_LOOP: [&str; 2] = ["DONE:", "JR DONE"];
+/// Post-boot DMG registers, the starting point for most cases
+const DMG: CpuInitState = CpuInitState::DMG;
+
+/// One test ROM and its expected outcome
+struct TestCase {
+    /// Case name, also used for the ROM title and file name
+    name: &'static str,
+    /// Registers the ROM loads before the program starts
+    init: CpuInitState,
+    /// Program assembled at `CODE_ORIGIN`; may jump to `DONE`
+    program: &'static [&'static str],
+    /// Timer interrupt handler assembled at `TIMER_VECTOR`
+    timer_handler: &'static [&'static str],
+    /// T-cycle budget for reaching the end loop from the program start;
+    /// a timeout, not an exact count
+    cycle_budget: u64,
+    /// Register A at the end loop
+    expected_a: u8,
+}
+
+const CASES: &[TestCase] = &[
+    TestCase {
+        name: "nop_chain",
+        init: CpuInitState { a: 0x5A, ..DMG },
+        program: &["NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP"],
+        timer_handler: &[],
+        cycle_budget: 64,
+        expected_a: 0x5A,
+    },
+    TestCase {
+        name: "nop_chain_cgb",
+        init: CpuInitState::CGB,
+        program: &["NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP", "NOP"],
+        timer_handler: &[],
+        cycle_budget: 80,
+        expected_a: 0x11,
+    },
+    TestCase {
+        name: "ld_immediate",
+        init: DMG,
+        program: &["LD A, 0x42"],
+        timer_handler: &[],
+        cycle_budget: 32,
+        expected_a: 0x42,
+    },
+    TestCase {
+        name: "ld_register",
+        init: DMG,
+        program: &["LD B, 0x37", "LD C, B", "LD A, C"],
+        timer_handler: &[],
+        cycle_budget: 48,
+        expected_a: 0x37,
+    },
+    TestCase {
+        name: "add_immediate",
+        init: DMG,
+        program: &["LD A, 0x12", "ADD A, 0x34"],
+        timer_handler: &[],
+        cycle_budget: 48,
+        expected_a: 0x46,
+    },
+    TestCase {
+        name: "add_register",
+        init: CpuInitState { a: 0x20, b: 0x05, ..DMG },
+        program: &["ADD A, B", "ADD A, B"],
+        timer_handler: &[],
+        cycle_budget: 32,
+        expected_a: 0x2A,
+    },
+    TestCase {
+        name: "add_wraps",
+        init: DMG,
+        program: &["LD A, 0xF0", "ADD A, 0x20"],
+        timer_handler: &[],
+        cycle_budget: 48,
+        expected_a: 0x10,
+    },
+    TestCase {
+        name: "add_sets_carry",
+        init: DMG,
+        program: &["LD A, 0xF0", "ADD A, 0x20", "LD A, 0x01", "JR C, DONE", "LD A, 0xEE"],
+        timer_handler: &[],
+        cycle_budget: 64,
+        expected_a: 0x01,
+    },
+    TestCase {
+        name: "add_sets_zero",
+        init: DMG,
+        program: &["LD A, 0x80", "ADD A, 0x80", "LD A, 0x02", "JR Z, DONE", "LD A, 0xEE"],
+        timer_handler: &[],
+        cycle_budget: 64,
+        expected_a: 0x02,
+    },
+    TestCase {
+        name: "sub_immediate",
+        init: DMG,
+        program: &["LD A, 0x50", "SUB 0x20"],
+        timer_handler: &[],
+        cycle_budget: 48,
+        expected_a: 0x30,
+    },
+    TestCase {
+        name: "sub_borrow_sets_carry",
+        init: DMG,
+        program: &["LD A, 0x10", "SUB 0x20", "JR NC, DONE", "ADD A, 0x01"],
+        timer_handler: &[],
+        cycle_budget: 64,
+        expected_a: 0xF1,
+    },
+    TestCase {
+        name: "sub_no_borrow",
+        init: DMG,
+        program: &["LD A, 0x20", "SUB 0x10", "LD A, 0x03", "JR NC, DONE", "LD A, 0xEE"],
+        timer_handler: &[],
+        cycle_budget: 64,
+        expected_a: 0x03,
+    },
+    TestCase {
+        name: "sub_register",
+        init: CpuInitState { a: 0x08, b: 0x05, ..DMG },
+        program: &["SUB B"],
+        timer_handler: &[],
+        cycle_budget: 32,
+        expected_a: 0x03,
+    },
+    TestCase {
+        name: "logic_ops",
+        init: DMG,
+        program: &["LD A, 0xF0", "AND 0x3C", "OR 0x01", "XOR 0xFF"],
+        timer_handler: &[],
+        cycle_budget: 64,
+        expected_a: 0xCE,
+    },
+    TestCase {
+        name: "jr_nz_loop",
+        init: DMG,
+        program: &["LD A, 0", "LD B, 10", "LOOP:", "ADD A, 3", "DEC B", "JR NZ, LOOP"],
+        timer_handler: &[],
+        cycle_budget: 400,
+        expected_a: 0x1E,
+    },
+    TestCase {
+        name: "jr_z_taken",
+        init: DMG,
+        program: &["XOR A", "JR Z, SKIP", "LD A, 0xEE", "SKIP:", "INC A"],
+        timer_handler: &[],
+        cycle_budget: 48,
+        expected_a: 0x01,
+    },
+    TestCase {
+        name: "jr_c_not_taken",
+        init: DMG,
+        program: &["LD A, 5", "CP 3", "JR C, BAD", "LD A, 0x77", "JR DONE", "BAD:", "LD A, 0xEE"],
+        timer_handler: &[],
+        cycle_budget: 80,
+        expected_a: 0x77,
+    },
+    TestCase {
+        name: "jr_nc_not_taken",
+        init: DMG,
+        program: &["LD A, 2", "CP 3", "JR NC, BAD", "LD A, 0x33", "JR DONE", "BAD:", "LD A, 0xEE"],
+        timer_handler: &[],
+        cycle_budget: 80,
+        expected_a: 0x33,
+    },
+    TestCase {
+        name: "call_ret",
+        init: DMG,
+        program: &["LD A, 1", "CALL DOUBLE", "CALL DOUBLE", "JR DONE", "DOUBLE:", "ADD A, A", "RET"],
+        timer_handler: &[],
+        cycle_budget: 160,
+        expected_a: 0x04,
+    },
+    TestCase {
+        name: "nested_call",
+        init: DMG,
+        program: &[
+            "LD A, 0", "CALL OUTER", "JR DONE",
+            "OUTER:", "ADD A, 0x10", "CALL INNER", "ADD A, 0x10", "RET",
+            "INNER:", "ADD A, 0x01", "RET",
+        ],
+        timer_handler: &[],
+        cycle_budget: 240,
+        expected_a: 0x21,
+    },
+    TestCase {
+        name: "call_conditional",
+        init: DMG,
+        program: &["XOR A", "CALL NZ, BUMP", "CALL Z, BUMP", "JR DONE", "BUMP:", "ADD A, 0x10", "RET"],
+        timer_handler: &[],
+        cycle_budget: 160,
+        expected_a: 0x10,
+    },
+    TestCase {
+        name: "ret_conditional",
+        init: DMG,
+        program: &["CALL CHECK", "JR DONE", "CHECK:", "LD A, 1", "CP 1", "RET Z", "LD A, 0xEE", "RET"],
+        timer_handler: &[],
+        cycle_budget: 160,
+        expected_a: 0x01,
+    },
+    TestCase {
+        name: "push_pop",
+        init: DMG,
+        program: &["LD BC, 0x1234", "PUSH BC", "POP AF"],
+        timer_handler: &[],
+        cycle_budget: 80,
+        expected_a: 0x12,
+    },
+    TestCase {
+        name: "wram_write_read",
+        init: DMG,
+        program: &["LD HL, 0xC000", "LD (HL), 0x42", "XOR A", "LD A, (HL)"],
+        timer_handler: &[],
+        cycle_budget: 80,
+        expected_a: 0x42,
+    },
+    TestCase {
+        name: "hram_inc",
+        init: DMG,
+        program: &["LD HL, 0xFF80", "LD (HL), 0x0F", "INC (HL)", "INC (HL)", "LD A, (HL)"],
+        timer_handler: &[],
+        cycle_budget: 112,
+        expected_a: 0x11,
+    },
+    TestCase {
+        name: "timer_interrupt_count",
+        init: DMG,
+        // TAC 0x05: TIMA every 16 T-cycles; TMA 0xF0 overflows every 256
+        program: &[
+            "LD B, 0",
+            "LD HL, 0xFF06", "LD (HL), 0xF0",
+            "LD HL, 0xFF05", "LD (HL), 0xF0",
+            "LD HL, 0xFF0F", "LD (HL), 0x00",
+            "LD HL, 0xFFFF", "LD (HL), 0x04",
+            "LD HL, 0xFF07", "LD (HL), 0x05",
+            "EI",
+            "WAIT:", "LD A, B", "CP 5", "JR C, WAIT",
+            "DI",
+        ],
+        timer_handler: &["INC B", "RETI"],
+        cycle_budget: 2000,
+        expected_a: 0x05,
+    },
+    TestCase {
+        name: "timer_interrupt_disabled",
+        init: DMG,
+        // Same timer setup with IE clear: no interrupt is taken
+        program: &[
+            "LD B, 0",
+            "LD HL, 0xFF06", "LD (HL), 0xF0",
+            "LD HL, 0xFFFF", "LD (HL), 0x00",
+            "LD HL, 0xFF07", "LD (HL), 0x05",
+            "EI",
+            "LD C, 0",
+            "WAIT:", "DEC C", "JR NZ, WAIT",
+            "DI",
+            "LD A, B",
+        ],
+        timer_handler: &["INC B", "RETI"],
+        cycle_budget: 8000,
+        expected_a: 0x00,
+    },
+];
+
+/// Instructions that load `init` into the registers
+///
+/// AF goes through the stack since there is no `LD F, n`; POP AF drops the
+/// low nibble of F just like the hardware does.
+fn setup_lines(init: &CpuInitState) -> Vec<String> {
+    let pair = |hi: u8, lo: u8| u16::from_be_bytes([hi, lo]);
+    vec![
+        format!("LD SP, 0x{:04X}", init.sp),
+        format!("LD HL, 0x{:04X}", pair(init.a, init.f)),
+        "PUSH HL".to_string(),
+        "POP AF".to_string(),
+        format!("LD BC, 0x{:04X}", pair(init.b, init.c)),
+        format!("LD DE, 0x{:04X}", pair(init.d, init.e)),
+        format!("LD HL, 0x{:04X}", pair(init.h, init.l)),
+    ]
+}
+
+/// A case assembled into a ROM image
+struct TestRom {
+    /// 32KB ROM-only image
+    image: [u8; ROM_SIZE],
+    /// Address of the first program instruction, after the register setup
+    start: u16,
+    /// Address of the end loop
+    done: u16,
+}
+
+/// Build the 32KB ROM image for a case
+fn build_rom(case: &TestCase) -> Result<TestRom, String> {
+    let mut rom = [0u8; ROM_SIZE];
+
+    let entry = asm::assemble(&["NOP", "JP 0x0150"])?;
+    rom[0x100..0x100 + entry.len()].copy_from_slice(&entry);
+    rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+    let title = case.name.to_ascii_uppercase();
+    let title = &title.as_bytes()[..title.len().min(15)];
+    rom[0x134..0x134 + title.len()].copy_from_slice(title);
+
+    if !case.timer_handler.is_empty() {
+        let handler = asm::assemble_with_labels(case.timer_handler, TIMER_VECTOR)?;
+        let start = TIMER_VECTOR as usize;
+        rom[start..start + handler.len()].copy_from_slice(&handler);
+    }
+
+    let setup = setup_lines(&case.init);
+    let setup: Vec<&str> = setup.iter().map(String::as_str).collect();
+    let setup_len = asm::assemble(&setup)?.len();
+    let lines: Vec<&str> = setup.iter().copied().chain(case.program.iter().copied()).chain(END_LOOP).collect();
+    let code = asm::assemble_with_labels(&lines, CODE_ORIGIN)?;
+    let start = CODE_ORIGIN as usize;
+    if start + code.len() > ROM_SIZE {
+        return Err(format!("program is {} bytes, too large for the ROM", code.len()));
+    }
+    rom[start..start + code.len()].copy_from_slice(&code);
+
+    rom[0x14D] = Cartridge::calculate_checksum(&rom);
+    Ok(TestRom {
+        image: rom,
+        start: CODE_ORIGIN + setup_len as u16,
+        done: CODE_ORIGIN + code.len() as u16 - 2,
+    })
+}
+
+/// Run `emu` until PC reaches `target` or `budget` T-cycles pass
+///
+/// Returns the T-cycles taken.
+fn run_until(emu: &mut Emulator, target: u16, budget: u64) -> Result<u64, String> {
+    let start = emu.ctx.ticks;
+    while emu.cpu.regs.pc != target && emu.ctx.ticks - start < budget {
+        if !emu.step() {
+            return Err("emulator stopped".to_string());
+        }
+    }
+    if emu.cpu.regs.pc != target {
+        return Err(format!(
+            "did not reach {:04X} within {} cycles (PC={:04X})",
+            target, budget, emu.cpu.regs.pc
+        ));
+    }
+    Ok(emu.ctx.ticks - start)
+}
+
+/// Run a case, returning the T-cycles its program took to reach the end loop
+fn run_case(case: &TestCase) -> Result<u64, String> {
+    let rom = build_rom(case)?;
+    let mut emu = Emulator::from_bytes(rom.image.to_vec()).map_err(|e| e.to_string())?;
+
+    run_until(&mut emu, rom.start, SETUP_CYCLES)?;
+    let elapsed = run_until(&mut emu, rom.done, case.cycle_budget)?;
+
+    if emu.cpu.regs.a != case.expected_a {
+        return Err(format!("A={:02X}, expected {:02X}", emu.cpu.regs.a, case.expected_a));
+    }
+    Ok(elapsed)
+}
+
+/// Write every case's ROM to `dir` as `<name>.gb`
+fn write_roms(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    for case in CASES {
+        let rom = build_rom(case).map_err(|e| format!("{}: {}", case.name, e))?;
+        let path = dir.join(format!("{}.gb", case.name));
+        fs::write(&path, rom.image).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--write-roms") {
+        let Some(dir) = args.get(i + 1) else {
+            eprintln!("Usage: {} [--write-roms <dir>]", args[0]);
+            process::exit(1);
+        };
+        if let Err(e) = write_roms(Path::new(dir)) {
+            eprintln!("Failed to write ROMs: {}", e);
+            process::exit(1);
+        }
+        println!("Wrote {} ROMs to {}", CASES.len(), dir);
+    }
+
+    let mut failures = 0;
+    for case in CASES {
+        match run_case(case) {
+            Ok(cycles) => println!("PASS {} ({} cycles)", case.name, cycles),
+            Err(e) => {
+                println!("FAIL {}: {}", case.name, e);
+                failures += 1;
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", CASES.len() - failures, failures);
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_cases_pass() {
+        for case in CASES {
+            if let Err(e) = run_case(case) {
+                panic!("{}: {}", case.name, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_roms_have_valid_headers() {
+        for case in CASES {
+            let rom = build_rom(case).unwrap();
+            assert!(Cartridge::validate_checksum(&rom.image), "{}", case.name);
+            assert_eq!(rom.image[0x104..0x134], NINTENDO_LOGO);
+        }
+    }
+
+    #[test]
+    fn test_roms_load_their_own_registers() {
+        // Without the harness touching the CPU, the setup leaves `init` in place
+        for case in CASES {
+            let rom = build_rom(case).unwrap();
+            let mut emu = Emulator::from_bytes(rom.image.to_vec()).unwrap();
+            run_until(&mut emu, rom.start, SETUP_CYCLES).unwrap();
+            let regs = &emu.cpu.regs;
+            let init = &case.init;
+            assert_eq!(
+                (regs.a, regs.b, regs.c, regs.d, regs.e, regs.h, regs.l, regs.sp),
+                (init.a, init.b, init.c, init.d, init.e, init.h, init.l, init.sp),
+                "{}",
+                case.name
+            );
+        }
+    }
+}
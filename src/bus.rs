@@ -4,11 +4,25 @@
 //! memory accesses to the appropriate hardware components based on address.
 
 use crate::common::{Byte, Word};
+#[cfg(feature = "std")]
+use crate::error::EmulatorError;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
 
 /// Memory bus trait for reading and writing memory
 pub trait MemoryBus {
     /// Read a byte from the given address
     fn read(&self, address: Word) -> Byte;
+
+    /// Read a byte for a debugger or log, without side effects such as
+    /// access recording or playback
+    fn peek(&self, address: Word) -> Byte {
+        self.read(address)
+    }
     
     /// Write a byte to the given address
     fn write(&mut self, address: Word, value: Byte);
@@ -25,11 +39,173 @@ pub trait MemoryBus {
         self.write(address, (value & 0xFF) as Byte);
         self.write(address.wrapping_add(1), ((value >> 8) & 0xFF) as Byte);
     }
+
+    /// Whether a STOP now would switch CPU speed (CGB mode with KEY1 bit 0 set)
+    fn speed_switch_armed(&self) -> bool {
+        false
+    }
+}
+
+/// Bus access needed by the `Emulator` loop on top of `MemoryBus`
+///
+/// `Bus` keeps I/O registers, interrupt flags and video memory in dedicated
+/// fields that the emulator syncs with its components after every step.
+/// Other implementations (such as `MockBus`) can back the registers with
+/// plain memory and rely on the no-op defaults for the rest.
+pub trait SystemBus: MemoryBus {
+    /// Read I/O register `0xFF00 + index` without side effects
+    fn io_register(&self, index: usize) -> Byte;
+
+    /// Store I/O register `0xFF00 + index` on behalf of a component
+    fn set_io_register(&mut self, index: usize, value: Byte);
+
+    /// Whether the CPU wrote I/O register `index` since the last call
+    fn take_io_written(&mut self, _index: usize) -> bool {
+        false
+    }
+
+    /// IE register (0xFFFF)
+    fn interrupt_enable(&self) -> Byte;
+
+    /// IF register (0xFF0F)
+    fn interrupt_flags(&self) -> Byte;
+
+    /// Store the IF register
+    fn set_interrupt_flags(&mut self, value: Byte);
+
+    /// Copy VRAM and OAM into the PPU's copies if the CPU changed them
+    fn sync_video_memory(&mut self, _vram: &mut [Byte], _oam: &mut [Byte]) {}
+
+    /// Copy CGB palette RAM into the PPU's copy if the CPU changed it
+    fn sync_cgb_palettes(&mut self, _palettes: &mut CgbPalettes) {}
+
+    /// VRAM bank selected by VBK (always 0 outside CGB mode)
+    fn vram_bank(&self) -> u8 {
+        0
+    }
+
+    /// Read a byte for OAM DMA, bypassing CPU access restrictions
+    fn read_direct(&self, address: Word) -> Byte {
+        self.read(address)
+    }
+
+    /// Store a byte transferred by OAM DMA
+    fn write_oam(&mut self, index: usize, value: Byte) {
+        self.write(0xFE00 + index as Word, value);
+    }
+
+    /// Store a byte transferred by VRAM DMA into the bank selected by VBK
+    fn write_vram(&mut self, address: Word, value: Byte) {
+        self.write(address, value);
+    }
+
+    /// Block CPU access outside HRAM while OAM DMA runs
+    fn set_dma_active(&mut self, _active: bool) {}
+
+    /// Publish the PPU mode for VRAM/OAM access locking
+    fn set_ppu_mode(&mut self, _mode: PpuMode) {}
+
+    /// Flip KEY1 to the other CPU speed once a STOP speed switch completes
+    fn complete_speed_switch(&mut self) {}
+
+    /// Whether the CPU runs at CGB double speed (KEY1 bit 7)
+    fn double_speed(&self) -> bool {
+        false
+    }
+
+    /// Start or stop logging writes for `write_log`
+    fn set_track_writes(&mut self, _enabled: bool) {}
+
+    /// Writes logged for plugins since the last `clear_write_log`
+    fn write_log(&self) -> &[(Word, Byte)] {
+        &[]
+    }
+
+    /// Forget the writes logged for plugins
+    fn clear_write_log(&mut self) {}
 }
 
 use crate::cart::Cartridge;
+use crate::lcd::PpuMode;
+use crate::memory_map::MemoryRegion;
+use crate::ppu::palette::CgbPalettes;
+use crate::ppu::{VRAM_BANKS, VRAM_BANK_SIZE};
 use crate::ram::Ram;
 
+/// File signature for saved bus recordings
+#[cfg(feature = "std")]
+const RECORDING_MAGIC: &[u8; 4] = b"GBBR";
+
+/// Log of CPU-visible bus accesses, in the order they happened
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BusRecording {
+    /// (address, value returned)
+    pub reads: Vec<(Word, Byte)>,
+    /// (address, value written)
+    pub writes: Vec<(Word, Byte)>,
+}
+
+impl BusRecording {
+    /// Save the recording in a compact binary format
+    ///
+    /// Layout: `GBBR`, read count and write count (u32 LE), then each read
+    /// followed by each write as address (u16 LE) and value.
+    #[cfg(feature = "std")]
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<(), EmulatorError> {
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        out.write_all(RECORDING_MAGIC)?;
+        out.write_all(&(self.reads.len() as u32).to_le_bytes())?;
+        out.write_all(&(self.writes.len() as u32).to_le_bytes())?;
+        for &(address, value) in self.reads.iter().chain(&self.writes) {
+            out.write_all(&address.to_le_bytes())?;
+            out.write_all(&[value])?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Load a recording written by `to_file`
+    #[cfg(feature = "std")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, EmulatorError> {
+        let mut input = io::BufReader::new(std::fs::File::open(path)?);
+        let mut header = [0u8; 12];
+        input.read_exact(&mut header)?;
+        if &header[..4] != RECORDING_MAGIC {
+            return Err(EmulatorError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a bus recording",
+            )));
+        }
+        let read_count = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let write_count = u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as usize;
+
+        let mut read_entries = |count: usize| -> io::Result<Vec<(Word, Byte)>> {
+            let mut entries = Vec::new();
+            let mut entry = [0u8; 3];
+            for _ in 0..count {
+                input.read_exact(&mut entry)?;
+                entries.push((Word::from_le_bytes([entry[0], entry[1]]), entry[2]));
+            }
+            Ok(entries)
+        };
+        let reads = read_entries(read_count)?;
+        let writes = read_entries(write_count)?;
+        Ok(Self { reads, writes })
+    }
+}
+
+/// Whether bus accesses are being recorded or replayed
+#[derive(Debug, Clone, Default)]
+enum AccessLog {
+    /// Normal operation
+    #[default]
+    Off,
+    /// Every read and write is appended to the recording
+    Recording(BusRecording),
+    /// Reads are served from `reads[next..]`; writes are dropped
+    Playback { recording: BusRecording, next: usize },
+}
+
 /// Game Boy memory bus
 /// 
 /// Routes memory accesses to the appropriate hardware components:
@@ -37,12 +213,14 @@ use crate::ram::Ram;
 /// - 0x8000-0x9FFF: PPU VRAM
 /// - 0xA000-0xBFFF: Cartridge RAM
 /// - 0xC000-0xDFFF: WRAM
-/// - 0xE000-0xFDFF: Echo RAM (returns 0)
+/// - 0xE000-0xFDFF: Echo RAM (mirror of 0xC000-0xDDFF, reads and writes)
 /// - 0xFE00-0xFE9F: PPU OAM
 /// - 0xFEA0-0xFEFF: Unusable (returns 0)
 /// - 0xFF00-0xFF7F: I/O registers
 /// - 0xFF80-0xFFFE: HRAM
 /// - 0xFFFF: IE register
+#[derive(Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bus {
     /// RAM (WRAM + HRAM)
     pub ram: Ram,
@@ -50,15 +228,20 @@ pub struct Bus {
     pub ie_register: Byte,
     /// Interrupt flags register (0xFF0F)
     pub int_flags: Byte,
-    /// Cartridge (handles MBC)
+    /// Cartridge (handles MBC; saved separately as `MbcState`)
+    #[cfg_attr(feature = "save-state", serde(skip))]
     pub cart: Option<Cartridge>,
-    /// VRAM (shared with PPU)
-    pub vram: [Byte; 0x2000],
+    /// VRAM, both CGB banks back to back (shared with PPU)
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
+    pub vram: [Byte; VRAM_BANK_SIZE * VRAM_BANKS],
     /// OAM (shared with PPU)
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
     pub oam: [Byte; 0xA0],
     /// I/O registers
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
     pub io_regs: [Byte; 0x80],
     /// I/O register write event flags (FF00 offset indexing)
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
     pub io_written: [bool; 0x80],
     /// DMA transferring flag
     pub dma_active: bool,
@@ -66,6 +249,37 @@ pub struct Bus {
     pub vram_dirty: bool,
     /// OAM was written since last PPU sync
     pub oam_dirty: bool,
+    /// Current PPU mode (VRAM is locked in mode 3, OAM in modes 2 and 3)
+    pub ppu_mode: PpuMode,
+    /// CPU writes to VRAM dropped because the PPU was drawing
+    pub vram_blocked_writes: u32,
+    /// CGB hardware registers (VBK, SVBK, KEY1, HDMA, palettes) are available
+    pub cgb_mode: bool,
+    /// Selected VRAM bank (VBK, CGB only)
+    pub vram_bank: u8,
+    /// CGB background and object palette RAM (BCPS/BCPD, OCPS/OCPD)
+    pub cgb_palettes: CgbPalettes,
+    /// Palette RAM was written since last PPU sync
+    pub palettes_dirty: bool,
+    /// KEY1 speed switch register (bit 7: current speed, bit 0: switch armed)
+    pub key1: Byte,
+    /// RP infrared port (bit 0: LED on, bit 1: signal being received, bits 6-7: read enable)
+    pub rp: Byte,
+    /// Record writes in `write_log` (enabled while plugins or watchpoints are set)
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    pub track_writes: bool,
+    /// Writes since the log was last drained
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    pub write_log: Vec<(Word, Byte)>,
+    /// Access recording or playback (reads go through `&self`)
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    access_log: RefCell<AccessLog>,
+    /// `access_log` is not `Off`; keeps the `RefCell` off the normal read path
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    logging_accesses: bool,
+    /// Boot ROM overlaid on the cartridge until 0xFF50 is written
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    boot_rom: Option<Vec<Byte>>,
 }
 
 impl Default for Bus {
@@ -82,15 +296,162 @@ impl Bus {
             ie_register: 0,
             int_flags: 0,
             cart: None,
-            vram: [0; 0x2000],
+            vram: [0; VRAM_BANK_SIZE * VRAM_BANKS],
             oam: [0; 0xA0],
             io_regs: [0; 0x80],
             io_written: [false; 0x80],
             dma_active: false,
             vram_dirty: true,
             oam_dirty: true,
+            ppu_mode: PpuMode::HBlank,
+            vram_blocked_writes: 0,
+            cgb_mode: false,
+            vram_bank: 0,
+            cgb_palettes: CgbPalettes::new(),
+            palettes_dirty: true,
+            key1: 0,
+            rp: 0,
+            track_writes: false,
+            write_log: Vec::new(),
+            access_log: RefCell::new(AccessLog::Off),
+            logging_accesses: false,
+            boot_rom: None,
+        }
+    }
+
+    /// Replace the bus state with one from a save state
+    ///
+    /// The cartridge, write log and access recording are kept. The boot ROM
+    /// stays mapped only if it was mapped when the state was saved.
+    #[cfg(feature = "save-state")]
+    pub(crate) fn load_state(&mut self, mut saved: Bus, boot_rom_mapped: bool) {
+        saved.cart = self.cart.take();
+        saved.track_writes = self.track_writes;
+        saved.write_log = core::mem::take(&mut self.write_log);
+        saved.access_log = core::mem::take(&mut self.access_log);
+        saved.logging_accesses = self.logging_accesses;
+        saved.boot_rom = self.boot_rom.take().filter(|_| boot_rom_mapped);
+        *self = saved;
+    }
+
+    /// Clear memory and registers as on power-up
+    ///
+    /// The cartridge, write tracking, access recording or playback and a
+    /// still-mapped boot ROM are kept.
+    pub(crate) fn reset(&mut self) {
+        let mut bus = Bus::new();
+        bus.cart = self.cart.take();
+        bus.track_writes = self.track_writes;
+        bus.access_log = core::mem::take(&mut self.access_log);
+        bus.logging_accesses = self.logging_accesses;
+        bus.boot_rom = self.boot_rom.take();
+        *self = bus;
+    }
+
+    /// Map a boot ROM over the start of the cartridge
+    ///
+    /// It covers 0x0000-0x00FF, plus 0x0200 onward for CGB-sized images
+    /// (0x0100-0x01FF always shows the cartridge header). Writing bit 0 of
+    /// 0xFF50 unmaps it for good.
+    pub fn load_boot_rom(&mut self, data: Vec<Byte>) {
+        self.boot_rom = Some(data);
+    }
+
+    /// Check if the boot ROM is still mapped
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    /// Byte from the boot ROM, if it covers `address`
+    fn boot_rom_read(&self, address: Word) -> Option<Byte> {
+        let boot_rom = self.boot_rom.as_ref()?;
+        if (0x0100..0x0200).contains(&address) {
+            return None;
+        }
+        boot_rom.get(address as usize).copied()
+    }
+
+    /// Start logging every CPU-visible read and write
+    ///
+    /// Any recording or playback in progress is discarded.
+    pub fn start_recording(&mut self) {
+        self.set_access_log(AccessLog::Recording(BusRecording::default()));
+    }
+
+    /// Stop recording and return the log (empty if not recording)
+    pub fn stop_recording(&mut self) -> BusRecording {
+        match core::mem::take(self.access_log.get_mut()) {
+            AccessLog::Recording(recording) => {
+                self.logging_accesses = false;
+                recording
+            }
+            other => {
+                *self.access_log.get_mut() = other;
+                BusRecording::default()
+            }
         }
     }
+
+    /// Serve reads from a recording instead of the hardware
+    ///
+    /// Reads must happen in the recorded order; writes are ignored.
+    ///
+    /// # Panics
+    ///
+    /// A read panics once the recording is exhausted or if its address
+    /// differs from the recorded one (the replay has diverged).
+    pub fn enable_playback(&mut self, recording: BusRecording) {
+        self.set_access_log(AccessLog::Playback { recording, next: 0 });
+    }
+
+    /// Return to normal operation after playback
+    pub fn disable_playback(&mut self) {
+        if matches!(self.access_log.get_mut(), AccessLog::Playback { .. }) {
+            self.set_access_log(AccessLog::Off);
+        }
+    }
+
+    /// Switch access recording or playback
+    fn set_access_log(&mut self, log: AccessLog) {
+        self.logging_accesses = !matches!(log, AccessLog::Off);
+        *self.access_log.get_mut() = log;
+    }
+
+    /// Read as the CPU sees it: during OAM DMA only HRAM is reachable
+    fn cpu_read(&self, address: Word) -> Byte {
+        if self.dma_active && !(0xFF80..=0xFFFE).contains(&address) {
+            0xFF
+        } else {
+            self.read_direct(address)
+        }
+    }
+
+    /// `read` while recording or playing back accesses
+    fn logged_read(&self, address: Word) -> Byte {
+        let mut access_log = self.access_log.borrow_mut();
+        if let AccessLog::Playback { recording, next } = &mut *access_log {
+            let Some(&(recorded_address, value)) = recording.reads.get(*next) else {
+                panic!("bus playback ran out of recorded reads at {:04X}", address);
+            };
+            assert_eq!(
+                recorded_address, address,
+                "bus playback diverged at read {}", *next
+            );
+            *next += 1;
+            return value;
+        }
+
+        let value = self.cpu_read(address);
+        if let AccessLog::Recording(recording) = &mut *access_log {
+            recording.reads.push((address, value));
+        }
+        value
+    }
+
+    /// Check if reads are being served from a recording
+    pub fn is_playback(&self) -> bool {
+        matches!(*self.access_log.borrow(), AccessLog::Playback { .. })
+    }
 
     /// Load cartridge into bus
     pub fn load_cartridge(&mut self, cart: Cartridge) {
@@ -117,20 +478,175 @@ impl Bus {
         written
     }
 
+    /// Get the region `address` currently resolves to, without accessing it
+    pub fn resolve_region(&self, address: Word) -> MemoryRegion {
+        match address {
+            0x0000..=0x3FFF => MemoryRegion::CartRom0,
+            0x4000..=0x7FFF => {
+                let bank = self.cart.as_ref().map_or(1, |cart| cart.current_rom_bank());
+                MemoryRegion::CartRomN(bank as u8)
+            }
+            0x8000..=0x9FFF => MemoryRegion::Vram(self.vram_bank),
+            0xA000..=0xBFFF => {
+                let bank = self.cart.as_ref().map_or(0, |cart| cart.current_ram_bank());
+                MemoryRegion::CartRam(bank as u8)
+            }
+            0xC000..=0xCFFF => MemoryRegion::Wram0,
+            0xD000..=0xDFFF => MemoryRegion::WramN(self.ram.wram_bank()),
+            0xE000..=0xFDFF => MemoryRegion::EchoRam,
+            0xFE00..=0xFE9F => MemoryRegion::Oam,
+            0xFEA0..=0xFEFF => MemoryRegion::Unusable,
+            0xFF00..=0xFF7F => MemoryRegion::IoRegister((address - 0xFF00) as u8),
+            0xFF80..=0xFFFE => MemoryRegion::Hram,
+            0xFFFF => MemoryRegion::IeRegister,
+        }
+    }
+
+    /// Enable or disable CGB hardware registers, resetting their banks
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.vram_bank = 0;
+        self.cgb_palettes = CgbPalettes::new();
+        self.palettes_dirty = true;
+        self.key1 = 0;
+        self.rp = 0;
+        self.ram.set_wram_bank(1);
+    }
+
+    /// Read a CGB-only I/O register, if `address` is one
+    ///
+    /// Outside CGB mode these registers are unmapped and read 0xFF.
+    fn cgb_register_read(&self, address: Word) -> Option<Byte> {
+        let value = match address {
+            0xFF4D => 0x7E | self.key1,
+            0xFF4F => 0xFE | self.vram_bank,
+            0xFF56 => self.rp_read(),
+            // HDMA1-4 are write-only; HDMA5 is kept in sync by the emulator
+            0xFF51..=0xFF54 => 0xFF,
+            0xFF55 => self.io_regs[0x55],
+            0xFF68 => self.cgb_palettes.bg.read_index(),
+            0xFF69 if self.palettes_locked() => 0xFF,
+            0xFF69 => self.cgb_palettes.bg.read_data(),
+            0xFF6A => self.cgb_palettes.obj.read_index(),
+            0xFF6B if self.palettes_locked() => 0xFF,
+            0xFF6B => self.cgb_palettes.obj.read_data(),
+            0xFF70 => 0xF8 | self.ram.wram_bank(),
+            _ => return None,
+        };
+        Some(if self.cgb_mode { value } else { 0xFF })
+    }
+
+    /// Write a CGB-only I/O register; returns false if `address` isn't one
+    fn cgb_register_write(&mut self, address: Word, value: Byte) -> bool {
+        if !matches!(address, 0xFF4D | 0xFF4F | 0xFF51..=0xFF55 | 0xFF56 | 0xFF68..=0xFF6B | 0xFF70) {
+            return false;
+        }
+        if !self.cgb_mode {
+            return true;
+        }
+        match address {
+            0xFF4D => self.key1 = (self.key1 & 0x80) | (value & 0x01),
+            0xFF4F => self.vram_bank = value & 0x01,
+            0xFF56 => self.rp = (self.rp & 0x02) | (value & 0xC1),
+            0xFF68 => self.cgb_palettes.bg.write_index(value),
+            0xFF69 => {
+                let store = !self.palettes_locked();
+                self.cgb_palettes.bg.write_data(value, store);
+                self.palettes_dirty = true;
+            }
+            0xFF6A => self.cgb_palettes.obj.write_index(value),
+            0xFF6B => {
+                let store = !self.palettes_locked();
+                self.cgb_palettes.obj.write_data(value, store);
+                self.palettes_dirty = true;
+            }
+            0xFF70 => self.ram.set_wram_bank(value),
+            _ => self.io_regs[(address - 0xFF00) as usize] = value,
+        }
+        true
+    }
+
+    /// Offset into `vram` of VRAM `address` in the bank selected by VBK
+    fn vram_offset(&self, address: Word) -> usize {
+        self.vram_bank as usize * VRAM_BANK_SIZE + (address - 0x8000) as usize
+    }
+
+    /// Palette data is inaccessible to the CPU while the PPU is drawing
+    fn palettes_locked(&self) -> bool {
+        self.ppu_mode == PpuMode::Transfer
+    }
+
+    /// RP as seen by the CPU
+    ///
+    /// Bit 1 reads 0 only while a signal is received and both read enable
+    /// bits are set; unused bits read 1.
+    fn rp_read(&self) -> Byte {
+        let receiving = self.rp & 0x02 != 0 && self.rp & 0xC0 == 0xC0;
+        (self.rp & 0xC1) | 0x3C | if receiving { 0x00 } else { 0x02 }
+    }
+
+    /// Set whether an infrared signal is hitting the RP receiver
+    pub fn set_ir_receive(&mut self, active: bool) {
+        if active {
+            self.rp |= 0x02;
+        } else {
+            self.rp &= !0x02;
+        }
+    }
+
     /// Save cartridge battery (if applicable)
     pub fn save_battery(&mut self) {
         if let Some(ref mut cart) = self.cart {
             let _ = cart.save_battery();
         }
     }
-}
 
-impl MemoryBus for Bus {
-    fn read(&self, address: Word) -> Byte {
+    /// Echo RAM addresses that do not mirror WRAM, as `(echo_addr, wram_addr)`
+    ///
+    /// Writes two test patterns straight into each WRAM byte backing echo RAM
+    /// (0xC000-0xDDFF, current SVBK bank) and reads them back through the
+    /// echo address with `read_direct`. Nothing goes through the CPU-visible
+    /// path, so the write log, access recording and DMA lock are untouched.
+    /// WRAM contents are restored afterwards.
+    pub fn mirror_diagnostic(&mut self) -> Vec<(Word, Word)> {
+        self.echo_mismatches(Self::read_direct)
+    }
+
+    /// Run the echo RAM probe with `read_echo` answering the echo reads
+    fn echo_mismatches(&mut self, read_echo: impl Fn(&Self, Word) -> Byte) -> Vec<(Word, Word)> {
+        let mut mismatches = Vec::new();
+        for wram_addr in 0xC000..=0xDDFF {
+            let echo_addr = wram_addr + 0x2000;
+            let original = self.ram.wram_read(wram_addr);
+            let pattern = (wram_addr ^ (wram_addr >> 8)) as Byte;
+            let mut mirrored = true;
+            for value in [pattern, !pattern] {
+                self.ram.wram_write(wram_addr, value);
+                mirrored &= read_echo(self, echo_addr) == value;
+            }
+            self.ram.wram_write(wram_addr, original);
+            if !mirrored {
+                mismatches.push((echo_addr, wram_addr));
+            }
+        }
+        mismatches
+    }
+
+    /// Check that every echo RAM address mirrors its WRAM counterpart
+    pub fn verify_echo_ram(&mut self) -> bool {
+        self.mirror_diagnostic().is_empty()
+    }
+
+    /// Read a byte ignoring the OAM DMA bus lock
+    ///
+    /// Used by the DMA controller to fetch its source bytes.
+    pub fn read_direct(&self, address: Word) -> Byte {
         match address {
             // Cartridge ROM (0x0000-0x7FFF)
             0x0000..=0x7FFF => {
-                if let Some(ref cart) = self.cart {
+                if let Some(value) = self.boot_rom_read(address) {
+                    value
+                } else if let Some(ref cart) = self.cart {
                     cart.read(address)
                 } else {
                     0xFF
@@ -138,7 +654,7 @@ impl MemoryBus for Bus {
             }
             // VRAM (0x8000-0x9FFF)
             0x8000..=0x9FFF => {
-                self.vram[(address - 0x8000) as usize]
+                self.vram[self.vram_offset(address)]
             }
             // Cartridge RAM (0xA000-0xBFFF)
             0xA000..=0xBFFF => {
@@ -158,11 +674,7 @@ impl MemoryBus for Bus {
             }
             // OAM (0xFE00-0xFE9F)
             0xFE00..=0xFE9F => {
-                if self.dma_active {
-                    0xFF
-                } else {
-                    self.oam[(address - 0xFE00) as usize]
-                }
+                self.oam[(address - 0xFE00) as usize]
             }
             // Unusable (0xFEA0-0xFEFF)
             0xFEA0..=0xFEFF => 0xFF,
@@ -171,6 +683,8 @@ impl MemoryBus for Bus {
                 // Special case for IF register
                 if address == 0xFF0F {
                     self.int_flags | 0xE0
+                } else if let Some(value) = self.cgb_register_read(address) {
+                    value
                 } else {
                     self.io_regs[(address - 0xFF00) as usize]
                 }
@@ -183,8 +697,33 @@ impl MemoryBus for Bus {
             0xFFFF => self.ie_register,
         }
     }
+}
+
+impl MemoryBus for Bus {
+    fn read(&self, address: Word) -> Byte {
+        if self.logging_accesses {
+            return self.logged_read(address);
+        }
+        self.cpu_read(address)
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        self.read_direct(address)
+    }
 
     fn write(&mut self, address: Word, value: Byte) {
+        if self.logging_accesses {
+            match self.access_log.get_mut() {
+                AccessLog::Off => {}
+                AccessLog::Recording(recording) => recording.writes.push((address, value)),
+                AccessLog::Playback { .. } => return,
+            }
+        }
+
+        if self.track_writes {
+            self.write_log.push((address, value));
+        }
+
         match address {
             // Cartridge ROM (0x0000-0x7FFF) - writes go to MBC
             0x0000..=0x7FFF => {
@@ -194,7 +733,12 @@ impl MemoryBus for Bus {
             }
             // VRAM (0x8000-0x9FFF)
             0x8000..=0x9FFF => {
-                self.vram[(address - 0x8000) as usize] = value;
+                if self.ppu_mode == PpuMode::Transfer {
+                    self.vram_blocked_writes = self.vram_blocked_writes.wrapping_add(1);
+                    return;
+                }
+                let offset = self.vram_offset(address);
+                self.vram[offset] = value;
                 self.vram_dirty = true;
             }
             // Cartridge RAM (0xA000-0xBFFF)
@@ -207,13 +751,15 @@ impl MemoryBus for Bus {
             0xC000..=0xDFFF => {
                 self.ram.wram_write(address, value);
             }
-            // Echo RAM (0xE000-0xFDFF) - mirror of WRAM
+            // Echo RAM (0xE000-0xFDFF) - same cells as WRAM, so writes land
+            // in WRAM too; 0xF000+ follows the SVBK bank like 0xD000+
             0xE000..=0xFDFF => {
                 self.ram.wram_write(address - 0x2000, value);
             }
             // OAM (0xFE00-0xFE9F)
             0xFE00..=0xFE9F => {
-                if !self.dma_active {
+                let oam_locked = matches!(self.ppu_mode, PpuMode::OamScan | PpuMode::Transfer);
+                if !self.dma_active && !oam_locked {
                     self.oam[(address - 0xFE00) as usize] = value;
                     self.oam_dirty = true;
                 }
@@ -226,7 +772,11 @@ impl MemoryBus for Bus {
                 // Special case for IF register
                 if address == 0xFF0F {
                     self.int_flags = value;
-                } else {
+                } else if address == 0xFF50 {
+                    if value & 0x01 != 0 {
+                        self.boot_rom = None;
+                    }
+                } else if !self.cgb_register_write(address, value) {
                     self.io_regs[io_index] = value;
                 }
                 self.io_written[io_index] = true;
@@ -241,11 +791,111 @@ impl MemoryBus for Bus {
             }
         }
     }
+
+    fn speed_switch_armed(&self) -> bool {
+        self.cgb_mode && self.key1 & 0x01 != 0
+    }
+}
+
+impl SystemBus for Bus {
+    fn io_register(&self, index: usize) -> Byte {
+        self.io_regs[index]
+    }
+
+    fn set_io_register(&mut self, index: usize, value: Byte) {
+        self.io_regs[index] = value;
+    }
+
+    fn take_io_written(&mut self, index: usize) -> bool {
+        Bus::take_io_written(self, index)
+    }
+
+    fn interrupt_enable(&self) -> Byte {
+        self.ie_register
+    }
+
+    fn interrupt_flags(&self) -> Byte {
+        self.int_flags
+    }
+
+    fn set_interrupt_flags(&mut self, value: Byte) {
+        self.int_flags = value;
+    }
+
+    fn sync_video_memory(&mut self, vram: &mut [Byte], oam: &mut [Byte]) {
+        // Only copy when the CPU actually changed the memory
+        if self.vram_dirty {
+            vram.copy_from_slice(&self.vram);
+            self.vram_dirty = false;
+        }
+        if self.oam_dirty {
+            oam.copy_from_slice(&self.oam);
+            self.oam_dirty = false;
+        }
+    }
+
+    fn sync_cgb_palettes(&mut self, palettes: &mut CgbPalettes) {
+        if self.palettes_dirty {
+            palettes.clone_from(&self.cgb_palettes);
+            self.palettes_dirty = false;
+        }
+    }
+
+    fn vram_bank(&self) -> u8 {
+        self.vram_bank
+    }
+
+    fn read_direct(&self, address: Word) -> Byte {
+        Bus::read_direct(self, address)
+    }
+
+    fn write_oam(&mut self, index: usize, value: Byte) {
+        self.oam[index] = value;
+    }
+
+    fn write_vram(&mut self, address: Word, value: Byte) {
+        let offset = self.vram_offset(address);
+        self.vram[offset] = value;
+        self.vram_dirty = true;
+    }
+
+    fn set_dma_active(&mut self, active: bool) {
+        Bus::set_dma_active(self, active);
+    }
+
+    fn set_ppu_mode(&mut self, mode: PpuMode) {
+        self.ppu_mode = mode;
+    }
+
+    fn complete_speed_switch(&mut self) {
+        // Report the new speed in bit 7 and disarm the switch
+        self.key1 = (self.key1 ^ 0x80) & 0x80;
+    }
+
+    fn double_speed(&self) -> bool {
+        self.key1 & 0x80 != 0
+    }
+
+    fn set_track_writes(&mut self, enabled: bool) {
+        self.track_writes = enabled;
+        if !enabled {
+            self.write_log.clear();
+        }
+    }
+
+    fn write_log(&self) -> &[(Word, Byte)] {
+        &self.write_log
+    }
+
+    fn clear_write_log(&mut self) {
+        self.write_log.clear();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cpu::{Cpu, CpuState};
 
     #[test]
     fn test_wram_routing() {
@@ -258,6 +908,179 @@ mod tests {
         assert_eq!(bus.read(0xDFFF), 0xAB);
     }
 
+    #[test]
+    fn test_cgb_registers_unmapped_on_dmg() {
+        let mut bus = Bus::new();
+        for address in [0xFF4D, 0xFF4F, 0xFF55, 0xFF68, 0xFF70] {
+            bus.write(address, 0x01);
+            assert_eq!(bus.read(address), 0xFF, "{:04X}", address);
+        }
+        assert_eq!(bus.vram_bank, 0);
+        assert_eq!(bus.ram.wram_bank(), 1);
+    }
+
+    #[test]
+    fn test_vram_banking() {
+        let mut bus = Bus::new();
+        bus.set_cgb_mode(true);
+        bus.write(0x8000, 0xAA);
+        bus.write(0xFF4F, 0x01);
+        bus.write(0x8000, 0xBB);
+        bus.write(0x9FFF, 0xCC);
+
+        assert_eq!(bus.read(0x8000), 0xBB);
+        bus.write(0xFF4F, 0x00);
+        assert_eq!(bus.read(0x8000), 0xAA);
+        assert_eq!(bus.read(0x9FFF), 0x00);
+        bus.write(0xFF4F, 0xFF);
+        assert_eq!(bus.read(0x9FFF), 0xCC);
+        assert_eq!(SystemBus::vram_bank(&bus), 1);
+
+        let mut vram = [0; VRAM_BANK_SIZE * VRAM_BANKS];
+        bus.sync_video_memory(&mut vram, &mut [0; 0xA0]);
+        assert_eq!((vram[0], vram[VRAM_BANK_SIZE], vram[2 * VRAM_BANK_SIZE - 1]), (0xAA, 0xBB, 0xCC));
+
+        // DMG ignores VBK and always uses bank 0
+        let mut bus = Bus::new();
+        bus.write(0xFF4F, 0x01);
+        bus.write(0x8000, 0x55);
+        assert_eq!(bus.vram[0], 0x55);
+        assert_eq!(bus.vram[VRAM_BANK_SIZE], 0x00);
+    }
+
+    #[test]
+    fn test_cgb_palette_registers() {
+        let mut bus = Bus::new();
+        bus.set_cgb_mode(true);
+        bus.palettes_dirty = false;
+
+        bus.write(0xFF68, 0x82);
+        bus.write(0xFF69, 0x1F);
+        bus.write(0xFF69, 0x00);
+        assert_eq!(bus.read(0xFF68), 0xC4);
+        assert_eq!(bus.cgb_palettes.bg.color(0, 1), 0x001F);
+        assert!(bus.palettes_dirty);
+
+        bus.write(0xFF6A, 0x08);
+        bus.write(0xFF6B, 0xE0);
+        assert_eq!(bus.read(0xFF6A), 0x48);
+        assert_eq!(bus.read(0xFF6B), 0xE0);
+        assert_eq!(bus.cgb_palettes.obj.color(1, 0), 0xFFE0);
+
+        // Data is locked while the PPU draws
+        bus.set_ppu_mode(PpuMode::Transfer);
+        bus.write(0xFF6B, 0x00);
+        assert_eq!(bus.read(0xFF6B), 0xFF);
+        bus.set_ppu_mode(PpuMode::HBlank);
+        assert_eq!(bus.read(0xFF6B), 0xE0);
+
+        let mut synced = CgbPalettes::new();
+        bus.sync_cgb_palettes(&mut synced);
+        assert_eq!(synced, bus.cgb_palettes);
+        assert!(!bus.palettes_dirty);
+    }
+
+    #[test]
+    fn test_cgb_register_banking() {
+        let mut bus = Bus::new();
+        bus.set_cgb_mode(true);
+
+        bus.write(0xFF4F, 0x01);
+        assert_eq!(bus.read(0xFF4F), 0xFF);
+        assert_eq!(bus.vram_bank, 1);
+        bus.write(0xFF4F, 0x00);
+        assert_eq!(bus.read(0xFF4F), 0xFE);
+
+        bus.write(0xD000, 0x11);
+        bus.write(0xFF70, 0x03);
+        assert_eq!(bus.read(0xFF70), 0xFB);
+        assert_eq!(bus.read(0xD000), 0x00);
+
+        bus.write(0xFF4D, 0x01);
+        assert_eq!(bus.read(0xFF4D), 0x7F);
+    }
+
+    #[test]
+    fn test_boot_rom_overlay() {
+        let mut bus = bus_with_mbc1_ram();
+        let mut boot_rom = vec![0x31; 0x900];
+        boot_rom[0x150] = 0x99;
+        bus.load_boot_rom(boot_rom);
+
+        assert_eq!(bus.read(0x0000), 0x31);
+        assert_eq!(bus.read(0x0150), 0x00);
+        assert_eq!(bus.read(0x0200), 0x31);
+        assert_eq!(bus.read(0x0900), 0x00);
+
+        bus.write(0xFF50, 0x00);
+        assert!(bus.boot_rom_mapped());
+        bus.write(0xFF50, 0x01);
+        assert!(!bus.boot_rom_mapped());
+        assert_eq!(bus.read(0x0000), 0xAB);
+    }
+
+    #[test]
+    fn test_rp_register() {
+        let mut bus = Bus::new();
+        bus.write(0xFF56, 0xFF);
+        assert_eq!(bus.read(0xFF56), 0xFF);
+
+        bus.set_cgb_mode(true);
+        assert_eq!(bus.read(0xFF56), 0x3E);
+        bus.write(0xFF56, 0xFF);
+        assert_eq!(bus.rp, 0xC1);
+        assert_eq!(bus.read(0xFF56), 0xFF);
+
+        // Receiving pulls bit 1 low, but only with reads enabled
+        bus.set_ir_receive(true);
+        assert_eq!(bus.read(0xFF56), 0xFD);
+        bus.write(0xFF56, 0x01);
+        assert_eq!(bus.read(0xFF56), 0x3F);
+        bus.write(0xFF56, 0xC0);
+        assert_eq!(bus.read(0xFF56), 0xFC);
+        bus.set_ir_receive(false);
+        assert_eq!(bus.read(0xFF56), 0xFE);
+    }
+
+    #[test]
+    fn test_vram_locked_during_transfer() {
+        let mut bus = Bus::new();
+        bus.write(0x8000, 0x11);
+
+        bus.ppu_mode = PpuMode::Transfer;
+        bus.write(0x8000, 0x22);
+        bus.write(0xFE00, 0x33);
+        assert_eq!(bus.read(0x8000), 0x11);
+        assert_eq!(bus.oam[0], 0x00);
+        assert_eq!(bus.vram_blocked_writes, 1);
+
+        bus.ppu_mode = PpuMode::OamScan;
+        bus.write(0x8000, 0x22);
+        bus.write(0xFE00, 0x33);
+        assert_eq!(bus.read(0x8000), 0x22);
+        assert_eq!(bus.oam[0], 0x00);
+
+        bus.ppu_mode = PpuMode::HBlank;
+        bus.write(0xFE00, 0x33);
+        assert_eq!(bus.oam[0], 0x33);
+    }
+
+    #[test]
+    fn test_resolve_region() {
+        let mut bus = Bus::new();
+        assert_eq!(bus.resolve_region(0x8000), MemoryRegion::Vram(0));
+        assert_eq!(bus.resolve_region(0x4000), MemoryRegion::CartRomN(1));
+        assert_eq!(bus.resolve_region(0xD000), MemoryRegion::WramN(1));
+        assert_eq!(bus.resolve_region(0xFF40), MemoryRegion::IoRegister(0x40));
+        assert_eq!(bus.resolve_region(0xFFFE), MemoryRegion::Hram);
+
+        bus.set_cgb_mode(true);
+        bus.write(0xFF4F, 0x01);
+        bus.write(0xFF70, 0x05);
+        assert_eq!(bus.resolve_region(0x9FFF), MemoryRegion::Vram(1));
+        assert_eq!(bus.resolve_region(0xD000), MemoryRegion::WramN(5));
+    }
+
     #[test]
     fn test_hram_routing() {
         let mut bus = Bus::new();
@@ -319,6 +1142,73 @@ mod tests {
         // Echo RAM mirrors WRAM
         bus.write(0xC000, 0x42);
         assert_eq!(bus.read(0xE000), 0x42);
+        bus.write(0xFDFF, 0x24);
+        assert_eq!(bus.read(0xDDFF), 0x24);
+    }
+
+    #[test]
+    fn test_echo_ram_follows_wram_bank() {
+        let mut bus = Bus::new();
+        bus.set_cgb_mode(true);
+
+        // 0xE000-0xEFFF is always bank 0
+        bus.write(0xFF70, 0x03);
+        bus.write(0xE123, 0x11);
+        bus.write(0xFF70, 0x05);
+        assert_eq!(bus.read(0xC123), 0x11);
+        assert_eq!(bus.read(0xE123), 0x11);
+
+        // 0xF000-0xFDFF tracks the selected bank in both directions
+        bus.write(0xF010, 0x33);
+        bus.write(0xD020, 0x55);
+        assert_eq!(bus.read(0xD010), 0x33);
+        assert_eq!(bus.read(0xF020), 0x55);
+
+        bus.write(0xFF70, 0x02);
+        assert_eq!(bus.read(0xF010), 0x00);
+        assert_eq!(bus.read(0xF020), 0x00);
+        bus.write(0xF010, 0x22);
+
+        bus.write(0xFF70, 0x05);
+        assert_eq!(bus.read(0xF010), 0x33);
+        bus.write(0xFF70, 0x02);
+        assert_eq!(bus.read(0xD010), 0x22);
+    }
+
+    #[test]
+    fn test_verify_echo_ram() {
+        let mut bus = Bus::new();
+        bus.write(0xC000, 0x42);
+        assert!(bus.verify_echo_ram());
+        assert!(bus.mirror_diagnostic().is_empty());
+        // WRAM is left as it was
+        assert_eq!(bus.read(0xC000), 0x42);
+        assert_eq!(bus.read(0xDDFF), 0x00);
+
+        // Route one echo address to the wrong WRAM cell
+        let broken = |bus: &Bus, address: Word| {
+            let address = if address == 0xF123 { 0xF124 } else { address };
+            bus.read_direct(address)
+        };
+        assert_eq!(bus.echo_mismatches(broken), [(0xF123, 0xD123)]);
+    }
+
+    #[test]
+    fn test_mirror_diagnostic_has_no_side_effects() {
+        let mut bus = Bus::new();
+        bus.set_track_writes(true);
+        bus.write(0xC000, 0x42);
+        bus.start_recording();
+        assert!(bus.verify_echo_ram());
+        let recording = bus.stop_recording();
+        assert_eq!(bus.write_log(), [(0xC000, 0x42)]);
+        assert!(recording.reads.is_empty());
+        assert!(recording.writes.is_empty());
+
+        // Playback and an OAM DMA bus lock do not affect the probe
+        bus.enable_playback(recording);
+        bus.set_dma_active(true);
+        assert!(bus.verify_echo_ram());
     }
 
     #[test]
@@ -338,6 +1228,129 @@ mod tests {
         assert_eq!(bus.read16(0xC000), 0x1234);
     }
 
+    /// Bus with an MBC1+RAM cartridge whose first ROM byte is 0xAB
+    fn bus_with_mbc1_ram() -> Bus {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0000] = 0xAB;
+        rom[0x147] = 0x02; // MBC1+RAM
+        rom[0x149] = 0x02; // 8KB
+        let mut bus = Bus::new();
+        bus.load_cartridge(Cartridge::from_bytes(rom).unwrap());
+        bus
+    }
+
+    #[test]
+    fn test_read16_wraps_from_ie_to_rom() {
+        let mut bus = bus_with_mbc1_ram();
+        bus.write(0xFFFF, 0x1F);
+        assert_eq!(bus.read16(0xFFFF), 0xAB1F);
+    }
+
+    #[test]
+    fn test_write16_wraps_from_ie_to_mbc() {
+        let mut bus = bus_with_mbc1_ram();
+        bus.write(0xA000, 0x55);
+        assert_eq!(bus.read(0xA000), 0xFF); // RAM disabled
+
+        // Low byte lands in IE, high byte (0x0A) hits the MBC1 RAM enable register
+        bus.write16(0xFFFF, 0x0A05);
+        assert_eq!(bus.ie_register, 0x05);
+        assert_eq!(bus.read(0x0000), 0xAB);
+        bus.write(0xA000, 0x55);
+        assert_eq!(bus.read(0xA000), 0x55);
+    }
+
+    /// CPU at the post-boot state on a bus whose ROM loops over WRAM and the stack
+    fn recording_fixture() -> (Cpu, Bus) {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0100..0x010B].copy_from_slice(&[
+            0x21, 0x00, 0xC0, // LD HL,C000
+            0x3C,             // INC A
+            0x22,             // LD (HL+),A
+            0x86,             // ADD A,(HL)
+            0x47,             // LD B,A
+            0xC5,             // PUSH BC
+            0xD1,             // POP DE
+            0x18, 0xF8,       // JR -8
+        ]);
+        let mut bus = Bus::new();
+        bus.load_cartridge(Cartridge::from_bytes(rom).unwrap());
+        let mut cpu = Cpu::new();
+        cpu.init();
+        (cpu, bus)
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let (mut cpu, mut bus) = recording_fixture();
+        bus.start_recording();
+        for _ in 0..100 {
+            cpu.step_with_cycles(&mut bus);
+        }
+        let recording = bus.stop_recording();
+        assert!(!recording.reads.is_empty());
+        assert!(recording.writes.contains(&(0xC000, 0x02)));
+
+        let path = std::env::temp_dir().join(format!("rgbe_bus_recording_{}.bin", std::process::id()));
+        recording.to_file(&path).unwrap();
+        let loaded = BusRecording::from_file(&path);
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, recording);
+
+        // Replay on a bus with no cartridge and no memory contents
+        let mut replay_bus = Bus::new();
+        replay_bus.enable_playback(loaded);
+        let mut replay_cpu = Cpu::new();
+        replay_cpu.init();
+        for _ in 0..100 {
+            replay_cpu.step_with_cycles(&mut replay_bus);
+        }
+        assert_eq!(CpuState::from(&replay_cpu), CpuState::from(&cpu));
+
+        // Writes were dropped during playback
+        replay_bus.disable_playback();
+        assert_eq!(replay_bus.read(0xC000), 0x00);
+    }
+
+    #[test]
+    #[should_panic(expected = "ran out of recorded reads")]
+    fn test_playback_exhausted() {
+        let (mut cpu, mut bus) = recording_fixture();
+        bus.start_recording();
+        cpu.step_with_cycles(&mut bus);
+
+        let mut replay_bus = Bus::new();
+        replay_bus.enable_playback(bus.stop_recording());
+        let mut replay_cpu = Cpu::new();
+        replay_cpu.init();
+        replay_cpu.step_with_cycles(&mut replay_bus);
+        replay_cpu.step_with_cycles(&mut replay_bus);
+    }
+
+    #[test]
+    fn test_debugger_reads_skip_access_log() {
+        let (mut cpu, mut bus) = recording_fixture();
+        bus.start_recording();
+        cpu.step_with_cycles(&mut bus);
+        let recording = bus.stop_recording();
+
+        // Disassembling and logging peek, so playback stays in step
+        let mut replay_bus = Bus::new();
+        replay_bus.enable_playback(recording.clone());
+        let mut replay_cpu = Cpu::new();
+        replay_cpu.init();
+        replay_cpu.disassemble_next(&replay_bus);
+        replay_cpu.format_gameboy_doctor(&replay_bus);
+        replay_cpu.step_with_cycles(&mut replay_bus);
+        assert_eq!(CpuState::from(&replay_cpu), CpuState::from(&cpu));
+
+        // Peeks are not recorded either
+        bus.start_recording();
+        bus.peek(0x0100);
+        assert!(bus.stop_recording().reads.is_empty());
+    }
+
     #[test]
     fn test_io_write_flag_tracks_same_value_writes() {
         let mut bus = Bus::new();
@@ -4,13 +4,22 @@
 //! ROM header parsing, MBC (Memory Bank Controller) support, and battery backup.
 
 use crate::common::{Byte, Word};
+use crate::error::EmulatorError;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::fs;
-use std::io::{self, Read, Write};
-use std::path::Path;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
 
 /// ROM header offsets
 const HEADER_TITLE_START: usize = 0x134;
 const HEADER_TITLE_END: usize = 0x143;
+const HEADER_CGB_FLAG: usize = 0x143;
 const HEADER_CART_TYPE: usize = 0x147;
 const HEADER_ROM_SIZE: usize = 0x148;
 const HEADER_RAM_SIZE: usize = 0x149;
@@ -21,8 +30,10 @@ const HEADER_CHECKSUM: usize = 0x14D;
 /// ROM header information
 #[derive(Debug, Clone)]
 pub struct RomHeader {
-    /// Game title (up to 16 characters)
+    /// Game title (up to 16 characters, 15 on CGB-aware ROMs)
     pub title: String,
+    /// CGB flag (0x80 = CGB enhanced, 0xC0 = CGB only)
+    pub cgb_flag: Byte,
     /// Cartridge type (MBC type)
     pub cart_type: Byte,
     /// ROM size code
@@ -44,8 +55,11 @@ impl RomHeader {
             return None;
         }
 
-        // Extract title (null-terminated string)
-        let title_bytes = &rom_data[HEADER_TITLE_START..=HEADER_TITLE_END];
+        // Extract title (null-terminated string); on CGB-aware ROMs the last
+        // title byte holds the CGB flag instead
+        let cgb_flag = rom_data[HEADER_CGB_FLAG];
+        let title_end = if cgb_flag & 0x80 != 0 { HEADER_TITLE_END - 1 } else { HEADER_TITLE_END };
+        let title_bytes = &rom_data[HEADER_TITLE_START..=title_end];
         let title = title_bytes
             .iter()
             .take_while(|&&b| b != 0)
@@ -54,6 +68,7 @@ impl RomHeader {
 
         Some(Self {
             title,
+            cgb_flag,
             cart_type: rom_data[HEADER_CART_TYPE],
             rom_size: rom_data[HEADER_ROM_SIZE],
             ram_size: rom_data[HEADER_RAM_SIZE],
@@ -63,6 +78,11 @@ impl RomHeader {
         })
     }
 
+    /// Check if the ROM supports CGB features (enhanced or CGB-only)
+    pub fn supports_cgb(&self) -> bool {
+        self.cgb_flag & 0x80 != 0
+    }
+
     /// Get ROM size in bytes
     pub fn rom_size_bytes(&self) -> usize {
         32768 << self.rom_size as usize
@@ -100,28 +120,202 @@ impl RomHeader {
             0x19 => "MBC5",
             0x1A => "MBC5+RAM",
             0x1B => "MBC5+RAM+BATTERY",
+            0x1C => "MBC5+RUMBLE",
+            0x1D => "MBC5+RUMBLE+RAM",
+            0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+            0x1F => "POCKET CAMERA",
+            0xFF => "HuC1+RAM+BATTERY",
             _ => "UNKNOWN",
         }
     }
 
     /// Check if cartridge has battery backup
     pub fn has_battery(&self) -> bool {
-        matches!(self.cart_type, 0x03 | 0x06 | 0x09 | 0x0F | 0x10 | 0x13 | 0x1B)
+        matches!(self.cart_type, 0x03 | 0x06 | 0x09 | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x1F | 0xFF)
     }
 
     /// Check if cartridge has RAM
     pub fn has_ram(&self) -> bool {
-        matches!(self.cart_type, 0x02 | 0x03 | 0x08 | 0x09 | 0x10 | 0x12 | 0x13 | 0x1A | 0x1B)
+        matches!(self.cart_type, 0x02 | 0x03 | 0x08 | 0x09 | 0x10 | 0x12 | 0x13 | 0x1A | 0x1B | 0x1D | 0x1E | 0x1F | 0xFF)
+    }
+
+    /// Check if cartridge has a rumble motor
+    pub fn has_rumble(&self) -> bool {
+        matches!(self.cart_type, 0x1C..=0x1E)
+    }
+}
+
+/// Where battery saves are stored
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SavePathStrategy {
+    /// `{rom path}.sav` next to the ROM file
+    SiblingFile,
+    /// `$XDG_DATA_HOME/rgbe/saves/{title}.sav` (`~/.local/share` if unset)
+    XdgData,
+    /// `{title}.sav` inside the given directory
+    Custom(PathBuf),
+}
+
+#[cfg(feature = "std")]
+impl Default for SavePathStrategy {
+    #[cfg(windows)]
+    fn default() -> Self {
+        let app_data = std::env::var_os("APPDATA").map(PathBuf::from).unwrap_or_default();
+        SavePathStrategy::Custom(app_data.join("rgbe").join("saves"))
+    }
+
+    #[cfg(not(windows))]
+    fn default() -> Self {
+        SavePathStrategy::XdgData
+    }
+}
+
+#[cfg(feature = "std")]
+impl SavePathStrategy {
+    /// Base directory for `XdgData` saves
+    fn xdg_save_dir() -> PathBuf {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+            .unwrap_or_default();
+        data_home.join("rgbe").join("saves")
+    }
+}
+
+/// First RAM bank number that selects an MBC3 clock register
+const RTC_FIRST_REGISTER: u8 = 0x08;
+/// Writable bits of the seconds, minutes, hours, day-low and day-high registers
+const RTC_REGISTER_MASKS: [Byte; 5] = [0x3F, 0x3F, 0x1F, 0xFF, 0xC1];
+/// Day-high bit 0: bit 8 of the day counter
+const RTC_DAY_HIGH_BIT8: Byte = 0x01;
+/// Day-high bit 6: clock stopped
+const RTC_HALT: Byte = 0x40;
+/// Day-high bit 7: day counter overflowed past 511
+const RTC_DAY_CARRY: Byte = 0x80;
+/// Size of the clock footer appended to battery saves
+#[cfg(feature = "std")]
+const RTC_SAVE_SIZE: usize = 48;
+
+/// Seconds since the Unix epoch, used to advance the MBC3 clock
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Without `std`, or on wasm32 where `SystemTime::now` panics, there is no
+/// wall clock, so the MBC3 clock stands still
+#[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+fn unix_time() -> u64 {
+    0
+}
+
+/// MBC3 real-time clock
+///
+/// The live registers catch up with the host clock whenever they are
+/// touched; the game only sees the copy frozen by the last latch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+struct Rtc {
+    /// Seconds, minutes, hours, day-low, day-high
+    regs: [Byte; 5],
+    /// Copy of `regs` returned by reads
+    latched: [Byte; 5],
+    /// 0x00 was written to the latch register; 0x01 next latches
+    latch_armed: bool,
+    /// Unix time the live registers were last brought up to date
+    last_sync: u64,
+}
+
+impl Rtc {
+    /// Clock at zero, counting from `now`
+    fn new(now: u64) -> Self {
+        Self { regs: [0; 5], latched: [0; 5], latch_armed: false, last_sync: now }
+    }
+
+    /// Advance the live registers to `now`, unless the clock is halted
+    fn sync(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.last_sync);
+        self.last_sync = now;
+        if elapsed == 0 || self.regs[4] & RTC_HALT != 0 {
+            return;
+        }
+
+        let day = u64::from(self.regs[3]) | (u64::from(self.regs[4] & RTC_DAY_HIGH_BIT8) << 8);
+        let total = u64::from(self.regs[0])
+            + u64::from(self.regs[1]) * 60
+            + u64::from(self.regs[2]) * 3600
+            + day * 86400
+            + elapsed;
+        let day = total / 86400;
+
+        self.regs[0] = (total % 60) as Byte;
+        self.regs[1] = (total / 60 % 60) as Byte;
+        self.regs[2] = (total / 3600 % 24) as Byte;
+        self.regs[3] = day as Byte;
+        self.regs[4] = (self.regs[4] & !RTC_DAY_HIGH_BIT8) | ((day >> 8) & 1) as Byte;
+        if day > 0x1FF {
+            self.regs[4] |= RTC_DAY_CARRY;
+        }
+    }
+
+    /// Handle a write to 0x6000-0x7FFF: 0x00 then 0x01 latches the clock
+    fn write_latch(&mut self, value: Byte, now: u64) {
+        if self.latch_armed && value == 0x01 {
+            self.sync(now);
+            self.latched = self.regs;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    /// Latched value of register `index` (0-4)
+    fn read(&self, index: usize) -> Byte {
+        self.latched[index]
+    }
+
+    /// Set live register `index` (0-4), keeping only its defined bits
+    fn write(&mut self, index: usize, value: Byte, now: u64) {
+        self.sync(now);
+        self.regs[index] = value & RTC_REGISTER_MASKS[index];
+        self.latched[index] = self.regs[index];
+    }
+
+    /// Clock footer in the format shared with BGB and VBA-M: live and
+    /// latched registers as little-endian u32s, then the Unix timestamp
+    #[cfg(feature = "std")]
+    fn to_save_bytes(&self) -> [Byte; RTC_SAVE_SIZE] {
+        let mut data = [0; RTC_SAVE_SIZE];
+        for (i, &value) in self.regs.iter().chain(self.latched.iter()).enumerate() {
+            data[i * 4..i * 4 + 4].copy_from_slice(&u32::from(value).to_le_bytes());
+        }
+        data[40..].copy_from_slice(&self.last_sync.to_le_bytes());
+        data
+    }
+
+    /// Parse a footer written by `to_save_bytes`
+    #[cfg(feature = "std")]
+    fn from_save_bytes(data: &[Byte]) -> Option<Self> {
+        let data: &[Byte; RTC_SAVE_SIZE] = data.try_into().ok()?;
+        let mut rtc = Self::new(u64::from_le_bytes(data[40..].try_into().ok()?));
+        for i in 0..5 {
+            rtc.regs[i] = data[i * 4] & RTC_REGISTER_MASKS[i];
+            rtc.latched[i] = data[20 + i * 4] & RTC_REGISTER_MASKS[i];
+        }
+        Some(rtc)
     }
 }
 
 /// Cartridge emulation
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Cartridge {
-    /// ROM file path
+    /// ROM file path (used for the battery save path; empty for in-memory ROMs)
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
     filename: String,
-    /// ROM data
-    pub rom: Vec<Byte>,
+    /// ROM data (shared between forked emulators)
+    pub rom: Arc<Vec<Byte>>,
     /// Parsed ROM header
     pub header: RomHeader,
     /// RAM enabled flag (for MBC)
@@ -132,15 +326,79 @@ pub struct Cartridge {
     ram_bank: u8,
     /// Banking mode (0 = ROM, 1 = RAM)
     banking_mode: u8,
+    /// HuC1: 0xA000-0xBFFF maps the infrared register instead of RAM
+    ir_mode: bool,
     /// Cartridge RAM
     ram: Vec<Byte>,
+    /// MBC3 real-time clock, for MBC3+TIMER cartridges
+    rtc: Option<Rtc>,
     /// Battery backup flag
     battery: bool,
+    /// MBC5 rumble motor running
+    rumble: bool,
     /// RAM needs to be saved
     need_save: bool,
+    /// Where battery saves are read from and written to
+    #[cfg(feature = "std")]
+    save_strategy: SavePathStrategy,
+}
+
+/// Banking registers and RAM of a cartridge, as kept in a save state
+///
+/// The ROM itself is not included; save states are matched to a ROM by hash.
+#[cfg(feature = "save-state")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MbcState {
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    banking_mode: u8,
+    ir_mode: bool,
+    rumble: bool,
+    ram: Vec<Byte>,
+    rtc: Option<Rtc>,
 }
 
 impl Cartridge {
+    /// Capture the banking registers and cartridge RAM
+    #[cfg(feature = "save-state")]
+    pub fn mbc_state(&self) -> MbcState {
+        MbcState {
+            ram_enabled: self.ram_enabled,
+            rom_bank: self.rom_bank,
+            ram_bank: self.ram_bank,
+            banking_mode: self.banking_mode,
+            ir_mode: self.ir_mode,
+            rumble: self.rumble,
+            ram: self.ram.clone(),
+            rtc: self.rtc.clone(),
+        }
+    }
+
+    /// Restore banking registers and cartridge RAM from a save state
+    ///
+    /// Fails without changing anything if the saved RAM size differs.
+    #[cfg(feature = "save-state")]
+    pub fn load_mbc_state(&mut self, state: MbcState) -> Result<(), EmulatorError> {
+        if state.ram.len() != self.ram.len() {
+            return Err(EmulatorError::InvalidSaveState(format!(
+                "cartridge RAM is {} bytes, state has {}",
+                self.ram.len(),
+                state.ram.len()
+            )));
+        }
+        self.ram_enabled = state.ram_enabled;
+        self.rom_bank = state.rom_bank;
+        self.ram_bank = state.ram_bank;
+        self.banking_mode = state.banking_mode;
+        self.ir_mode = state.ir_mode;
+        self.rumble = state.rumble;
+        self.ram = state.ram;
+        self.rtc = state.rtc;
+        self.need_save = self.battery;
+        Ok(())
+    }
+
     /// Number of 16KB ROM banks available in this cartridge
     fn rom_bank_count(&self) -> usize {
         (self.rom.len() / 0x4000).max(1)
@@ -185,43 +443,154 @@ impl Cartridge {
         }
     }
 
+    /// ROM bank currently mapped at 0x4000-0x7FFF
+    pub fn current_rom_bank(&self) -> usize {
+        if self.is_mbc1() {
+            return self.mbc1_romx_bank();
+        }
+        let bank_count = self.rom_bank_count();
+        let bank = (self.rom_bank as usize) % bank_count;
+        if bank == 0 && bank_count > 1 {
+            1
+        } else {
+            bank
+        }
+    }
+
+    /// RAM bank currently mapped at 0xA000-0xBFFF
+    pub fn current_ram_bank(&self) -> usize {
+        // MBC3: RAM bank 0-3 (0x08-0x0C select the clock, see `rtc_register`)
+        // HuC1: RAM bank 0-3
+        // Pocket Camera: RAM bank 0-15
+        // MBC5: RAM bank 0-15 (0-7 with rumble, see `write`)
+        // MBC1: RAM bank depends on banking_mode
+        let bank = if self.is_camera() || self.is_mbc5() {
+            (self.ram_bank & 0x0F) as usize
+        } else if self.is_mbc3() || self.is_huc1() {
+            (self.ram_bank & 0x03) as usize
+        } else if self.banking_mode == 1 {
+            self.ram_bank as usize
+        } else {
+            0
+        };
+        bank % self.ram_bank_count()
+    }
+
     /// Load a cartridge from a ROM file
+    ///
+    /// ROMs within 10% of the size the header declares still load; call
+    /// `verify_rom_integrity` to report the mismatch.
+    #[cfg(feature = "std")]
     pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let path = path.as_ref();
-        let filename = path.to_string_lossy().to_string();
-        
         let rom = fs::read(path)?;
-        
+
+        let mut cart = Self::from_bytes(rom)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        cart.filename = path.to_string_lossy().to_string();
+
+        match cart.verify_rom_integrity() {
+            Ok(()) => {}
+            // Small mismatches are only a warning, left to the caller
+            Err(EmulatorError::RomIntegrity { expected_size, actual_size })
+                if expected_size.abs_diff(actual_size) * 10 < expected_size => {}
+            Err(e @ EmulatorError::RomIntegrity { .. }) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+            }
+            // Checksum problems were already reported by `from_bytes`
+            Err(_) => {}
+        }
+
+        // Load battery save if exists
+        if cart.battery {
+            cart.load_battery_save();
+        }
+
+        Ok(cart)
+    }
+
+    /// Create a cartridge from an in-memory ROM image
+    ///
+    /// Battery-backed RAM is not persisted for cartridges created this way
+    /// unless `enable_battery_saves` is called.
+    pub fn from_bytes(rom: Vec<Byte>) -> Result<Self, EmulatorError> {
         let header = RomHeader::parse(&rom)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid ROM header"))?;
-        
+            .ok_or_else(|| EmulatorError::InvalidRom(String::from("Invalid ROM header")))?;
+
         // Validate checksum
+        #[cfg(feature = "std")]
         if !Self::validate_checksum(&rom) {
             eprintln!("Warning: ROM header checksum invalid");
         }
-        
-        let ram_size = header.ram_size_bytes();
+
+        let mut ram_size = header.ram_size_bytes();
+        if header.cart_type == 0x1F {
+            // The Pocket Camera always carries 128KB (16 banks) of RAM
+            ram_size = ram_size.max(0x20000);
+        }
         let battery = header.has_battery();
-        
-        let mut cart = Self {
-            filename: filename.clone(),
-            rom,
+        let rtc = matches!(header.cart_type, 0x0F | 0x10).then(|| Rtc::new(unix_time()));
+
+        Ok(Self {
+            filename: String::new(),
+            rom: Arc::new(rom),
             header,
             ram_enabled: false,
             rom_bank: 1,
             ram_bank: 0,
             banking_mode: 0,
+            ir_mode: false,
             ram: vec![0; ram_size],
+            rtc,
             battery,
+            rumble: false,
             need_save: false,
-        };
-        
-        // Load battery save if exists
-        if battery {
-            cart.load_battery_save();
+            #[cfg(feature = "std")]
+            save_strategy: SavePathStrategy::default(),
+        })
+    }
+
+    /// Create a cartridge from an async byte source (e.g. a network response)
+    ///
+    /// The whole stream is read into memory and handed to `from_bytes`.
+    #[cfg(feature = "async")]
+    pub async fn from_async_bytes<R>(mut reader: R) -> Result<Self, EmulatorError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        use tokio::io::AsyncReadExt;
+
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom).await?;
+        Self::from_bytes(rom)
+    }
+
+    /// Check the ROM image against its header
+    ///
+    /// Fails if the image size differs from the declared ROM size by more
+    /// than 1%, if a declared bank starts past the end of the image, or if
+    /// the header checksum is wrong.
+    pub fn verify_rom_integrity(&self) -> Result<(), EmulatorError> {
+        let expected_size = self.header.rom_size_bytes();
+        let actual_size = self.rom.len();
+        let mismatch = EmulatorError::RomIntegrity { expected_size, actual_size };
+
+        if expected_size.abs_diff(actual_size) * 100 > expected_size {
+            return Err(mismatch);
         }
-        
-        Ok(cart)
+        if (0..expected_size).step_by(0x4000).any(|start| start >= actual_size) {
+            return Err(mismatch);
+        }
+        if !Self::validate_checksum(&self.rom) {
+            return Err(EmulatorError::InvalidRom(String::from("Header checksum mismatch")));
+        }
+        Ok(())
+    }
+
+    /// Loading from a file requires `std`
+    #[cfg(not(feature = "std"))]
+    pub fn load(_path: &str) -> Result<Self, EmulatorError> {
+        Err(EmulatorError::NotSupported)
     }
 
     /// Validate ROM header checksum
@@ -230,10 +599,9 @@ impl Cartridge {
             return false;
         }
         
-        let mut checksum: u8 = 0;
-        for i in 0x134..=0x14C {
-            checksum = checksum.wrapping_sub(rom_data[i]).wrapping_sub(1);
-        }
+        let checksum = rom_data[0x134..=0x14C]
+            .iter()
+            .fold(0u8, |acc, &b| acc.wrapping_sub(b).wrapping_sub(1));
         
         checksum == rom_data[HEADER_CHECKSUM]
     }
@@ -244,10 +612,9 @@ impl Cartridge {
             return 0;
         }
         
-        let mut checksum: u8 = 0;
-        for i in 0x134..=0x14C {
-            checksum = checksum.wrapping_sub(rom_data[i]).wrapping_sub(1);
-        }
+        let checksum = rom_data[0x134..=0x14C]
+            .iter()
+            .fold(0u8, |acc, &b| acc.wrapping_sub(b).wrapping_sub(1));
         checksum
     }
 
@@ -266,36 +633,31 @@ impl Cartridge {
             }
             // ROM Bank 1-N (0x4000-0x7FFF)
             0x4000..=0x7FFF => {
-                let bank = if self.is_mbc1() {
-                    self.mbc1_romx_bank()
-                } else {
-                    let bank_count = self.rom_bank_count();
-                    let mut bank = (self.rom_bank as usize) % bank_count;
-                    if bank == 0 && bank_count > 1 {
-                        bank = 1;
-                    }
-                    bank
-                };
-                
+                let bank = self.current_rom_bank();
                 let addr = (bank * 0x4000) + ((address as usize) - 0x4000);
                 self.rom.get(addr).copied().unwrap_or(0xFF)
             }
             // Cartridge RAM (0xA000-0xBFFF)
             0xA000..=0xBFFF => {
+                if self.ir_mode {
+                    // HuC1 IR register: no light received
+                    return 0xC0;
+                }
+                if self.camera_registers_selected() {
+                    // Camera sensor registers are not emulated
+                    return 0xFF;
+                }
+                if let Some(index) = self.rtc_register() {
+                    return match &self.rtc {
+                        Some(rtc) if self.ram_enabled => rtc.read(index),
+                        _ => 0xFF,
+                    };
+                }
                 if !self.ram_enabled || self.ram.is_empty() {
                     return 0xFF;
                 }
                 
-                // MBC3: RAM bank 0-3 (RTC registers 0x08-0x0C not implemented)
-                // MBC1: RAM bank depends on banking_mode
-                let bank = if self.is_mbc3() {
-                    (self.ram_bank & 0x03) as usize
-                } else if self.banking_mode == 1 {
-                    self.ram_bank as usize
-                } else {
-                    0
-                };
-                let bank = bank % self.ram_bank_count();
+                let bank = self.current_ram_bank();
                 let addr = (bank * 0x2000) + ((address as usize) - 0xA000);
                 self.ram.get(addr).copied().unwrap_or(0xFF)
             }
@@ -308,9 +670,16 @@ impl Cartridge {
         match address {
             // RAM Enable (0x0000-0x1FFF)
             0x0000..=0x1FFF => {
-                if self.is_mbc1() || self.is_mbc3() || self.is_mbc5() {
+                if self.is_mbc1() || self.is_mbc3() || self.is_mbc5() || self.is_camera() {
                     // Enable RAM if lower nibble is 0x0A
                     self.ram_enabled = (value & 0x0F) == 0x0A;
+                } else if self.is_huc1() {
+                    // HuC1: 0x0A maps RAM, 0x0E maps the IR register, anything else neither
+                    (self.ram_enabled, self.ir_mode) = match value {
+                        0x0A => (true, false),
+                        0x0E => (false, true),
+                        _ => (false, false),
+                    };
                 }
             }
             // ROM Bank Number (0x2000-0x3FFF)
@@ -322,8 +691,8 @@ impl Cartridge {
                         bank = 1;
                     }
                     self.rom_bank = bank;
-                } else if self.is_mbc3() {
-                    // MBC3: 7-bit bank number
+                } else if self.is_mbc3() || self.is_camera() {
+                    // MBC3 / Pocket Camera: 7-bit bank number
                     let mut bank = value & 0x7F;
                     if bank == 0 {
                         bank = 1;
@@ -332,6 +701,9 @@ impl Cartridge {
                 } else if self.is_mbc5() {
                     // MBC5: 8-bit bank number (low byte)
                     self.rom_bank = value;
+                } else if self.is_huc1() {
+                    // HuC1: 6-bit bank number
+                    self.rom_bank = value & 0x3F;
                 }
             }
             // RAM Bank Number / Upper ROM Bank (0x4000-0x5FFF)
@@ -342,31 +714,54 @@ impl Cartridge {
                 } else if self.is_mbc3() {
                     // MBC3: RAM bank (0-3) or RTC register select
                     self.ram_bank = value & 0x0F;
+                } else if self.is_huc1() {
+                    // HuC1: RAM bank (0-3)
+                    self.ram_bank = value & 0x03;
+                } else if self.is_camera() {
+                    // Pocket Camera: RAM bank (0-15), bit 4 selects the camera registers
+                    self.ram_bank = value & 0x1F;
+                } else if self.header.has_rumble() {
+                    // MBC5+RUMBLE: RAM bank (0-7), bit 3 drives the motor
+                    self.ram_bank = value & 0x07;
+                    self.rumble = value & 0x08 != 0;
+                } else if self.is_mbc5() {
+                    // MBC5: RAM bank (0-15)
+                    self.ram_bank = value & 0x0F;
                 }
             }
-            // Banking Mode Select (0x6000-0x7FFF)
+            // Banking Mode Select / Clock Latch (0x6000-0x7FFF)
             0x6000..=0x7FFF => {
                 if self.is_mbc1() {
                     // MBC1: 0 = ROM banking, 1 = RAM banking
                     self.banking_mode = value & 0x01;
+                } else if self.is_mbc3() {
+                    // MBC3: latch the RTC registers
+                    if let Some(rtc) = self.rtc.as_mut() {
+                        rtc.write_latch(value, unix_time());
+                    }
                 }
             }
             // Cartridge RAM (0xA000-0xBFFF)
             0xA000..=0xBFFF => {
+                if self.ir_mode {
+                    // HuC1 IR LED control; there is no receiver to drive
+                    return;
+                }
+                if self.camera_registers_selected() {
+                    return;
+                }
+                if let Some(index) = self.rtc_register() {
+                    if let Some(rtc) = self.rtc.as_mut().filter(|_| self.ram_enabled) {
+                        rtc.write(index, value, unix_time());
+                        self.need_save = true;
+                    }
+                    return;
+                }
                 if !self.ram_enabled || self.ram.is_empty() {
                     return;
                 }
                 
-                // MBC3: RAM bank 0-3 (RTC registers 0x08-0x0C not implemented)
-                // MBC1: RAM bank depends on banking_mode
-                let bank = if self.is_mbc3() {
-                    (self.ram_bank & 0x03) as usize
-                } else if self.banking_mode == 1 {
-                    self.ram_bank as usize
-                } else {
-                    0
-                };
-                let bank = bank % self.ram_bank_count();
+                let bank = self.current_ram_bank();
                 let addr = (bank * 0x2000) + ((address as usize) - 0xA000);
                 
                 if addr < self.ram.len() {
@@ -393,38 +788,205 @@ impl Cartridge {
         matches!(self.header.cart_type, 0x19..=0x1E)
     }
 
-    /// Get save file path
-    fn save_path(&self) -> String {
-        format!("{}.sav", self.filename)
+    /// Whether the rumble motor is running (always false without one)
+    pub fn rumble_active(&self) -> bool {
+        self.rumble
+    }
+
+    /// Check if this is a HuC1 cartridge
+    fn is_huc1(&self) -> bool {
+        self.header.cart_type == 0xFF
+    }
+
+    /// Check if this is a Pocket Camera cartridge
+    fn is_camera(&self) -> bool {
+        self.header.cart_type == 0x1F
+    }
+
+    /// MBC3 clock register (0-4) mapped at 0xA000, if one is selected
+    fn rtc_register(&self) -> Option<usize> {
+        if !self.is_mbc3() {
+            return None;
+        }
+        match self.ram_bank {
+            0x08..=0x0C => Some((self.ram_bank - RTC_FIRST_REGISTER) as usize),
+            _ => None,
+        }
+    }
+
+    /// Check if the Pocket Camera register bank is mapped at 0xA000
+    fn camera_registers_selected(&self) -> bool {
+        self.is_camera() && self.ram_bank & 0x10 != 0
+    }
+
+    /// Choose where battery saves are stored
+    ///
+    /// Unless the RAM has unsaved changes, the save at the new location (if
+    /// any) is loaded in place of the current RAM contents.
+    #[cfg(feature = "std")]
+    pub fn set_save_strategy(&mut self, strategy: SavePathStrategy) {
+        self.save_strategy = strategy;
+        if self.battery && !self.need_save && !self.filename.is_empty() {
+            self.load_battery_save();
+        }
+    }
+
+    /// Persist battery saves for a cartridge created with `from_bytes`
+    ///
+    /// `rom_path` stands in for the ROM file location: `SiblingFile` saves
+    /// next to it, and title-based strategies use its stem when the header
+    /// title is blank. An existing save is loaded unless the RAM has unsaved
+    /// changes.
+    #[cfg(feature = "std")]
+    pub fn enable_battery_saves(&mut self, rom_path: impl Into<String>) {
+        self.filename = rom_path.into();
+        if self.battery && !self.need_save && !self.filename.is_empty() {
+            self.load_battery_save();
+        }
+    }
+
+    /// Stop this cartridge from reading or writing a battery save
+    ///
+    /// `save_battery` and dropping the cartridge then leave the save file
+    /// alone, as for cartridges created with `from_bytes`.
+    pub fn detach_battery_save(&mut self) {
+        self.filename.clear();
+    }
+
+    /// Current save location strategy
+    #[cfg(feature = "std")]
+    pub fn save_strategy(&self) -> &SavePathStrategy {
+        &self.save_strategy
+    }
+
+    /// File name used for title-based saves
+    ///
+    /// Falls back to the ROM file stem when the header title is blank, and
+    /// replaces characters that are not safe in file names.
+    #[cfg(feature = "std")]
+    fn save_file_name(&self) -> String {
+        let title = self.header.title.trim();
+        let stem = if title.is_empty() {
+            Path::new(&self.filename)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| String::from("untitled"))
+        } else {
+            title.to_string()
+        };
+        let stem: String = stem
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') { c } else { '_' })
+            .collect();
+        format!("{}.sav", stem)
+    }
+
+    /// Path of the battery save for the current strategy
+    #[cfg(feature = "std")]
+    pub fn effective_save_path(&self) -> PathBuf {
+        match &self.save_strategy {
+            SavePathStrategy::SiblingFile => PathBuf::from(format!("{}.sav", self.filename)),
+            SavePathStrategy::XdgData => SavePathStrategy::xdg_save_dir().join(self.save_file_name()),
+            SavePathStrategy::Custom(dir) => dir.join(self.save_file_name()),
+        }
+    }
+
+    /// Save next to the ROM, where older versions kept every save
+    #[cfg(feature = "std")]
+    fn legacy_save_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.sav", self.filename))
     }
 
     /// Load battery save from file
+    ///
+    /// A save left next to the ROM by older versions is used if none exists
+    /// at the effective path yet; it is written to the new location on the
+    /// next save.
+    #[cfg(feature = "std")]
     fn load_battery_save(&mut self) {
-        let save_path = self.save_path();
-        if let Ok(mut file) = fs::File::open(&save_path) {
-            let _ = file.read_exact(&mut self.ram);
-            println!("Loaded save file: {}", save_path);
+        let mut save_path = self.effective_save_path();
+        if !save_path.exists() {
+            save_path = self.legacy_save_path();
+        }
+        if let Ok(data) = fs::read(&save_path) {
+            let ram_len = self.ram.len().min(data.len());
+            self.ram[..ram_len].copy_from_slice(&data[..ram_len]);
+            if let Some(rtc) = self.rtc.as_mut() {
+                let footer = data.get(self.ram.len()..).and_then(Rtc::from_save_bytes);
+                if let Some(saved) = footer {
+                    *rtc = saved;
+                }
+            }
+            println!("Loaded save file: {}", save_path.display());
         }
     }
 
     /// Save battery backup to file
+    #[cfg(feature = "std")]
     pub fn save_battery(&mut self) -> io::Result<()> {
-        if !self.battery || !self.need_save {
+        if !self.battery || !self.need_save || self.filename.is_empty() {
             return Ok(());
         }
         
-        let save_path = self.save_path();
+        let save_path = self.effective_save_path();
+        if let Some(dir) = save_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
         let mut file = fs::File::create(&save_path)?;
         file.write_all(&self.ram)?;
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.sync(unix_time());
+            file.write_all(&rtc.to_save_bytes())?;
+        }
         self.need_save = false;
-        println!("Saved to: {}", save_path);
+        println!("Saved to: {}", save_path.display());
         Ok(())
     }
 
+    /// Saving to a file requires `std`
+    #[cfg(not(feature = "std"))]
+    pub fn save_battery(&mut self) -> Result<(), EmulatorError> {
+        if !self.battery || !self.need_save {
+            return Ok(());
+        }
+        Err(EmulatorError::NotSupported)
+    }
+
     /// Check if save is needed
     pub fn needs_save(&self) -> bool {
         self.battery && self.need_save
     }
+
+    /// Clear cartridge RAM, reset the MBC3 clock and delete the battery save, if any
+    ///
+    /// Both the save at the effective path and a legacy save next to the
+    /// ROM are removed, so neither comes back on the next load; use
+    /// `backup_save_data` first to keep a copy.
+    pub fn erase_save_data(&mut self) -> Result<(), EmulatorError> {
+        self.ram.fill(0);
+        if let Some(rtc) = self.rtc.as_mut() {
+            *rtc = Rtc::new(unix_time());
+        }
+        self.need_save = false;
+
+        #[cfg(feature = "std")]
+        if self.battery && !self.filename.is_empty() {
+            for path in [self.effective_save_path(), self.legacy_save_path()] {
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the battery save file to `dest`
+    #[cfg(feature = "std")]
+    pub fn backup_save_data(&self, dest: impl AsRef<Path>) -> Result<(), EmulatorError> {
+        fs::copy(self.effective_save_path(), dest)?;
+        Ok(())
+    }
 }
 
 impl Drop for Cartridge {
@@ -497,4 +1059,515 @@ mod tests {
         let header = RomHeader::parse(&rom).unwrap();
         assert_eq!(header.cart_type_name(), "ROM ONLY");
     }
+
+    #[test]
+    fn test_header_cgb_flag() {
+        let mut rom = create_test_rom();
+        assert!(!RomHeader::parse(&rom).unwrap().supports_cgb());
+
+        rom[HEADER_CGB_FLAG] = 0x80;
+        let header = RomHeader::parse(&rom).unwrap();
+        assert!(header.supports_cgb());
+        assert_eq!(header.title, "TEST ROM");
+    }
+
+    /// Build a 128KB HuC1 image with 32KB RAM where each ROM bank starts with its number
+    fn create_huc1_rom() -> Vec<Byte> {
+        let mut rom = create_test_rom();
+        rom.resize(0x20000, 0);
+        for bank in 0..8 {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom[HEADER_CART_TYPE] = 0xFF;
+        rom[HEADER_ROM_SIZE] = 0x02;
+        rom[HEADER_RAM_SIZE] = 0x03;
+        rom[HEADER_CHECKSUM] = Cartridge::calculate_checksum(&rom);
+        rom
+    }
+
+    #[test]
+    fn test_huc1_bank_selection() {
+        let mut cart = Cartridge::from_bytes(create_huc1_rom()).unwrap();
+        assert!(cart.is_huc1());
+        assert_eq!(cart.header.cart_type_name(), "HuC1+RAM+BATTERY");
+
+        cart.write(0x2000, 0x05);
+        assert_eq!(cart.read(0x4000), 5);
+
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x02);
+        cart.write(0xA000, 0x22);
+        cart.write(0x4000, 0x00);
+        assert_eq!(cart.read(0xA000), 0x00);
+        cart.write(0x4000, 0x02);
+        assert_eq!(cart.read(0xA000), 0x22);
+    }
+
+    /// Build a 128KB MBC5 image of `cart_type` with 128KB RAM
+    fn create_mbc5_rom(cart_type: Byte) -> Vec<Byte> {
+        let mut rom = create_test_rom();
+        rom.resize(0x20000, 0);
+        rom[HEADER_CART_TYPE] = cart_type;
+        rom[HEADER_ROM_SIZE] = 0x02;
+        rom[HEADER_RAM_SIZE] = 0x04;
+        rom[HEADER_CHECKSUM] = Cartridge::calculate_checksum(&rom);
+        rom
+    }
+
+    #[test]
+    fn test_mbc5_ram_bank_selection() {
+        let mut cart = Cartridge::from_bytes(create_mbc5_rom(0x1B)).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x0B);
+        assert_eq!(cart.current_ram_bank(), 11);
+        cart.write(0xA000, 0x42);
+        cart.write(0x4000, 0x03);
+        assert_eq!(cart.read(0xA000), 0x00);
+        cart.write(0x4000, 0x0B);
+        assert_eq!(cart.read(0xA000), 0x42);
+        assert!(!cart.rumble_active());
+    }
+
+    #[test]
+    fn test_mbc5_rumble() {
+        let mut cart = Cartridge::from_bytes(create_mbc5_rom(0x1E)).unwrap();
+        assert!(cart.header.has_rumble());
+        assert!(cart.header.has_battery() && cart.header.has_ram());
+        assert_eq!(cart.header.cart_type_name(), "MBC5+RUMBLE+RAM+BATTERY");
+        assert!(!cart.rumble_active());
+
+        // Bit 3 drives the motor and is masked out of the RAM bank
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x0B);
+        assert!(cart.rumble_active());
+        assert_eq!(cart.current_ram_bank(), 3);
+        cart.write(0xA000, 0x42);
+
+        cart.write(0x4000, 0x03);
+        assert!(!cart.rumble_active());
+        assert_eq!(cart.current_ram_bank(), 3);
+        assert_eq!(cart.read(0xA000), 0x42);
+
+        cart.write(0x5FFF, 0x08);
+        assert!(cart.rumble_active());
+        assert_eq!(cart.current_ram_bank(), 0);
+        assert_eq!(cart.read(0xA000), 0x00);
+    }
+
+    #[cfg(feature = "save-state")]
+    #[test]
+    fn test_mbc_state_keeps_rumble() {
+        let mut cart = Cartridge::from_bytes(create_mbc5_rom(0x1C)).unwrap();
+        cart.write(0x4000, 0x08);
+        let state = cart.mbc_state();
+
+        cart.write(0x4000, 0x00);
+        cart.load_mbc_state(state).unwrap();
+        assert!(cart.rumble_active());
+    }
+
+    #[test]
+    fn test_huc1_ir_mode() {
+        let mut cart = Cartridge::from_bytes(create_huc1_rom()).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x55);
+
+        cart.write(0x0000, 0x0E);
+        assert!(cart.ir_mode);
+        assert_eq!(cart.read(0xA000), 0xC0);
+        cart.write(0xA000, 0x01);
+
+        cart.write(0x0000, 0x0A);
+        assert!(!cart.ir_mode);
+        assert_eq!(cart.read(0xA000), 0x55);
+    }
+
+    #[test]
+    fn test_huc1_ram_enable_needs_0x0a() {
+        let mut cart = Cartridge::from_bytes(create_huc1_rom()).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x55);
+
+        for value in [0x00, 0xFF] {
+            cart.write(0x0000, value);
+            assert!(!cart.ram_enabled, "{:02X}", value);
+            assert!(!cart.ir_mode, "{:02X}", value);
+            assert_eq!(cart.read(0xA000), 0xFF);
+            cart.write(0xA000, 0x11);
+        }
+
+        cart.write(0x0000, 0x0A);
+        assert_eq!(cart.read(0xA000), 0x55);
+    }
+
+    #[test]
+    fn test_pocket_camera_stub() {
+        let mut rom = create_test_rom();
+        rom[HEADER_CART_TYPE] = 0x1F;
+        rom[HEADER_CHECKSUM] = Cartridge::calculate_checksum(&rom);
+        let mut cart = Cartridge::from_bytes(rom).unwrap();
+        assert!(cart.is_camera());
+        assert_eq!(cart.header.cart_type_name(), "POCKET CAMERA");
+        assert!(cart.header.has_battery());
+        assert_eq!(cart.ram.len(), 0x20000);
+
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x0F);
+        cart.write(0xA000, 0x77);
+        cart.write(0x4000, 0x00);
+        assert_eq!(cart.read(0xA000), 0x00);
+        cart.write(0x4000, 0x0F);
+        assert_eq!(cart.read(0xA000), 0x77);
+
+        // Register bank: reads 0xFF and leaves RAM untouched
+        cart.write(0x4000, 0x10);
+        cart.write(0xA000, 0x01);
+        assert_eq!(cart.read(0xA000), 0xFF);
+        cart.write(0x4000, 0x00);
+        assert_eq!(cart.read(0xA000), 0x00);
+    }
+
+    /// Build a 32KB MBC3+TIMER+RAM+BATTERY image with 8KB RAM
+    fn create_mbc3_rtc_rom() -> Vec<Byte> {
+        let mut rom = create_test_rom();
+        rom[HEADER_CART_TYPE] = 0x10;
+        rom[HEADER_RAM_SIZE] = 0x02;
+        rom[HEADER_CHECKSUM] = Cartridge::calculate_checksum(&rom);
+        rom
+    }
+
+    /// Latch the clock and read all five registers
+    fn read_rtc(cart: &mut Cartridge) -> [Byte; 5] {
+        cart.write(0x6000, 0x00);
+        cart.write(0x6000, 0x01);
+        let mut regs = [0; 5];
+        for (i, reg) in regs.iter_mut().enumerate() {
+            cart.write(0x4000, 0x08 + i as Byte);
+            *reg = cart.read(0xA000);
+        }
+        regs
+    }
+
+    #[test]
+    fn test_mbc3_rtc_register_masking() {
+        let mut cart = Cartridge::from_bytes(create_mbc3_rtc_rom()).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x0C);
+        cart.write(0xA000, RTC_HALT);
+
+        for (i, mask) in RTC_REGISTER_MASKS.iter().enumerate() {
+            cart.write(0x4000, 0x08 + i as Byte);
+            cart.write(0xA000, 0xFF);
+            assert_eq!(cart.read(0xA000), *mask, "register {:02X}", 0x08 + i);
+        }
+
+        // Clock registers do not touch RAM, and need RAM enabled
+        cart.write(0x4000, 0x00);
+        assert_eq!(cart.read(0xA000), 0x00);
+        cart.write(0x0000, 0x00);
+        cart.write(0x4000, 0x08);
+        assert_eq!(cart.read(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_mbc3_rtc_latch_sequence() {
+        let mut cart = Cartridge::from_bytes(create_mbc3_rtc_rom()).unwrap();
+        cart.write(0x0000, 0x0A);
+        read_rtc(&mut cart);
+        // Halted, so the wall clock cannot move the values under test
+        cart.rtc.as_mut().unwrap().regs = [30, 15, 2, 0, RTC_HALT];
+
+        // Reads stay frozen at the last latch
+        cart.write(0x4000, 0x08);
+        assert_eq!(cart.read(0xA000), 0);
+
+        // Only 0x00 immediately followed by 0x01 latches
+        cart.write(0x6000, 0x01);
+        assert_eq!(cart.read(0xA000), 0);
+        cart.write(0x6000, 0x00);
+        cart.write(0x6000, 0x02);
+        cart.write(0x6000, 0x01);
+        assert_eq!(cart.read(0xA000), 0);
+
+        assert_eq!(read_rtc(&mut cart), [30, 15, 2, 0, RTC_HALT]);
+    }
+
+    #[test]
+    fn test_mbc3_rtc_advances_and_halts() {
+        let mut rtc = Rtc::new(1000);
+        rtc.sync(1000 + 3600 * 25 + 61);
+        assert_eq!(rtc.regs, [1, 1, 1, 1, 0]);
+
+        rtc.sync(1000 + 86400 * 300);
+        assert_eq!(rtc.regs[3..], [0x2C, 0x01]);
+
+        rtc.regs[4] |= RTC_HALT;
+        let halted = rtc.regs;
+        rtc.sync(1000 + 86400 * 400);
+        assert_eq!(rtc.regs, halted);
+
+        // Day 511 rolls over to 0 and sets the carry bit
+        rtc.regs = [59, 59, 23, 0xFF, RTC_DAY_HIGH_BIT8];
+        rtc.sync(rtc.last_sync + 1);
+        assert_eq!(rtc.regs, [0, 0, 0, 0, RTC_DAY_CARRY]);
+    }
+
+    #[test]
+    fn test_mbc3_rtc_saved_with_battery() {
+        let dir = std::env::temp_dir().join(format!("rgbe_rtc_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut cart = Cartridge::from_bytes(create_mbc3_rtc_rom()).unwrap();
+        cart.filename = String::from("test.gb");
+        cart.set_save_strategy(SavePathStrategy::Custom(dir.clone()));
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        cart.write(0x4000, 0x0C);
+        cart.write(0xA000, RTC_HALT);
+        cart.write(0x4000, 0x0A);
+        cart.write(0xA000, 0x07);
+        cart.save_battery().unwrap();
+
+        let saved = fs::read(cart.effective_save_path()).unwrap();
+        assert_eq!(saved.len(), 0x2000 + RTC_SAVE_SIZE);
+
+        let mut loaded = Cartridge::from_bytes(create_mbc3_rtc_rom()).unwrap();
+        loaded.filename = String::from("test.gb");
+        loaded.set_save_strategy(SavePathStrategy::Custom(dir.clone()));
+        let _ = fs::remove_dir_all(&dir);
+        loaded.write(0x0000, 0x0A);
+        assert_eq!(loaded.read(0xA000), 0x42);
+        assert_eq!(read_rtc(&mut loaded)[2..], [0x07, 0x00, RTC_HALT]);
+    }
+
+    #[test]
+    fn test_verify_rom_integrity() {
+        let cart = Cartridge::from_bytes(create_test_rom()).unwrap();
+        assert!(cart.verify_rom_integrity().is_ok());
+
+        // Header declares 64KB but only two banks are present
+        let mut rom = create_test_rom();
+        rom[HEADER_ROM_SIZE] = 0x01;
+        rom[HEADER_CHECKSUM] = Cartridge::calculate_checksum(&rom);
+        let cart = Cartridge::from_bytes(rom).unwrap();
+        assert!(matches!(
+            cart.verify_rom_integrity(),
+            Err(EmulatorError::RomIntegrity { expected_size: 0x10000, actual_size: 0x8000 })
+        ));
+
+        // A few trailing bytes are tolerated
+        let mut rom = create_test_rom();
+        rom.extend_from_slice(&[0; 16]);
+        assert!(Cartridge::from_bytes(rom).unwrap().verify_rom_integrity().is_ok());
+
+        let mut rom = create_test_rom();
+        rom[HEADER_CHECKSUM] ^= 0xFF;
+        assert!(matches!(
+            Cartridge::from_bytes(rom).unwrap().verify_rom_integrity(),
+            Err(EmulatorError::InvalidRom(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_rom_size_mismatch() {
+        let path = std::env::temp_dir().join(format!("rgbe_integrity_{}.gb", std::process::id()));
+
+        // 5% short: loads, with the mismatch left for the caller to report
+        let mut rom = create_test_rom();
+        rom.truncate(0x8000 - 0x600);
+        fs::write(&path, &rom).unwrap();
+        let cart = Cartridge::load(&path).unwrap();
+        assert!(matches!(
+            cart.verify_rom_integrity(),
+            Err(EmulatorError::RomIntegrity { expected_size: 0x8000, actual_size: 0x7A00 })
+        ));
+
+        // Half the declared size: rejected
+        rom.truncate(0x4000);
+        fs::write(&path, &rom).unwrap();
+        let result = Cartridge::load(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_effective_save_path() {
+        let mut cart = Cartridge::from_bytes(create_test_rom()).unwrap();
+        cart.filename = String::from("roms/test.gb");
+
+        cart.set_save_strategy(SavePathStrategy::SiblingFile);
+        let sibling = cart.effective_save_path();
+        assert_eq!(sibling, PathBuf::from("roms/test.gb.sav"));
+
+        cart.set_save_strategy(SavePathStrategy::XdgData);
+        let xdg = cart.effective_save_path();
+        assert!(xdg.ends_with("rgbe/saves/TEST ROM.sav"));
+
+        cart.set_save_strategy(SavePathStrategy::Custom(PathBuf::from("/saves")));
+        let custom = cart.effective_save_path();
+        assert_eq!(custom, PathBuf::from("/saves/TEST ROM.sav"));
+
+        assert_ne!(sibling, xdg);
+        assert_ne!(xdg, custom);
+    }
+
+    #[test]
+    fn test_save_battery_creates_directory() {
+        let mut rom = create_test_rom();
+        rom[HEADER_CART_TYPE] = 0x03; // MBC1+RAM+BATTERY
+        rom[HEADER_RAM_SIZE] = 0x02;
+        let dir = std::env::temp_dir().join(format!("rgbe_saves_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut cart = Cartridge::from_bytes(rom).unwrap();
+        cart.filename = String::from("test.gb");
+        cart.set_save_strategy(SavePathStrategy::Custom(dir.join("nested")));
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        cart.save_battery().unwrap();
+
+        let saved = fs::read(dir.join("nested/TEST ROM.sav")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(saved[0], 0x42);
+        assert!(!cart.needs_save());
+    }
+
+    #[test]
+    fn test_erase_save_data() {
+        let mut rom = create_test_rom();
+        rom[HEADER_CART_TYPE] = 0x03; // MBC1+RAM+BATTERY
+        rom[HEADER_RAM_SIZE] = 0x02;
+        let dir = std::env::temp_dir().join(format!("rgbe_erase_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut cart = Cartridge::from_bytes(rom).unwrap();
+        cart.filename = dir.join("test.gb").to_string_lossy().to_string();
+        cart.set_save_strategy(SavePathStrategy::SiblingFile);
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        cart.write(0xA123, 0x99);
+        cart.save_battery().unwrap();
+        let save_path = cart.effective_save_path();
+        assert!(save_path.exists());
+
+        let backup = dir.join("backup.sav");
+        cart.backup_save_data(&backup).unwrap();
+        cart.erase_save_data().unwrap();
+
+        let backed_up = fs::read(&backup).unwrap();
+        let save_exists = save_path.exists();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(cart.ram.iter().all(|&b| b == 0));
+        assert_eq!(cart.read(0xA000), 0x00);
+        assert!(!cart.needs_save());
+        assert!(!save_exists);
+        assert_eq!(backed_up[0], 0x42);
+        assert_eq!(backed_up[0x123], 0x99);
+
+        // Erasing again with no save file left is fine
+        assert!(cart.erase_save_data().is_ok());
+    }
+
+    #[test]
+    fn test_erase_save_data_removes_legacy_save() {
+        let mut rom = create_test_rom();
+        rom[HEADER_CART_TYPE] = 0x03; // MBC1+RAM+BATTERY
+        rom[HEADER_RAM_SIZE] = 0x02;
+        let dir = std::env::temp_dir().join(format!("rgbe_erase_legacy_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join("test.gb.sav");
+        fs::write(&legacy, [0x42; 0x2000]).unwrap();
+
+        let mut cart = Cartridge::from_bytes(rom).unwrap();
+        cart.set_save_strategy(SavePathStrategy::Custom(dir.join("saves")));
+        cart.enable_battery_saves(dir.join("test.gb").to_string_lossy().to_string());
+        assert_eq!(cart.ram[0], 0x42);
+
+        cart.erase_save_data().unwrap();
+        let legacy_exists = legacy.exists();
+        cart.set_save_strategy(SavePathStrategy::Custom(dir.join("saves")));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(!legacy_exists);
+        assert_eq!(cart.ram[0], 0x00);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_from_async_bytes() {
+        let rom = create_test_rom();
+        let cart = Cartridge::from_async_bytes(std::io::Cursor::new(rom.clone())).await.unwrap();
+        assert_eq!(cart.header.title, "TEST ROM");
+        assert_eq!(*cart.rom, rom);
+
+        let short: &[u8] = &[0; 0x100];
+        assert!(matches!(
+            Cartridge::from_async_bytes(short).await,
+            Err(EmulatorError::InvalidRom(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_battery_saves_are_opt_in() {
+        let mut rom = create_test_rom();
+        rom[HEADER_CART_TYPE] = 0x03; // MBC1+RAM+BATTERY
+        rom[HEADER_RAM_SIZE] = 0x02;
+        rom[HEADER_CHECKSUM] = Cartridge::calculate_checksum(&rom);
+        let dir = std::env::temp_dir().join(format!("rgbe_bytes_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut cart = Cartridge::from_bytes(rom.clone()).unwrap();
+        cart.set_save_strategy(SavePathStrategy::Custom(dir.clone()));
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        cart.save_battery().unwrap();
+        assert!(!dir.exists());
+
+        cart.enable_battery_saves("memory.gb");
+        cart.save_battery().unwrap();
+        let saved = fs::read(dir.join("TEST ROM.sav"));
+
+        let mut reloaded = Cartridge::from_bytes(rom).unwrap();
+        reloaded.set_save_strategy(SavePathStrategy::Custom(dir.clone()));
+        reloaded.enable_battery_saves("memory.gb");
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(saved.unwrap()[0], 0x42);
+        reloaded.write(0x0000, 0x0A);
+        assert_eq!(reloaded.read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_enable_battery_saves_keeps_unsaved_ram() {
+        let mut rom = create_test_rom();
+        rom[HEADER_CART_TYPE] = 0x03; // MBC1+RAM+BATTERY
+        rom[HEADER_RAM_SIZE] = 0x02;
+        let dir = std::env::temp_dir().join(format!("rgbe_unsaved_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("TEST ROM.sav"), [0x11; 0x2000]).unwrap();
+
+        let mut cart = Cartridge::from_bytes(rom).unwrap();
+        cart.set_save_strategy(SavePathStrategy::Custom(dir.clone()));
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        cart.enable_battery_saves("memory.gb");
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(cart.read(0xA000), 0x42);
+        assert!(cart.needs_save());
+        // Keep the drop from writing the save back
+        cart.need_save = false;
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let cart = Cartridge::from_bytes(create_test_rom()).unwrap();
+        assert_eq!(cart.header.title, "TEST ROM");
+        assert_eq!(cart.read(0x0134), b'T');
+
+        assert!(matches!(
+            Cartridge::from_bytes(vec![0; 0x100]),
+            Err(EmulatorError::InvalidRom(_))
+        ));
+    }
 }
@@ -1,7 +1,9 @@
 //! Common types and utilities for the Game Boy emulator
 //!
 //! This module defines type aliases matching Game Boy hardware specifications
-//! and provides bit manipulation utilities.
+//! and provides bit manipulation and pixel format utilities.
+
+use alloc::vec::Vec;
 
 /// 8-bit unsigned integer (Game Boy byte)
 pub type Byte = u8;
@@ -57,6 +59,91 @@ pub fn between(value: Word, low: Word, high: Word) -> bool {
     value >= low && value <= high
 }
 
+/// Packing of a pixel in a `u32`
+///
+/// The PPU produces `Argb8888`. Formats without alpha leave the unused high
+/// bits zero and read back as opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// `0xAARRGGBB`
+    Argb8888,
+    /// `0xRRGGBBAA`
+    Rgba8888,
+    /// `0x00RRGGBB`
+    Rgb888,
+    /// `0x00BBGGRR`
+    Bgr888,
+    /// `RRRRRGGG GGGBBBBB` in the low 16 bits
+    Rgb565,
+}
+
+impl PixelFormat {
+    /// Split a pixel into `[r, g, b, a]`
+    fn unpack(self, pixel: u32) -> [u8; 4] {
+        match self {
+            PixelFormat::Argb8888 => [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, (pixel >> 24) as u8],
+            PixelFormat::Rgba8888 => [(pixel >> 24) as u8, (pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8],
+            PixelFormat::Rgb888 => [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 0xFF],
+            PixelFormat::Bgr888 => [pixel as u8, (pixel >> 8) as u8, (pixel >> 16) as u8, 0xFF],
+            PixelFormat::Rgb565 => {
+                // Replicate the top bits so full intensity stays 0xFF
+                let r = ((pixel >> 11) & 0x1F) as u8;
+                let g = ((pixel >> 5) & 0x3F) as u8;
+                let b = (pixel & 0x1F) as u8;
+                [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 0xFF]
+            }
+        }
+    }
+
+    /// Join `[r, g, b, a]` into a pixel
+    fn pack(self, [r, g, b, a]: [u8; 4]) -> u32 {
+        let (r, g, b, a) = (r as u32, g as u32, b as u32, a as u32);
+        match self {
+            PixelFormat::Argb8888 => (a << 24) | (r << 16) | (g << 8) | b,
+            PixelFormat::Rgba8888 => (r << 24) | (g << 16) | (b << 8) | a,
+            PixelFormat::Rgb888 => (r << 16) | (g << 8) | b,
+            PixelFormat::Bgr888 => (b << 16) | (g << 8) | r,
+            PixelFormat::Rgb565 => ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3),
+        }
+    }
+}
+
+/// Convert a single pixel between formats
+#[inline]
+pub fn convert_pixel(pixel: u32, from: PixelFormat, to: PixelFormat) -> u32 {
+    if from == to {
+        return pixel;
+    }
+    to.pack(from.unpack(pixel))
+}
+
+/// Convert a pixel buffer into a new buffer
+pub fn convert_buffer(buf: &[u32], from: PixelFormat, to: PixelFormat) -> Vec<u32> {
+    buf.iter().map(|&p| convert_pixel(p, from, to)).collect()
+}
+
+/// Convert a pixel buffer in place
+pub fn convert_buffer_inplace(buf: &mut [u32], from: PixelFormat, to: PixelFormat) {
+    if from == to {
+        return;
+    }
+    for p in buf.iter_mut() {
+        *p = convert_pixel(*p, from, to);
+    }
+}
+
+/// Split an ARGB8888 pixel into `[r, g, b]` bytes
+#[inline]
+pub fn argb_to_rgb888(p: u32) -> [u8; 3] {
+    [(p >> 16) as u8, (p >> 8) as u8, p as u8]
+}
+
+/// Build an opaque ARGB8888 pixel from RGB bytes
+#[inline]
+pub fn rgb888_to_argb(r: u8, g: u8, b: u8) -> u32 {
+    0xFF000000 | ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,4 +183,58 @@ mod tests {
         assert!(!between(0xC000, 0x8000, 0x9FFF));
         assert!(between(0xC000, 0xC000, 0xDFFF));
     }
+
+    const FORMATS: [PixelFormat; 5] = [
+        PixelFormat::Argb8888,
+        PixelFormat::Rgba8888,
+        PixelFormat::Rgb888,
+        PixelFormat::Bgr888,
+        PixelFormat::Rgb565,
+    ];
+
+    #[test]
+    fn test_convert_pixel_known_values() {
+        let orange = 0xFFFF8040;
+        assert_eq!(convert_pixel(orange, PixelFormat::Argb8888, PixelFormat::Rgba8888), 0xFF8040FF);
+        assert_eq!(convert_pixel(orange, PixelFormat::Argb8888, PixelFormat::Rgb888), 0x00FF8040);
+        assert_eq!(convert_pixel(orange, PixelFormat::Argb8888, PixelFormat::Bgr888), 0x004080FF);
+        assert_eq!(convert_pixel(orange, PixelFormat::Argb8888, PixelFormat::Rgb565), 0xFC08);
+        assert_eq!(convert_pixel(0xFFFF, PixelFormat::Rgb565, PixelFormat::Argb8888), 0xFFFFFFFF);
+        assert_eq!(convert_pixel(0x00FF8040, PixelFormat::Rgb888, PixelFormat::Argb8888), orange);
+    }
+
+    #[test]
+    fn test_convert_pixel_round_trip() {
+        // Shades of the DMG palettes survive every format (565 loses low bits)
+        for pixel in [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000, 0xFF7BFF31, 0xFF0063C5] {
+            for format in FORMATS {
+                let there = convert_pixel(pixel, PixelFormat::Argb8888, format);
+                let back = convert_pixel(there, format, PixelFormat::Argb8888);
+                if format == PixelFormat::Rgb565 {
+                    let [r, g, b] = argb_to_rgb888(back);
+                    let [r0, g0, b0] = argb_to_rgb888(pixel);
+                    assert!(r.abs_diff(r0) < 8 && g.abs_diff(g0) < 4 && b.abs_diff(b0) < 8);
+                } else {
+                    assert_eq!(back, pixel, "{:08X} via {:?}", pixel, format);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_convert_buffer() {
+        let argb = [0xFF112233, 0x80445566];
+        let rgba = convert_buffer(&argb, PixelFormat::Argb8888, PixelFormat::Rgba8888);
+        assert_eq!(rgba, [0x112233FF, 0x44556680]);
+
+        let mut buf = rgba.clone();
+        convert_buffer_inplace(&mut buf, PixelFormat::Rgba8888, PixelFormat::Argb8888);
+        assert_eq!(buf, argb);
+    }
+
+    #[test]
+    fn test_rgb888_helpers() {
+        assert_eq!(argb_to_rgb888(0xFF123456), [0x12, 0x34, 0x56]);
+        assert_eq!(rgb888_to_argb(0x12, 0x34, 0x56), 0xFF123456);
+    }
 }
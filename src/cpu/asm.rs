@@ -0,0 +1,359 @@
+//! LR35902 Assembler
+//!
+//! A minimal assembler for building test ROMs from mnemonic strings instead
+//! of hand-written opcode arrays. Only a practical subset of the instruction
+//! set is supported.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Label name to address table
+type Labels = BTreeMap<String, u16>;
+
+/// Assemble instructions into raw bytes
+///
+/// Immediates may be hex (`0xFF`) or decimal (`255`, `-2`). Labels are not
+/// allowed, so any other identifier is an undefined symbol; use
+/// `assemble_with_labels` for that.
+pub fn assemble(instructions: &[&str]) -> Result<Vec<u8>, String> {
+    let labels = Labels::new();
+    let mut out = Vec::new();
+    for (line_no, line) in instructions.iter().enumerate() {
+        let line = strip_comment(line);
+        if line.is_empty() {
+            continue;
+        }
+        if line.ends_with(':') {
+            return Err(format!("line {}: labels require assemble_with_labels", line_no + 1));
+        }
+        let addr = out.len() as u16;
+        encode(line, addr, Some(&labels), &mut out).map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+    }
+    Ok(out)
+}
+
+/// Assemble instructions that may define (`loop:`) and reference labels
+///
+/// `origin` is the address the first byte will be loaded at (0x0100 for
+/// a cartridge entry point); it is used to resolve `JP`/`CALL` targets.
+pub fn assemble_with_labels(instructions: &[&str], origin: u16) -> Result<Vec<u8>, String> {
+    // Pass 1: every instruction has a fixed size, so label addresses can be
+    // collected before their values are known
+    let mut labels = Labels::new();
+    let mut scratch = Vec::new();
+    for (line_no, line) in instructions.iter().enumerate() {
+        let line = strip_comment(line);
+        if let Some(name) = line.strip_suffix(':') {
+            let name = name.trim().to_ascii_uppercase();
+            let addr = origin.wrapping_add(scratch.len() as u16);
+            if labels.insert(name.clone(), addr).is_some() {
+                return Err(format!("line {}: duplicate label '{}'", line_no + 1, name));
+            }
+        } else if !line.is_empty() {
+            let addr = origin.wrapping_add(scratch.len() as u16);
+            encode(line, addr, None, &mut scratch).map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+        }
+    }
+
+    // Pass 2: emit bytes with labels resolved
+    let mut out = Vec::with_capacity(scratch.len());
+    for (line_no, line) in instructions.iter().enumerate() {
+        let line = strip_comment(line);
+        if line.is_empty() || line.ends_with(':') {
+            continue;
+        }
+        let addr = origin.wrapping_add(out.len() as u16);
+        encode(line, addr, Some(&labels), &mut out).map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+    }
+    Ok(out)
+}
+
+/// Remove a trailing `;` comment and surrounding whitespace
+fn strip_comment(line: &str) -> &str {
+    line.split(';').next().unwrap_or("").trim()
+}
+
+/// Encode one instruction at `addr`
+///
+/// With `labels` set to `None` (first pass), label references encode as 0.
+fn encode(line: &str, addr: u16, labels: Option<&Labels>, out: &mut Vec<u8>) -> Result<(), String> {
+    let line = line.to_ascii_uppercase();
+    let (mnemonic, rest) = match line.split_once(char::is_whitespace) {
+        Some((mnemonic, rest)) => (mnemonic, rest.trim()),
+        None => (line.as_str(), ""),
+    };
+    let operands: Vec<&str> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').map(str::trim).collect()
+    };
+
+    match (mnemonic, operands.as_slice()) {
+        ("NOP", []) => out.push(0x00),
+        ("HALT", []) => out.push(0x76),
+        ("DI", []) => out.push(0xF3),
+        ("EI", []) => out.push(0xFB),
+        ("RET", []) => out.push(0xC9),
+        ("RETI", []) => out.push(0xD9),
+        ("RET", [cc]) => out.push(0xC0 | condition(cc)? << 3),
+
+        ("LD", [dst, src]) => {
+            if let Some(rr) = reg16(dst) {
+                out.push(0x01 | rr << 4);
+                push_word(out, value(src, labels)?)?;
+            } else {
+                let dst = reg8(dst).ok_or_else(|| format!("invalid operand '{}'", dst))?;
+                match reg8(src) {
+                    Some(6) if dst == 6 => return Err("LD (HL), (HL) is not an instruction".to_string()),
+                    Some(src) => out.push(0x40 | dst << 3 | src),
+                    None => {
+                        out.push(0x06 | dst << 3);
+                        push_byte(out, value(src, labels)?)?;
+                    }
+                }
+            }
+        }
+
+        ("ADD", ["A", src]) => alu(out, 0x80, 0xC6, src, labels)?,
+        ("SUB", ["A", src]) | ("SUB", [src]) => alu(out, 0x90, 0xD6, src, labels)?,
+        ("AND", ["A", src]) | ("AND", [src]) => alu(out, 0xA0, 0xE6, src, labels)?,
+        ("XOR", ["A", src]) | ("XOR", [src]) => alu(out, 0xA8, 0xEE, src, labels)?,
+        ("OR", ["A", src]) | ("OR", [src]) => alu(out, 0xB0, 0xF6, src, labels)?,
+        ("CP", ["A", src]) | ("CP", [src]) => alu(out, 0xB8, 0xFE, src, labels)?,
+
+        ("INC", [r]) => match reg16(r) {
+            Some(rr) => out.push(0x03 | rr << 4),
+            None => out.push(0x04 | reg8_operand(r)? << 3),
+        },
+        ("DEC", [r]) => match reg16(r) {
+            Some(rr) => out.push(0x0B | rr << 4),
+            None => out.push(0x05 | reg8_operand(r)? << 3),
+        },
+
+        ("JP", [target]) => {
+            out.push(0xC3);
+            push_word(out, value(target, labels)?)?;
+        }
+        ("JP", [cc, target]) => {
+            out.push(0xC2 | condition(cc)? << 3);
+            push_word(out, value(target, labels)?)?;
+        }
+        ("JR", [target]) => {
+            out.push(0x18);
+            push_relative(out, addr, target, labels)?;
+        }
+        ("JR", [cc, target]) => {
+            out.push(0x20 | condition(cc)? << 3);
+            push_relative(out, addr, target, labels)?;
+        }
+        ("CALL", [target]) => {
+            out.push(0xCD);
+            push_word(out, value(target, labels)?)?;
+        }
+        ("CALL", [cc, target]) => {
+            out.push(0xC4 | condition(cc)? << 3);
+            push_word(out, value(target, labels)?)?;
+        }
+
+        ("PUSH", [rr]) => out.push(0xC5 | stack_reg16(rr)? << 4),
+        ("POP", [rr]) => out.push(0xC1 | stack_reg16(rr)? << 4),
+
+        _ => return Err(format!("unsupported instruction '{}'", line)),
+    }
+    Ok(())
+}
+
+/// Encode an 8-bit ALU operation with a register or immediate source
+fn alu(out: &mut Vec<u8>, reg_base: u8, imm_opcode: u8, src: &str, labels: Option<&Labels>) -> Result<(), String> {
+    match reg8(src) {
+        Some(r) => out.push(reg_base | r),
+        None => {
+            out.push(imm_opcode);
+            push_byte(out, value(src, labels)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// 8-bit register operand encoding (`(HL)` is 6)
+fn reg8(operand: &str) -> Option<u8> {
+    Some(match operand {
+        "B" => 0,
+        "C" => 1,
+        "D" => 2,
+        "E" => 3,
+        "H" => 4,
+        "L" => 5,
+        "(HL)" => 6,
+        "A" => 7,
+        _ => return None,
+    })
+}
+
+/// 8-bit register operand that must be present
+fn reg8_operand(operand: &str) -> Result<u8, String> {
+    reg8(operand).ok_or_else(|| format!("invalid register '{}'", operand))
+}
+
+/// 16-bit register pair encoding for LD/INC/DEC
+fn reg16(operand: &str) -> Option<u8> {
+    Some(match operand {
+        "BC" => 0,
+        "DE" => 1,
+        "HL" => 2,
+        "SP" => 3,
+        _ => return None,
+    })
+}
+
+/// 16-bit register pair encoding for PUSH/POP
+fn stack_reg16(operand: &str) -> Result<u8, String> {
+    match operand {
+        "AF" => Ok(3),
+        "SP" => Err("PUSH/POP take AF, not SP".to_string()),
+        _ => reg16(operand).ok_or_else(|| format!("invalid register pair '{}'", operand)),
+    }
+}
+
+/// Branch condition encoding
+fn condition(operand: &str) -> Result<u8, String> {
+    match operand {
+        "NZ" => Ok(0),
+        "Z" => Ok(1),
+        "NC" => Ok(2),
+        "C" => Ok(3),
+        _ => Err(format!("invalid condition '{}'", operand)),
+    }
+}
+
+/// Parse a numeric immediate or resolve a label
+fn value(operand: &str, labels: Option<&Labels>) -> Result<i32, String> {
+    if let Some(n) = parse_number(operand) {
+        return Ok(n);
+    }
+    if !operand.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return Err(format!("invalid operand '{}'", operand));
+    }
+    match labels {
+        None => Ok(0),
+        Some(labels) => labels
+            .get(operand)
+            .map(|&addr| addr as i32)
+            .ok_or_else(|| format!("undefined label '{}'", operand)),
+    }
+}
+
+/// Parse `0x` hex or decimal (optionally negative)
+fn parse_number(operand: &str) -> Option<i32> {
+    let (negative, digits) = match operand.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, operand),
+    };
+    let n = match digits.strip_prefix("0X") {
+        Some(hex) => i32::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i32>().ok()?,
+    };
+    Some(if negative { -n } else { n })
+}
+
+/// Append an 8-bit immediate (accepts -128..=255)
+fn push_byte(out: &mut Vec<u8>, n: i32) -> Result<(), String> {
+    if !(-128..=255).contains(&n) {
+        return Err(format!("value {} does not fit in 8 bits", n));
+    }
+    out.push(n as u8);
+    Ok(())
+}
+
+/// Append a little-endian 16-bit immediate
+fn push_word(out: &mut Vec<u8>, n: i32) -> Result<(), String> {
+    if !(-32768..=65535).contains(&n) {
+        return Err(format!("value {} does not fit in 16 bits", n));
+    }
+    out.extend_from_slice(&(n as u16).to_le_bytes());
+    Ok(())
+}
+
+/// Append a JR displacement; labels are relative to the next instruction
+fn push_relative(out: &mut Vec<u8>, addr: u16, target: &str, labels: Option<&Labels>) -> Result<(), String> {
+    if let Some(n) = parse_number(target) {
+        return push_byte(out, n);
+    }
+    let target = value(target, labels)?;
+    if labels.is_none() {
+        out.push(0);
+        return Ok(());
+    }
+    let offset = target - (addr as i32 + 2);
+    if !(-128..=127).contains(&offset) {
+        return Err(format!("jump to '{}' out of range ({})", target, offset));
+    }
+    out.push(offset as u8);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_assemble_basic() {
+        let bytes = assemble(&[
+            "NOP",
+            "LD B, 0x10",
+            "LD A, (HL)",
+            "LD (HL), C",
+            "ADD A, B",
+            "XOR A, A",
+            "PUSH AF",
+            "POP HL",
+            "JR -2",
+            "JP 0x0150",
+            "CALL 336",
+            "DI",
+            "EI",
+            "RET",
+            "RETI",
+            "HALT",
+        ])
+        .unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0x00, 0x06, 0x10, 0x7E, 0x71, 0x80, 0xAF, 0xF5, 0xE1, 0x18, 0xFE, 0xC3, 0x50, 0x01,
+                0xCD, 0x50, 0x01, 0xF3, 0xFB, 0xC9, 0xD9, 0x76,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_counter_loop() {
+        let bytes = assemble_with_labels(
+            &[
+                "ld b, 100",
+                "loop:",
+                "  dec b        ; count down",
+                "  jr nz, loop",
+                "done:",
+                "  jp done",
+            ],
+            0x0100,
+        )
+        .unwrap();
+        assert_eq!(bytes, vec![0x06, 100, 0x05, 0x20, 0xFD, 0xC3, 0x05, 0x01]);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert!(assemble(&["FOO"]).is_err());
+        assert!(assemble(&["LD B, 0x100"]).is_err());
+        assert!(assemble(&["loop:"]).is_err());
+        assert_eq!(assemble(&["JP start"]), Err("line 1: undefined label 'START'".to_string()));
+        assert!(assemble(&["LD A, value"]).is_err());
+        assert!(assemble_with_labels(&["JP missing"], 0).is_err());
+        assert!(assemble_with_labels(&["a:", "a:"], 0).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! LR35902 Disassembler
+//!
+//! Turns the instruction at an address into assembly text with its operands
+//! filled in, using the mnemonics of the instruction tables.
+
+use crate::bus::MemoryBus;
+use crate::common::Word;
+use alloc::format;
+use alloc::string::{String, ToString};
+use super::instructions::{AddressingMode, cb_instruction_by_opcode, instruction_by_opcode};
+
+/// Disassemble the instruction at `address`
+///
+/// Returns the text (`LD A, $3C`, `JR NZ, $0150`) and the address of the
+/// following instruction. Immediates are printed as hex; relative jumps show
+/// their absolute target.
+pub fn disassemble<B: MemoryBus>(address: Word, bus: &B) -> (String, Word) {
+    let opcode = bus.peek(address);
+    if opcode == 0xCB {
+        let cb_opcode = bus.peek(address.wrapping_add(1));
+        return (cb_instruction_by_opcode(cb_opcode).to_string(), address.wrapping_add(2));
+    }
+
+    let inst = instruction_by_opcode(opcode);
+    let operand_len = match inst.mode {
+        AddressingMode::RegisterD8
+        | AddressingMode::RegisterA8
+        | AddressingMode::A8Register
+        | AddressingMode::MemoryRegisterD8
+        | AddressingMode::HlSpr
+        | AddressingMode::D8 => 1,
+        AddressingMode::RegisterD16
+        | AddressingMode::RegisterA16
+        | AddressingMode::A16Register
+        | AddressingMode::D16 => 2,
+        _ => 0,
+    };
+    let next = address.wrapping_add(1 + operand_len);
+    let text = inst.to_string();
+    let text = match operand_len {
+        1 => {
+            let value = bus.peek(address.wrapping_add(1));
+            let offset = value as i8;
+            let signed = if offset < 0 {
+                format!("-${:02X}", offset.unsigned_abs())
+            } else {
+                format!("+${:02X}", offset)
+            };
+            text.replacen("(a8)", &format!("(${:04X})", 0xFF00 | value as Word), 1)
+                .replacen("+r8", &signed, 1)
+                .replacen("r8", &signed, 1)
+                .replacen("rel", &format!("${:04X}", next.wrapping_add(offset as Word)), 1)
+                .replacen("d8", &format!("${:02X}", value), 1)
+        }
+        2 => {
+            let lo = bus.peek(address.wrapping_add(1)) as Word;
+            let hi = bus.peek(address.wrapping_add(2)) as Word;
+            let value = lo | (hi << 8);
+            text.replacen("(a16)", &format!("(${:04X})", value), 1)
+                .replacen("a16", &format!("${:04X}", value), 1)
+                .replacen("d16", &format!("${:04X}", value), 1)
+        }
+        _ => text,
+    };
+    (text, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockBus;
+
+    fn disasm(bytes: &[u8]) -> (String, Word) {
+        disassemble(0x0100, &MockBus::with_data(0x0100, bytes))
+    }
+
+    #[test]
+    fn test_disassemble_operands() {
+        assert_eq!(disasm(&[0x00]), (String::from("NOP"), 0x0101));
+        assert_eq!(disasm(&[0x3E, 0x3C]), (String::from("LD A, $3C"), 0x0102));
+        assert_eq!(disasm(&[0x21, 0x34, 0x12]), (String::from("LD HL, $1234"), 0x0103));
+        assert_eq!(disasm(&[0xEA, 0x00, 0xC0]), (String::from("LD ($C000), A"), 0x0103));
+        assert_eq!(disasm(&[0xC3, 0x50, 0x01]), (String::from("JP $0150"), 0x0103));
+        assert_eq!(disasm(&[0xE0, 0x40]), (String::from("LDH ($FF40), A"), 0x0102));
+        assert_eq!(disasm(&[0x36, 0x7F]), (String::from("LD (HL), $7F"), 0x0102));
+    }
+
+    #[test]
+    fn test_disassemble_signed_and_relative() {
+        assert_eq!(disasm(&[0x20, 0xFE]), (String::from("JR NZ, $0100"), 0x0102));
+        assert_eq!(disasm(&[0x18, 0x10]), (String::from("JR $0112"), 0x0102));
+        assert_eq!(disasm(&[0xE8, 0xF8]), (String::from("ADD SP, -$08"), 0x0102));
+        assert_eq!(disasm(&[0xF8, 0x05]), (String::from("LD HL, SP+$05"), 0x0102));
+    }
+
+    #[test]
+    fn test_disassemble_cb_prefix() {
+        assert_eq!(disasm(&[0xCB, 0x7C]), (String::from("BIT 7, H"), 0x0102));
+        assert_eq!(disasm(&[0xCB, 0x36]), (String::from("SWAP (HL)"), 0x0102));
+    }
+}
@@ -8,7 +8,7 @@ use super::instructions::{
     AddressingMode, ConditionType, Instruction, InstructionType, RegisterType,
     CB_INSTRUCTIONS,
 };
-use super::Cpu;
+use super::{Cpu, SPEED_SWITCH_CYCLES};
 
 impl Cpu {
     /// Check if a register type is 16-bit
@@ -92,7 +92,7 @@ impl Cpu {
             InstructionType::Rrca => self.proc_rrca(),
             InstructionType::Rla => self.proc_rla(),
             InstructionType::Rra => self.proc_rra(),
-            InstructionType::Stop => self.proc_stop(),
+            InstructionType::Stop => self.proc_stop(bus),
             InstructionType::Halt => self.proc_halt(),
             InstructionType::Daa => self.proc_daa(),
             InstructionType::Cpl => self.proc_cpl(),
@@ -116,8 +116,9 @@ impl Cpu {
 
     // ========== Instruction Processors ==========
 
-    fn proc_none(&self) {
-        panic!("INVALID INSTRUCTION!");
+    fn proc_none(&mut self) {
+        // Continues as a NOP when a handler is installed
+        self.report_invalid_opcode();
     }
 
     fn proc_nop(&self) {
@@ -141,9 +142,15 @@ impl Cpu {
             let cflag = (self.read_reg(inst.reg2) & 0xFF) + (self.fetched_data & 0xFF) >= 0x100;
             self.regs.set_flags(false, false, hflag, cflag);
             self.write_reg(inst.reg1, self.read_reg(inst.reg2).wrapping_add(self.fetched_data as i8 as i16 as Word));
+            self.add_m_cycles(1);
             return;
         }
 
+        // LD SP, HL takes an extra internal cycle
+        if inst.reg1 == RegisterType::Sp && inst.reg2 == RegisterType::Hl {
+            self.add_m_cycles(1);
+        }
+
         self.write_reg(inst.reg1, self.fetched_data);
     }
 
@@ -217,6 +224,7 @@ impl Cpu {
 
         if inst.reg1 == RegisterType::Sp {
             val = reg_val.wrapping_add(self.fetched_data as i8 as i16 as Word);
+            self.add_m_cycles(1);
         }
 
         let mut z = (val & 0xFF) == 0;
@@ -313,6 +321,11 @@ impl Cpu {
     }
 
     fn proc_jp(&mut self, inst: &Instruction) {
+        // JP (HL) loads PC directly, without the extra jump cycle
+        if inst.reg1 == RegisterType::Hl {
+            self.regs.pc = self.fetched_data;
+            return;
+        }
         self.jump_to_if(self.fetched_data, inst.cond);
     }
 
@@ -393,13 +406,24 @@ impl Cpu {
         self.regs.set_flags(false, false, false, new_c != 0);
     }
 
-    fn proc_stop(&mut self) {
+    fn proc_stop<B: MemoryBus>(&mut self, bus: &B) {
         // STOP instruction - typically used for speed switching on CGB
         // For DMG, this just halts until a button is pressed
+        if bus.speed_switch_armed() {
+            // STOP's own M-cycle counts towards the switch
+            self.speed_switch_pending = true;
+            self.stop_countdown = SPEED_SWITCH_CYCLES - 4;
+        }
     }
 
     fn proc_halt(&mut self) {
-        self.halted = true;
+        // With IME clear and an interrupt already pending the CPU does not
+        // halt, and fails to increment PC on the next opcode fetch
+        if !self.ime && self.interrupts_pending() {
+            self.halt_bug = true;
+        } else {
+            self.halted = true;
+        }
     }
 
 
@@ -613,9 +637,63 @@ impl Cpu {
         (hi << 8) | lo
     }
 
+    /// Run one instruction (or interrupt dispatch / halted cycle) and
+    /// return the T-cycles it consumed
+    ///
+    /// Only the CPU is stepped; the caller ticks the other components by the
+    /// returned count and keeps `ie_register`/`int_flags` in sync with the bus.
+    pub fn step_with_cycles<B: MemoryBus>(&mut self, bus: &mut B) -> u32 {
+        self.reset_step_cycles();
+
+        if self.speed_switch_pending {
+            return self.step_speed_switch();
+        }
+
+        if self.handle_interrupts(bus) {
+            return self.take_t_cycles();
+        }
+
+        // Handle delayed IME enable
+        if self.enabling_ime {
+            self.enabling_ime = false;
+            self.ime = true;
+        }
+
+        if self.halted {
+            self.add_m_cycles(1);
+            if self.interrupts_pending() {
+                self.halted = false;
+            }
+            return self.take_t_cycles();
+        }
+
+        self.fetch_instruction(bus);
+        self.fetch_data(bus);
+        self.execute(bus);
+        self.take_t_cycles()
+    }
+
+    /// Spend up to one M-cycle of a pending speed switch and return the
+    /// T-cycles consumed
+    ///
+    /// The CPU stays stopped, without dispatching interrupts, until the
+    /// countdown expires, so the next fetch happens exactly
+    /// `SPEED_SWITCH_CYCLES` after STOP. The last step clears
+    /// `speed_switch_pending` and sets `speed_switch_completed`.
+    pub fn step_speed_switch(&mut self) -> u32 {
+        let t_cycles = self.stop_countdown.min(4);
+        self.stop_countdown -= t_cycles;
+        if self.stop_countdown == 0 {
+            self.speed_switch_pending = false;
+            self.speed_switch_completed = true;
+        }
+        t_cycles
+    }
+
     /// Handle pending interrupts
-    /// 
-    /// Returns true if an interrupt was handled
+    ///
+    /// Dispatch takes 5 M-cycles: 2 internal, 2 to push PC and 1 to jump to
+    /// the vector. Returns true if an interrupt was handled
     pub fn handle_interrupts<B: MemoryBus>(&mut self, bus: &mut B) -> bool {
         // Check if any interrupts are pending and enabled
         if !self.ime || !self.interrupts_pending() {
@@ -624,17 +702,22 @@ impl Cpu {
 
         // Get the highest priority pending interrupt
         if let Some(interrupt) = self.get_pending_interrupt() {
+            // Two wait cycles
+            self.add_m_cycles(2);
+
             // Disable IME
             self.ime = false;
             
             // Clear the interrupt flag
             self.clear_interrupt(interrupt);
             
-            // Push PC to stack
+            // Push PC to stack (one cycle per byte)
             self.stack_push16(bus, self.regs.pc);
+            self.add_m_cycles(2);
             
             // Jump to interrupt vector
             self.regs.pc = interrupt.vector();
+            self.add_m_cycles(1);
             
             // Exit halt mode if halted
             self.halted = false;
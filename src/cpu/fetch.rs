@@ -55,8 +55,16 @@ impl Cpu {
     /// Fetch the next opcode and get the instruction
     pub fn fetch_instruction<B: MemoryBus>(&mut self, bus: &B) -> &'static Instruction {
         self.cur_opcode = bus.read(self.regs.pc);
-        self.regs.pc = self.regs.pc.wrapping_add(1);
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.regs.pc = self.regs.pc.wrapping_add(1);
+        }
         self.add_m_cycles(1);
+        // CB-prefixed opcodes are counted once their second byte is fetched
+        if self.cur_opcode != 0xCB {
+            self.record_opcode(self.cur_opcode, false);
+        }
         let inst = instruction_by_opcode(self.cur_opcode);
         self.set_current_instruction(Some(inst));
         inst
@@ -87,6 +95,9 @@ impl Cpu {
                 self.fetched_data = bus.read(self.regs.pc) as Word;
                 self.regs.pc = self.regs.pc.wrapping_add(1);
                 self.add_m_cycles(1);
+                if self.cur_opcode == 0xCB {
+                    self.record_opcode(self.fetched_data as Byte, true);
+                }
             }
 
             AddressingMode::RegisterD16 | AddressingMode::D16 => {
@@ -3,6 +3,10 @@
 //! This module defines instruction types, addressing modes, and the instruction table.
 
 use crate::common::Byte;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
 
 /// CPU instruction types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -143,6 +147,199 @@ impl Default for Instruction {
     }
 }
 
+impl InstructionType {
+    /// Check if this is one of the CB-prefixed bit operations
+    pub fn is_cb_operation(self) -> bool {
+        matches!(
+            self,
+            InstructionType::Rlc
+                | InstructionType::Rrc
+                | InstructionType::Rl
+                | InstructionType::Rr
+                | InstructionType::Sla
+                | InstructionType::Sra
+                | InstructionType::Swap
+                | InstructionType::Srl
+                | InstructionType::Bit
+                | InstructionType::Res
+                | InstructionType::Set
+        )
+    }
+}
+
+impl fmt::Display for InstructionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InstructionType::None => "INVALID",
+            InstructionType::Nop => "NOP",
+            InstructionType::Ld => "LD",
+            InstructionType::Inc => "INC",
+            InstructionType::Dec => "DEC",
+            InstructionType::Rlca => "RLCA",
+            InstructionType::Add => "ADD",
+            InstructionType::Rrca => "RRCA",
+            InstructionType::Stop => "STOP",
+            InstructionType::Rla => "RLA",
+            InstructionType::Jr => "JR",
+            InstructionType::Rra => "RRA",
+            InstructionType::Daa => "DAA",
+            InstructionType::Cpl => "CPL",
+            InstructionType::Scf => "SCF",
+            InstructionType::Ccf => "CCF",
+            InstructionType::Halt => "HALT",
+            InstructionType::Adc => "ADC",
+            InstructionType::Sub => "SUB",
+            InstructionType::Sbc => "SBC",
+            InstructionType::And => "AND",
+            InstructionType::Xor => "XOR",
+            InstructionType::Or => "OR",
+            InstructionType::Cp => "CP",
+            InstructionType::Pop => "POP",
+            InstructionType::Jp => "JP",
+            InstructionType::Push => "PUSH",
+            InstructionType::Ret => "RET",
+            InstructionType::Cb => "CB",
+            InstructionType::Call => "CALL",
+            InstructionType::Reti => "RETI",
+            InstructionType::Ldh => "LDH",
+            InstructionType::Di => "DI",
+            InstructionType::Ei => "EI",
+            InstructionType::Rst => "RST",
+            InstructionType::Rlc => "RLC",
+            InstructionType::Rrc => "RRC",
+            InstructionType::Rl => "RL",
+            InstructionType::Rr => "RR",
+            InstructionType::Sla => "SLA",
+            InstructionType::Sra => "SRA",
+            InstructionType::Swap => "SWAP",
+            InstructionType::Srl => "SRL",
+            InstructionType::Bit => "BIT",
+            InstructionType::Res => "RES",
+            InstructionType::Set => "SET",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for AddressingMode {
+    /// Operand shape, with `r` for a register operand
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shape = match self {
+            AddressingMode::Implied => "",
+            AddressingMode::Register => "r",
+            AddressingMode::RegisterRegister => "r,r",
+            AddressingMode::MemoryRegister => "(r),r",
+            AddressingMode::RegisterMemory => "r,(r)",
+            AddressingMode::RegisterD8 => "r,d8",
+            AddressingMode::RegisterD16 => "r,d16",
+            AddressingMode::RegisterA8 => "r,(a8)",
+            AddressingMode::RegisterA16 => "r,(a16)",
+            AddressingMode::A8Register => "(a8),r",
+            AddressingMode::A16Register => "(a16),r",
+            AddressingMode::MemoryRegisterD8 => "(r),d8",
+            AddressingMode::HliRegister => "(HL+),r",
+            AddressingMode::HldRegister => "(HL-),r",
+            AddressingMode::RegisterHli => "r,(HL+)",
+            AddressingMode::RegisterHld => "r,(HL-)",
+            AddressingMode::HlSpr => "HL,SP+r8",
+            AddressingMode::D8 => "d8",
+            AddressingMode::D16 => "d16",
+            AddressingMode::MemoryRegisterOnly => "(r)",
+        };
+        f.write_str(shape)
+    }
+}
+
+impl fmt::Display for RegisterType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RegisterType::None => "",
+            RegisterType::A => "A",
+            RegisterType::F => "F",
+            RegisterType::B => "B",
+            RegisterType::C => "C",
+            RegisterType::D => "D",
+            RegisterType::E => "E",
+            RegisterType::H => "H",
+            RegisterType::L => "L",
+            RegisterType::Af => "AF",
+            RegisterType::Bc => "BC",
+            RegisterType::De => "DE",
+            RegisterType::Hl => "HL",
+            RegisterType::Sp => "SP",
+            RegisterType::Pc => "PC",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for ConditionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConditionType::None => "",
+            ConditionType::Nz => "NZ",
+            ConditionType::Z => "Z",
+            ConditionType::Nc => "NC",
+            ConditionType::C => "C",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Disassembly-style text with placeholder immediates (`LD A, (BC)`, `JR NZ, rel`)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (r1, r2) = (self.reg1, self.reg2);
+        let mut operands: Vec<String> = Vec::new();
+        if self.cond != ConditionType::None {
+            operands.push(self.cond.to_string());
+        }
+        match self.inst_type {
+            InstructionType::Rst => operands.push(format!("${:02X}", self.param)),
+            InstructionType::Bit | InstructionType::Res | InstructionType::Set => {
+                operands.push(self.param.to_string())
+            }
+            _ => {}
+        }
+        match self.mode {
+            AddressingMode::Implied => {}
+            // The CB table encodes (HL) operands as plain HL registers
+            AddressingMode::Register if r1 == RegisterType::Hl && self.inst_type.is_cb_operation() => {
+                operands.push(String::from("(HL)"))
+            }
+            AddressingMode::Register => operands.push(r1.to_string()),
+            AddressingMode::RegisterRegister => operands.extend([r1.to_string(), r2.to_string()]),
+            AddressingMode::MemoryRegister => operands.extend([format!("({})", r1), r2.to_string()]),
+            AddressingMode::RegisterMemory => operands.extend([r1.to_string(), format!("({})", r2)]),
+            AddressingMode::RegisterD8 if r1 == RegisterType::Sp => {
+                operands.extend([r1.to_string(), String::from("r8")])
+            }
+            AddressingMode::RegisterD8 => operands.extend([r1.to_string(), String::from("d8")]),
+            AddressingMode::RegisterD16 => operands.extend([r1.to_string(), String::from("d16")]),
+            AddressingMode::RegisterA8 => operands.extend([r1.to_string(), String::from("(a8)")]),
+            AddressingMode::RegisterA16 => operands.extend([r1.to_string(), String::from("(a16)")]),
+            AddressingMode::A8Register => operands.extend([String::from("(a8)"), r2.to_string()]),
+            AddressingMode::A16Register => operands.extend([String::from("(a16)"), r2.to_string()]),
+            AddressingMode::MemoryRegisterD8 => operands.extend([format!("({})", r1), String::from("d8")]),
+            AddressingMode::HliRegister => operands.extend([String::from("(HL+)"), r2.to_string()]),
+            AddressingMode::HldRegister => operands.extend([String::from("(HL-)"), r2.to_string()]),
+            AddressingMode::RegisterHli => operands.extend([r1.to_string(), String::from("(HL+)")]),
+            AddressingMode::RegisterHld => operands.extend([r1.to_string(), String::from("(HL-)")]),
+            AddressingMode::HlSpr => operands.extend([String::from("HL"), String::from("SP+r8")]),
+            AddressingMode::D8 if self.inst_type == InstructionType::Jr => operands.push(String::from("rel")),
+            AddressingMode::D8 => operands.push(String::from("d8")),
+            AddressingMode::D16 => operands.push(String::from("a16")),
+            AddressingMode::MemoryRegisterOnly => operands.push(format!("({})", r1)),
+        }
+
+        write!(f, "{}", self.inst_type)?;
+        if !operands.is_empty() {
+            write!(f, " {}", operands.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 // Helper macro for instruction definition
 macro_rules! inst {
     ($t:ident) => {
@@ -568,3 +765,115 @@ pub static CB_INSTRUCTIONS: [Instruction; 256] = [
 pub fn cb_instruction_by_opcode(opcode: Byte) -> &'static Instruction {
     &CB_INSTRUCTIONS[opcode as usize]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Disassemble an opcode from the main table, or the CB table for `0xCBxx`
+    fn disasm(opcode: u16) -> String {
+        if opcode > 0xFF {
+            cb_instruction_by_opcode(opcode as Byte).to_string()
+        } else {
+            instruction_by_opcode(opcode as Byte).to_string()
+        }
+    }
+
+    #[test]
+    fn test_every_instruction_type() {
+        let cases: [(u16, InstructionType, &str); 46] = [
+            (0xD3, InstructionType::None, "INVALID"),
+            (0x00, InstructionType::Nop, "NOP"),
+            (0x0A, InstructionType::Ld, "LD A, (BC)"),
+            (0x34, InstructionType::Inc, "INC (HL)"),
+            (0x0B, InstructionType::Dec, "DEC BC"),
+            (0x07, InstructionType::Rlca, "RLCA"),
+            (0xE8, InstructionType::Add, "ADD SP, r8"),
+            (0x0F, InstructionType::Rrca, "RRCA"),
+            (0x10, InstructionType::Stop, "STOP"),
+            (0x17, InstructionType::Rla, "RLA"),
+            (0x20, InstructionType::Jr, "JR NZ, rel"),
+            (0x1F, InstructionType::Rra, "RRA"),
+            (0x27, InstructionType::Daa, "DAA"),
+            (0x2F, InstructionType::Cpl, "CPL"),
+            (0x37, InstructionType::Scf, "SCF"),
+            (0x3F, InstructionType::Ccf, "CCF"),
+            (0x76, InstructionType::Halt, "HALT"),
+            (0xCE, InstructionType::Adc, "ADC A, d8"),
+            (0x90, InstructionType::Sub, "SUB A, B"),
+            (0x9E, InstructionType::Sbc, "SBC A, (HL)"),
+            (0xE6, InstructionType::And, "AND A, d8"),
+            (0xAF, InstructionType::Xor, "XOR A, A"),
+            (0xB1, InstructionType::Or, "OR A, C"),
+            (0xFE, InstructionType::Cp, "CP A, d8"),
+            (0xF1, InstructionType::Pop, "POP AF"),
+            (0xC2, InstructionType::Jp, "JP NZ, a16"),
+            (0xC5, InstructionType::Push, "PUSH BC"),
+            (0xD8, InstructionType::Ret, "RET C"),
+            (0xCB, InstructionType::Cb, "CB d8"),
+            (0xCD, InstructionType::Call, "CALL a16"),
+            (0xD9, InstructionType::Reti, "RETI"),
+            (0xE0, InstructionType::Ldh, "LDH (a8), A"),
+            (0xF3, InstructionType::Di, "DI"),
+            (0xFB, InstructionType::Ei, "EI"),
+            (0xFF, InstructionType::Rst, "RST $38"),
+            (0xCB00, InstructionType::Rlc, "RLC B"),
+            (0xCB0E, InstructionType::Rrc, "RRC (HL)"),
+            (0xCB11, InstructionType::Rl, "RL C"),
+            (0xCB1F, InstructionType::Rr, "RR A"),
+            (0xCB22, InstructionType::Sla, "SLA D"),
+            (0xCB2B, InstructionType::Sra, "SRA E"),
+            (0xCB37, InstructionType::Swap, "SWAP A"),
+            (0xCB3C, InstructionType::Srl, "SRL H"),
+            (0xCB7E, InstructionType::Bit, "BIT 7, (HL)"),
+            (0xCB85, InstructionType::Res, "RES 0, L"),
+            (0xCBDF, InstructionType::Set, "SET 3, A"),
+        ];
+        for (opcode, inst_type, text) in cases {
+            let inst = if opcode > 0xFF {
+                cb_instruction_by_opcode(opcode as Byte)
+            } else {
+                instruction_by_opcode(opcode as Byte)
+            };
+            assert_eq!(inst.inst_type, inst_type, "{:04X}", opcode);
+            assert_eq!(disasm(opcode), text, "{:04X}", opcode);
+        }
+
+        let mut names: Vec<String> = cases.iter().map(|(_, t, _)| t.to_string()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), cases.len());
+    }
+
+    #[test]
+    fn test_operand_forms() {
+        assert_eq!(disasm(0x02), "LD (BC), A");
+        assert_eq!(disasm(0x08), "LD (a16), SP");
+        assert_eq!(disasm(0x21), "LD HL, d16");
+        assert_eq!(disasm(0x22), "LD (HL+), A");
+        assert_eq!(disasm(0x2A), "LD A, (HL+)");
+        assert_eq!(disasm(0x32), "LD (HL-), A");
+        assert_eq!(disasm(0x3A), "LD A, (HL-)");
+        assert_eq!(disasm(0x36), "LD (HL), d8");
+        assert_eq!(disasm(0x18), "JR rel");
+        assert_eq!(disasm(0xE9), "JP HL");
+        assert_eq!(disasm(0xF0), "LDH A, (a8)");
+        assert_eq!(disasm(0xF8), "LD HL, SP+r8");
+        assert_eq!(disasm(0xFA), "LD A, (a16)");
+        assert_eq!(disasm(0x09), "ADD HL, BC");
+    }
+
+    #[test]
+    fn test_component_display() {
+        assert_eq!(AddressingMode::RegisterRegister.to_string(), "r,r");
+        assert_eq!(AddressingMode::RegisterMemory.to_string(), "r,(r)");
+        assert_eq!(AddressingMode::RegisterD16.to_string(), "r,d16");
+        assert_eq!(AddressingMode::Implied.to_string(), "");
+        assert_eq!(RegisterType::A.to_string(), "A");
+        assert_eq!(RegisterType::Bc.to_string(), "BC");
+        assert_eq!(RegisterType::Hl.to_string(), "HL");
+        assert_eq!(ConditionType::Nz.to_string(), "NZ");
+        assert_eq!(ConditionType::Nc.to_string(), "NC");
+        assert_eq!(ConditionType::None.to_string(), "");
+    }
+}
@@ -2,22 +2,188 @@
 //!
 //! This module implements the Sharp LR35902 CPU emulation for the Game Boy.
 
+pub mod asm;
+pub mod disasm;
 pub mod execute;
 pub mod fetch;
 pub mod instructions;
 pub mod registers;
 
+use crate::bus::MemoryBus;
 use crate::common::{Byte, Word};
+use crate::profiler::OpcodeProfiler;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::Write;
 use registers::Registers;
-use std::fmt;
+
+/// Cycles the CPU stays stopped after STOP triggers a CGB speed switch
+pub const SPEED_SWITCH_CYCLES: u32 = 2050;
+
+/// Instruction about to be executed, passed to the single-step callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepEvent {
+    /// Address of the instruction
+    pub pc: Word,
+    /// Opcode at `pc`
+    pub opcode: Byte,
+}
+
+/// Single-step callback verdict
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepAction {
+    /// Run the instruction; `false` pauses the emulator before it
+    pub should_continue: bool,
+    /// Fetch the instruction and its operands but do not execute it
+    pub skip_instruction: bool,
+}
+
+/// Callback invoked before each instruction in `Emulator::step`
+pub type StepCallback = Box<dyn FnMut(&Cpu, StepEvent) -> StepAction>;
+
+/// Handler for undefined opcodes, called with `(opcode, pc)`
+pub type InvalidOpcodeHandler = Box<dyn FnMut(Byte, Word)>;
+
+/// Holder for a CPU callback
+///
+/// Callbacks cannot be cloned, so a cloned CPU (e.g. `Emulator::fork`) starts without one.
+struct CallbackSlot<T>(Option<T>);
+
+impl<T> Default for CallbackSlot<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<T> Clone for CallbackSlot<T> {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+impl<T> fmt::Debug for CallbackSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Some(<callback>)" } else { "None" })
+    }
+}
+
+/// Snapshot of the architectural CPU state
+///
+/// `Display` prints the register part of a Gameboy Doctor log line
+/// (everything before `PCMEM`, which needs bus access).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CpuState {
+    pub a: Byte,
+    pub f: Byte,
+    pub b: Byte,
+    pub c: Byte,
+    pub d: Byte,
+    pub e: Byte,
+    pub h: Byte,
+    pub l: Byte,
+    pub sp: Word,
+    pub pc: Word,
+    pub ime: bool,
+    pub halted: bool,
+    pub int_flags: Byte,
+    pub ie_register: Byte,
+}
+
+impl CpuState {
+    /// Flags as `ZNHC`, with lowercase letters for cleared flags (e.g. `Znhc`)
+    pub fn flags_string(&self) -> String {
+        [(7, 'Z'), (6, 'N'), (5, 'H'), (4, 'C')]
+            .iter()
+            .map(|&(bit, name)| if self.f & (1 << bit) != 0 { name } else { name.to_ascii_lowercase() })
+            .collect()
+    }
+}
+
+impl From<&Cpu> for CpuState {
+    fn from(cpu: &Cpu) -> Self {
+        let r = &cpu.regs;
+        Self {
+            a: r.a,
+            f: r.f,
+            b: r.b,
+            c: r.c,
+            d: r.d,
+            e: r.e,
+            h: r.h,
+            l: r.l,
+            sp: r.sp,
+            pc: r.pc,
+            ime: cpu.ime,
+            halted: cpu.halted,
+            int_flags: cpu.int_flags,
+            ie_register: cpu.ie_register,
+        }
+    }
+}
+
+impl fmt::Display for CpuState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X}",
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l, self.sp, self.pc,
+        )
+    }
+}
+
+/// Register values left behind by a boot ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuInitState {
+    pub a: Byte,
+    pub f: Byte,
+    pub b: Byte,
+    pub c: Byte,
+    pub d: Byte,
+    pub e: Byte,
+    pub h: Byte,
+    pub l: Byte,
+    pub sp: Word,
+    pub pc: Word,
+}
+
+impl CpuInitState {
+    /// DMG boot ROM (A=0x01, F=Z-HC)
+    pub const DMG: Self = Self {
+        a: 0x01, f: 0xB0, b: 0x00, c: 0x13, d: 0x00, e: 0xD8, h: 0x01, l: 0x4D, sp: 0xFFFE, pc: 0x0100,
+    };
+    /// CGB boot ROM running a CGB game (A=0x11 identifies GBC hardware)
+    pub const CGB: Self = Self {
+        a: 0x11, f: 0x80, b: 0x00, c: 0x00, d: 0xFF, e: 0x56, h: 0x00, l: 0x0D, sp: 0xFFFE, pc: 0x0100,
+    };
+    /// Super Game Boy boot ROM
+    pub const SGB: Self = Self {
+        a: 0x01, f: 0x00, b: 0x00, c: 0x14, d: 0x00, e: 0x00, h: 0xC0, l: 0x60, sp: 0xFFFE, pc: 0x0100,
+    };
+}
 
 /// CPU state for the Sharp LR35902 processor
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cpu {
     /// CPU registers (A, F, B, C, D, E, H, L, SP, PC)
     pub regs: Registers,
     /// CPU is in halted state (waiting for interrupt)
     pub halted: bool,
+    /// HALT ran with IME clear and an interrupt pending, so the next opcode
+    /// fetch does not advance PC (the HALT bug)
+    pub halt_bug: bool,
+    /// STOP armed a CGB speed switch; the CPU is stopped until it completes
+    pub speed_switch_pending: bool,
+    /// Cycles left before a pending speed switch completes
+    pub stop_countdown: u32,
+    /// The last step finished a speed switch; the caller flips KEY1 to the
+    /// new speed
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    pub speed_switch_completed: bool,
     /// Interrupt Master Enable flag
     pub ime: bool,
     /// IME will be enabled after next instruction (for EI instruction)
@@ -35,9 +201,26 @@ pub struct Cpu {
     /// Current opcode being executed
     pub cur_opcode: Byte,
     /// Current instruction reference
+    #[cfg_attr(feature = "save-state", serde(skip))]
     cur_inst: Option<&'static instructions::Instruction>,
     /// M-cycles consumed by the current step
     pending_m_cycles: u32,
+    /// Total M-cycles executed
+    pub cycle_count: u64,
+    /// PC at the start of the current step (histogram key)
+    step_pc: Word,
+    /// M-cycles spent per PC address, when profiling is enabled
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    cycle_histogram: Option<Box<[u32; 65536]>>,
+    /// Executed opcode counts, when profiling is enabled
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    opcode_profiler: Option<Box<OpcodeProfiler>>,
+    /// Debugger hook called before each instruction
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    single_step_callback: CallbackSlot<StepCallback>,
+    /// Called for undefined opcodes instead of panicking, if set
+    #[cfg_attr(feature = "save-state", serde(skip))]
+    invalid_opcode_handler: CallbackSlot<InvalidOpcodeHandler>,
 }
 
 impl Default for Cpu {
@@ -52,6 +235,10 @@ impl Cpu {
         Self {
             regs: Registers::new(),
             halted: false,
+            halt_bug: false,
+            speed_switch_pending: false,
+            stop_countdown: 0,
+            speed_switch_completed: false,
             ime: false,
             enabling_ime: false,
             ie_register: 0,
@@ -62,23 +249,64 @@ impl Cpu {
             cur_opcode: 0,
             cur_inst: None,
             pending_m_cycles: 0,
+            cycle_count: 0,
+            step_pc: 0,
+            cycle_histogram: None,
+            opcode_profiler: None,
+            single_step_callback: CallbackSlot::default(),
+            invalid_opcode_handler: CallbackSlot::default(),
         }
     }
 
+    /// Replace the processor state with one from a save state
+    ///
+    /// Profiling data and debugger hooks stay attached.
+    #[cfg(feature = "save-state")]
+    pub(crate) fn load_state(&mut self, mut saved: Cpu) {
+        saved.cycle_histogram = self.cycle_histogram.take();
+        saved.opcode_profiler = self.opcode_profiler.take();
+        saved.single_step_callback = core::mem::take(&mut self.single_step_callback);
+        saved.invalid_opcode_handler = core::mem::take(&mut self.invalid_opcode_handler);
+        *self = saved;
+    }
+
     /// Initialize CPU to boot ROM skip state
     ///
     /// This sets the registers to the values they would have after
     /// the boot ROM has finished executing.
     pub fn init(&mut self) {
         // Boot ROM skip values (DMG)
-        self.regs.pc = 0x0100; // Entry point after boot ROM
-        self.regs.sp = 0xFFFE; // Stack pointer
-        self.regs.set_af(0x01B0); // A=0x01, F=0xB0 (Z=1, N=0, H=1, C=1)
-        self.regs.set_bc(0x0013);
-        self.regs.set_de(0x00D8);
-        self.regs.set_hl(0x014D);
+        self.init_from(&CpuInitState::DMG);
+    }
+
+    /// Initialize CPU to the CGB boot ROM skip state
+    pub fn init_cgb(&mut self) {
+        self.init_from(&CpuInitState::CGB);
+    }
+
+    /// Initialize CPU to the Super Game Boy boot ROM skip state
+    pub fn init_sgb(&mut self) {
+        self.init_from(&CpuInitState::SGB);
+    }
+
+    /// Load post-boot registers and clear interrupt/halt state
+    pub fn init_from(&mut self, state: &CpuInitState) {
+        self.regs.a = state.a;
+        self.regs.f = state.f & 0xF0;
+        self.regs.b = state.b;
+        self.regs.c = state.c;
+        self.regs.d = state.d;
+        self.regs.e = state.e;
+        self.regs.h = state.h;
+        self.regs.l = state.l;
+        self.regs.sp = state.sp;
+        self.regs.pc = state.pc;
 
         self.halted = false;
+        self.halt_bug = false;
+        self.speed_switch_pending = false;
+        self.stop_countdown = 0;
+        self.speed_switch_completed = false;
         self.ime = false;
         self.enabling_ime = false;
         self.ie_register = 0;
@@ -91,6 +319,30 @@ impl Cpu {
         self.cur_inst
     }
 
+    /// Disassemble the instruction at PC; returns the text and the next address
+    pub fn disassemble_next<B: MemoryBus>(&self, bus: &B) -> (String, Word) {
+        disasm::disassemble(self.regs.pc, bus)
+    }
+
+    /// Disassemble the instruction at `address`; returns the text and its length in bytes
+    pub fn disassemble<B: MemoryBus>(&self, bus: &B, address: Word) -> (String, u8) {
+        let (text, next) = disasm::disassemble(address, bus);
+        (text, next.wrapping_sub(address) as u8)
+    }
+
+    /// Disassemble `count` consecutive instructions starting at `start`
+    pub fn disassemble_range<B: MemoryBus>(&self, start: Word, count: usize, bus: &B) -> Vec<(Word, String)> {
+        let mut address = start;
+        (0..count)
+            .map(|_| {
+                let (text, next) = disasm::disassemble(address, bus);
+                let entry = (address, text);
+                address = next;
+                entry
+            })
+            .collect()
+    }
+
     /// Set the current instruction
     pub fn set_current_instruction(&mut self, inst: Option<&'static instructions::Instruction>) {
         self.cur_inst = inst;
@@ -99,11 +351,131 @@ impl Cpu {
     /// Reset M-cycle accounting for a new CPU step
     pub fn reset_step_cycles(&mut self) {
         self.pending_m_cycles = 0;
+        self.speed_switch_completed = false;
+        self.step_pc = self.regs.pc;
     }
 
     /// Add consumed M-cycles
     pub fn add_m_cycles(&mut self, cycles: u32) {
         self.pending_m_cycles = self.pending_m_cycles.saturating_add(cycles);
+        self.cycle_count = self.cycle_count.wrapping_add(cycles as u64);
+        if let Some(histogram) = self.cycle_histogram.as_mut() {
+            let entry = &mut histogram[self.step_pc as usize];
+            *entry = entry.saturating_add(cycles);
+        }
+    }
+
+    /// Reset the total M-cycle counter
+    pub fn reset_cycle_count(&mut self) {
+        self.cycle_count = 0;
+    }
+
+    /// Start recording M-cycles per PC address (clears any previous data)
+    pub fn enable_cycle_histogram(&mut self) {
+        self.cycle_histogram = Some(Box::new([0; 65536]));
+    }
+
+    /// Start counting executed opcodes (clears any previous counts)
+    pub fn enable_opcode_profiler(&mut self) {
+        self.opcode_profiler = Some(Box::default());
+    }
+
+    /// Stop counting opcodes and return the counts (empty if never enabled)
+    pub fn take_opcode_profile(&mut self) -> OpcodeProfiler {
+        self.opcode_profiler.take().map_or_else(OpcodeProfiler::new, |profiler| *profiler)
+    }
+
+    /// Count an executed opcode if the profiler is enabled
+    fn record_opcode(&mut self, opcode: Byte, is_cb: bool) {
+        if let Some(profiler) = self.opcode_profiler.as_mut() {
+            profiler.record(opcode, is_cb);
+        }
+    }
+
+    /// Get the M-cycles recorded at a PC address (0 if profiling is disabled)
+    pub fn cycle_histogram_at(&self, addr: Word) -> u32 {
+        self.cycle_histogram
+            .as_ref()
+            .map_or(0, |histogram| histogram[addr as usize])
+    }
+
+    /// Get all non-zero histogram entries, sorted by M-cycles descending
+    pub fn dump_cycle_histogram(&self) -> Vec<(Word, u32)> {
+        let mut entries: Vec<(Word, u32)> = match self.cycle_histogram.as_ref() {
+            Some(histogram) => histogram
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count > 0)
+                .map(|(addr, &count)| (addr as Word, count))
+                .collect(),
+            None => Vec::new(),
+        };
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        entries
+    }
+
+    /// Install a callback invoked before each instruction (replaces any previous one)
+    pub fn set_step_callback(&mut self, callback: StepCallback) {
+        self.single_step_callback.0 = Some(callback);
+    }
+
+    /// Remove the single-step callback
+    pub fn clear_step_callback(&mut self) {
+        self.single_step_callback.0 = None;
+    }
+
+    /// Notify the single-step callback of the instruction at PC
+    ///
+    /// Returns `None` when no callback is installed.
+    pub fn notify_step<B: MemoryBus>(&mut self, bus: &B) -> Option<StepAction> {
+        let mut callback = self.single_step_callback.0.take()?;
+        let pc = self.regs.pc;
+        let action = callback(self, StepEvent { pc, opcode: bus.peek(pc) });
+        self.single_step_callback.0 = Some(callback);
+        Some(action)
+    }
+
+    /// Run undefined opcodes as NOPs, reporting each to `handler`
+    ///
+    /// The handler receives the opcode and its address.
+    pub fn set_invalid_opcode_handler(&mut self, handler: impl FnMut(Byte, Word) + 'static) {
+        self.invalid_opcode_handler.0 = Some(Box::new(handler));
+    }
+
+    /// Remove the invalid opcode handler so undefined opcodes panic again
+    pub fn panic_on_invalid_opcode(&mut self) {
+        self.invalid_opcode_handler.0 = None;
+    }
+
+    /// Report an undefined opcode, panicking if no handler is installed
+    fn report_invalid_opcode(&mut self) {
+        let pc = self.regs.pc.wrapping_sub(1);
+        match self.invalid_opcode_handler.0.as_mut() {
+            Some(handler) => handler(self.cur_opcode, pc),
+            None => panic!("INVALID INSTRUCTION!"),
+        }
+    }
+
+    /// Format the current state as a Gameboy Doctor log line (with newline)
+    ///
+    /// Must be called before `fetch_instruction` so `PCMEM` shows the four
+    /// bytes at the instruction about to run.
+    pub fn format_gameboy_doctor<B: MemoryBus>(&self, bus: &B) -> String {
+        let pc = self.regs.pc;
+        format!(
+            "{} PCMEM:{:02X},{:02X},{:02X},{:02X}\n",
+            CpuState::from(self),
+            bus.peek(pc),
+            bus.peek(pc.wrapping_add(1)),
+            bus.peek(pc.wrapping_add(2)),
+            bus.peek(pc.wrapping_add(3)),
+        )
+    }
+
+    /// Write a Gameboy Doctor log line for the current state
+    #[cfg(feature = "std")]
+    pub fn write_gameboy_doctor_line<B: MemoryBus>(&self, bus: &B, writer: &mut dyn Write) -> std::io::Result<()> {
+        writer.write_all(self.format_gameboy_doctor(bus).as_bytes())
     }
 
     /// Consume and return pending T-cycles (M-cycles * 4)
@@ -133,12 +505,10 @@ impl Cpu {
         }
 
         // Check in priority order (VBlank highest, Joypad lowest)
-        for &int_type in InterruptType::all() {
-            if (pending & int_type.bit()) != 0 {
-                return Some(int_type);
-            }
-        }
-        None
+        InterruptType::all()
+            .iter()
+            .copied()
+            .find(|int_type| (pending & int_type.bit()) != 0)
     }
 
     /// Clear an interrupt flag
@@ -217,6 +587,8 @@ impl InterruptType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bus::MemoryBus;
+    use crate::test_utils::MockBus;
 
     #[test]
     fn test_cpu_new() {
@@ -246,6 +618,41 @@ mod tests {
         assert!(cpu.regs.flag_c());
     }
 
+    #[test]
+    fn test_cpu_init_cgb() {
+        let mut cpu = Cpu::new();
+        cpu.init_cgb();
+
+        assert_eq!(cpu.regs.af(), 0x1180);
+        assert_eq!(cpu.regs.bc(), 0x0000);
+        assert_eq!(cpu.regs.de(), 0xFF56);
+        assert_eq!(cpu.regs.hl(), 0x000D);
+        assert_eq!(cpu.regs.sp, 0xFFFE);
+        assert_eq!(cpu.regs.pc, 0x0100);
+        assert!(cpu.regs.flag_z());
+        assert!(!cpu.regs.flag_c());
+    }
+
+    #[test]
+    fn test_cpu_init_cgb_overwrites_dmg() {
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.halted = true;
+        cpu.ime = true;
+        cpu.init_cgb();
+
+        assert_eq!(CpuState::from(&cpu), CpuState {
+            a: 0x11, f: 0x80, b: 0x00, c: 0x00, d: 0xFF, e: 0x56, h: 0x00, l: 0x0D,
+            sp: 0xFFFE, pc: 0x0100,
+            ..CpuState::default()
+        });
+
+        cpu.init_sgb();
+        assert_eq!(cpu.regs.af(), 0x0100);
+        assert_eq!(cpu.regs.bc(), 0x0014);
+        assert_eq!(cpu.regs.hl(), 0xC060);
+    }
+
     #[test]
     fn test_interrupt_request() {
         let mut cpu = Cpu::new();
@@ -299,6 +706,29 @@ mod tests {
         assert_eq!(InterruptType::Joypad.vector(), 0x0060);
     }
 
+    #[test]
+    fn test_cycle_count() {
+        let mut cpu = Cpu::new();
+        cpu.add_m_cycles(3);
+        cpu.add_m_cycles(2);
+        assert_eq!(cpu.cycle_count, 5);
+
+        // Taking T-cycles for a step doesn't reset the running total
+        assert_eq!(cpu.take_t_cycles(), 20);
+        assert_eq!(cpu.cycle_count, 5);
+
+        cpu.reset_cycle_count();
+        assert_eq!(cpu.cycle_count, 0);
+    }
+
+    #[test]
+    fn test_cycle_histogram_disabled_by_default() {
+        let mut cpu = Cpu::new();
+        cpu.add_m_cycles(4);
+        assert_eq!(cpu.cycle_histogram_at(0x0000), 0);
+        assert!(cpu.dump_cycle_histogram().is_empty());
+    }
+
     #[test]
     fn test_clear_interrupt() {
         let mut cpu = Cpu::new();
@@ -310,4 +740,337 @@ mod tests {
         cpu.clear_interrupt(InterruptType::Timer);
         assert_eq!(cpu.int_flags, 0x1A);
     }
+
+    #[test]
+    fn test_execute_with_mock_bus() {
+        // LD (HL),A; PUSH BC
+        let mut bus = MockBus::with_data(0x0100, &[0x77, 0xC5]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.regs.set_hl(0xC000);
+
+        for _ in 0..2 {
+            cpu.fetch_instruction(&bus);
+            cpu.fetch_data(&bus);
+            cpu.execute(&mut bus);
+        }
+
+        assert_eq!(bus.read(0xC000), 0x01);
+        assert_eq!(bus.write_log(), &[(0xC000, 0x01), (0xFFFD, 0x00), (0xFFFC, 0x13)]);
+    }
+
+    #[test]
+    fn test_invalid_opcode_handler() {
+        // NOP; invalid 0xD3; INC A
+        let mut bus = MockBus::with_data(0x0100, &[0x00, 0xD3, 0x3C]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = seen.clone();
+        cpu.set_invalid_opcode_handler(move |opcode, pc| log.borrow_mut().push((opcode, pc)));
+
+        for _ in 0..3 {
+            cpu.fetch_instruction(&bus);
+            cpu.fetch_data(&bus);
+            cpu.execute(&mut bus);
+        }
+
+        assert_eq!(*seen.borrow(), [(0xD3, 0x0101)]);
+        // Execution carried on past the invalid opcode
+        assert_eq!(cpu.regs.pc, 0x0103);
+        assert_eq!(cpu.regs.a, 0x02);
+    }
+
+    #[test]
+    #[should_panic(expected = "INVALID INSTRUCTION!")]
+    fn test_invalid_opcode_panics_without_handler() {
+        let mut bus = MockBus::with_data(0x0100, &[0xD3]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.set_invalid_opcode_handler(|_, _| {});
+        cpu.panic_on_invalid_opcode();
+
+        cpu.fetch_instruction(&bus);
+        cpu.fetch_data(&bus);
+        cpu.execute(&mut bus);
+    }
+
+    #[test]
+    fn test_step_with_cycles() {
+        // NOP; EI; NOP
+        let mut bus = MockBus::with_data(0x0100, &[0x00, 0xFB, 0x00]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.ie_register = 0x01;
+        cpu.int_flags = 0x01;
+
+        assert_eq!(cpu.step_with_cycles(&mut bus), 4);
+        assert_eq!(cpu.step_with_cycles(&mut bus), 4);
+        // IME takes effect after the instruction following EI
+        assert_eq!(cpu.step_with_cycles(&mut bus), 4);
+        assert_eq!(cpu.step_with_cycles(&mut bus), 20);
+        assert_eq!(cpu.regs.pc, 0x0040);
+        assert_eq!(cpu.int_flags, 0x00);
+    }
+
+    #[test]
+    fn test_step_with_cycles_speed_switch() {
+        // STOP; (padding); INC A
+        let mut bus = MockBus::with_data(0x0100, &[0x10, 0x00, 0x3C]);
+        bus.write(0xFF4D, 0x01);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.ie_register = 0x01;
+        cpu.int_flags = 0x01;
+        let a = cpu.regs.a;
+
+        let mut cycles = cpu.step_with_cycles(&mut bus);
+        assert!(cpu.speed_switch_pending);
+        let stop_pc = cpu.regs.pc;
+
+        // Stopped CPUs neither fetch nor dispatch interrupts
+        cpu.ime = true;
+        while cpu.speed_switch_pending {
+            assert!(!cpu.speed_switch_completed);
+            cycles += cpu.step_with_cycles(&mut bus);
+            assert_eq!(cpu.regs.pc, stop_pc);
+            assert_eq!(cpu.int_flags, 0x01);
+        }
+        assert!(cpu.speed_switch_completed);
+        assert_eq!(cycles, SPEED_SWITCH_CYCLES);
+
+        cpu.ime = false;
+        cpu.step_with_cycles(&mut bus);
+        assert!(!cpu.speed_switch_completed);
+        cpu.step_with_cycles(&mut bus);
+        assert_eq!(cpu.regs.a, a.wrapping_add(1));
+    }
+
+    /// M-cycles per unprefixed opcode with Z and C set (NZ/NC branches not
+    /// taken, Z/C taken); 0 marks illegal opcodes
+    const OPCODE_M_CYCLES: [u32; 256] = [
+        1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1,
+        1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1,
+        2, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1,
+        2, 3, 2, 2, 3, 3, 3, 1, 3, 2, 2, 2, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        2, 3, 3, 4, 3, 4, 2, 4, 5, 4, 4, 0, 6, 6, 2, 4,
+        2, 3, 3, 0, 3, 4, 2, 4, 5, 4, 4, 0, 6, 0, 2, 4,
+        3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4,
+        3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4,
+    ];
+
+    #[test]
+    fn test_instruction_cycle_counts() {
+        for opcode in 0..=0xFFu8 {
+            let expected = OPCODE_M_CYCLES[opcode as usize];
+            if expected == 0 || opcode == 0xCB {
+                continue;
+            }
+            let mut bus = MockBus::with_data(0x0100, &[opcode, 0x00, 0x00]);
+            let mut cpu = Cpu::new();
+            cpu.init();
+            cpu.regs.f = 0x90;
+            assert_eq!(cpu.step_with_cycles(&mut bus), expected * 4, "opcode {:02X}", opcode);
+        }
+
+        // CB: 2 M-cycles on registers, 4 on (HL), 3 for BIT n,(HL)
+        for op in 0..=0xFFu8 {
+            let expected = match (op & 0x07, op >> 6) {
+                (6, 1) => 3,
+                (6, _) => 4,
+                _ => 2,
+            };
+            let mut bus = MockBus::with_data(0x0100, &[0xCB, op]);
+            let mut cpu = Cpu::new();
+            cpu.init();
+            assert_eq!(cpu.step_with_cycles(&mut bus), expected * 4, "opcode CB {:02X}", op);
+        }
+    }
+
+    #[test]
+    fn test_interrupt_dispatch_costs_5_m_cycles() {
+        let mut bus = MockBus::new();
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.ime = true;
+        cpu.ie_register = 0x04;
+        cpu.int_flags = 0x04;
+
+        cpu.reset_step_cycles();
+        assert!(cpu.handle_interrupts(&mut bus));
+        assert_eq!(cpu.take_t_cycles(), 20);
+        assert_eq!(cpu.regs.pc, 0x0050);
+        assert_eq!(bus.read16(0xFFFC), 0x0100);
+    }
+
+    #[test]
+    fn test_halt_exits_without_dispatch_when_ime_clear() {
+        let mut bus = MockBus::with_data(0x0100, &[0x00]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.halted = true;
+        cpu.ie_register = 0x01;
+        assert_eq!(cpu.step_with_cycles(&mut bus), 4);
+        assert!(cpu.halted);
+
+        // VBlank requested: one cycle later the CPU is awake but nothing ran
+        cpu.int_flags = 0x01;
+        assert_eq!(cpu.step_with_cycles(&mut bus), 4);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.regs.pc, 0x0100);
+        assert_eq!(cpu.regs.sp, 0xFFFE);
+        assert_eq!(cpu.int_flags, 0x01);
+    }
+
+    #[test]
+    fn test_halt_bug_repeats_next_opcode() {
+        // HALT; INC A; NOP
+        let mut bus = MockBus::with_data(0x0100, &[0x76, 0x3C, 0x00]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.regs.a = 0;
+        cpu.ime = false;
+        cpu.ie_register = 0x04;
+        cpu.int_flags = 0x04;
+
+        cpu.step_with_cycles(&mut bus);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.regs.pc, 0x0101);
+
+        // INC A is fetched without advancing PC, so it runs twice
+        cpu.step_with_cycles(&mut bus);
+        assert_eq!(cpu.regs.pc, 0x0101);
+        cpu.step_with_cycles(&mut bus);
+        assert_eq!(cpu.regs.pc, 0x0102);
+        assert_eq!(cpu.regs.a, 2);
+        cpu.step_with_cycles(&mut bus);
+        assert_eq!(cpu.regs.a, 2);
+    }
+
+    #[test]
+    fn test_halt_without_pending_interrupt_halts() {
+        let mut bus = MockBus::with_data(0x0100, &[0x76, 0x3C]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+        cpu.ime = false;
+        cpu.ie_register = 0x04;
+
+        cpu.step_with_cycles(&mut bus);
+        assert!(cpu.halted);
+        assert!(!cpu.halt_bug);
+    }
+
+    #[test]
+    fn test_format_gameboy_doctor() {
+        let bus = MockBus::with_data(0x0100, &[0x00, 0xC3, 0x50, 0x01]);
+        let mut cpu = Cpu::new();
+        cpu.init();
+
+        assert_eq!(
+            cpu.format_gameboy_doctor(&bus),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01\n"
+        );
+
+        let mut out = Vec::new();
+        cpu.regs.a = 0xAB;
+        cpu.write_gameboy_doctor_line(&bus, &mut out).unwrap();
+        assert!(out.starts_with(b"A:AB F:B0"));
+        assert_eq!(*out.last().unwrap(), b'\n');
+    }
+
+    #[test]
+    fn test_cpu_state_from_cpu() {
+        let mut cpu = Cpu::new();
+        cpu.regs.a = 0x12;
+        cpu.regs.f = 0x80;
+        cpu.regs.b = 0x34;
+        cpu.regs.c = 0x56;
+        cpu.regs.d = 0x78;
+        cpu.regs.e = 0x9A;
+        cpu.regs.h = 0xBC;
+        cpu.regs.l = 0xDE;
+        cpu.regs.sp = 0xCFF0;
+        cpu.regs.pc = 0x4321;
+        cpu.ime = true;
+        cpu.halted = true;
+        cpu.int_flags = 0x05;
+        cpu.ie_register = 0x1F;
+
+        let state = CpuState::from(&cpu);
+        assert_eq!(
+            state,
+            CpuState {
+                a: 0x12,
+                f: 0x80,
+                b: 0x34,
+                c: 0x56,
+                d: 0x78,
+                e: 0x9A,
+                h: 0xBC,
+                l: 0xDE,
+                sp: 0xCFF0,
+                pc: 0x4321,
+                ime: true,
+                halted: true,
+                int_flags: 0x05,
+                ie_register: 0x1F,
+            }
+        );
+        assert_eq!(state.flags_string(), "Znhc");
+        assert_eq!(CpuState { f: 0x70, ..state }.flags_string(), "zNHC");
+        assert_eq!(
+            state.to_string(),
+            "A:12 F:80 B:34 C:56 D:78 E:9A H:BC L:DE SP:CFF0 PC:4321"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_next_follows_pc() {
+        let mut bus = MockBus::with_data(0x0100, &[0x3E, 0x42, 0xC3, 0x00, 0x02]);
+        let mut cpu = Cpu::new();
+        cpu.regs.pc = 0x0100;
+
+        let (text, next) = cpu.disassemble_next(&bus);
+        assert_eq!(text, "LD A, $42");
+        cpu.step_with_cycles(&mut bus);
+        assert_eq!(cpu.regs.a, 0x42);
+        assert_eq!(cpu.regs.pc, next);
+        assert_eq!(cpu.disassemble_next(&bus), (String::from("JP $0200"), 0x0105));
+
+        assert_eq!(
+            cpu.disassemble_range(0x0100, 2, &bus),
+            alloc::vec![(0x0100, String::from("LD A, $42")), (0x0102, String::from("JP $0200"))]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_lengths() {
+        let cpu = Cpu::new();
+        let cases: [(&[u8], &str, u8); 12] = [
+            (&[0x01, 0x34, 0x12], "LD BC, $1234", 3),
+            (&[0x20, 0x05], "JR NZ, $C007", 2),
+            (&[0x38, 0xFB], "JR C, $BFFD", 2),
+            (&[0xCB, 0x7E], "BIT 7, (HL)", 2),
+            (&[0xF8, 0xFE], "LD HL, SP-$02", 2),
+            (&[0x08, 0x00, 0xD0], "LD ($D000), SP", 3),
+            (&[0xFA, 0x10, 0xC0], "LD A, ($C010)", 3),
+            (&[0xF0, 0x44], "LDH A, ($FF44)", 2),
+            (&[0xE2], "LD (C), A", 1),
+            (&[0x2A], "LD A, (HL+)", 1),
+            (&[0xCD, 0x00, 0x40], "CALL $4000", 3),
+            (&[0xFF], "RST $38", 1),
+        ];
+        for (bytes, text, len) in cases {
+            let bus = MockBus::with_data(0xC000, bytes);
+            assert_eq!(cpu.disassemble(&bus, 0xC000), (String::from(text), len));
+        }
+    }
 }
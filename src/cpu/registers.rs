@@ -11,6 +11,7 @@ use crate::common::{bit, bit_set, Byte, Word};
 /// and 2 16-bit registers (SP, PC). The 8-bit registers can be
 /// combined into 16-bit register pairs (AF, BC, DE, HL).
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Registers {
     /// Accumulator register
     pub a: Byte,
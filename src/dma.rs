@@ -2,11 +2,17 @@
 //!
 //! This module implements OAM DMA transfer for the Game Boy.
 //! DMA transfers 160 bytes from source address to OAM (0xFE00-0xFE9F).
+//! It also implements CGB VRAM DMA (HDMA1-HDMA5, 0xFF51-0xFF55), which
+//! copies 16-byte blocks into VRAM either all at once or one per HBlank.
 
-use crate::common::Byte;
+use crate::common::{Byte, Word};
+
+/// Bytes copied per VRAM DMA block
+pub const HDMA_BLOCK_SIZE: Word = 0x10;
 
 /// DMA Transfer Controller
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dma {
     /// DMA is currently active
     pub active: bool,
@@ -54,8 +60,11 @@ impl Dma {
     }
 
     /// Get source address for current byte
+    ///
+    /// Pages 0xE0-0xFF are mirrored down to WRAM (0xC0-0xDF).
     pub fn source_address(&self) -> u16 {
-        (self.value as u16) << 8 | (self.byte as u16)
+        let page = if self.value >= 0xE0 { self.value - 0x20 } else { self.value };
+        (page as u16) << 8 | (self.byte as u16)
     }
 
     /// Get destination address for current byte
@@ -107,6 +116,88 @@ impl Dma {
     }
 }
 
+/// CGB VRAM DMA controller (HDMA1-HDMA5)
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct HdmaController {
+    /// A transfer is in progress
+    pub active: bool,
+    /// The transfer copies one block per HBlank (HDMA5 bit 7 was set)
+    pub hblank: bool,
+    /// Source of the next block
+    pub source: Word,
+    /// VRAM destination of the next block
+    pub dest: Word,
+    /// Blocks left to copy
+    pub remaining: u8,
+}
+
+impl HdmaController {
+    /// Create an idle VRAM DMA controller
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Initialize VRAM DMA
+    pub fn init(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Handle a write to HDMA5, with `source` from HDMA1-2 and `dest` from HDMA3-4
+    ///
+    /// Bits 0-6 give the length in blocks minus one and bit 7 selects an
+    /// HBlank transfer. Writing bit 7 clear while an HBlank transfer runs
+    /// cancels it instead of starting a general-purpose transfer.
+    pub fn write(&mut self, value: Byte, source: Word, dest: Word) {
+        if self.active && self.hblank && value & 0x80 == 0 {
+            self.active = false;
+            return;
+        }
+        self.active = true;
+        self.hblank = value & 0x80 != 0;
+        self.source = source & 0xFFF0;
+        self.dest = 0x8000 | (dest & 0x1FF0);
+        self.remaining = (value & 0x7F) + 1;
+    }
+
+    /// Read HDMA5: blocks left minus one, bit 7 set when no transfer runs
+    ///
+    /// Reads 0xFF once a transfer has completed.
+    pub fn read(&self) -> Byte {
+        let length = self.remaining.wrapping_sub(1) & 0x7F;
+        if self.active { length } else { 0x80 | length }
+    }
+
+    /// A general-purpose transfer is waiting to be copied
+    pub fn general_transfer_pending(&self) -> bool {
+        self.active && !self.hblank
+    }
+
+    /// An HBlank transfer is waiting for the next HBlank
+    pub fn hblank_transfer_pending(&self) -> bool {
+        self.active && self.hblank
+    }
+
+    /// Claim the next block to copy
+    ///
+    /// Returns (source, dest) of `HDMA_BLOCK_SIZE` bytes. The transfer
+    /// ends after the last block or when the destination passes 0x9FFF.
+    pub fn next_block(&mut self) -> Option<(Word, Word)> {
+        if !self.active {
+            return None;
+        }
+        let block = (self.source, self.dest);
+
+        self.source = self.source.wrapping_add(HDMA_BLOCK_SIZE);
+        self.dest += HDMA_BLOCK_SIZE;
+        self.remaining -= 1;
+        if self.remaining == 0 || self.dest > 0x9FF0 {
+            self.active = false;
+        }
+        Some(block)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,4 +270,43 @@ mod tests {
         assert!(!dma.active);
         assert!(dma.tick().is_none());
     }
+
+    #[test]
+    fn test_hdma_general_transfer_blocks() {
+        let mut hdma = HdmaController::new();
+        assert_eq!(hdma.read(), 0xFF);
+
+        hdma.write(0x02, 0xC123, 0xE345);
+        assert!(hdma.general_transfer_pending());
+        assert_eq!(hdma.read(), 0x02);
+
+        assert_eq!(hdma.next_block(), Some((0xC120, 0x8340)));
+        assert_eq!(hdma.next_block(), Some((0xC130, 0x8350)));
+        assert_eq!(hdma.next_block(), Some((0xC140, 0x8360)));
+        assert_eq!(hdma.next_block(), None);
+        assert_eq!(hdma.read(), 0xFF);
+    }
+
+    #[test]
+    fn test_hdma_hblank_cancel() {
+        let mut hdma = HdmaController::new();
+        hdma.write(0x83, 0x4000, 0x8000);
+        assert!(hdma.hblank_transfer_pending());
+        assert_eq!(hdma.read(), 0x03);
+
+        hdma.next_block();
+        hdma.write(0x00, 0, 0);
+        assert!(!hdma.active);
+        assert_eq!(hdma.read(), 0x82);
+        assert_eq!(hdma.next_block(), None);
+    }
+
+    #[test]
+    fn test_hdma_stops_at_end_of_vram() {
+        let mut hdma = HdmaController::new();
+        hdma.write(0x7F, 0xC000, 0x1FE0);
+        assert_eq!(hdma.next_block(), Some((0xC000, 0x9FE0)));
+        assert_eq!(hdma.next_block(), Some((0xC010, 0x9FF0)));
+        assert_eq!(hdma.next_block(), None);
+    }
 }
@@ -3,16 +3,46 @@
 //! This module contains the main emulator structure that integrates
 //! all hardware components and manages the emulation loop.
 
-use crate::apu::Apu;
-use crate::bus::{Bus, MemoryBus};
+use crate::apu::{Apu, CPU_CLOCK};
+use crate::apu::channels::ChannelState;
+use crate::apu::mixer;
+use crate::bus::{Bus, SystemBus};
 use crate::cart::Cartridge;
-use crate::cpu::Cpu;
-use crate::dma::Dma;
-use crate::gamepad::Gamepad;
-use crate::lcd::Lcd;
-use crate::ppu::Ppu;
-use crate::timer::Timer;
-use crate::cpu::InterruptType;
+use crate::common::{convert_buffer, PixelFormat, Word};
+use crate::cpu::registers::Registers;
+use crate::cpu::{Cpu, CpuState};
+use crate::dma::{Dma, HdmaController, HDMA_BLOCK_SIZE};
+use crate::events::{EventQueue, HardwareEvent};
+use crate::gamepad::{Button, Gamepad};
+use crate::lcd::{Lcd, PpuMode};
+use crate::apu::HardwareModel;
+use crate::ppu::{self, DmgPalette, FrameDiff, OamEntry, Ppu};
+use crate::profiler::OpcodeProfiler;
+use crate::timer::{Timer, TimerState};
+#[cfg(feature = "std")]
+use crate::audio::{AudioOutput, WavRecorder};
+#[cfg(feature = "std")]
+use crate::ppu::VideoOutput;
+use crate::error::EmulatorError;
+#[cfg(feature = "std")]
+use crate::plugin::EmulatorPlugin;
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use std::time::{Duration, Instant};
+#[cfg(feature = "gif-recording")]
+use crate::recording::GifRecorder;
+#[cfg(feature = "save-state")]
+use crate::savestate::{self, SaveState, SAVE_STATE_VERSION};
+use crate::serial::{self, SerialDevice};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
 
 /// Emulator context state
 #[derive(Debug, Clone)]
@@ -25,6 +55,10 @@ pub struct EmulatorContext {
     pub die: bool,
     /// Total T-cycles executed
     pub ticks: u64,
+    /// Value of `ticks` at the last `Emulator::reset`
+    pub reset_ticks: u64,
+    /// Multiplier on the T-cycles the frontend runs per frame
+    pub overclock_factor: f32,
 }
 
 impl Default for EmulatorContext {
@@ -34,12 +68,294 @@ impl Default for EmulatorContext {
             running: true,
             die: false,
             ticks: 0,
+            reset_ticks: 0,
+            overclock_factor: 1.0,
         }
     }
 }
 
+/// Debugger stop that paused the emulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// PC reached a breakpoint; the instruction there has not run yet
+    Breakpoint(Word),
+    /// The last instruction wrote to a watched address
+    Watchpoint(Word),
+}
+
+/// Measures emulation speed against the real hardware clock
+///
+/// The ratio is taken over a rolling window: once the window has elapsed,
+/// the next `update` starts a new one. Not available on wasm32, where
+/// `Instant::now` traps.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone)]
+pub struct Speedometer {
+    /// Start of the current window
+    pub measure_start: Instant,
+    /// T-cycle count at the start of the current window
+    pub cycles_at_start: u64,
+    /// Window length
+    window: Duration,
+    /// Ratio measured over the last complete window
+    last_speed: f64,
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Speedometer {
+    /// Start measuring from `current_cycles` with a 1-second window
+    pub fn new(current_cycles: u64) -> Self {
+        Self {
+            measure_start: Instant::now(),
+            cycles_at_start: current_cycles,
+            window: Duration::from_secs(1),
+            last_speed: 0.0,
+        }
+    }
+
+    /// Emulation speed so far in the window (1.0 = realtime)
+    pub fn update(&mut self, current_cycles: u64) -> f64 {
+        let elapsed = self.measure_start.elapsed();
+        if elapsed.is_zero() {
+            return self.last_speed;
+        }
+        let cycles = current_cycles.saturating_sub(self.cycles_at_start);
+        let speed = cycles as f64 / CPU_CLOCK as f64 / elapsed.as_secs_f64();
+        if elapsed >= self.window {
+            self.last_speed = speed;
+            self.measure_start = Instant::now();
+            self.cycles_at_start = current_cycles;
+        }
+        speed
+    }
+}
+
+/// T-cycles from one VBlank to the next (456 * 154)
+const T_CYCLES_PER_FRAME: u64 = 70224;
+/// Frame starts kept by `TimingVerifier`
+const TIMING_HISTORY: usize = 60;
+/// T-cycles the CPU is halted per block of a general-purpose VRAM DMA
+const HDMA_BLOCK_STALL_CYCLES: u32 = 32;
+/// Bytes of serial output kept before the oldest half is dropped
+const SERIAL_OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// Checks that the PPU starts a frame every `expected_cycles_per_frame` T-cycles
+#[derive(Debug, Clone)]
+pub struct TimingVerifier {
+    /// Frame length the PPU should keep to
+    expected_cycles_per_frame: u64,
+    /// T-cycle counts at the most recent frame starts, oldest first
+    frame_starts: VecDeque<u64>,
+    /// Allowed deviation of the average frame length
+    tolerance_cycles: u64,
+}
+
+impl TimingVerifier {
+    /// Create a verifier for the given frame length and tolerance
+    pub fn new(expected_cycles_per_frame: u64, tolerance_cycles: u64) -> Self {
+        Self {
+            expected_cycles_per_frame,
+            frame_starts: VecDeque::with_capacity(TIMING_HISTORY),
+            tolerance_cycles,
+        }
+    }
+
+    /// Note that a frame started at T-cycle `cycle`
+    pub fn record_frame_start(&mut self, cycle: u64) {
+        if self.frame_starts.len() == TIMING_HISTORY {
+            self.frame_starts.pop_front();
+        }
+        self.frame_starts.push_back(cycle);
+    }
+
+    /// Average T-cycles between recorded frame starts (0.0 until two are recorded)
+    pub fn average_frame_cycles(&self) -> f64 {
+        match (self.frame_starts.front(), self.frame_starts.back()) {
+            (Some(&first), Some(&last)) if self.frame_starts.len() > 1 => {
+                (last - first) as f64 / (self.frame_starts.len() - 1) as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Check if the average frame length is within the tolerance
+    ///
+    /// Returns false until at least two frame starts are recorded.
+    pub fn is_synchronized(&self) -> bool {
+        if self.frame_starts.len() < 2 {
+            return false;
+        }
+        let deviation = self.average_frame_cycles() - self.expected_cycles_per_frame as f64;
+        deviation.abs() <= self.tolerance_cycles as f64
+    }
+}
+
+/// Hardware model the emulator presents to the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub enum EmulatorMode {
+    /// Pick DMG or CGB from the cartridge header
+    #[default]
+    Auto,
+    /// Original Game Boy
+    Dmg,
+    /// Game Boy Color
+    Cgb,
+    /// CGB hardware running a DMG game (compatibility palettes, no CGB registers)
+    DmgOnCgb,
+}
+
+/// Accepted range for `EmulatorBuilder::audio_rate`
+const AUDIO_RATE_RANGE: core::ops::RangeInclusive<u32> = 8000..=192000;
+
+/// Step-by-step construction of an `Emulator`
+///
+/// Exactly one of `rom` or `rom_bytes` must be given.
+#[derive(Debug, Clone)]
+pub struct EmulatorBuilder {
+    #[cfg(feature = "std")]
+    rom_path: Option<String>,
+    rom_bytes: Option<Vec<u8>>,
+    mode: EmulatorMode,
+    audio_rate: u32,
+    #[cfg(feature = "std")]
+    save_dir: Option<PathBuf>,
+    random_wram: bool,
+    wram_seed: u64,
+    boot_rom: Option<Vec<u8>>,
+}
+
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EmulatorBuilder {
+    /// Start from the defaults: `Auto` mode, 44.1 kHz audio, zeroed WRAM, no boot ROM
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            rom_path: None,
+            rom_bytes: None,
+            mode: EmulatorMode::Auto,
+            audio_rate: crate::apu::SAMPLE_RATE,
+            #[cfg(feature = "std")]
+            save_dir: None,
+            random_wram: false,
+            wram_seed: 0,
+            boot_rom: None,
+        }
+    }
+
+    /// Load the ROM from a file (battery saves are persisted)
+    #[cfg(feature = "std")]
+    pub fn rom(mut self, path: impl Into<String>) -> Self {
+        self.rom_path = Some(path.into());
+        self
+    }
+
+    /// Use an in-memory ROM image
+    ///
+    /// Battery saves are not persisted unless enabled on the cartridge with
+    /// `Cartridge::enable_battery_saves`.
+    pub fn rom_bytes(mut self, data: Vec<u8>) -> Self {
+        self.rom_bytes = Some(data);
+        self
+    }
+
+    /// Hardware model to emulate
+    pub fn mode(mut self, mode: EmulatorMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Audio output rate in Hz (8000-192000)
+    pub fn audio_rate(mut self, rate: u32) -> Self {
+        self.audio_rate = rate;
+        self
+    }
+
+    /// Store battery saves in this directory instead of the platform default
+    #[cfg(feature = "std")]
+    pub fn save_directory(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_dir = Some(path.into());
+        self
+    }
+
+    /// Fill WRAM with pseudo-random bytes at power-on, like real hardware
+    pub fn random_wram(mut self, enabled: bool) -> Self {
+        self.random_wram = enabled;
+        self
+    }
+
+    /// Seed for `random_wram`
+    pub fn wram_seed(mut self, seed: u64) -> Self {
+        self.wram_seed = seed;
+        self
+    }
+
+    /// Run this boot ROM from 0x0000 instead of starting at the post-boot state
+    pub fn boot_rom(mut self, data: Vec<u8>) -> Self {
+        self.boot_rom = Some(data);
+        self
+    }
+
+    /// Load the cartridge named by exactly one ROM source
+    fn load_cartridge(&mut self) -> Result<Cartridge, EmulatorError> {
+        #[cfg(feature = "std")]
+        if let Some(path) = self.rom_path.take() {
+            if self.rom_bytes.is_some() {
+                return Err(EmulatorError::InvalidConfig(String::from(
+                    "both a ROM path and ROM bytes were given",
+                )));
+            }
+            return Ok(Cartridge::load(path)?);
+        }
+        let data = self
+            .rom_bytes
+            .take()
+            .ok_or_else(|| EmulatorError::InvalidConfig(String::from("no ROM was given")))?;
+        Cartridge::from_bytes(data)
+    }
+
+    /// Validate the configuration and create the emulator
+    pub fn build(mut self) -> Result<Emulator, EmulatorError> {
+        if !AUDIO_RATE_RANGE.contains(&self.audio_rate) {
+            return Err(EmulatorError::InvalidConfig(format!(
+                "audio rate {} Hz is outside {}-{} Hz",
+                self.audio_rate,
+                AUDIO_RATE_RANGE.start(),
+                AUDIO_RATE_RANGE.end()
+            )));
+        }
+
+        #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+        let mut cart = self.load_cartridge()?;
+        #[cfg(feature = "std")]
+        if let Some(dir) = self.save_dir {
+            cart.set_save_strategy(crate::cart::SavePathStrategy::Custom(dir));
+        }
+
+        let mut emu = Emulator::from_cartridge(cart);
+        emu.set_cgb_mode(self.mode);
+        emu.apu.set_sample_rate(self.audio_rate);
+        if self.random_wram {
+            emu.bus.ram.randomize_wram(self.wram_seed);
+        }
+        if let Some(boot_rom) = self.boot_rom {
+            emu.bus.load_boot_rom(boot_rom);
+            emu.cpu.regs = Registers::new();
+        }
+        Ok(emu)
+    }
+}
+
 /// Main Emulator structure
-pub struct Emulator {
+///
+/// Generic over the memory bus so the emulation loop can run on a
+/// `MockBus`; everything cartridge-related needs the real `Bus`.
+pub struct Emulator<B: SystemBus = Bus> {
     /// Emulator context/state
     pub ctx: EmulatorContext,
     /// CPU
@@ -52,52 +368,111 @@ pub struct Emulator {
     pub timer: Timer,
     /// DMA controller
     pub dma: Dma,
+    /// CGB VRAM DMA controller
+    pub hdma: HdmaController,
     /// LCD controller
     pub lcd: Lcd,
     /// Gamepad
     pub gamepad: Gamepad,
     /// Memory bus (includes cartridge)
-    pub bus: Bus,
+    pub bus: B,
+    /// Events raised by components, turned into interrupts after each tick
+    events: EventQueue,
+    /// Active hardware mode (never `Auto`)
+    mode: EmulatorMode,
+    /// Peripheral on the serial port, if any
+    serial_device: Option<Box<dyn SerialDevice>>,
+    /// A `SerialLink` is stepping this emulator (transfers wait for the partner)
+    pub(crate) serial_linked: bool,
+    /// Every byte sent over the serial port
+    serial_output: String,
+    /// Active WAV recording, if any
+    #[cfg(feature = "std")]
+    audio_recorder: Option<WavRecorder>,
+    /// Active GIF recording, if any
+    #[cfg(feature = "gif-recording")]
+    gif_recorder: Option<GifRecorder>,
+    /// Error that stopped the GIF recording, reported by `stop_gif_recording`
+    #[cfg(feature = "gif-recording")]
+    gif_error: Option<EmulatorError>,
+    /// Attached plugins
+    #[cfg(feature = "std")]
+    plugins: Vec<Box<dyn EmulatorPlugin>>,
+    /// Gameboy Doctor CPU log sink, if enabled
+    #[cfg(feature = "std")]
+    cpu_log: Option<Box<dyn Write>>,
+    /// Write error that stopped the CPU log, reported by `disable_cpu_log`
+    #[cfg(feature = "std")]
+    cpu_log_error: Option<std::io::Error>,
+    /// Asked before `erase_save_data` runs, if set
+    erase_confirm: Option<Box<dyn Fn() -> bool>>,
+    /// Frame timing checks, if enabled
+    timing_verifier: Option<TimingVerifier>,
+    /// Button changes to apply when the keyed frame starts (TAS input)
+    queued_inputs: BTreeMap<u32, Vec<(Button, bool)>>,
+    /// PC addresses that pause `step` before the instruction runs
+    breakpoints: BTreeSet<Word>,
+    /// Addresses whose writes pause `step` after the instruction
+    watchpoints: BTreeSet<Word>,
+    /// Why the emulator last stopped for the debugger
+    break_reason: Option<BreakReason>,
+    /// Rolling speed measurement for `emulation_speed`
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    speedometer: Speedometer,
 }
 
 impl Emulator {
     /// Create a new emulator instance with the given ROM file
+    #[deprecated(note = "use `EmulatorBuilder::new().rom(path).build()`")]
     pub fn new(rom_path: &str) -> Result<Self, String> {
         // Load cartridge
         let cart = Cartridge::load(rom_path)
             .map_err(|e| format!("Failed to load ROM: {}", e))?;
 
-        println!("Loaded ROM: {}", cart.header.title);
-        println!("Type: {} (0x{:02X})", cart.header.cart_type_name(), cart.header.cart_type);
-        println!("ROM Size: {} KB", cart.header.rom_size_bytes() / 1024);
-        println!("RAM Size: {} KB", cart.header.ram_size_bytes() / 1024);
-
-        // Create components
-        let mut cpu = Cpu::new();
-        cpu.init();
-
-        let mut ppu = Ppu::new();
-        ppu.init();
-
-        let mut apu = Apu::new();
-        apu.init();
+        Ok(Self::from_cartridge(cart))
+    }
 
-        let mut timer = Timer::new();
-        timer.init();
+    /// Create a new emulator instance from an in-memory ROM image
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Self, String> {
+        let cart = Cartridge::from_bytes(rom)
+            .map_err(|e| format!("Failed to load ROM: {}", e))?;
 
-        let mut dma = Dma::new();
-        dma.init();
+        Ok(Self::from_cartridge(cart))
+    }
 
-        let mut lcd = Lcd::new();
-        lcd.init();
+    /// Create a new emulator instance from an async ROM source
+    ///
+    /// Useful where ROM data arrives asynchronously, such as a `fetch` in WASM.
+    #[cfg(feature = "async")]
+    pub async fn from_async_rom<R>(reader: R) -> Result<Self, EmulatorError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        let cart = Cartridge::from_async_bytes(reader).await?;
+        Ok(Self::from_cartridge(cart))
+    }
 
-        let mut gamepad = Gamepad::new();
-        gamepad.init();
+    /// Build the emulator around a loaded cartridge
+    fn from_cartridge(cart: Cartridge) -> Self {
+        #[cfg(feature = "std")]
+        {
+            println!("Loaded ROM: {}", cart.header.title);
+            println!("Type: {} (0x{:02X})", cart.header.cart_type_name(), cart.header.cart_type);
+            println!("ROM Size: {} KB", cart.header.rom_size_bytes() / 1024);
+            println!("RAM Size: {} KB", cart.header.ram_size_bytes() / 1024);
+        }
 
         let mut bus = Bus::new();
         bus.load_cartridge(cart);
 
-        // Initialize I/O registers to boot ROM skip values
+        let mut emu = Self::from_bus(bus);
+        Self::init_io_registers(&mut emu.bus, &emu.lcd);
+        emu.set_cgb_mode(EmulatorMode::Auto);
+        emu
+    }
+
+    /// Set I/O registers to the values the boot ROM leaves behind
+    fn init_io_registers(bus: &mut Bus, lcd: &Lcd) {
         // Sound registers
         bus.io_regs[0x10] = 0x80;
         bus.io_regs[0x11] = 0xBF;
@@ -126,31 +501,351 @@ impl Emulator {
         bus.io_regs[0x47] = lcd.bgp;   // BGP
         bus.io_regs[0x48] = lcd.obp0;  // OBP0
         bus.io_regs[0x49] = lcd.obp1;  // OBP1
+    }
+
+    /// Restart the game as if the console were power cycled
+    ///
+    /// Every component returns to the post-boot state of the current mode.
+    /// The cartridge (including its RAM), attached devices, recordings and
+    /// callbacks are kept, and `total_cycles` keeps counting.
+    pub fn reset(&mut self) {
+        self.bus.reset();
+
+        self.cpu.reset_cycle_count();
+        self.ppu.init();
+        self.apu.init();
+        self.dma.init();
+        self.hdma.init();
+        self.lcd.init();
+        self.gamepad.init();
+        self.events.clear();
+        self.serial_output.clear();
+        Self::init_io_registers(&mut self.bus, &self.lcd);
+        self.set_cgb_mode(self.mode);
+        self.ctx.reset_ticks = self.ctx.ticks;
+    }
+
+    /// Load the CPU and timer values the active mode's boot ROM leaves behind
+    ///
+    /// DMG games on a CGB see the CGB values. With a boot ROM still mapped
+    /// the CPU starts from 0x0000 instead.
+    fn init_post_boot_state(&mut self) {
+        if self.mode == EmulatorMode::Dmg {
+            self.cpu.init();
+            self.timer.init();
+        } else {
+            self.cpu.init_cgb();
+            self.timer.init_cgb();
+        }
+        if self.bus.boot_rom_mapped() {
+            self.cpu.regs = Registers::new();
+        }
+    }
+
+    /// Switch the emulated hardware model
+    ///
+    /// `Auto` selects CGB when the cartridge header advertises CGB support.
+    /// Outside `Cgb` mode the VRAM/WRAM bank, KEY1, HDMA and CGB palette
+    /// registers are unmapped. The CPU and timer restart from the new
+    /// model's post-boot state.
+    pub fn set_cgb_mode(&mut self, mode: EmulatorMode) {
+        let mode = match mode {
+            EmulatorMode::Auto => {
+                let cgb = self.bus.cart.as_ref().is_some_and(|cart| cart.header.supports_cgb());
+                if cgb { EmulatorMode::Cgb } else { EmulatorMode::Dmg }
+            }
+            mode => mode,
+        };
+
+        self.bus.set_cgb_mode(mode == EmulatorMode::Cgb);
+        self.ppu.cgb_mode = mode == EmulatorMode::Cgb;
+        self.apu.hardware_model = match mode {
+            EmulatorMode::Dmg => HardwareModel::Dmg,
+            _ => HardwareModel::Cgb,
+        };
+
+        let (bg, obj) = match mode {
+            EmulatorMode::DmgOnCgb => (DmgPalette::CGB_COMPAT_BG, DmgPalette::CGB_COMPAT_OBJ),
+            _ => (DmgPalette::GRAYSCALE, DmgPalette::GRAYSCALE),
+        };
+        self.ppu.bg_palette = bg;
+        self.ppu.obj0_palette = obj;
+        self.ppu.obj1_palette = obj;
+
+        self.mode = mode;
+        self.init_post_boot_state();
+    }
+
+    /// Attach a plugin
+    #[cfg(feature = "std")]
+    pub fn add_plugin(&mut self, plugin: Box<dyn EmulatorPlugin>) {
+        self.plugins.push(plugin);
+        self.update_write_tracking();
+    }
+
+    /// Detach all plugins with the given name
+    #[cfg(feature = "std")]
+    pub fn remove_plugin(&mut self, name: &str) {
+        self.plugins.retain(|plugin| plugin.name() != name);
+        self.update_write_tracking();
+    }
+
+    /// Create an independent copy of the emulator in its current state
+    ///
+    /// All mutable state is deep-cloned; the cartridge ROM is shared.
+    /// Active audio/GIF recordings, plugins, breakpoints, watchpoints and the
+    /// erase confirmation callback stay with the original. The fork never
+    /// writes the battery save, so it cannot overwrite the original's.
+    pub fn fork(&self) -> Emulator {
+        let mut bus = self.bus.clone();
+        bus.track_writes = false;
+        bus.write_log.clear();
+        if let Some(cart) = bus.cart.as_mut() {
+            cart.detach_battery_save();
+        }
+
+        Emulator {
+            ctx: self.ctx.clone(),
+            cpu: self.cpu.clone(),
+            ppu: self.ppu.clone(),
+            apu: self.apu.clone(),
+            timer: self.timer.clone(),
+            dma: self.dma.clone(),
+            hdma: self.hdma.clone(),
+            lcd: self.lcd.clone(),
+            gamepad: self.gamepad.clone(),
+            bus,
+            events: self.events.clone(),
+            mode: self.mode,
+            serial_device: None,
+            serial_linked: false,
+            serial_output: self.serial_output.clone(),
+            #[cfg(feature = "std")]
+            audio_recorder: None,
+            #[cfg(feature = "gif-recording")]
+            gif_recorder: None,
+            #[cfg(feature = "gif-recording")]
+            gif_error: None,
+            #[cfg(feature = "std")]
+            plugins: Vec::new(),
+            #[cfg(feature = "std")]
+            cpu_log: None,
+            #[cfg(feature = "std")]
+            cpu_log_error: None,
+            erase_confirm: None,
+            timing_verifier: self.timing_verifier.clone(),
+            queued_inputs: self.queued_inputs.clone(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            break_reason: None,
+            #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+            speedometer: Speedometer::new(self.ctx.ticks),
+        }
+    }
+
+    /// Snapshot the whole machine
+    ///
+    /// The state covers every component and the cartridge RAM and banking,
+    /// but not the ROM, host settings (sample rate, mixer, palettes) or
+    /// debugger state. Load it with `load_state` on an emulator running the
+    /// same ROM.
+    #[cfg(feature = "save-state")]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut bus = Box::new(self.bus.clone());
+        bus.cart = None;
+
+        SaveState {
+            version: SAVE_STATE_VERSION,
+            rom_hash: self.bus.cart.as_ref().map(|cart| savestate::rom_hash(&cart.rom)),
+            mode: self.mode,
+            ticks: self.ctx.ticks,
+            reset_ticks: self.ctx.reset_ticks,
+            cpu: self.cpu.clone(),
+            ppu: Box::new(self.ppu.clone()),
+            apu: Box::new(self.apu.clone()),
+            timer: self.timer.clone(),
+            dma: self.dma.clone(),
+            hdma: self.hdma.clone(),
+            lcd: self.lcd.clone(),
+            gamepad: self.gamepad.clone(),
+            bus,
+            cart: self.bus.cart.as_ref().map(Cartridge::mbc_state),
+            events: self.events.clone(),
+            boot_rom_mapped: self.bus.boot_rom_mapped(),
+        }
+        .encode()
+    }
+
+    /// Restore a snapshot taken by `save_state`
+    ///
+    /// Fails without changing anything if the data is corrupt, from another
+    /// format version, or from a different ROM.
+    #[cfg(feature = "save-state")]
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), EmulatorError> {
+        let state = SaveState::decode(data)?;
+        let rom_hash = self.bus.cart.as_ref().map(|cart| savestate::rom_hash(&cart.rom));
+        if state.rom_hash != rom_hash {
+            return Err(EmulatorError::InvalidSaveState(
+                "state was saved with a different ROM".to_string(),
+            ));
+        }
+
+        if let (Some(cart), Some(saved)) = (self.bus.cart.as_mut(), state.cart) {
+            cart.load_mbc_state(saved)?;
+        }
+
+        let palettes = (self.ppu.bg_palette, self.ppu.obj0_palette, self.ppu.obj1_palette);
+        self.ppu = *state.ppu;
+        (self.ppu.bg_palette, self.ppu.obj0_palette, self.ppu.obj1_palette) = palettes;
+
+        self.cpu.load_state(state.cpu);
+        self.apu.load_state(*state.apu);
+        self.bus.load_state(*state.bus, state.boot_rom_mapped);
+        self.timer = state.timer;
+        self.dma = state.dma;
+        self.hdma = state.hdma;
+        self.lcd = state.lcd;
+        self.gamepad = state.gamepad;
+        self.events = state.events;
+        self.mode = state.mode;
+        self.ctx.ticks = state.ticks;
+        self.ctx.reset_ticks = state.reset_ticks;
+        self.break_reason = None;
+        Ok(())
+    }
+
+    /// Drive the CGB infrared receiver (RP register) as if a signal were present
+    pub fn simulate_ir_signal(&mut self, active: bool) {
+        self.bus.set_ir_receive(active);
+    }
+
+    /// Whether the cartridge's rumble motor is running, for controller vibration
+    pub fn rumble_active(&self) -> bool {
+        self.bus.cart.as_ref().is_some_and(Cartridge::rumble_active)
+    }
 
-        Ok(Self {
+    /// Clear cartridge RAM and delete its battery save file
+    ///
+    /// Does nothing if the confirmation callback declines or no cartridge
+    /// is inserted.
+    pub fn erase_save_data(&mut self) -> Result<(), EmulatorError> {
+        if self.erase_confirm.as_ref().is_some_and(|confirm| !confirm()) {
+            return Ok(());
+        }
+        match self.bus.cart.as_mut() {
+            Some(cart) => cart.erase_save_data(),
+            None => Ok(()),
+        }
+    }
+
+    /// CPU clock in Hz: 4194304, or 8388608 in CGB double speed
+    pub fn cpu_frequency(&self) -> u32 {
+        if self.bus.double_speed() {
+            CPU_CLOCK * 2
+        } else {
+            CPU_CLOCK
+        }
+    }
+
+    /// CPU clock scaled by the overclock factor, in Hz
+    pub fn effective_speed(&self) -> f32 {
+        self.cpu_frequency() as f32 * self.ctx.overclock_factor
+    }
+
+    /// Run a test script (see `Script` for the syntax), returning its output
+    #[cfg(feature = "std")]
+    pub fn run_script(&mut self, script: &crate::script::Script) -> Result<String, EmulatorError> {
+        script.run(self)
+    }
+}
+
+impl<B: SystemBus> Emulator<B> {
+    /// Create an emulator around any bus, with every component in its
+    /// post-boot DMG state
+    ///
+    /// Used by the `Emulator<Bus>` constructors, and by tests that run the
+    /// emulation loop on a `MockBus` without a cartridge.
+    pub fn from_bus(bus: B) -> Self {
+        let mut emu = Self {
             ctx: EmulatorContext::default(),
-            cpu,
-            ppu,
-            apu,
-            timer,
-            dma,
-            lcd,
-            gamepad,
+            cpu: Cpu::new(),
+            ppu: Ppu::new(),
+            apu: Apu::default(),
+            timer: Timer::new(),
+            dma: Dma::new(),
+            hdma: HdmaController::new(),
+            lcd: Lcd::new(),
+            gamepad: Gamepad::new(),
             bus,
-        })
+            events: EventQueue::new(),
+            mode: EmulatorMode::Dmg,
+            serial_device: None,
+            serial_linked: false,
+            serial_output: String::new(),
+            #[cfg(feature = "std")]
+            audio_recorder: None,
+            #[cfg(feature = "gif-recording")]
+            gif_recorder: None,
+            #[cfg(feature = "gif-recording")]
+            gif_error: None,
+            #[cfg(feature = "std")]
+            plugins: Vec::new(),
+            #[cfg(feature = "std")]
+            cpu_log: None,
+            #[cfg(feature = "std")]
+            cpu_log_error: None,
+            erase_confirm: None,
+            timing_verifier: None,
+            queued_inputs: BTreeMap::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            break_reason: None,
+            #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+            speedometer: Speedometer::new(0),
+        };
+        emu.cpu.init();
+        emu.ppu.init();
+        emu.apu.init();
+        emu.timer.init();
+        emu.dma.init();
+        emu.lcd.init();
+        emu.gamepad.init();
+        emu
+    }
+
+    /// Get the active hardware mode
+    pub fn mode(&self) -> EmulatorMode {
+        self.mode
     }
 
     /// Run one CPU instruction and tick all components
     pub fn step(&mut self) -> bool {
+        self.run_step(true).0
+    }
+
+    /// Run one CPU step, tick all components by its length and return the
+    /// T-cycles consumed
+    ///
+    /// Unlike `step`, breakpoints, the debugger hook, the CPU log and
+    /// plugins are skipped.
+    pub fn step_raw(&mut self) -> u32 {
+        self.run_step(false).1
+    }
+
+    /// Body shared by `step` and `step_raw`; `hooks` enables breakpoints,
+    /// the debugger hook, the CPU log and plugins
+    ///
+    /// Returns whether to keep running and the T-cycles ticked.
+    fn run_step(&mut self, hooks: bool) -> (bool, u32) {
         if self.ctx.paused || !self.ctx.running {
-            return true;
+            return (true, 0);
         }
 
         self.cpu.reset_step_cycles();
 
         // Sync IE/IF registers from Bus to CPU
-        self.cpu.ie_register = self.bus.ie_register;
-        self.cpu.int_flags = self.bus.int_flags;
+        self.cpu.ie_register = self.bus.interrupt_enable();
+        self.cpu.int_flags = self.bus.interrupt_flags();
 
         // Sync LCD registers from Bus to LCD
         self.sync_lcd_from_bus();
@@ -167,16 +862,27 @@ impl Emulator {
         // Check if DMA should start
         self.check_dma_start();
 
+        self.apply_queued_inputs();
+
+        // A CGB speed switch keeps the CPU stopped until the countdown expires
+        if self.cpu.speed_switch_pending {
+            let t_cycles = self.cpu.step_speed_switch();
+            self.tick_components(t_cycles);
+            if self.cpu.speed_switch_completed {
+                self.bus.complete_speed_switch();
+            }
+            return (!self.ctx.die, t_cycles);
+        }
+
         // Handle interrupts
         if self.cpu.handle_interrupts(&mut self.bus) {
-            self.cpu.add_m_cycles(5);
             let t_cycles = self.cpu.take_t_cycles();
             self.tick_components(t_cycles);
-            return !self.ctx.die;
+            return (!self.ctx.die, t_cycles);
         }
 
         // Sync IF back to Bus after interrupt handling
-        self.bus.int_flags = self.cpu.int_flags;
+        self.bus.set_interrupt_flags(self.cpu.int_flags);
 
         // Handle delayed IME enable
         if self.cpu.enabling_ime {
@@ -194,20 +900,61 @@ impl Emulator {
             if self.cpu.interrupts_pending() {
                 self.cpu.halted = false;
             }
-            return true;
+            return (true, t_cycles);
+        }
+
+        let mut skip_instruction = false;
+        if hooks {
+            // Stop before the instruction at a breakpoint, unless resuming from it
+            let pc = self.cpu.regs.pc;
+            let resuming = self.break_reason == Some(BreakReason::Breakpoint(pc));
+            self.break_reason = None;
+            if !resuming && self.breakpoints.contains(&pc) {
+                self.break_reason = Some(BreakReason::Breakpoint(pc));
+                self.ctx.paused = true;
+                return (true, 0);
+            }
+
+            // Let an attached debugger inspect the instruction before it runs
+            let action = self.cpu.notify_step(&self.bus);
+            if action.is_some_and(|action| !action.should_continue) {
+                self.ctx.paused = true;
+                return (true, 0);
+            }
+            skip_instruction = action.is_some_and(|action| action.skip_instruction);
+
+            #[cfg(feature = "std")]
+            self.write_cpu_log();
         }
 
         // Fetch instruction
+        #[cfg(feature = "std")]
+        let inst_pc = self.cpu.regs.pc;
         self.cpu.fetch_instruction(&self.bus);
+        #[cfg(feature = "std")]
+        if hooks {
+            for plugin in self.plugins.iter_mut() {
+                plugin.on_instruction(inst_pc, self.cpu.cur_opcode, &self.cpu);
+            }
+        }
         self.cpu.fetch_data(&self.bus);
 
         // Execute instruction
-        self.cpu.execute(&mut self.bus);
+        if !skip_instruction {
+            self.cpu.execute(&mut self.bus);
+        }
+
+        self.run_serial_transfer();
+
+        self.check_watchpoints();
+        #[cfg(feature = "std")]
+        self.dispatch_memory_writes();
+        self.bus.clear_write_log();
 
         // CPU instructions may have written IE/IF through the bus.
         // Re-sync Bus -> CPU so interrupt state stays coherent.
-        self.cpu.ie_register = self.bus.ie_register;
-        self.cpu.int_flags = self.bus.int_flags;
+        self.cpu.ie_register = self.bus.interrupt_enable();
+        self.cpu.int_flags = self.bus.interrupt_flags();
 
         // CPU may have written I/O registers via the bus. Apply those writes to
         // component state before ticking so effects are visible immediately.
@@ -217,71 +964,117 @@ impl Emulator {
         self.sync_apu_from_bus();
         self.check_dma_start();
 
-        // Tick components based on consumed CPU cycles
+        // Tick components by the cycles the fetch, operand reads and execution consumed
         let t_cycles = self.cpu.take_t_cycles();
-        if t_cycles == 0 {
-            self.tick_components(4);
-        } else {
-            self.tick_components(t_cycles);
-        }
+        self.tick_components(t_cycles);
+
+        (!self.ctx.die, t_cycles)
+    }
 
-        !self.ctx.die
+    /// Advance every component except the CPU by `cycles` T-cycles
+    ///
+    /// Register writes made through the bus (e.g. starting a DMA) are applied first.
+    pub fn advance_cycles(&mut self, cycles: u32) {
+        self.cpu.ie_register = self.bus.interrupt_enable();
+        self.cpu.int_flags = self.bus.interrupt_flags();
+        self.sync_lcd_from_bus();
+        self.sync_timer_from_bus();
+        self.sync_gamepad_from_bus();
+        self.sync_apu_from_bus();
+        self.check_dma_start();
+        self.tick_components(cycles);
     }
 
     /// Sync LCD registers from Bus I/O area
     fn sync_lcd_from_bus(&mut self) {
-        self.lcd.lcdc = self.bus.io_regs[0x40];
-        self.lcd.stat = (self.lcd.stat & 0x07) | (self.bus.io_regs[0x41] & 0xF8);
-        self.lcd.scy = self.bus.io_regs[0x42];
-        self.lcd.scx = self.bus.io_regs[0x43];
+        self.lcd.lcdc = self.bus.io_register(0x40);
+        self.lcd.stat = (self.lcd.stat & 0x07) | (self.bus.io_register(0x41) & 0xF8);
+        self.lcd.scy = self.bus.io_register(0x42);
+        self.lcd.scx = self.bus.io_register(0x43);
         // LY is read-only, don't sync from bus
-        self.lcd.lyc = self.bus.io_regs[0x45];
-        self.lcd.bgp = self.bus.io_regs[0x47];
-        self.lcd.obp0 = self.bus.io_regs[0x48];
-        self.lcd.obp1 = self.bus.io_regs[0x49];
-        self.lcd.wy = self.bus.io_regs[0x4A];
-        self.lcd.wx = self.bus.io_regs[0x4B];
+        self.lcd.lyc = self.bus.io_register(0x45);
+        self.lcd.bgp = self.bus.io_register(0x47);
+        self.lcd.obp0 = self.bus.io_register(0x48);
+        self.lcd.obp1 = self.bus.io_register(0x49);
+        self.lcd.wy = self.bus.io_register(0x4A);
+        self.lcd.wx = self.bus.io_register(0x4B);
     }
 
     /// Sync Timer registers from Bus I/O area
     fn sync_timer_from_bus(&mut self) {
-        // Check if DIV was written (any write resets it)
-        // We track this by checking if the value changed to 0
-        let bus_div = self.bus.io_regs[0x04];
-        if bus_div == 0 && self.timer.read(0xFF04) != 0 {
+        // Check if DIV was written (any write resets it); buses without
+        // write tracking are detected by the value changing to 0
+        let bus_div = self.bus.io_register(0x04);
+        if self.bus.take_io_written(0x04) || (bus_div == 0 && self.timer.read(0xFF04) != 0) {
             self.timer.write(0xFF04, 0); // Reset DIV
         }
-        // TIMA, TMA, TAC are synced
-        self.timer.write(0xFF05, self.bus.io_regs[0x05]); // TIMA
-        self.timer.write(0xFF06, self.bus.io_regs[0x06]); // TMA
-        self.timer.write(0xFF07, self.bus.io_regs[0x07]); // TAC
+        // TIMA and TMA are only forwarded when written: around a TIMA
+        // reload, writes have side effects a plain re-sync must not trigger
+        for index in [0x05, 0x06] {
+            let value = self.bus.io_register(index);
+            if self.bus.take_io_written(index) || value != self.timer.read(0xFF00 + index as u16) {
+                self.timer.write(0xFF00 + index as u16, value);
+            }
+        }
+        self.timer.write(0xFF07, self.bus.io_register(0x07)); // TAC
     }
 
     /// Sync Timer registers to Bus I/O area
     fn sync_timer_to_bus(&mut self) {
-        self.bus.io_regs[0x04] = self.timer.read(0xFF04); // DIV
-        self.bus.io_regs[0x05] = self.timer.read(0xFF05); // TIMA
-        self.bus.io_regs[0x06] = self.timer.read(0xFF06); // TMA
-        self.bus.io_regs[0x07] = self.timer.read(0xFF07); // TAC
+        self.bus.set_io_register(0x04, self.timer.read(0xFF04)); // DIV
+        self.bus.set_io_register(0x05, self.timer.read(0xFF05)); // TIMA
+        self.bus.set_io_register(0x06, self.timer.read(0xFF06)); // TMA
+        self.bus.set_io_register(0x07, self.timer.read(0xFF07)); // TAC
     }
 
     /// Sync Gamepad register from Bus I/O area
     fn sync_gamepad_from_bus(&mut self) {
-        self.gamepad.write(self.bus.io_regs[0x00]);
+        self.gamepad.write(self.bus.io_register(0x00));
     }
 
     /// Sync Gamepad register to Bus I/O area
     fn sync_gamepad_to_bus(&mut self) {
-        self.bus.io_regs[0x00] = self.gamepad.read();
+        self.bus.set_io_register(0x00, self.gamepad.read());
     }
 
     /// Check and start DMA if requested
     fn check_dma_start(&mut self) {
         if self.bus.take_io_written(0x46) {
-            let dma_reg = self.bus.io_regs[0x46];
+            let dma_reg = self.bus.io_register(0x46);
             self.dma.start(dma_reg);
             self.bus.set_dma_active(true);
         }
+
+        if self.mode == EmulatorMode::Cgb && self.bus.take_io_written(0x55) {
+            let reg = |bus: &B, index: usize| bus.io_register(index) as Word;
+            let source = (reg(&self.bus, 0x51) << 8) | reg(&self.bus, 0x52);
+            let dest = (reg(&self.bus, 0x53) << 8) | reg(&self.bus, 0x54);
+            self.hdma.write(self.bus.io_register(0x55), source, dest);
+
+            // A general-purpose transfer copies everything at once, halting the CPU
+            let mut blocks = 0;
+            if self.hdma.general_transfer_pending() {
+                while self.copy_hdma_block() {
+                    blocks += 1;
+                }
+            }
+            self.bus.set_io_register(0x55, self.hdma.read());
+            if blocks > 0 {
+                self.tick_components(blocks * HDMA_BLOCK_STALL_CYCLES);
+            }
+        }
+    }
+
+    /// Copy the next VRAM DMA block; returns false if no transfer is running
+    fn copy_hdma_block(&mut self) -> bool {
+        let Some((src, dst)) = self.hdma.next_block() else {
+            return false;
+        };
+        for i in 0..HDMA_BLOCK_SIZE {
+            let value = self.bus.read_direct(src.wrapping_add(i));
+            self.bus.write_vram(dst + i, value);
+        }
+        true
     }
 
     /// Sync APU registers from Bus I/O area
@@ -296,7 +1089,7 @@ impl Emulator {
 
         for &reg in &APU_IO_REGS {
             if self.bus.take_io_written(reg) {
-                let value = self.bus.io_regs[reg];
+                let value = self.bus.io_register(reg);
                 self.apu.write(0xFF00 + reg as u16, value);
             }
         }
@@ -304,7 +1097,7 @@ impl Emulator {
         // Wave RAM (0xFF30-0xFF3F)
         for reg in 0x30..=0x3F {
             if self.bus.take_io_written(reg) {
-                let value = self.bus.io_regs[reg];
+                let value = self.bus.io_register(reg);
                 self.apu.write(0xFF00 + reg as u16, value);
             }
         }
@@ -313,62 +1106,72 @@ impl Emulator {
     /// Sync APU registers to Bus I/O area
     fn sync_apu_to_bus(&mut self) {
         // Expose status register readback without feeding it back as writes.
-        self.bus.io_regs[0x26] = self.apu.read(0xFF26);
+        self.bus.set_io_register(0x26, self.apu.read(0xFF26));
+        // Wave RAM changes under the CPU when a DMG retrigger corrupts it
+        for reg in 0x30..=0x3F {
+            self.bus.set_io_register(reg, self.apu.read(0xFF00 + reg as u16));
+        }
     }
 
     /// Sync LCD registers to Bus I/O area
     fn sync_lcd_to_bus(&mut self) {
-        self.bus.io_regs[0x40] = self.lcd.lcdc;
-        self.bus.io_regs[0x41] = self.lcd.stat | 0x80;
-        self.bus.io_regs[0x42] = self.lcd.scy;
-        self.bus.io_regs[0x43] = self.lcd.scx;
-        self.bus.io_regs[0x44] = self.lcd.ly;
-        self.bus.io_regs[0x45] = self.lcd.lyc;
-        self.bus.io_regs[0x47] = self.lcd.bgp;
-        self.bus.io_regs[0x48] = self.lcd.obp0;
-        self.bus.io_regs[0x49] = self.lcd.obp1;
-        self.bus.io_regs[0x4A] = self.lcd.wy;
-        self.bus.io_regs[0x4B] = self.lcd.wx;
+        self.bus.set_io_register(0x40, self.lcd.lcdc);
+        self.bus.set_io_register(0x41, self.lcd.stat | 0x80);
+        self.bus.set_io_register(0x42, self.lcd.scy);
+        self.bus.set_io_register(0x43, self.lcd.scx);
+        self.bus.set_io_register(0x44, self.lcd.ly);
+        self.bus.set_io_register(0x45, self.lcd.lyc);
+        self.bus.set_io_register(0x47, self.lcd.bgp);
+        self.bus.set_io_register(0x48, self.lcd.obp0);
+        self.bus.set_io_register(0x49, self.lcd.obp1);
+        self.bus.set_io_register(0x4A, self.lcd.wy);
+        self.bus.set_io_register(0x4B, self.lcd.wx);
     }
 
     /// Tick all components by the given number of T-cycles
+    ///
+    /// In CGB double speed the timer and OAM DMA follow the CPU clock, while
+    /// the PPU, HBlank DMA and APU only advance on every other cycle.
     fn tick_components(&mut self, cycles: u32) {
         // Sync VRAM/OAM lazily: only copy when Bus memory actually changed.
-        if self.bus.vram_dirty {
-            self.ppu.vram.copy_from_slice(&self.bus.vram);
-            self.bus.vram_dirty = false;
-        }
-        if self.bus.oam_dirty {
-            self.ppu.oam.copy_from_slice(&self.bus.oam);
-            self.bus.oam_dirty = false;
-        }
+        self.bus.sync_video_memory(&mut self.ppu.vram, &mut self.ppu.oam);
+        self.bus.sync_cgb_palettes(&mut self.ppu.cgb_palettes);
+        self.ppu.vram_bank = self.bus.vram_bank();
 
         for _ in 0..cycles {
             self.ctx.ticks += 1;
 
             // Tick timer
-            self.timer.tick();
-            if self.timer.interrupt_requested {
-                self.cpu.request_interrupt(InterruptType::Timer);
-                self.timer.clear_interrupt();
-            }
+            self.timer.tick(&mut self.events);
+
+            let double_speed = self.bus.double_speed();
+            let normal_speed_tick = !double_speed || self.ctx.ticks & 1 == 0;
 
             // Tick PPU
-            self.ppu.tick(&mut self.lcd);
-            if self.ppu.vblank_interrupt {
-                self.cpu.request_interrupt(InterruptType::VBlank);
-                self.ppu.clear_vblank_interrupt();
-            }
-            if self.lcd.stat_interrupt {
-                self.cpu.request_interrupt(InterruptType::LcdStat);
-                self.lcd.clear_stat_interrupt();
+            if normal_speed_tick {
+                let frame = self.ppu.current_frame;
+                let mode = self.lcd.mode();
+                self.ppu.tick(&mut self.lcd, &mut self.events);
+
+                // An HBlank VRAM DMA copies one block as each visible line enters HBlank
+                if self.hdma.hblank_transfer_pending()
+                    && mode != PpuMode::HBlank
+                    && self.lcd.mode() == PpuMode::HBlank
+                {
+                    self.copy_hdma_block();
+                }
+                if self.ppu.current_frame != frame {
+                    if let Some(verifier) = self.timing_verifier.as_mut() {
+                        verifier.record_frame_start(self.ctx.ticks);
+                    }
+                }
             }
 
             // Tick DMA
             if let Some((src, dst)) = self.dma.tick() {
-                let value = self.bus.read(src);
+                let value = self.bus.read_direct(src);
                 let oam_index = (dst - 0xFE00) as usize;
-                self.bus.oam[oam_index] = value;
+                self.bus.write_oam(oam_index, value);
                 self.ppu.oam[oam_index] = value;
             }
             
@@ -377,18 +1180,33 @@ impl Emulator {
                 self.bus.set_dma_active(false);
             }
 
-            // Tick APU
-            self.apu.tick();
+            // Tick APU; its frame sequencer moves to the next DIV bit in
+            // double speed, keeping it at 512 Hz
+            if normal_speed_tick {
+                let div = self.timer.div_internal();
+                self.apu.tick(if double_speed { div >> 1 } else { div });
+            }
         }
 
-        // Check gamepad interrupt
-        if self.gamepad.interrupt_requested {
-            self.cpu.request_interrupt(InterruptType::Joypad);
-            self.gamepad.clear_interrupt();
+        // Publish the PPU mode for VRAM/OAM access locking (mode 0 while the LCD is off)
+        self.bus.set_ppu_mode(if self.lcd.lcd_enabled() {
+            self.lcd.mode()
+        } else {
+            PpuMode::HBlank
+        });
+
+        // Turn queued events into interrupt requests
+        while let Some(event) = self.events.pop() {
+            self.cpu.request_interrupt(event.interrupt());
+            if event == HardwareEvent::VBlank {
+                self.gamepad.tick_turbos(self.ppu.current_frame, &mut self.events);
+                #[cfg(feature = "gif-recording")]
+                self.capture_gif_frame();
+            }
         }
 
         // Sync IF register back to Bus
-        self.bus.int_flags = self.cpu.int_flags;
+        self.bus.set_interrupt_flags(self.cpu.int_flags);
 
         // Sync LCD registers to Bus
         self.sync_lcd_to_bus();
@@ -399,22 +1217,201 @@ impl Emulator {
         // Sync Gamepad register to Bus
         self.sync_gamepad_to_bus();
 
-        // Sync DMA register to Bus
-        self.bus.io_regs[0x46] = self.dma.read();
+        // Sync DMA registers to Bus
+        self.bus.set_io_register(0x46, self.dma.read());
+        self.bus.set_io_register(0x55, self.hdma.read());
 
         // Sync APU registers to Bus
         self.sync_apu_to_bus();
     }
 
     /// Run the emulator for one frame
+    ///
+    /// A frame takes twice as many CPU cycles in CGB double speed.
     pub fn run_frame(&mut self) {
-        const T_CYCLES_PER_FRAME: u64 = 70224;
         let start_ticks = self.ctx.ticks;
-        while self.ctx.ticks.saturating_sub(start_ticks) < T_CYCLES_PER_FRAME && !self.ctx.die {
+        while self.ctx.ticks.saturating_sub(start_ticks) < self.cycles_per_frame() && !self.ctx.die && !self.ctx.paused {
             if !self.step() {
                 break;
             }
         }
+
+        #[cfg(feature = "std")]
+        for plugin in self.plugins.iter_mut() {
+            plugin.on_frame(&self.ppu.video_buffer, self.apu.pending_samples());
+        }
+    }
+
+    /// Run one frame and hand the picture and the frame's samples to the given backends
+    #[cfg(feature = "std")]
+    pub fn run_frame_to(&mut self, video: &mut dyn VideoOutput, audio: &mut dyn AudioOutput) {
+        self.run_frame();
+        video.present_frame(&self.ppu.video_buffer);
+        audio.write_samples(self.get_audio_buffer());
+    }
+
+    /// CPU cycles in one video frame at the current CPU speed
+    fn cycles_per_frame(&self) -> u64 {
+        if self.bus.double_speed() {
+            T_CYCLES_PER_FRAME * 2
+        } else {
+            T_CYCLES_PER_FRAME
+        }
+    }
+
+    /// Complete a transfer started on the internal clock, unless a link cable drives the port
+    fn run_serial_transfer(&mut self) {
+        if self.serial_linked {
+            return;
+        }
+        if let Some(byte) = serial::run_master_transfer(&mut self.bus, self.serial_device.as_deref_mut()) {
+            self.record_serial_byte(byte);
+        }
+    }
+
+    /// Append a byte sent over the serial port to `serial_output`
+    ///
+    /// Past `SERIAL_OUTPUT_LIMIT` the oldest half of the text is dropped.
+    pub(crate) fn record_serial_byte(&mut self, byte: u8) {
+        if self.serial_output.len() >= SERIAL_OUTPUT_LIMIT {
+            let mut cut = self.serial_output.len() / 2;
+            while !self.serial_output.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.serial_output.drain(..cut);
+        }
+        self.serial_output.push(char::from(byte));
+    }
+
+    /// Text sent over the serial port since power-on, the last reset or the
+    /// last `take_serial_output`
+    ///
+    /// Test ROMs (Blargg, Mooneye) report their results here. Each byte is
+    /// one character (bytes above 0x7F map to U+0080-U+00FF). Only the most
+    /// recent 32-64 KiB are kept.
+    pub fn serial_output(&self) -> &str {
+        &self.serial_output
+    }
+
+    /// Take the captured serial output, leaving it empty
+    pub fn take_serial_output(&mut self) -> String {
+        core::mem::take(&mut self.serial_output)
+    }
+
+    /// Forget the captured serial output
+    pub fn clear_serial_output(&mut self) {
+        self.serial_output.clear();
+    }
+
+    /// Plug a device (e.g. `GbPrinter`) into the serial port
+    pub fn attach_serial_device(&mut self, device: Box<dyn SerialDevice>) {
+        self.serial_device = Some(device);
+    }
+
+    /// Unplug the serial device
+    pub fn detach_serial_device(&mut self) -> Option<Box<dyn SerialDevice>> {
+        self.serial_device.take()
+    }
+
+    /// Get the attached serial device if it is a `T`
+    pub fn serial_device<T: SerialDevice>(&self) -> Option<&T> {
+        let device: &dyn Any = self.serial_device.as_deref()?;
+        device.downcast_ref()
+    }
+
+    /// Log the CPU state before every instruction in Gameboy Doctor format
+    #[cfg(feature = "std")]
+    pub fn enable_cpu_log(&mut self, writer: Box<dyn Write>) {
+        self.cpu_log = Some(writer);
+    }
+
+    /// Stop CPU logging and flush the log
+    ///
+    /// If a write error already stopped the log, that error is returned.
+    #[cfg(feature = "std")]
+    pub fn disable_cpu_log(&mut self) -> Result<(), EmulatorError> {
+        if let Some(e) = self.cpu_log_error.take() {
+            return Err(e.into());
+        }
+        if let Some(mut writer) = self.cpu_log.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the pre-fetch CPU state to the log (logging stops on I/O errors)
+    #[cfg(feature = "std")]
+    fn write_cpu_log(&mut self) {
+        if let Some(writer) = self.cpu_log.as_mut() {
+            if let Err(e) = self.cpu.write_gameboy_doctor_line(&self.bus, writer.as_mut()) {
+                self.cpu_log_error = Some(e);
+                self.cpu_log = None;
+            }
+        }
+    }
+
+    /// Forward bus writes logged during the last instruction to plugins
+    #[cfg(feature = "std")]
+    fn dispatch_memory_writes(&mut self) {
+        if self.bus.write_log().is_empty() {
+            return;
+        }
+        for &(address, value) in self.bus.write_log() {
+            for plugin in self.plugins.iter_mut() {
+                plugin.on_memory_write(address, value);
+            }
+        }
+    }
+
+    /// Pause if the last instruction wrote to a watched address
+    fn check_watchpoints(&mut self) {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+        let hit = self.bus.write_log().iter().find(|(address, _)| self.watchpoints.contains(address));
+        if let Some(&(address, _)) = hit {
+            self.break_reason = Some(BreakReason::Watchpoint(address));
+            self.ctx.paused = true;
+        }
+    }
+
+    /// Log bus writes while plugins or watchpoints need them
+    fn update_write_tracking(&mut self) {
+        #[cfg(feature = "std")]
+        let plugins = !self.plugins.is_empty();
+        #[cfg(not(feature = "std"))]
+        let plugins = false;
+        self.bus.set_track_writes(plugins || !self.watchpoints.is_empty());
+    }
+
+    /// Pause `step` before the instruction at `address` runs
+    ///
+    /// Resuming continues with that instruction; the breakpoint fires again
+    /// the next time PC reaches it.
+    pub fn add_breakpoint(&mut self, address: Word) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Remove a PC breakpoint
+    pub fn remove_breakpoint(&mut self, address: Word) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Pause `step` after any instruction that writes to `address`
+    pub fn add_watchpoint(&mut self, address: Word) {
+        self.watchpoints.insert(address);
+        self.update_write_tracking();
+    }
+
+    /// Remove a write watchpoint
+    pub fn remove_watchpoint(&mut self, address: Word) {
+        self.watchpoints.remove(&address);
+        self.update_write_tracking();
+    }
+
+    /// Breakpoint or watchpoint that paused the emulator, kept until the next instruction runs
+    pub fn break_reason(&self) -> Option<BreakReason> {
+        self.break_reason
     }
 
     /// Pause the emulator
@@ -438,19 +1435,286 @@ impl Emulator {
         self.ctx.running = false;
     }
 
+    /// Snapshot of the CPU registers and interrupt state
+    ///
+    /// IE and IF are taken from the bus, which holds the live values between steps.
+    pub fn cpu_state(&self) -> CpuState {
+        CpuState {
+            int_flags: self.bus.interrupt_flags(),
+            ie_register: self.bus.interrupt_enable(),
+            ..CpuState::from(&self.cpu)
+        }
+    }
+
     /// Get the video buffer for rendering
     pub fn get_video_buffer(&self) -> &[u32] {
         &self.ppu.video_buffer
     }
 
-    /// Get the audio buffer
-    pub fn get_audio_buffer(&mut self) -> &[i16] {
-        self.apu.get_audio_buffer()
+    /// Compare the current frame against a reference (e.g. a golden image)
+    pub fn compare_frame(&self, golden: &[u32]) -> FrameDiff {
+        ppu::diff_frames(&self.ppu.video_buffer, golden)
+    }
+
+    /// Copy the video buffer converted to another pixel format
+    pub fn get_video_buffer_as(&self, format: PixelFormat) -> Vec<u32> {
+        convert_buffer(&self.ppu.video_buffer, PixelFormat::Argb8888, format)
+    }
+
+    /// Render all 384 VRAM tiles as a 128x192 sheet (see `Ppu::generate_tileset_image`)
+    ///
+    /// `palette_name` is looked up with `DmgPalette::from_name`; unknown names
+    /// use the current background palette. `bank` selects the CGB VRAM bank.
+    pub fn tileset_image(&self, palette_name: &str, bank: u8) -> Vec<u32> {
+        let palette = DmgPalette::from_name(palette_name).unwrap_or(self.ppu.bg_palette);
+        self.ppu.generate_tileset_image(&palette, bank)
+    }
+
+    /// Encode the current video buffer as a 160x144 RGBA PNG
+    #[cfg(feature = "screenshot")]
+    pub fn take_screenshot(&self) -> Vec<u8> {
+        crate::screenshot::encode_png(
+            &self.ppu.video_buffer,
+            crate::ppu::SCREEN_WIDTH as u32,
+            crate::ppu::SCREEN_HEIGHT as u32,
+        )
+        .expect("in-memory PNG encoding of a full frame cannot fail")
+    }
+
+    /// Write the current video buffer to disk as a PNG file
+    #[cfg(feature = "screenshot")]
+    pub fn save_screenshot(&self, path: impl AsRef<std::path::Path>) -> Result<(), EmulatorError> {
+        std::fs::write(path, self.take_screenshot())?;
+        Ok(())
+    }
+
+    /// Write the current frame to disk as a PNG file
+    #[cfg(feature = "png-output")]
+    pub fn save_frame_as_png(&self, path: &std::path::Path) -> Result<(), EmulatorError> {
+        let png_data = self.ppu.framebuffer_as_png().map_err(|e| EmulatorError::Image(e.0))?;
+        std::fs::write(path, png_data)?;
+        Ok(())
+    }
+
+    /// Write the current frame to disk as a 24-bit BMP file
+    #[cfg(feature = "std")]
+    pub fn save_frame_as_bmp(&self, path: &std::path::Path) -> Result<(), EmulatorError> {
+        std::fs::write(path, self.ppu.framebuffer_as_bmp())?;
+        Ok(())
+    }
+
+    /// Get the audio buffer
+    ///
+    /// Samples are also forwarded to the active WAV recording, if any.
+    pub fn get_audio_buffer(&mut self) -> &[i16] {
+        let samples = self.apu.get_audio_buffer();
+        #[cfg(feature = "std")]
+        if let Some(recorder) = self.audio_recorder.as_mut() {
+            recorder.write_samples(samples);
+        }
+        samples
+    }
+
+    /// Pull up to `out.len()` interleaved stereo samples, oldest first
+    ///
+    /// For host audio callbacks; returns the number of samples written.
+    /// Like `get_audio_buffer`, samples are also forwarded to the active
+    /// WAV recording.
+    pub fn read_audio_samples(&mut self, out: &mut [i16]) -> usize {
+        let count = self.apu.read_samples(out);
+        #[cfg(feature = "std")]
+        if let Some(recorder) = self.audio_recorder.as_mut() {
+            recorder.write_samples(&out[..count]);
+        }
+        count
+    }
+
+    /// Start recording audio output to a WAV file
+    ///
+    /// Any recording already in progress is finished first.
+    #[cfg(feature = "std")]
+    pub fn start_audio_recording(&mut self, path: &str) -> Result<(), EmulatorError> {
+        self.stop_audio_recording()?;
+        self.audio_recorder = Some(WavRecorder::open(path, self.apu.sample_rate())?);
+        Ok(())
+    }
+
+    /// Stop the active WAV recording and finalize the file
+    #[cfg(feature = "std")]
+    pub fn stop_audio_recording(&mut self) -> Result<(), EmulatorError> {
+        match self.audio_recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Start recording video output to an animated GIF
+    ///
+    /// Captures one of every `fps_divisor` frames. Any recording already
+    /// in progress is finished first.
+    #[cfg(feature = "gif-recording")]
+    pub fn start_gif_recording(&mut self, path: &str, fps_divisor: u32) -> Result<(), EmulatorError> {
+        self.stop_gif_recording()?;
+        self.gif_recorder = Some(GifRecorder::new(
+            path,
+            crate::ppu::SCREEN_WIDTH as u16,
+            crate::ppu::SCREEN_HEIGHT as u16,
+            fps_divisor,
+        )?);
+        Ok(())
+    }
+
+    /// Stop the active GIF recording and finalize the file
+    ///
+    /// If a write error already stopped the recording, that error is returned.
+    #[cfg(feature = "gif-recording")]
+    pub fn stop_gif_recording(&mut self) -> Result<(), EmulatorError> {
+        if let Some(e) = self.gif_error.take() {
+            return Err(e);
+        }
+        match self.gif_recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Check if video is being recorded to a GIF
+    #[cfg(feature = "gif-recording")]
+    pub fn is_recording_gif(&self) -> bool {
+        self.gif_recorder.is_some()
+    }
+
+    /// Feed the completed frame to the GIF recorder, stopping it on error
+    #[cfg(feature = "gif-recording")]
+    fn capture_gif_frame(&mut self) {
+        if let Some(recorder) = self.gif_recorder.as_mut() {
+            if let Err(e) = recorder.add_frame(&self.ppu.video_buffer) {
+                self.gif_error = Some(e);
+                self.gif_recorder = None;
+            }
+        }
+    }
+
+    /// Check if audio is being recorded
+    #[cfg(feature = "std")]
+    pub fn is_recording_audio(&self) -> bool {
+        self.audio_recorder.is_some()
     }
 
     /// Set button state
     pub fn set_button(&mut self, button: crate::gamepad::Button, pressed: bool) {
-        self.gamepad.set_button(button, pressed);
+        self.gamepad.set_button(button, pressed, &mut self.events);
+    }
+
+    /// Queue button changes for a frame, for frame-accurate (TAS) input
+    ///
+    /// The inputs are applied before the first instruction of `frame`, so
+    /// every JOYP read in that frame sees them regardless of when the game
+    /// polls. Inputs for a frame that has already started apply on the next
+    /// step. Queuing the same frame again appends to its inputs.
+    pub fn queue_frame_input(&mut self, frame: u32, inputs: &[(Button, bool)]) {
+        self.queued_inputs.entry(frame).or_default().extend_from_slice(inputs);
+    }
+
+    /// Frame number that queued inputs are matched against
+    pub fn current_input_frame(&self) -> u32 {
+        self.ppu.current_frame
+    }
+
+    /// Apply queued inputs whose frame has been reached and refresh JOYP
+    fn apply_queued_inputs(&mut self) {
+        let frame = self.current_input_frame();
+        if self.queued_inputs.first_key_value().is_none_or(|(&next, _)| next > frame) {
+            return;
+        }
+        let later = self.queued_inputs.split_off(&(frame + 1));
+        for (_, inputs) in core::mem::replace(&mut self.queued_inputs, later) {
+            for (button, pressed) in inputs {
+                self.gamepad.set_button(button, pressed, &mut self.events);
+            }
+        }
+        self.sync_gamepad_to_bus();
+    }
+
+    /// Map an analog stick position onto the D-pad (see `Gamepad::analog_to_digital`)
+    pub fn set_analog_stick(&mut self, x: i16, y: i16, dead_zone: i16) {
+        self.gamepad.analog_to_digital(x, y, dead_zone, &mut self.events);
+    }
+
+    /// Auto-repeat a held button every `rate` frames (0 disables turbo)
+    pub fn set_turbo_button(&mut self, button: crate::gamepad::Button, rate: u32) {
+        self.gamepad.set_turbo(button, rate, &mut self.events);
+    }
+
+    /// Run undefined opcodes as NOPs, reporting each `(opcode, pc)` to `handler`
+    ///
+    /// Without a handler an undefined opcode panics.
+    pub fn set_invalid_opcode_handler(&mut self, handler: impl FnMut(u8, u16) + 'static) {
+        self.cpu.set_invalid_opcode_handler(handler);
+    }
+
+    /// Ask before erasing save data, e.g. with a frontend dialog
+    ///
+    /// `erase_save_data` only proceeds when the callback returns `true`.
+    pub fn set_erase_confirm_callback(&mut self, cb: impl Fn() -> bool + 'static) {
+        self.erase_confirm = Some(Box::new(cb));
+    }
+
+    /// Start checking that the PPU begins a frame every 70224 T-cycles
+    ///
+    /// Each VBlank records the current T-cycle count; `timing_ok` passes
+    /// while the average frame length is within `tolerance` T-cycles.
+    pub fn enable_timing_verification(&mut self, tolerance: u64) {
+        self.timing_verifier = Some(TimingVerifier::new(T_CYCLES_PER_FRAME, tolerance));
+    }
+
+    /// Check if frame timing is within tolerance (false when not enabled)
+    pub fn timing_ok(&self) -> bool {
+        self.timing_verifier.as_ref().is_some_and(|verifier| verifier.is_synchronized())
+    }
+
+    /// Average T-cycles per frame seen by the timing verifier (0.0 when not enabled)
+    pub fn average_frame_cycles(&self) -> f64 {
+        self.timing_verifier.as_ref().map_or(0.0, |verifier| verifier.average_frame_cycles())
+    }
+
+    /// T-cycles executed since the emulator was created
+    pub fn total_cycles(&self) -> u64 {
+        self.ctx.ticks
+    }
+
+    /// T-cycles executed since the last `reset`
+    pub fn cycles_since_reset(&self) -> u64 {
+        self.ctx.ticks - self.ctx.reset_ticks
+    }
+
+    /// Emulation speed relative to real hardware (1.0 = realtime)
+    ///
+    /// Measured over a rolling 1-second window of wall-clock time.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn emulation_speed(&mut self) -> f64 {
+        self.speedometer.update(self.ctx.ticks)
+    }
+
+    /// Run the CPU faster or slower than real hardware
+    ///
+    /// Only the frontend's cycles per frame change; the timer and APU keep
+    /// counting against the hardware clock. Non-positive or non-finite
+    /// factors are ignored.
+    pub fn set_overclock_factor(&mut self, factor: f32) {
+        if factor.is_finite() && factor > 0.0 {
+            self.ctx.overclock_factor = factor;
+        }
+    }
+
+    /// Select stereo output or one of the mono downmixes
+    pub fn set_audio_output_mode(&mut self, mode: mixer::AudioOutput) {
+        self.apu.mixer.set_output_mode(mode);
+    }
+
+    /// Blend part of each audio channel into the other for headphones (0.0-1.0)
+    pub fn set_crossfeed(&mut self, amount: f32) {
+        self.apu.mixer.apply_crossfeed(amount);
     }
 
     /// Check if emulator is running
@@ -463,12 +1727,50 @@ impl Emulator {
         self.ctx.paused
     }
 
+    /// Source and priority bits for each pixel of the last frame
+    ///
+    /// See `ppu::PixelSource` and the `ppu::PRIORITY_*` bit masks.
+    pub fn pixel_priority_buffer(&self) -> &[u8] {
+        &self.ppu.pixel_priority_buffer
+    }
+
+    /// Start counting executed opcodes (clears any previous counts)
+    pub fn enable_opcode_profiler(&mut self) {
+        self.cpu.enable_opcode_profiler();
+    }
+
+    /// Stop counting opcodes and return the counts (empty if never enabled)
+    pub fn take_opcode_profile(&mut self) -> OpcodeProfiler {
+        self.cpu.take_opcode_profile()
+    }
+
+    /// Disassembly of the instruction at PC, for debugger UIs
+    pub fn current_disassembly(&self) -> String {
+        self.cpu.disassemble_next(&self.bus).0
+    }
+
+    /// Sprites the OAM scan would select for scanline `ly`, sorted by X
+    pub fn sprites_on_scanline(&self, ly: u8) -> Vec<OamEntry> {
+        self.ppu.oam_scan_result_for_line(ly, &self.lcd)
+    }
+
+    /// State of the four sound channels, for audio visualisers
+    pub fn audio_channel_states(&self) -> [ChannelState; 4] {
+        self.apu.get_channel_state()
+    }
+
     /// Get current frame number
     pub fn current_frame(&self) -> u32 {
         self.ppu.current_frame
     }
 
+    /// Get a snapshot of the timer state
+    pub fn timer_state(&self) -> TimerState {
+        self.timer.inspect()
+    }
+
     /// Run the emulator (simple loop without UI)
+    #[cfg(feature = "std")]
     pub fn run(&mut self) -> Result<(), String> {
         println!("Starting emulation...");
         println!("Note: This is a headless run. Use with SDL2 UI for graphics.");
@@ -491,8 +1793,25 @@ impl Emulator {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::bus::MemoryBus;
+    use crate::cpu::SPEED_SWITCH_CYCLES;
+
+    /// Build an emulator from a synthetic 32KB ROM-only image with `program` at 0x0100
+    pub(crate) fn test_emulator(program: &[u8]) -> Emulator {
+        Emulator::from_bytes(test_rom(program, 0x00)).unwrap()
+    }
+
+    /// Build a synthetic 32KB ROM image with the given CGB header flag
+    fn test_rom(program: &[u8], cgb_flag: u8) -> Vec<u8> {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0100..0x0100 + program.len()].copy_from_slice(program);
+        rom[0x0134..0x0138].copy_from_slice(b"TEST");
+        rom[0x0143] = cgb_flag;
+        rom[0x014D] = Cartridge::calculate_checksum(&rom);
+        rom
+    }
 
     // Note: These tests require a valid ROM file, so they're marked as ignored
     // Run with: cargo test -- --ignored
@@ -500,7 +1819,1145 @@ mod tests {
     #[test]
     #[ignore]
     fn test_emulator_creation() {
-        let emu = Emulator::new("../roms/cpu_instrs.gb");
+        let emu = EmulatorBuilder::new().rom("../roms/cpu_instrs.gb").build();
         assert!(emu.is_ok());
     }
+
+    #[test]
+    fn test_synthetic_rom_runs_frame() {
+        // JR -2: spin in place at the entry point
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        emu.run_frame();
+        assert_eq!(emu.cpu.regs.pc, 0x0100);
+    }
+
+    /// Log sink shared with the test
+    struct SharedLog(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Write for SharedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cpu_log_gameboy_doctor() {
+        // NOP; LD B,0x42; JR -2
+        let mut emu = test_emulator(&[0x00, 0x06, 0x42, 0x18, 0xFE]);
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        emu.enable_cpu_log(Box::new(SharedLog(log.clone())));
+        emu.step();
+        emu.step();
+        emu.disable_cpu_log().unwrap();
+        emu.step();
+
+        let text = String::from_utf8(log.borrow().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,06,42,18"
+        );
+        assert!(lines[1].ends_with("PC:0101 PCMEM:06,42,18,FE"));
+    }
+
+    #[test]
+    fn test_cpu_log_write_error_is_returned() {
+        struct FailingLog;
+
+        impl Write for FailingLog {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        emu.enable_cpu_log(Box::new(FailingLog));
+        emu.step();
+        emu.step();
+        assert!(matches!(emu.disable_cpu_log(), Err(EmulatorError::Io(_))));
+        assert!(emu.disable_cpu_log().is_ok());
+    }
+
+    #[test]
+    fn test_step_raw_returns_t_cycles() {
+        // NOP; LD BC,0x1234
+        let mut emu = test_emulator(&[0x00, 0x01, 0x34, 0x12]);
+        let ticks = emu.ctx.ticks;
+        assert_eq!(emu.step_raw(), 4);
+        assert_eq!(emu.ctx.ticks - ticks, 4);
+        assert_eq!(emu.step_raw(), 12);
+        assert_eq!(emu.cpu.regs.bc(), 0x1234);
+    }
+
+    #[test]
+    fn test_step_raw_clears_write_log() {
+        // LD A,0x42; LD (0xC000),A
+        let mut emu = test_emulator(&[0x3E, 0x42, 0xEA, 0x00, 0xC0]);
+        emu.bus.set_track_writes(true);
+        emu.step_raw();
+        assert_eq!(emu.step_raw(), 16);
+        assert_eq!(emu.bus.read(0xC000), 0x42);
+        assert!(emu.bus.write_log().is_empty());
+    }
+
+    #[test]
+    fn test_step_ticks_instruction_cycles() {
+        // LD HL,$C000 (12); LD (HL),$42 (12); INC (HL) (12); PUSH HL (16);
+        // CALL $0110 (24); at $0110: POP BC (12); ADD SP,-2 (16); JP (HL) (4)
+        let mut program = vec![0x21, 0x00, 0xC0, 0x36, 0x42, 0x34, 0xE5, 0xCD, 0x10, 0x01];
+        program.resize(0x10, 0x00);
+        program.extend_from_slice(&[0xC1, 0xE8, 0xFE, 0xE9]);
+        let mut emu = test_emulator(&program);
+
+        let expected = [12, 12, 12, 16, 24, 12, 16, 4];
+        for &cycles in &expected {
+            let ticks = emu.ctx.ticks;
+            emu.step();
+            assert_eq!(emu.ctx.ticks - ticks, cycles);
+        }
+        assert_eq!(emu.bus.read(0xC000), 0x43);
+        assert_eq!(emu.cpu.regs.pc, 0xC000);
+    }
+
+    #[test]
+    fn test_interrupt_dispatch_ticks_20_cycles() {
+        let mut emu = test_emulator(&[0x00; 4]);
+        emu.cpu.ime = true;
+        emu.bus.write(0xFFFF, 0x01);
+        emu.bus.write(0xFF0F, 0x01);
+        let sp = emu.cpu.regs.sp;
+        let ticks = emu.ctx.ticks;
+
+        emu.step();
+        assert_eq!(emu.ctx.ticks - ticks, 20);
+        assert_eq!(emu.cpu.regs.pc, 0x0040);
+        assert_eq!(emu.cpu.regs.sp, sp.wrapping_sub(2));
+        assert_eq!(emu.bus.read16(emu.cpu.regs.sp), 0x0100);
+        assert!(!emu.cpu.ime);
+    }
+
+    #[test]
+    fn test_breakpoint_stops_before_instruction() {
+        // INC A; INC A; JR -4
+        let mut emu = test_emulator(&[0x3C, 0x3C, 0x18, 0xFC]);
+        emu.cpu.regs.a = 0;
+        emu.add_breakpoint(0x0101);
+
+        emu.step();
+        assert!(emu.step());
+        assert!(emu.is_paused());
+        assert_eq!(emu.break_reason(), Some(BreakReason::Breakpoint(0x0101)));
+        assert_eq!(emu.cpu.regs.pc, 0x0101);
+        assert_eq!(emu.cpu.regs.a, 1);
+
+        // Stepping while stopped does nothing
+        let ticks = emu.ctx.ticks;
+        emu.step();
+        assert_eq!((emu.cpu.regs.pc, emu.ctx.ticks), (0x0101, ticks));
+
+        // Resuming runs the instruction and stops again on the next pass
+        emu.resume();
+        emu.step();
+        assert_eq!(emu.cpu.regs.a, 2);
+        assert_eq!(emu.break_reason(), None);
+        emu.run_frame();
+        assert!(emu.is_paused());
+        assert_eq!(emu.cpu.regs.pc, 0x0101);
+        assert_eq!(emu.cpu.regs.a, 3);
+
+        emu.remove_breakpoint(0x0101);
+        emu.resume();
+        emu.run_frame();
+        assert!(!emu.is_paused());
+        assert!(emu.cpu.regs.a > 3);
+    }
+
+    #[test]
+    fn test_watchpoint_stops_after_write() {
+        // LD HL,$C000; LD (HL),$01; LD A,(HL); LD (HL),$02; JR -2
+        let mut emu = test_emulator(&[0x21, 0x00, 0xC0, 0x36, 0x01, 0x7E, 0x36, 0x02, 0x18, 0xFE]);
+        emu.add_watchpoint(0xC000);
+
+        emu.run_frame();
+        assert!(emu.is_paused());
+        assert_eq!(emu.break_reason(), Some(BreakReason::Watchpoint(0xC000)));
+        assert_eq!(emu.cpu.regs.pc, 0x0105);
+        assert_eq!(emu.bus.read(0xC000), 0x01);
+
+        emu.resume();
+        emu.run_frame();
+        assert_eq!(emu.cpu.regs.pc, 0x0108);
+        assert_eq!(emu.bus.read(0xC000), 0x02);
+
+        emu.remove_watchpoint(0xC000);
+        assert!(!emu.bus.track_writes);
+        emu.resume();
+        emu.run_frame();
+        assert!(!emu.is_paused());
+    }
+
+    #[test]
+    fn test_step_callback_pauses_after_five_instructions() {
+        use crate::cpu::StepAction;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let mut emu = test_emulator(&[0x00; 16]);
+        let calls = Rc::new(Cell::new(0));
+        let seen = Rc::clone(&calls);
+        emu.cpu.set_step_callback(Box::new(move |_, event| {
+            assert_eq!(event.opcode, 0x00);
+            seen.set(seen.get() + 1);
+            StepAction { should_continue: seen.get() <= 5, skip_instruction: false }
+        }));
+
+        for _ in 0..10 {
+            emu.step();
+        }
+        assert!(emu.ctx.paused);
+        assert_eq!(calls.get(), 6);
+        assert_eq!(emu.cpu.regs.pc, 0x0105);
+
+        emu.cpu.clear_step_callback();
+        emu.resume();
+        emu.step();
+        assert_eq!(emu.cpu.regs.pc, 0x0106);
+    }
+
+    #[test]
+    fn test_step_callback_skips_instruction() {
+        use crate::cpu::StepAction;
+
+        // LD A,0x42; INC A
+        let mut emu = test_emulator(&[0x3E, 0x42, 0x3C]);
+        emu.cpu.set_step_callback(Box::new(|cpu, _| StepAction {
+            should_continue: true,
+            skip_instruction: cpu.regs.pc == 0x0100,
+        }));
+        emu.step();
+        emu.step();
+        assert_eq!(emu.cpu.regs.pc, 0x0103);
+        assert_eq!(emu.cpu.regs.a, 0x02);
+    }
+
+    #[test]
+    fn test_wave_ram_corruption_visible_to_cpu() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        for i in 0..16 {
+            emu.bus.write(0xFF30 + i, 0x10 + i as u8);
+        }
+        // DAC on, highest frequency so the wave position moves every 2 cycles
+        emu.bus.write(0xFF1A, 0x80);
+        emu.bus.write(0xFF1D, 0xFF);
+        emu.bus.write(0xFF1E, 0x87);
+        emu.advance_cycles(40);
+
+        // Retriggering while playing on DMG corrupts the first byte
+        emu.bus.write(0xFF1E, 0x87);
+        emu.advance_cycles(4);
+        assert_ne!(emu.apu.read(0xFF30), 0x10);
+        assert_eq!(emu.bus.read(0xFF30), emu.apu.read(0xFF30));
+        assert_eq!(emu.bus.read(0xFF31), 0x11);
+    }
+
+    #[test]
+    fn test_auto_mode_detection() {
+        assert_eq!(test_emulator(&[]).mode(), EmulatorMode::Dmg);
+
+        let emu = Emulator::from_bytes(test_rom(&[], 0x80)).unwrap();
+        assert_eq!(emu.mode(), EmulatorMode::Cgb);
+        assert_eq!(emu.apu.hardware_model, HardwareModel::Cgb);
+    }
+
+    #[test]
+    fn test_video_buffer_as() {
+        let mut emu = test_emulator(&[]);
+        emu.ppu.video_buffer[0] = 0xFF123456;
+        let rgba = emu.get_video_buffer_as(PixelFormat::Rgba8888);
+        assert_eq!(rgba.len(), emu.get_video_buffer().len());
+        assert_eq!(rgba[0], 0x123456FF);
+        assert_eq!(emu.get_video_buffer_as(PixelFormat::Bgr888)[0], 0x00563412);
+    }
+
+    #[test]
+    fn test_lcdc_write_resets_ly_and_mode() {
+        let mut emu = test_emulator(&[0x18, 0xFE]); // JR -2
+        while emu.bus.read(0xFF44) < 10 {
+            emu.step();
+        }
+
+        emu.bus.write(0xFF40, 0x11);
+        emu.step();
+        assert_eq!(emu.bus.read(0xFF44), 0);
+        assert_eq!(emu.bus.read(0xFF41) & 0x03, 0);
+        emu.run_frame();
+        assert_eq!(emu.bus.read(0xFF44), 0);
+
+        // Back on, the PPU starts over at line 0 in mode 0
+        emu.bus.write(0xFF40, 0x91);
+        emu.step();
+        assert_eq!(emu.bus.read(0xFF44), 0);
+        assert_eq!(emu.bus.read(0xFF41) & 0x03, 0);
+        while emu.bus.read(0xFF44) == 0 {
+            assert_ne!(emu.bus.read(0xFF41) & 0x03, 2, "line 0 has no OAM scan");
+            emu.step();
+        }
+        assert_eq!(emu.bus.read(0xFF44), 1);
+    }
+
+    #[test]
+    fn test_cgb_post_boot_state() {
+        let emu = Emulator::from_bytes(test_rom(&[], 0x80)).unwrap();
+        assert_eq!(emu.cpu.regs.a, 0x11);
+        assert_eq!(emu.cpu.regs.de(), 0xFF56);
+        assert_eq!(emu.timer.inspect().div_internal, 0x1EA0);
+
+        let emu = test_emulator(&[]);
+        assert_eq!(emu.cpu.regs.a, 0x01);
+        assert_eq!(emu.timer.inspect().div_internal, 0xABCC);
+
+        let emu = EmulatorBuilder::new()
+            .rom_bytes(test_rom(&[], 0x00))
+            .mode(EmulatorMode::Cgb)
+            .build()
+            .unwrap();
+        assert_eq!(emu.cpu.regs.a, 0x11);
+    }
+
+    #[test]
+    fn test_cgb_palette_colors_background() {
+        // With the LCD off, set BG palette 0 color 0 to pure red, then turn it on
+        let program = [
+            0xAF, // XOR A
+            0xE0, 0x40, // LDH (0x40),A
+            0x3E, 0x80, // LD A,0x80
+            0xE0, 0x68, // LDH (0x68),A
+            0x3E, 0x1F, // LD A,0x1F
+            0xE0, 0x69, // LDH (0x69),A
+            0xAF, // XOR A
+            0xE0, 0x69, // LDH (0x69),A
+            0x3E, 0x91, // LD A,0x91
+            0xE0, 0x40, // LDH (0x40),A
+            0x18, 0xFE, // JR -2
+        ];
+        let mut emu = Emulator::from_bytes(test_rom(&program, 0x80)).unwrap();
+        for _ in 0..3 {
+            emu.run_frame();
+        }
+        assert!(emu.get_video_buffer().iter().all(|&pixel| pixel == 0xFFFF0000));
+
+        // DMG mode ignores the palette registers
+        let mut emu = test_emulator(&program);
+        for _ in 0..3 {
+            emu.run_frame();
+        }
+        assert_eq!(emu.get_video_buffer()[0], DmgPalette::GRAYSCALE.argb(0));
+    }
+
+    #[test]
+    fn test_rumble_active() {
+        let program = [
+            0x3E, 0x08, // LD A,0x08
+            0xEA, 0x00, 0x40, // LD (0x4000),A
+            0xAF, // XOR A
+            0xEA, 0x00, 0x40, // LD (0x4000),A
+        ];
+        let mut rom = test_rom(&program, 0x00);
+        rom[0x0147] = 0x1C; // MBC5+RUMBLE
+        rom[0x014D] = Cartridge::calculate_checksum(&rom);
+        let mut emu = Emulator::from_bytes(rom).unwrap();
+        assert!(!emu.rumble_active());
+
+        emu.step();
+        emu.step();
+        assert!(emu.rumble_active());
+        emu.step();
+        emu.step();
+        assert!(!emu.rumble_active());
+        assert!(!test_emulator(&[]).rumble_active());
+    }
+
+    #[test]
+    fn test_div_write_of_any_value_resets_div() {
+        let program = [
+            0x3E, 0x42, // LD A,0x42
+            0xE0, 0x04, // LDH (0x04),A
+            0x18, 0xFE, // JR -2
+        ];
+        let mut emu = test_emulator(&program);
+        assert_ne!(emu.timer.div_internal(), 0);
+        emu.step();
+        emu.step();
+        assert!(emu.timer.div_internal() < 16);
+        assert_eq!(emu.bus.io_register(0x04), 0);
+    }
+
+    #[test]
+    fn test_tima_write_during_reload_delay() {
+        // Only a CPU write to TIMA cancels the reload, even one writing the 0 it already reads
+        for cpu_write in [false, true] {
+            let mut emu = test_emulator(&[]);
+            emu.timer.write(0xFF06, 0x42);
+            emu.timer.write(0xFF05, 0xFF);
+            emu.timer.write(0xFF07, 0x05);
+            for _ in 0..emu.timer.cycles_until_tima_tick() {
+                emu.timer.tick(&mut emu.events);
+            }
+            emu.sync_timer_to_bus();
+            assert_eq!(emu.bus.io_register(0x05), 0);
+
+            if cpu_write {
+                emu.bus.write(0xFF05, 0x00);
+            }
+            emu.sync_timer_from_bus();
+            for _ in 0..8 {
+                emu.timer.tick(&mut emu.events);
+            }
+            let expected = if cpu_write { 0x00 } else { 0x42 };
+            assert_eq!(emu.timer.read(0xFF05), expected);
+            assert_eq!(emu.events.is_empty(), cpu_write);
+        }
+    }
+
+    #[test]
+    fn test_hdma_general_purpose_copy() {
+        // Copy 0x20 bytes from 0xC000 to 0x8010 in one go
+        let program = [
+            0x3E, 0xC0, // LD A,0xC0
+            0xE0, 0x51, // LDH (0x51),A
+            0xAF, // XOR A
+            0xE0, 0x52, // LDH (0x52),A
+            0xE0, 0x53, // LDH (0x53),A
+            0x3E, 0x10, // LD A,0x10
+            0xE0, 0x54, // LDH (0x54),A
+            0x3E, 0x01, // LD A,0x01
+            0xE0, 0x55, // LDH (0x55),A
+            0x18, 0xFE, // JR -2
+        ];
+        let mut emu = Emulator::from_bytes(test_rom(&program, 0x80)).unwrap();
+        for i in 0..0x20 {
+            emu.bus.write(0xC000 + i, i as u8 + 1);
+        }
+        for _ in 0..8 {
+            emu.step();
+        }
+
+        let ticks = emu.ctx.ticks;
+        emu.step();
+        // LDH takes 12 T-cycles, then the CPU is halted for both blocks
+        assert_eq!(emu.ctx.ticks - ticks, 12 + 2 * HDMA_BLOCK_STALL_CYCLES as u64);
+        assert_eq!(emu.bus.read(0xFF55), 0xFF);
+        assert!(emu.bus.vram[..0x10].iter().all(|&b| b == 0));
+        assert!((0..0x20).all(|i| emu.bus.vram[0x10 + i] == i as u8 + 1));
+    }
+
+    #[test]
+    fn test_hdma_hblank_transfer_cancel() {
+        let mut emu = Emulator::from_bytes(test_rom(&[0x18, 0xFE], 0x80)).unwrap();
+        for i in 0..0x40 {
+            emu.bus.write(0xC000 + i, 0xAA);
+        }
+        emu.bus.write(0xFF51, 0xC0);
+        emu.bus.write(0xFF52, 0x00);
+        emu.bus.write(0xFF53, 0x00);
+        emu.bus.write(0xFF54, 0x00);
+        emu.bus.write(0xFF55, 0x83);
+        emu.step();
+        assert!(emu.bus.read(0xFF55) <= 0x03);
+
+        // Let two HBlanks pass, then cancel the transfer
+        let mut steps = 0;
+        while emu.bus.read(0xFF55) != 0x01 {
+            emu.step();
+            steps += 1;
+            assert!(steps < 1000, "HBlank transfer never progressed");
+        }
+        emu.bus.write(0xFF55, 0x00);
+        emu.step();
+        assert_eq!(emu.bus.read(0xFF55), 0x81);
+
+        emu.run_frame();
+        assert_eq!(emu.bus.read(0xFF55), 0x81);
+        assert!(emu.bus.vram[..0x20].iter().all(|&b| b == 0xAA));
+        assert!(emu.bus.vram[0x20..0x40].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_cgb_rom_forced_to_dmg() {
+        let mut emu = Emulator::from_bytes(test_rom(&[], 0x80)).unwrap();
+        emu.bus.write(0xFF4F, 0x01);
+        assert_eq!(emu.bus.read(0xFF4F), 0xFF);
+
+        emu.set_cgb_mode(EmulatorMode::Dmg);
+        assert_eq!(emu.mode(), EmulatorMode::Dmg);
+        assert_eq!(emu.bus.vram_bank, 0);
+        emu.bus.write(0xFF4F, 0x00);
+        assert_eq!(emu.bus.read(0xFF4F), 0xFF);
+        emu.bus.write(0xFF70, 0x02);
+        assert_eq!(emu.bus.read(0xFF70), 0xFF);
+        assert_eq!(emu.bus.ram.wram_bank(), 1);
+    }
+
+    #[test]
+    fn test_dmg_on_cgb_uses_compat_palettes() {
+        let mut emu = test_emulator(&[]);
+        emu.set_cgb_mode(EmulatorMode::DmgOnCgb);
+        assert_eq!(emu.mode(), EmulatorMode::DmgOnCgb);
+        assert_eq!(emu.ppu.bg_palette, DmgPalette::CGB_COMPAT_BG);
+        assert_eq!(emu.ppu.obj1_palette, DmgPalette::CGB_COMPAT_OBJ);
+        assert_eq!(emu.apu.hardware_model, HardwareModel::Cgb);
+        // CGB registers stay hidden from DMG games
+        assert_eq!(emu.bus.read(0xFF4D), 0xFF);
+        // The CGB boot ROM leaves A=0x11
+        assert_eq!(emu.cpu.regs.a, 0x11);
+
+        emu.set_cgb_mode(EmulatorMode::Dmg);
+        assert_eq!(emu.cpu.regs.a, 0x01);
+    }
+
+    #[test]
+    fn test_cycle_histogram_finds_hot_loop() {
+        // LD B,100; loop: DEC B; JR NZ,loop; JR -2
+        let mut emu = test_emulator(&[0x06, 100, 0x05, 0x20, 0xFD, 0x18, 0xFE]);
+        emu.cpu.enable_cycle_histogram();
+        while emu.cpu.regs.pc != 0x0105 {
+            emu.step();
+        }
+
+        let top = emu.cpu.dump_cycle_histogram();
+        assert_eq!(top[0], (0x0103, 99 * 3 + 2));
+        assert_eq!(top[1], (0x0102, 100));
+        assert_eq!(emu.cpu.cycle_histogram_at(0x0100), 2);
+        assert_eq!(emu.cpu.cycle_count, 2 + 100 + 99 * 3 + 2);
+    }
+
+    #[test]
+    fn test_opcode_profiler_counts_executed_instructions() {
+        // NOP; LD A,5; NOP; LD B,A; BIT 7,H; NOP; JR -2
+        let mut emu = test_emulator(&[0x00, 0x3E, 0x05, 0x00, 0x47, 0xCB, 0x7C, 0x00, 0x18, 0xFE]);
+        emu.step();
+        emu.enable_opcode_profiler();
+        for _ in 0..8 {
+            emu.step();
+        }
+
+        let profile = emu.take_opcode_profile();
+        assert_eq!(profile.counts[0x00], 2);
+        assert_eq!(profile.counts[0x3E], 1);
+        assert_eq!(profile.counts[0x47], 1);
+        assert_eq!(profile.counts[0xCB], 0);
+        assert_eq!(profile.cb_counts[0x7C], 1);
+        assert_eq!(profile.counts[0x18], 3);
+        assert_eq!(profile.total(), 8);
+        assert_eq!(profile.top_n(2), [(0x18, false, 3), (0x00, false, 2)]);
+
+        // Taking the profile stops profiling
+        emu.step();
+        assert_eq!(emu.take_opcode_profile().total(), 0);
+    }
+
+    #[test]
+    fn test_fork_diverges_independently() {
+        // Loop: select action buttons, copy JOYP into BGP
+        let mut emu = test_emulator(&[
+            0x3E, 0x10, // LD A,0x10
+            0xE0, 0x00, // LDH (0x00),A
+            0xF0, 0x00, // LDH A,(0x00)
+            0xE0, 0x47, // LDH (0x47),A
+            0x18, 0xF6, // JR 0x0100
+        ]);
+        for _ in 0..60 {
+            emu.run_frame();
+        }
+
+        let mut fork = emu.fork();
+        assert!(std::sync::Arc::ptr_eq(
+            &emu.bus.cart.as_ref().unwrap().rom,
+            &fork.bus.cart.as_ref().unwrap().rom
+        ));
+
+        emu.set_button(crate::gamepad::Button::A, true);
+        fork.set_button(crate::gamepad::Button::B, true);
+        for _ in 0..60 {
+            emu.run_frame();
+            fork.run_frame();
+        }
+
+        assert_ne!(emu.get_video_buffer(), fork.get_video_buffer());
+        assert_ne!(emu.bus.io_regs[0x47], fork.bus.io_regs[0x47]);
+    }
+
+    #[test]
+    fn test_dirty_fork_leaves_parent_save_alone() {
+        // MBC1+RAM+BATTERY with 8KB RAM
+        let mut rom = test_rom(&[0x18, 0xFE], 0x00);
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        rom[0x014D] = Cartridge::calculate_checksum(&rom);
+        let rom_path = std::env::temp_dir().join(format!("rgbe_fork_{}.gb", std::process::id()));
+        let save_path = std::path::PathBuf::from(format!("{}.sav", rom_path.display()));
+
+        let mut emu = Emulator::from_bytes(rom).unwrap();
+        let cart = emu.bus.cart.as_mut().unwrap();
+        cart.set_save_strategy(crate::cart::SavePathStrategy::SiblingFile);
+        cart.enable_battery_saves(rom_path.to_string_lossy());
+        emu.bus.write(0x0000, 0x0A);
+        emu.bus.write(0xA000, 0x11);
+        emu.bus.cart.as_mut().unwrap().save_battery().unwrap();
+
+        let mut fork = emu.fork();
+        fork.bus.write(0xA000, 0x66);
+        assert!(fork.bus.cart.as_ref().unwrap().needs_save());
+        drop(fork);
+
+        let saved = std::fs::read(&save_path);
+        let _ = std::fs::remove_file(&save_path);
+        assert_eq!(saved.unwrap()[0], 0x11);
+        assert_eq!(emu.bus.read(0xA000), 0x11);
+    }
+
+    /// Loop: INC A, write A to SCX and BGP
+    #[cfg(feature = "save-state")]
+    const SCROLLING_PROGRAM: [u8; 7] = [0x3C, 0xE0, 0x43, 0xE0, 0x47, 0x18, 0xFA];
+
+    #[test]
+    #[cfg(feature = "save-state")]
+    fn test_save_state_round_trip() {
+        let mut emu = test_emulator(&SCROLLING_PROGRAM);
+        for _ in 0..30 {
+            emu.run_frame();
+        }
+        let state = emu.save_state();
+
+        for _ in 0..20 {
+            emu.run_frame();
+        }
+        let first_video = emu.get_video_buffer().to_vec();
+        let first_cpu = emu.cpu_state();
+        let first_ticks = emu.ctx.ticks;
+
+        emu.load_state(&state).unwrap();
+        for _ in 0..20 {
+            emu.run_frame();
+        }
+        assert_eq!(emu.get_video_buffer(), &first_video[..]);
+        assert_eq!(emu.cpu_state(), first_cpu);
+        assert_eq!(emu.ctx.ticks, first_ticks);
+    }
+
+    #[test]
+    #[cfg(feature = "save-state")]
+    fn test_load_state_rejects_other_rom() {
+        let mut emu = test_emulator(&SCROLLING_PROGRAM);
+        emu.run_frame();
+        let state = emu.save_state();
+
+        let mut other = test_emulator(&[0x18, 0xFE]);
+        other.run_frame();
+        let cpu = other.cpu_state();
+        assert!(matches!(
+            other.load_state(&state),
+            Err(EmulatorError::InvalidSaveState(_))
+        ));
+        assert_eq!(other.cpu_state(), cpu);
+    }
+
+    #[test]
+    #[cfg(feature = "save-state")]
+    fn test_load_state_rejects_garbage() {
+        let mut emu = test_emulator(&SCROLLING_PROGRAM);
+        let mut state = emu.save_state();
+        state.truncate(state.len() / 2);
+
+        assert!(emu.load_state(&state).is_err());
+        assert!(emu.load_state(b"not a save state").is_err());
+    }
+
+    #[derive(Default)]
+    struct PluginCounts {
+        frames: usize,
+        instructions: usize,
+        writes: Vec<(u16, u8)>,
+    }
+
+    struct CountingPlugin(std::rc::Rc<std::cell::RefCell<PluginCounts>>);
+
+    impl EmulatorPlugin for CountingPlugin {
+        fn on_frame(&mut self, video: &[u32], _audio: &[i16]) {
+            assert_eq!(video.len(), 160 * 144);
+            self.0.borrow_mut().frames += 1;
+        }
+
+        fn on_instruction(&mut self, _pc: u16, _opcode: u8, _cpu: &Cpu) {
+            self.0.borrow_mut().instructions += 1;
+        }
+
+        fn on_memory_write(&mut self, address: u16, value: u8) {
+            self.0.borrow_mut().writes.push((address, value));
+        }
+
+        fn name(&self) -> &str {
+            "counter"
+        }
+    }
+
+    #[test]
+    fn test_vblank_if_set_when_ly_reaches_144() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        emu.bus.int_flags = 0;
+
+        while emu.bus.io_regs[0x44] != 144 {
+            assert_eq!(emu.bus.int_flags & 0x01, 0, "VBlank requested early at LY={}", emu.bus.io_regs[0x44]);
+            emu.step();
+        }
+        assert_ne!(emu.bus.int_flags & 0x01, 0);
+    }
+
+    #[test]
+    fn test_plugin_hooks() {
+        // LD A,0x5A; LD (0xC000),A; JR -2
+        let mut emu = test_emulator(&[0x3E, 0x5A, 0xEA, 0x00, 0xC0, 0x18, 0xFE]);
+        let counts = std::rc::Rc::new(std::cell::RefCell::new(PluginCounts::default()));
+        emu.add_plugin(Box::new(CountingPlugin(counts.clone())));
+
+        for _ in 0..3 {
+            emu.run_frame();
+        }
+        assert_eq!(counts.borrow().frames, 3);
+        assert!(counts.borrow().instructions > 3);
+        assert_eq!(counts.borrow().writes, vec![(0xC000, 0x5A)]);
+
+        emu.remove_plugin("counter");
+        emu.run_frame();
+        assert_eq!(counts.borrow().frames, 3);
+    }
+
+    #[test]
+    fn test_audio_recording_one_frame() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        let path = std::env::temp_dir().join(format!("rgbe_rec_{}.wav", std::process::id()));
+
+        emu.start_audio_recording(path.to_str().unwrap()).unwrap();
+        assert!(emu.is_recording_audio());
+        emu.run_frame();
+        let sample_count = emu.get_audio_buffer().len() as u32;
+        emu.stop_audio_recording().unwrap();
+        assert!(!emu.is_recording_audio());
+
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(sample_count > 0);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 44100);
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), sample_count * 2);
+        assert_eq!(data.len() as u32, 44 + sample_count * 2);
+    }
+
+    #[cfg(feature = "gif-recording")]
+    #[test]
+    fn test_gif_recording_ten_frames() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        let path = std::env::temp_dir().join(format!("rgbe_rec_{}.gif", std::process::id()));
+
+        emu.start_gif_recording(path.to_str().unwrap(), 1).unwrap();
+        assert!(emu.is_recording_gif());
+        for _ in 0..10 {
+            emu.run_frame();
+        }
+        emu.stop_gif_recording().unwrap();
+        assert!(!emu.is_recording_gif());
+
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(&data[0..6], b"GIF89a");
+        assert_eq!(u16::from_le_bytes([data[6], data[7]]), 160);
+        assert_eq!(u16::from_le_bytes([data[8], data[9]]), 144);
+        assert_eq!(*data.last().unwrap(), 0x3B);
+    }
+
+    #[cfg(all(feature = "gif-recording", target_os = "linux"))]
+    #[test]
+    fn test_gif_write_error_is_returned() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        emu.start_gif_recording("/dev/full", 1).unwrap();
+        let mut frames = 0;
+        while emu.is_recording_gif() && frames < 1000 {
+            emu.run_frame();
+            frames += 1;
+        }
+        assert!(!emu.is_recording_gif());
+        assert!(emu.stop_gif_recording().is_err());
+        assert!(emu.stop_gif_recording().is_ok());
+    }
+
+    #[test]
+    fn test_tileset_image_palette_by_name() {
+        let mut emu = test_emulator(&[]);
+        emu.ppu.vram[0] = 0x80;
+        assert_eq!(emu.tileset_image("grayscale", 0)[0], DmgPalette::GRAYSCALE.argb(1));
+        assert_eq!(emu.tileset_image("cgb-compat-obj", 0)[0], DmgPalette::CGB_COMPAT_OBJ.argb(1));
+        assert_eq!(emu.tileset_image("unknown", 0)[0], emu.ppu.bg_palette.argb(1));
+        assert_eq!(emu.tileset_image("grayscale", 1)[0], DmgPalette::GRAYSCALE.argb(0));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_from_async_rom() {
+        let rom = test_rom(&[0x18, 0xFE], 0x80);
+        let mut emu = Emulator::from_async_rom(rom.as_slice()).await.unwrap();
+        assert_eq!(emu.mode(), EmulatorMode::Cgb);
+        emu.run_frame();
+        assert_eq!(emu.cpu.regs.pc, 0x0100);
+    }
+
+    #[test]
+    fn test_builder_configures_emulator() {
+        let emu = EmulatorBuilder::new()
+            .rom_bytes(test_rom(&[], 0x80))
+            .mode(EmulatorMode::DmgOnCgb)
+            .audio_rate(48000)
+            .build()
+            .unwrap();
+        assert_eq!(emu.mode(), EmulatorMode::DmgOnCgb);
+        assert_eq!(emu.apu.sample_rate(), 48000);
+        assert_eq!(emu.cpu.regs.pc, 0x0100);
+        assert_eq!(emu.bus.read(0xC000), 0x00);
+
+        let emu = EmulatorBuilder::new().rom_bytes(test_rom(&[], 0x80)).build().unwrap();
+        assert_eq!(emu.mode(), EmulatorMode::Cgb);
+        assert_eq!(emu.apu.sample_rate(), crate::apu::SAMPLE_RATE);
+    }
+
+    #[test]
+    fn test_builder_random_wram_is_seeded() {
+        let build = |seed| {
+            let emu = EmulatorBuilder::new()
+                .rom_bytes(test_rom(&[], 0x00))
+                .random_wram(true)
+                .wram_seed(seed)
+                .build()
+                .unwrap();
+            (0xC000..0xE000).map(|a| emu.bus.read(a)).collect::<Vec<u8>>()
+        };
+        let wram = build(7);
+        assert_eq!(wram, build(7));
+        assert_ne!(wram, build(8));
+        assert!(wram.iter().any(|&b| b != 0));
+        // Even seeds no longer collide with the next odd one
+        assert_ne!(build(6), wram);
+        assert!(build(0).iter().any(|&b| b != 0));
+    }
+
+    #[test]
+    fn test_builder_boot_rom_starts_at_zero() {
+        let emu = EmulatorBuilder::new()
+            .rom_bytes(test_rom(&[], 0x00))
+            .boot_rom(vec![0x31; 0x100])
+            .build()
+            .unwrap();
+        assert_eq!(emu.cpu.regs.pc, 0x0000);
+        assert!(emu.bus.boot_rom_mapped());
+        assert_eq!(emu.bus.read(0x0000), 0x31);
+    }
+
+    #[test]
+    fn test_builder_save_directory() {
+        let emu = EmulatorBuilder::new()
+            .rom_bytes(test_rom(&[], 0x00))
+            .save_directory("/tmp/rgbe-saves")
+            .build()
+            .unwrap();
+        let cart = emu.bus.cart.as_ref().unwrap();
+        assert_eq!(
+            cart.save_strategy(),
+            &crate::cart::SavePathStrategy::Custom(PathBuf::from("/tmp/rgbe-saves"))
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_config() {
+        let invalid = |builder: EmulatorBuilder| matches!(builder.build(), Err(EmulatorError::InvalidConfig(_)));
+        assert!(invalid(EmulatorBuilder::new()));
+        assert!(invalid(EmulatorBuilder::new().rom("game.gb").rom_bytes(test_rom(&[], 0x00))));
+        assert!(invalid(EmulatorBuilder::new().rom_bytes(test_rom(&[], 0x00)).audio_rate(7999)));
+        assert!(invalid(EmulatorBuilder::new().rom_bytes(test_rom(&[], 0x00)).audio_rate(192001)));
+        assert!(EmulatorBuilder::new().rom_bytes(test_rom(&[], 0x00)).audio_rate(8000).build().is_ok());
+    }
+
+    #[cfg(feature = "screenshot")]
+    #[test]
+    fn test_take_screenshot_decodes_to_video_buffer() {
+        let mut emu = test_emulator(&[]);
+        for (i, pixel) in emu.ppu.video_buffer.iter_mut().enumerate() {
+            *pixel = if i % 2 == 0 { 0xFF112233 } else { 0xFFAAAAAA };
+        }
+
+        let png_data = emu.take_screenshot();
+        let decoder = png::Decoder::new(png_data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgba = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+
+        assert_eq!(info.width, 160);
+        assert_eq!(info.height, 144);
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(info.bit_depth, png::BitDepth::Eight);
+        assert_eq!(&rgba[0..4], &[0x11, 0x22, 0x33, 0xFF]);
+        assert_eq!(&rgba[4..8], &[0xAA, 0xAA, 0xAA, 0xFF]);
+    }
+
+    #[test]
+    fn test_effective_speed() {
+        let mut emu = test_emulator(&[]);
+        assert_eq!(emu.cpu_frequency(), 4194304);
+        assert_eq!(emu.effective_speed(), 4194304.0);
+
+        emu.set_overclock_factor(2.0);
+        assert_eq!(emu.effective_speed(), 8388608.0);
+        emu.set_overclock_factor(0.0);
+        emu.set_overclock_factor(f32::NAN);
+        assert_eq!(emu.ctx.overclock_factor, 2.0);
+
+        // KEY1 bit 7 reports double speed
+        emu.set_overclock_factor(1.0);
+        emu.bus.key1 = 0x80;
+        assert_eq!(emu.cpu_frequency(), 8388608);
+        assert_eq!(emu.effective_speed(), 8388608.0);
+    }
+
+    #[test]
+    fn test_double_speed_halves_ppu_ticks() {
+        let mut emu = test_emulator(&[]);
+        emu.bus.key1 = 0x80;
+        let frame = emu.current_frame();
+        let ticks = emu.total_cycles();
+        emu.run_frame();
+
+        // A whole frame's worth of PPU dots takes twice the CPU cycles
+        assert!(emu.total_cycles() - ticks >= 2 * T_CYCLES_PER_FRAME);
+        assert_eq!(emu.current_frame(), frame + 1);
+
+        let dots = emu.ppu.line_ticks;
+        emu.tick_components(100);
+        assert_eq!(emu.ppu.line_ticks, dots + 50);
+    }
+
+    #[test]
+    fn test_mock_bus_runs_frames() {
+        use crate::test_utils::MockBus;
+
+        // Unwritten memory reads as NOP; only LCDC needs to be set
+        let mut emu = Emulator::<MockBus>::from_bus(MockBus::with_data(0xFF40, &[0x91]));
+        for _ in 0..100 {
+            emu.run_frame();
+        }
+        assert!(emu.total_cycles() >= 100 * T_CYCLES_PER_FRAME);
+        assert!((99..=100).contains(&emu.current_frame()), "frame {}", emu.current_frame());
+        // VBlank was requested through the mock's IF register
+        assert_ne!(emu.bus.read(0xFF0F) & 0x01, 0);
+    }
+
+    #[test]
+    fn test_current_disassembly() {
+        // LD B, $12; JP $0150
+        let mut emu = test_emulator(&[0x06, 0x12, 0xC3, 0x50, 0x01]);
+        assert_eq!(emu.current_disassembly(), "LD B, $12");
+        emu.step();
+        assert_eq!(emu.current_disassembly(), "JP $0150");
+    }
+
+    #[test]
+    fn test_sprites_on_scanline() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        while emu.lcd.mode() != PpuMode::VBlank {
+            emu.step();
+        }
+
+        // Sprite 1 left of sprite 0 on lines 0-7, sprite 2 on lines 32-39
+        let oam = [16, 20, 1, 0, 16, 10, 2, 0x20, 48, 8, 3, 0];
+        for (i, &value) in oam.iter().enumerate() {
+            emu.bus.write(0xFE00 + i as u16, value);
+        }
+        emu.step();
+
+        let sprites = emu.sprites_on_scanline(4);
+        assert_eq!(sprites.len(), 2);
+        assert_eq!((sprites[0].x, sprites[0].tile, sprites[0].flags), (10, 2, 0x20));
+        assert_eq!((sprites[1].x, sprites[1].tile), (20, 1));
+        assert_eq!(emu.sprites_on_scanline(39)[0].tile, 3);
+        assert!(emu.sprites_on_scanline(100).is_empty());
+    }
+
+    #[test]
+    fn test_queue_frame_input() {
+        // Select the action buttons, then spin
+        let mut emu = test_emulator(&[0x3E, 0x10, 0xE0, 0x00, 0x18, 0xFE]);
+        emu.queue_frame_input(5, &[(Button::A, true), (Button::Start, true)]);
+        emu.queue_frame_input(7, &[(Button::A, false)]);
+
+        while emu.current_input_frame() < 5 {
+            emu.step();
+            assert_eq!(emu.bus.read(0xFF00) & 0x0F, 0x0F, "frame {}", emu.current_input_frame());
+        }
+        // The first instruction of frame 5 already sees the press
+        emu.step();
+        assert_eq!(emu.bus.read(0xFF00) & 0x0F, 0x06);
+
+        for _ in 0..2 {
+            emu.run_frame();
+        }
+        assert!(emu.current_input_frame() >= 7);
+        assert_eq!(emu.bus.read(0xFF00) & 0x0F, 0x07);
+        assert!(emu.queued_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_stop_switches_cgb_speed() {
+        // STOP; INC A
+        let mut emu = Emulator::from_bytes(test_rom(&[0x10, 0x00, 0x3C], 0x80)).unwrap();
+        assert!(emu.bus.cgb_mode);
+        emu.bus.write(0xFF4D, 0x01);
+        assert_eq!(emu.bus.read(0xFF4D), 0x7F);
+        let a = emu.cpu.regs.a;
+
+        emu.step();
+        assert!(emu.cpu.speed_switch_pending);
+        let stop_pc = emu.cpu.regs.pc;
+        // STOP's own 4 cycles count towards the countdown
+        let start = emu.total_cycles() - 4;
+
+        // The CPU fetches nothing until the countdown expires
+        while emu.cpu.speed_switch_pending {
+            emu.step();
+            assert_eq!(emu.cpu.regs.pc, stop_pc);
+        }
+        assert_eq!(emu.total_cycles() - start, SPEED_SWITCH_CYCLES as u64);
+        assert_eq!(emu.bus.read(0xFF4D), 0xFE);
+        assert_eq!(emu.cpu_frequency(), CPU_CLOCK * 2);
+
+        // STOP's padding byte decodes as NOP, then execution continues
+        emu.step();
+        emu.step();
+        assert_eq!(emu.cpu.regs.a, a.wrapping_add(1));
+    }
+
+    #[test]
+    fn test_stop_without_armed_key1_keeps_speed() {
+        let mut emu = Emulator::from_bytes(test_rom(&[0x10, 0x00], 0x80)).unwrap();
+        emu.step();
+        assert!(!emu.cpu.speed_switch_pending);
+        assert_eq!(emu.cpu_frequency(), CPU_CLOCK);
+
+        // KEY1 is unmapped on DMG, so STOP never switches speed
+        let mut emu = Emulator::from_bytes(test_rom(&[0x10, 0x00], 0x00)).unwrap();
+        emu.bus.key1 = 0x01;
+        emu.step();
+        assert!(!emu.cpu.speed_switch_pending);
+    }
+
+    #[test]
+    fn test_erase_save_data_asks_for_confirmation() {
+        let mut rom = test_rom(&[], 0x00);
+        rom[0x0147] = 0x02; // MBC1+RAM
+        rom[0x0149] = 0x02;
+        rom[0x014D] = Cartridge::calculate_checksum(&rom);
+        let mut emu = Emulator::from_bytes(rom).unwrap();
+        let cart = emu.bus.cart.as_mut().unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+
+        emu.set_erase_confirm_callback(|| false);
+        emu.erase_save_data().unwrap();
+        assert_eq!(emu.bus.cart.as_ref().unwrap().read(0xA000), 0x42);
+
+        emu.set_erase_confirm_callback(|| true);
+        emu.erase_save_data().unwrap();
+        assert_eq!(emu.bus.cart.as_ref().unwrap().read(0xA000), 0x00);
+    }
+
+    #[test]
+    fn test_emulation_speed() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        while emu.total_cycles() < CPU_CLOCK as u64 {
+            emu.step();
+        }
+        assert!(emu.emulation_speed() > 0.0);
+    }
+
+    #[test]
+    fn test_reset_keeps_boot_rom_and_recording() {
+        let mut emu = EmulatorBuilder::new()
+            .rom_bytes(test_rom(&[], 0x00))
+            .boot_rom(vec![0x00; 0x100])
+            .build()
+            .unwrap();
+        emu.bus.start_recording();
+        emu.bus.track_writes = true;
+        emu.step();
+
+        emu.reset();
+        assert!(emu.bus.boot_rom_mapped());
+        assert_eq!(emu.cpu.regs.pc, 0x0000);
+        assert!(emu.bus.track_writes);
+        assert!(!emu.bus.stop_recording().reads.is_empty());
+    }
+
+    #[test]
+    fn test_reset_restarts_cycle_count() {
+        let mut emu = test_emulator(&[0x3C, 0x18, 0xFD]); // INC A; JR -3
+        emu.run_frame();
+        let total = emu.total_cycles();
+        assert_eq!(emu.cycles_since_reset(), total);
+
+        emu.reset();
+        assert_eq!(emu.total_cycles(), total);
+        assert_eq!(emu.cycles_since_reset(), 0);
+        assert_eq!(emu.cpu.regs.pc, 0x0100);
+        assert_eq!(emu.cpu.regs.a, 0x01);
+        assert_eq!(emu.current_frame(), 0);
+        assert_eq!(emu.bus.read(0xFF26), 0xF1);
+
+        emu.step();
+        assert_eq!(emu.cycles_since_reset(), emu.total_cycles() - total);
+    }
+
+    #[test]
+    fn test_timing_verification() {
+        let mut emu = test_emulator(&[0x18, 0xFE]);
+        assert!(!emu.timing_ok());
+        emu.enable_timing_verification(1);
+        for _ in 0..10 {
+            emu.run_frame();
+        }
+
+        assert!((emu.average_frame_cycles() - 70224.0).abs() <= 1.0);
+        assert!(emu.timing_ok());
+    }
+
+    #[test]
+    fn test_timing_verifier_tolerance() {
+        let mut verifier = TimingVerifier::new(100, 2);
+        verifier.record_frame_start(0);
+        assert!(!verifier.is_synchronized());
+        verifier.record_frame_start(102);
+        assert_eq!(verifier.average_frame_cycles(), 102.0);
+        assert!(verifier.is_synchronized());
+        verifier.record_frame_start(205);
+        assert_eq!(verifier.average_frame_cycles(), 102.5);
+        assert!(!verifier.is_synchronized());
+    }
 }
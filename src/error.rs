@@ -0,0 +1,70 @@
+//! Emulator Errors
+//!
+//! This module defines the error type returned by fallible emulator APIs.
+
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Errors produced by the emulator and its host-facing helpers
+#[derive(Debug)]
+pub enum EmulatorError {
+    /// Underlying I/O failure (file access, etc.)
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// Image encoding failure
+    Image(String),
+    /// ROM image is malformed
+    InvalidRom(String),
+    /// ROM image size does not match the header (truncated or overdumped)
+    RomIntegrity { expected_size: usize, actual_size: usize },
+    /// Operation requires a feature unavailable in this build (e.g. file I/O without `std`)
+    NotSupported,
+    /// Emulator configuration is inconsistent or out of range
+    InvalidConfig(String),
+    /// Script text could not be parsed
+    InvalidScript(String),
+    /// A script assertion did not hold
+    ScriptAssertion(String),
+    /// Save state data is corrupt or belongs to a different ROM
+    InvalidSaveState(String),
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            EmulatorError::Io(e) => write!(f, "I/O error: {}", e),
+            EmulatorError::Image(msg) => write!(f, "Image encoding error: {}", msg),
+            EmulatorError::InvalidRom(msg) => write!(f, "Invalid ROM: {}", msg),
+            EmulatorError::RomIntegrity { expected_size, actual_size } => write!(
+                f,
+                "ROM size mismatch: header declares {} bytes, image has {}",
+                expected_size, actual_size
+            ),
+            EmulatorError::NotSupported => write!(f, "Operation not supported in this build"),
+            EmulatorError::InvalidConfig(msg) => write!(f, "Invalid configuration: {}", msg),
+            EmulatorError::InvalidScript(msg) => write!(f, "Invalid script: {}", msg),
+            EmulatorError::ScriptAssertion(msg) => write!(f, "Script assertion failed: {}", msg),
+            EmulatorError::InvalidSaveState(msg) => write!(f, "Invalid save state: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmulatorError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for EmulatorError {
+    fn from(e: io::Error) -> Self {
+        EmulatorError::Io(e)
+    }
+}
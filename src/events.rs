@@ -0,0 +1,100 @@
+//! Hardware Events
+//!
+//! Components push events here instead of keeping their own interrupt
+//! flags; the emulator drains the queue after ticking and turns each event
+//! into an interrupt request.
+
+use crate::cpu::InterruptType;
+use crate::gamepad::Button;
+use alloc::collections::VecDeque;
+
+/// Something a component wants the CPU to know about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub enum HardwareEvent {
+    /// PPU entered VBlank (a frame is complete)
+    VBlank,
+    /// A STAT interrupt source fired
+    LcdStat,
+    /// TIMA overflowed and was reloaded from TMA
+    TimerOverflow,
+    /// A serial transfer finished
+    SerialComplete,
+    /// A button went from released to pressed
+    JoypadPressed(Button),
+}
+
+impl HardwareEvent {
+    /// Interrupt requested by this event
+    pub fn interrupt(self) -> InterruptType {
+        match self {
+            HardwareEvent::VBlank => InterruptType::VBlank,
+            HardwareEvent::LcdStat => InterruptType::LcdStat,
+            HardwareEvent::TimerOverflow => InterruptType::Timer,
+            HardwareEvent::SerialComplete => InterruptType::Serial,
+            HardwareEvent::JoypadPressed(_) => InterruptType::Joypad,
+        }
+    }
+}
+
+/// FIFO of events raised since the last drain
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventQueue {
+    pending: VecDeque<HardwareEvent>,
+}
+
+impl EventQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raise an event
+    pub fn push(&mut self, event: HardwareEvent) {
+        self.pending.push_back(event);
+    }
+
+    /// Take the oldest pending event
+    pub fn pop(&mut self) -> Option<HardwareEvent> {
+        self.pending.pop_front()
+    }
+
+    /// Number of pending events
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Check if no events are pending
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Iterate over pending events without removing them
+    pub fn iter(&self) -> impl Iterator<Item = &HardwareEvent> {
+        self.pending.iter()
+    }
+
+    /// Discard all pending events
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_drain_in_order() {
+        let mut queue = EventQueue::new();
+        queue.push(HardwareEvent::TimerOverflow);
+        queue.push(HardwareEvent::JoypadPressed(Button::A));
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.pop().map(HardwareEvent::interrupt), Some(InterruptType::Timer));
+        assert_eq!(queue.pop().map(HardwareEvent::interrupt), Some(InterruptType::Joypad));
+        assert!(queue.pop().is_none());
+        assert!(queue.is_empty());
+    }
+}
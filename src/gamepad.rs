@@ -11,9 +11,13 @@
 //! - Bit 0: Right or A (0 = pressed)
 
 use crate::common::Byte;
+use crate::events::{EventQueue, HardwareEvent};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
 
 /// Game Boy buttons
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub enum Button {
     A,
     B,
@@ -25,8 +29,26 @@ pub enum Button {
     Down,
 }
 
+impl Button {
+    /// Look up a button by name (`a`, `b`, `select`, `start`, `right`, `left`, `up`, `down`; any case)
+    pub fn from_name(name: &str) -> Option<Button> {
+        const NAMES: [(&str, Button); 8] = [
+            ("a", Button::A),
+            ("b", Button::B),
+            ("select", Button::Select),
+            ("start", Button::Start),
+            ("right", Button::Right),
+            ("left", Button::Left),
+            ("up", Button::Up),
+            ("down", Button::Down),
+        ];
+        NAMES.iter().find(|(n, _)| name.eq_ignore_ascii_case(n)).map(|&(_, button)| button)
+    }
+}
+
 /// Gamepad state
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Gamepad {
     /// Button states (true = pressed)
     pub button_a: bool,
@@ -39,8 +61,14 @@ pub struct Gamepad {
     pub dpad_down: bool,
     /// Selection register (bits 4-5 of JOYP)
     pub selection: Byte,
-    /// Joypad interrupt requested
-    pub interrupt_requested: bool,
+    /// Autofire period in frames for each turbo button
+    turbo_settings: BTreeMap<Button, u32>,
+    /// Frame at which each held turbo button started repeating
+    turbo_counters: BTreeMap<Button, u32>,
+    /// Buttons physically held by the player (before turbo is applied)
+    held_buttons: BTreeSet<Button>,
+    /// Minor-axis deflection needed for an analog stick to press a diagonal
+    diagonal_threshold: i16,
 }
 
 impl Default for Gamepad {
@@ -62,7 +90,10 @@ impl Gamepad {
             dpad_up: false,
             dpad_down: false,
             selection: 0x30, // Both deselected
-            interrupt_requested: false,
+            turbo_settings: BTreeMap::new(),
+            turbo_counters: BTreeMap::new(),
+            held_buttons: BTreeSet::new(),
+            diagonal_threshold: 0,
         }
     }
 
@@ -77,7 +108,10 @@ impl Gamepad {
         self.dpad_up = false;
         self.dpad_down = false;
         self.selection = 0x30;
-        self.interrupt_requested = false;
+        self.turbo_settings.clear();
+        self.turbo_counters.clear();
+        self.held_buttons.clear();
+        self.diagonal_threshold = 0;
     }
 
     /// Read JOYP register (0xFF00)
@@ -112,8 +146,92 @@ impl Gamepad {
         self.selection = value & 0x30;
     }
 
-    /// Set button state
-    pub fn set_button(&mut self, button: Button, pressed: bool) {
+    /// Set button state, pushing `JoypadPressed` on a press
+    pub fn set_button(&mut self, button: Button, pressed: bool, events: &mut EventQueue) {
+        if pressed {
+            self.held_buttons.insert(button);
+        } else {
+            self.held_buttons.remove(&button);
+            self.turbo_counters.remove(&button);
+        }
+        self.apply_button(button, pressed, events);
+    }
+
+    /// Auto-repeat `button` every `frames_repeat` frames while it is held
+    ///
+    /// A rate of 0 disables turbo for the button.
+    pub fn set_turbo(&mut self, button: Button, frames_repeat: u32, events: &mut EventQueue) {
+        if frames_repeat == 0 {
+            self.clear_turbo(button, events);
+        } else {
+            self.turbo_settings.insert(button, frames_repeat);
+        }
+    }
+
+    /// Stop auto-repeating `button`; a held button stays pressed
+    pub fn clear_turbo(&mut self, button: Button, events: &mut EventQueue) {
+        self.turbo_settings.remove(&button);
+        self.turbo_counters.remove(&button);
+        let held = self.held_buttons.contains(&button);
+        self.apply_button(button, held, events);
+    }
+
+    /// Toggle held turbo buttons; called once per frame
+    ///
+    /// Each period starts with the button pressed for the first half
+    /// (rounded up) and released for the rest.
+    pub fn tick_turbos(&mut self, frame: u32, events: &mut EventQueue) {
+        let held: Vec<(Button, u32)> = self
+            .turbo_settings
+            .iter()
+            .filter(|(button, _)| self.held_buttons.contains(button))
+            .map(|(&button, &rate)| (button, rate))
+            .collect();
+
+        for (button, rate) in held {
+            let start = *self.turbo_counters.entry(button).or_insert(frame);
+            let phase = frame.wrapping_sub(start) % rate;
+            self.apply_button(button, phase < rate.div_ceil(2), events);
+        }
+    }
+
+    /// Set the minor-axis deflection below which a stick only presses its
+    /// dominant direction (0 allows any diagonal past the dead zone)
+    pub fn set_diagonal_threshold(&mut self, threshold: i16) {
+        self.diagonal_threshold = threshold;
+    }
+
+    /// Minor-axis deflection needed for a diagonal
+    pub fn diagonal_threshold(&self) -> i16 {
+        self.diagonal_threshold
+    }
+
+    /// Map an analog stick position onto the D-pad
+    ///
+    /// Axes use SDL's convention: negative X is left and negative Y is up.
+    /// An axis within `dead_zone` of the centre releases both of its
+    /// directions.
+    pub fn analog_to_digital(&mut self, x: i16, y: i16, dead_zone: i16, events: &mut EventQueue) {
+        let mut x_active = x > dead_zone || x < -dead_zone;
+        let mut y_active = y > dead_zone || y < -dead_zone;
+
+        // Below the diagonal threshold only the dominant axis counts
+        if x_active && y_active {
+            let (ax, ay) = (x.unsigned_abs(), y.unsigned_abs());
+            if ax.min(ay) < self.diagonal_threshold.unsigned_abs() {
+                x_active = ax >= ay;
+                y_active = !x_active;
+            }
+        }
+
+        self.set_button(Button::Left, x_active && x < 0, events);
+        self.set_button(Button::Right, x_active && x > 0, events);
+        self.set_button(Button::Up, y_active && y < 0, events);
+        self.set_button(Button::Down, y_active && y > 0, events);
+    }
+
+    /// Update the state seen by the game, pushing `JoypadPressed` on press
+    fn apply_button(&mut self, button: Button, pressed: bool, events: &mut EventQueue) {
         let was_pressed = self.is_pressed(button);
         
         match button {
@@ -129,7 +247,7 @@ impl Gamepad {
 
         // Request interrupt on button press (high to low transition)
         if pressed && !was_pressed {
-            self.interrupt_requested = true;
+            events.push(HardwareEvent::JoypadPressed(button));
         }
     }
 
@@ -146,11 +264,6 @@ impl Gamepad {
             Button::Down => self.dpad_down,
         }
     }
-
-    /// Clear interrupt flag
-    pub fn clear_interrupt(&mut self) {
-        self.interrupt_requested = false;
-    }
 }
 
 #[cfg(test)]
@@ -209,17 +322,104 @@ mod tests {
     #[test]
     fn test_button_interrupt() {
         let mut gamepad = Gamepad::new();
+        let mut events = EventQueue::new();
         
-        assert!(!gamepad.interrupt_requested);
-        
-        gamepad.set_button(Button::A, true);
-        assert!(gamepad.interrupt_requested);
+        gamepad.set_button(Button::A, true, &mut events);
+        assert_eq!(events.pop(), Some(HardwareEvent::JoypadPressed(Button::A)));
         
-        gamepad.clear_interrupt();
-        assert!(!gamepad.interrupt_requested);
+        // Holding doesn't trigger another interrupt
+        gamepad.set_button(Button::A, true, &mut events);
+        assert!(events.is_empty());
         
         // Releasing doesn't trigger interrupt
-        gamepad.set_button(Button::A, false);
-        assert!(!gamepad.interrupt_requested);
+        gamepad.set_button(Button::A, false, &mut events);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_turbo_fires_at_rate() {
+        let mut gamepad = Gamepad::new();
+        let mut events = EventQueue::new();
+        gamepad.set_turbo(Button::A, 2, &mut events);
+        gamepad.set_button(Button::A, true, &mut events);
+
+        for frame in 0..6 {
+            gamepad.tick_turbos(frame, &mut events);
+        }
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|&e| e == HardwareEvent::JoypadPressed(Button::A)));
+    }
+
+    #[test]
+    fn test_turbo_release_and_clear() {
+        let mut gamepad = Gamepad::new();
+        let mut events = EventQueue::new();
+        gamepad.set_turbo(Button::B, 3, &mut events);
+
+        // Turbo only affects held buttons
+        gamepad.tick_turbos(0, &mut events);
+        assert!(!gamepad.is_pressed(Button::B));
+
+        gamepad.set_button(Button::B, true, &mut events);
+        gamepad.tick_turbos(1, &mut events);
+        gamepad.tick_turbos(2, &mut events);
+        gamepad.tick_turbos(3, &mut events);
+        assert!(!gamepad.is_pressed(Button::B));
+
+        // Clearing turbo restores the held state
+        gamepad.clear_turbo(Button::B, &mut events);
+        assert!(gamepad.is_pressed(Button::B));
+        gamepad.tick_turbos(4, &mut events);
+        assert!(gamepad.is_pressed(Button::B));
+
+        gamepad.set_button(Button::B, false, &mut events);
+        assert!(!gamepad.is_pressed(Button::B));
+    }
+
+    /// D-pad state as (left, right, up, down)
+    fn dpad(gamepad: &Gamepad) -> (bool, bool, bool, bool) {
+        (gamepad.dpad_left, gamepad.dpad_right, gamepad.dpad_up, gamepad.dpad_down)
+    }
+
+    #[test]
+    fn test_analog_to_digital() {
+        let mut gamepad = Gamepad::new();
+        let mut events = EventQueue::new();
+        let cases = [
+            ((0, 0), (false, false, false, false)),
+            ((8000, -8000), (false, false, false, false)),
+            ((20000, 0), (false, true, false, false)),
+            ((-20000, 0), (true, false, false, false)),
+            ((0, -20000), (false, false, true, false)),
+            ((0, 20000), (false, false, false, true)),
+            ((20000, 20000), (false, true, false, true)),
+            ((-32768, -32768), (true, false, true, false)),
+            ((32767, 100), (false, true, false, false)),
+        ];
+        for ((x, y), expected) in cases {
+            gamepad.analog_to_digital(x, y, 8000, &mut events);
+            assert_eq!(dpad(&gamepad), expected, "({}, {})", x, y);
+        }
+
+        // Returning to the centre releases every direction
+        gamepad.analog_to_digital(0, 0, 8000, &mut events);
+        assert_eq!(dpad(&gamepad), (false, false, false, false));
+    }
+
+    #[test]
+    fn test_analog_diagonal_threshold() {
+        let mut gamepad = Gamepad::new();
+        let mut events = EventQueue::new();
+        gamepad.set_diagonal_threshold(16000);
+
+        // Both axes past the dead zone but the minor one below the threshold
+        gamepad.analog_to_digital(30000, -10000, 8000, &mut events);
+        assert_eq!(dpad(&gamepad), (false, true, false, false));
+        gamepad.analog_to_digital(-10000, 30000, 8000, &mut events);
+        assert_eq!(dpad(&gamepad), (false, false, false, true));
+
+        gamepad.analog_to_digital(-20000, -20000, 8000, &mut events);
+        assert_eq!(dpad(&gamepad), (true, false, true, false));
+        assert!(events.iter().any(|&e| e == HardwareEvent::JoypadPressed(Button::Up)));
     }
 }
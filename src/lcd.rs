@@ -17,9 +17,11 @@
 //! - WX (0xFF4B): Window X Position
 
 use crate::common::{bit, bit_set, Byte};
+use crate::events::{EventQueue, HardwareEvent};
 
 /// PPU modes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub enum PpuMode {
     HBlank = 0,
     VBlank = 1,
@@ -41,6 +43,7 @@ impl From<u8> for PpuMode {
 
 /// LCD Controller
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lcd {
     /// LCDC - LCD Control (0xFF40)
     pub lcdc: Byte,
@@ -64,8 +67,6 @@ pub struct Lcd {
     pub wy: Byte,
     /// WX - Window X Position (0xFF4B)
     pub wx: Byte,
-    /// STAT interrupt requested
-    pub stat_interrupt: bool,
 }
 
 impl Default for Lcd {
@@ -89,7 +90,6 @@ impl Lcd {
             obp1: 0xFF,
             wy: 0,
             wx: 0,
-            stat_interrupt: false,
         }
     }
 
@@ -106,7 +106,6 @@ impl Lcd {
         self.obp1 = 0xFF;
         self.wy = 0;
         self.wx = 0;
-        self.stat_interrupt = false;
     }
 
     /// Read LCD register
@@ -127,10 +126,17 @@ impl Lcd {
         }
     }
 
-    /// Write LCD register
-    pub fn write(&mut self, address: u16, value: Byte) {
+    /// Write LCD register (a LYC write can raise a STAT event)
+    pub fn write(&mut self, address: u16, value: Byte, events: &mut EventQueue) {
         match address {
-            0xFF40 => self.lcdc = value,
+            0xFF40 => {
+                self.lcdc = value;
+                // Turning the LCD off resets LY and leaves STAT in mode 0
+                if !self.lcd_enabled() {
+                    self.ly = 0;
+                    self.stat &= !0x03;
+                }
+            }
             0xFF41 => {
                 // Lower 3 bits are read-only (mode and LYC flag)
                 self.stat = (self.stat & 0x07) | (value & 0xF8);
@@ -140,7 +146,7 @@ impl Lcd {
             0xFF44 => {} // LY is read-only
             0xFF45 => {
                 self.lyc = value;
-                self.check_lyc();
+                self.check_lyc(events);
             }
             0xFF47 => self.bgp = value,
             0xFF48 => self.obp0 = value,
@@ -205,9 +211,9 @@ impl Lcd {
     }
 
     /// Set current PPU mode (bits 0-1)
-    pub fn set_mode(&mut self, mode: PpuMode) {
+    pub fn set_mode(&mut self, mode: PpuMode, events: &mut EventQueue) {
         self.stat = (self.stat & 0xFC) | (mode as u8);
-        self.check_stat_interrupt();
+        self.check_stat_interrupt(events);
     }
 
     /// LYC=LY Coincidence Flag (bit 2)
@@ -243,32 +249,32 @@ impl Lcd {
     // ========== LY/LYC Handling ==========
 
     /// Set current scanline (LY)
-    pub fn set_ly(&mut self, value: Byte) {
+    pub fn set_ly(&mut self, value: Byte, events: &mut EventQueue) {
         self.ly = value;
-        self.check_lyc();
+        self.check_lyc(events);
     }
 
     /// Increment LY and check for LYC match
-    pub fn inc_ly(&mut self) {
+    pub fn inc_ly(&mut self, events: &mut EventQueue) {
         self.ly = self.ly.wrapping_add(1);
         if self.ly > 153 {
             self.ly = 0;
         }
-        self.check_lyc();
+        self.check_lyc(events);
     }
 
     /// Check LY=LYC coincidence and request interrupt if enabled
-    fn check_lyc(&mut self) {
+    fn check_lyc(&mut self, events: &mut EventQueue) {
         let coincidence = self.ly == self.lyc;
         self.set_lyc_flag(coincidence);
         
         if coincidence && self.lyc_int_enabled() {
-            self.stat_interrupt = true;
+            events.push(HardwareEvent::LcdStat);
         }
     }
 
     /// Check if STAT interrupt should be requested based on current mode
-    fn check_stat_interrupt(&mut self) {
+    fn check_stat_interrupt(&mut self, events: &mut EventQueue) {
         let should_interrupt = match self.mode() {
             PpuMode::HBlank => self.hblank_int_enabled(),
             PpuMode::VBlank => self.vblank_int_enabled(),
@@ -277,15 +283,10 @@ impl Lcd {
         };
         
         if should_interrupt {
-            self.stat_interrupt = true;
+            events.push(HardwareEvent::LcdStat);
         }
     }
 
-    /// Clear STAT interrupt flag
-    pub fn clear_stat_interrupt(&mut self) {
-        self.stat_interrupt = false;
-    }
-
     // ========== Palette Helpers ==========
 
     /// Get color from background palette
@@ -345,30 +346,35 @@ mod tests {
     #[test]
     fn test_stat_mode() {
         let mut lcd = Lcd::new();
+        let mut events = EventQueue::new();
         
-        lcd.set_mode(PpuMode::OamScan);
+        lcd.set_mode(PpuMode::OamScan, &mut events);
         assert_eq!(lcd.mode(), PpuMode::OamScan);
         
-        lcd.set_mode(PpuMode::Transfer);
+        lcd.set_mode(PpuMode::Transfer, &mut events);
         assert_eq!(lcd.mode(), PpuMode::Transfer);
         
-        lcd.set_mode(PpuMode::HBlank);
+        lcd.set_mode(PpuMode::HBlank, &mut events);
         assert_eq!(lcd.mode(), PpuMode::HBlank);
         
-        lcd.set_mode(PpuMode::VBlank);
+        lcd.set_mode(PpuMode::VBlank, &mut events);
         assert_eq!(lcd.mode(), PpuMode::VBlank);
+
+        // No STAT sources are enabled
+        assert!(events.is_empty());
     }
 
     #[test]
     fn test_lyc_coincidence() {
         let mut lcd = Lcd::new();
         lcd.stat = 0x40; // Enable LYC interrupt
+        let mut events = EventQueue::new();
         
         lcd.lyc = 10;
-        lcd.set_ly(10);
+        lcd.set_ly(10, &mut events);
         
         assert!(lcd.lyc_flag());
-        assert!(lcd.stat_interrupt);
+        assert_eq!(events.pop(), Some(HardwareEvent::LcdStat));
     }
 
     #[test]
@@ -376,7 +382,7 @@ mod tests {
         let mut lcd = Lcd::new();
         lcd.ly = 50;
         
-        lcd.write(0xFF44, 0x00); // Try to write to LY
+        lcd.write(0xFF44, 0x00, &mut EventQueue::new()); // Try to write to LY
         
         assert_eq!(lcd.ly, 50); // Should be unchanged
     }
@@ -2,19 +2,59 @@
 //!
 //! This library provides a complete Game Boy emulator implementation in Rust.
 //! It emulates the Sharp LR35902 CPU, PPU, APU, and all other hardware components.
+//!
+//! The `std` feature (enabled by default) provides file I/O; `sdl` adds the SDL2 UI.
+//! With `--no-default-features --features alloc` the core builds as `no_std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "alloc"))]
+compile_error!("gbemu requires the `alloc` feature when `std` is disabled");
+
+extern crate alloc;
 
 pub mod common;
+pub mod error;
 pub mod emu;
 pub mod cpu;
 pub mod bus;
 pub mod cart;
 pub mod ppu;
 pub mod apu;
+#[cfg(feature = "std")]
+pub mod audio;
 pub mod lcd;
 pub mod timer;
 pub mod dma;
 pub mod ram;
+pub mod memory_map;
 pub mod gamepad;
+pub mod serial;
+pub mod printer;
+pub mod profiler;
+#[cfg(feature = "std")]
+pub mod script;
+#[cfg(feature = "std")]
+pub mod plugin;
 pub mod interrupts;
+pub mod events;
 pub mod stack;
+#[cfg(feature = "sdl")]
 pub mod ui;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+#[cfg(feature = "png-output")]
+pub mod screenshot;
+
+#[cfg(feature = "gif-recording")]
+pub mod recording;
+
+#[cfg(feature = "rom-database")]
+pub mod rom_database;
+
+#[cfg(feature = "save-state")]
+pub mod savestate;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -3,7 +3,8 @@
 //! This is the main entry point for the Game Boy emulator.
 //! It handles command line arguments and starts the emulation.
 
-use gbemu::emu::Emulator;
+use gbemu::emu::EmulatorBuilder;
+use gbemu::error::EmulatorError;
 use gbemu::ui::Ui;
 use std::env;
 use std::process;
@@ -18,8 +19,11 @@ fn main() {
 
     let rom_path = &args[1];
 
+    #[cfg(feature = "rom-database")]
+    print_rom_database_entry(rom_path);
+
     // Create emulator
-    let mut emulator = match Emulator::new(rom_path) {
+    let mut emulator = match EmulatorBuilder::new().rom(rom_path.as_str()).build() {
         Ok(emu) => emu,
         Err(e) => {
             eprintln!("Failed to initialize emulator: {}", e);
@@ -27,8 +31,14 @@ fn main() {
         }
     };
 
+    // Truncated or padded ROMs still load, but are worth a warning
+    let integrity = emulator.bus.cart.as_ref().map(|cart| cart.verify_rom_integrity());
+    if let Some(Err(e @ EmulatorError::RomIntegrity { .. })) = integrity {
+        eprintln!("Warning: {}", e);
+    }
+
     // Create UI and run
-    let mut ui = match Ui::new() {
+    let mut ui = match Ui::new(emulator.apu.sample_rate()) {
         Ok(ui) => ui,
         Err(e) => {
             eprintln!("Failed to initialize UI: {}", e);
@@ -46,3 +56,27 @@ fn main() {
         process::exit(1);
     }
 }
+
+/// Report the ROM's cached metadata from `~/.config/rgbe/romdb.json`, if present
+#[cfg(feature = "rom-database")]
+fn print_rom_database_entry(rom_path: &str) {
+    use gbemu::rom_database::RomDatabase;
+    use std::path::PathBuf;
+
+    let Some(home) = env::var_os("HOME") else { return };
+    let db_path = PathBuf::from(home).join(".config/rgbe/romdb.json");
+    if !db_path.exists() {
+        return;
+    }
+
+    let db = match RomDatabase::load(&db_path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to load ROM database {}: {}", db_path.display(), e);
+            return;
+        }
+    };
+    if let Some(info) = std::fs::read(rom_path).ok().and_then(|rom| db.lookup_rom(&rom).cloned()) {
+        println!("ROM database: {} ({})", info.title, info.cart_type_name);
+    }
+}
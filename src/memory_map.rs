@@ -0,0 +1,96 @@
+//! Memory Map
+//!
+//! This module describes how the bus routes each address, for debugging
+//! address decoding and bank switching without touching memory.
+
+use crate::bus::Bus;
+use crate::common::Word;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Hardware region an address resolves to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// Fixed cartridge ROM bank (0x0000-0x3FFF)
+    CartRom0,
+    /// Switchable cartridge ROM bank (0x4000-0x7FFF)
+    CartRomN(u8),
+    /// Video RAM with its bank (0x8000-0x9FFF)
+    Vram(u8),
+    /// Cartridge RAM with its bank (0xA000-0xBFFF)
+    CartRam(u8),
+    /// Fixed work RAM bank (0xC000-0xCFFF)
+    Wram0,
+    /// Switchable work RAM bank (0xD000-0xDFFF)
+    WramN(u8),
+    /// Mirror of work RAM (0xE000-0xFDFF)
+    EchoRam,
+    /// Object attribute memory (0xFE00-0xFE9F)
+    Oam,
+    /// Unusable area (0xFEA0-0xFEFF)
+    Unusable,
+    /// I/O register, by offset from 0xFF00 (0xFF00-0xFF7F)
+    IoRegister(u8),
+    /// High RAM (0xFF80-0xFFFE)
+    Hram,
+    /// Interrupt enable register (0xFFFF)
+    IeRegister,
+}
+
+impl fmt::Display for MemoryRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryRegion::CartRom0 => write!(f, "ROM bank 0"),
+            MemoryRegion::CartRomN(bank) => write!(f, "ROM bank {}", bank),
+            MemoryRegion::Vram(bank) => write!(f, "VRAM bank {}", bank),
+            MemoryRegion::CartRam(bank) => write!(f, "Cartridge RAM bank {}", bank),
+            MemoryRegion::Wram0 => write!(f, "WRAM bank 0"),
+            MemoryRegion::WramN(bank) => write!(f, "WRAM bank {}", bank),
+            MemoryRegion::EchoRam => write!(f, "Echo RAM"),
+            MemoryRegion::Oam => write!(f, "OAM"),
+            MemoryRegion::Unusable => write!(f, "Unusable"),
+            MemoryRegion::IoRegister(offset) => write!(f, "I/O register FF{:02X}", offset),
+            MemoryRegion::Hram => write!(f, "HRAM"),
+            MemoryRegion::IeRegister => write!(f, "IE register"),
+        }
+    }
+}
+
+/// List every address range with the region it currently resolves to
+///
+/// Ranges are inclusive, contiguous and cover 0x0000-0xFFFF in order.
+pub fn full_map(bus: &Bus) -> Vec<(Word, Word, MemoryRegion)> {
+    let mut map: Vec<(Word, Word, MemoryRegion)> = Vec::new();
+    for address in 0..=0xFFFF {
+        let region = bus.resolve_region(address);
+        match map.last_mut() {
+            Some((_, end, last)) if *last == region => *end = address,
+            _ => map.push((address, address, region)),
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_map_covers_address_space() {
+        let bus = Bus::new();
+        let map = full_map(&bus);
+
+        assert_eq!(map[0], (0x0000, 0x3FFF, MemoryRegion::CartRom0));
+        assert_eq!(map.last(), Some(&(0xFFFF, 0xFFFF, MemoryRegion::IeRegister)));
+
+        let mut next = 0u32;
+        for &(start, end, _) in &map {
+            assert_eq!(start as u32, next);
+            assert!(end >= start);
+            next = end as u32 + 1;
+        }
+        assert_eq!(next, 0x10000);
+        // 11 fixed ranges plus one entry per I/O register
+        assert_eq!(map.len(), 11 + 0x80);
+    }
+}
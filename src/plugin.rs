@@ -0,0 +1,22 @@
+//! Emulator Plugins
+//!
+//! This module defines the extension trait used by third-party crates to
+//! observe emulation (achievements, cheat databases, stream overlays, ...).
+
+use crate::cpu::Cpu;
+
+/// Hooks invoked by the emulator during execution
+pub trait EmulatorPlugin {
+    /// Called after each `run_frame` with the video buffer and the audio
+    /// samples generated since the buffer was last drained
+    fn on_frame(&mut self, video: &[u32], audio: &[i16]);
+
+    /// Called after each instruction fetch with its address and opcode
+    fn on_instruction(&mut self, pc: u16, opcode: u8, cpu: &Cpu);
+
+    /// Called for every CPU write through the memory bus
+    fn on_memory_write(&mut self, address: u16, value: u8);
+
+    /// Unique plugin name (used by `Emulator::remove_plugin`)
+    fn name(&self) -> &str;
+}
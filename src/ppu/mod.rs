@@ -4,19 +4,103 @@
 //! The PPU is responsible for rendering graphics to the screen.
 
 pub mod modes;
+pub mod palette;
 pub mod pipeline;
 
 use crate::common::{bit, Byte, Word};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use crate::events::{EventQueue, HardwareEvent};
 use crate::lcd::{Lcd, PpuMode};
+use palette::{rgb555_to_argb, CgbPalettes};
+use pipeline::PixelFifoContext;
 
 /// Screen dimensions
 pub const SCREEN_WIDTH: usize = 160;
 pub const SCREEN_HEIGHT: usize = 144;
 pub const LINES_PER_FRAME: u8 = 154;
 pub const TICKS_PER_LINE: u32 = 456;
+/// T-cycle within line 153 at which LY already reads 0
+const LY_153_RESET_TICKS: u32 = 4;
+/// T-cycles missing from line 0 after the LCD is switched on
+const LCD_ON_SHORT_LINE_TICKS: u32 = 4;
+
+/// Frame export failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PpuError(pub String);
+
+impl fmt::Display for PpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PPU error: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PpuError {}
+
+/// Size of one VRAM bank
+pub const VRAM_BANK_SIZE: usize = 0x2000;
+/// VRAM banks (bank 1 exists on CGB only; DMG never selects it)
+pub const VRAM_BANKS: usize = 2;
+
+/// CGB BG map attribute bits 0-2: BG palette number
+const ATTR_PALETTE_MASK: u8 = 0x07;
+/// CGB BG map attribute bit 3: tile data comes from VRAM bank 1
+const ATTR_VRAM_BANK: u8 = 3;
+/// CGB BG map attribute bit 5: flip the tile horizontally
+const ATTR_X_FLIP: u8 = 5;
+/// CGB BG map attribute bit 6: flip the tile vertically
+const ATTR_Y_FLIP: u8 = 6;
+/// CGB BG map attribute bit 7: BG colors 1-3 are drawn over sprites
+const ATTR_PRIORITY: u8 = 7;
+
+/// `pixel_priority_buffer` bits 0-1: layer that produced the pixel
+pub const PRIORITY_SOURCE_MASK: u8 = 0x03;
+/// `pixel_priority_buffer` bit 2: a sprite pixel here has its BG priority flag set
+pub const PRIORITY_SPRITE_BG_FLAG: u8 = 0x04;
+/// `pixel_priority_buffer` bit 3: the BG tile attribute asks to be drawn over sprites (CGB)
+pub const PRIORITY_BG_OVER_OBJ: u8 = 0x08;
+
+/// Layer that produced a pixel of the last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelSource {
+    /// Background (also used when BG and window are disabled)
+    Background = 0,
+    /// Window
+    Window = 1,
+    /// Sprite
+    Sprite = 2,
+}
+
+impl PixelSource {
+    /// Decode the source bits of a `pixel_priority_buffer` entry
+    pub fn from_priority(entry: u8) -> PixelSource {
+        match entry & PRIORITY_SOURCE_MASK {
+            1 => PixelSource::Window,
+            2 => PixelSource::Sprite,
+            _ => PixelSource::Background,
+        }
+    }
+}
+
+/// Size of the BMP file header plus the BITMAPINFOHEADER
+const BMP_HEADER_SIZE: usize = 14 + 40;
+
+/// Tile sheet layout: 384 tiles, 16 per row
+pub const TILESET_COLUMNS: usize = 16;
+pub const TILESET_ROWS: usize = 24;
+/// Tile sheet dimensions in pixels
+pub const TILESET_WIDTH: usize = TILESET_COLUMNS * 8;
+pub const TILESET_HEIGHT: usize = TILESET_ROWS * 8;
+/// Tile sheet dimensions with 1-pixel grid lines between tiles
+pub const TILESET_GRID_WIDTH: usize = TILESET_COLUMNS * 9 - 1;
+pub const TILESET_GRID_HEIGHT: usize = TILESET_ROWS * 9 - 1;
 
 /// OAM Entry (sprite attributes)
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct OamEntry {
     /// Y position (minus 16)
@@ -61,27 +145,213 @@ impl OamEntry {
     }
 }
 
+/// Sink for completed frames (`SCREEN_WIDTH * SCREEN_HEIGHT` ARGB8888 pixels)
+pub trait VideoOutput {
+    /// Consume a finished frame
+    fn present_frame(&mut self, frame: &[u32]);
+}
+
+/// Output colors (ARGB8888) for the four DMG shades
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct DmgPalette {
+    /// Colors for shades 0 (lightest) to 3 (darkest)
+    pub colors: [u32; 4],
+}
+
+impl DmgPalette {
+    /// Neutral grayscale palette (no green tint)
+    pub const GRAYSCALE: DmgPalette = DmgPalette {
+        colors: [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000],
+    };
+
+    /// CGB boot ROM compatibility palette for the background of DMG games
+    pub const CGB_COMPAT_BG: DmgPalette = DmgPalette {
+        colors: [0xFFFFFFFF, 0xFF7BFF31, 0xFF0063C5, 0xFF000000],
+    };
+
+    /// CGB boot ROM compatibility palette for the objects of DMG games
+    pub const CGB_COMPAT_OBJ: DmgPalette = DmgPalette {
+        colors: [0xFFFFFFFF, 0xFFFF8484, 0xFF943A3A, 0xFF000000],
+    };
+
+    /// Get the ARGB color for a shade (0-3)
+    #[inline]
+    pub fn argb(&self, shade: u8) -> u32 {
+        self.colors[(shade & 0x03) as usize]
+    }
+
+    /// Look up a built-in palette by name (`grayscale`, `cgb-compat-bg`, `cgb-compat-obj`)
+    pub fn from_name(name: &str) -> Option<DmgPalette> {
+        match name {
+            "grayscale" => Some(Self::GRAYSCALE),
+            "cgb-compat-bg" => Some(Self::CGB_COMPAT_BG),
+            "cgb-compat-obj" => Some(Self::CGB_COMPAT_OBJ),
+            _ => None,
+        }
+    }
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        Self::GRAYSCALE
+    }
+}
+
+/// Result of comparing two frames with `diff_frames`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameDiff {
+    /// Pixels whose R, G or B channel differs
+    pub changed_pixels: usize,
+    /// Pixels compared (the longer of the two buffers)
+    pub total_pixels: usize,
+    /// Largest difference seen in any single channel
+    pub max_channel_delta: u8,
+    /// Root mean square error over the R, G and B channels
+    pub rms_error: f32,
+}
+
+impl FrameDiff {
+    /// Check if the frames match exactly
+    pub fn is_identical(&self) -> bool {
+        self.changed_pixels == 0
+    }
+
+    /// Check if at most `threshold_pixels` pixels differ
+    pub fn similar_enough(&self, threshold_pixels: usize) -> bool {
+        self.changed_pixels <= threshold_pixels
+    }
+}
+
+/// Compare two ARGB8888 frames channel by channel (alpha is ignored)
+///
+/// Pixels present in only one buffer count as changed with the maximum delta.
+pub fn diff_frames(a: &[u32], b: &[u32]) -> FrameDiff {
+    let total_pixels = a.len().max(b.len());
+    let missing = total_pixels - a.len().min(b.len());
+    let mut changed_pixels = missing;
+    let mut max_channel_delta = if missing > 0 { 0xFF } else { 0 };
+    let mut squared_error = missing as f64 * 3.0 * 255.0 * 255.0;
+
+    for (&pa, &pb) in a.iter().zip(b) {
+        let mut changed = false;
+        for shift in [16, 8, 0] {
+            let delta = ((pa >> shift) as u8).abs_diff((pb >> shift) as u8);
+            changed |= delta > 0;
+            max_channel_delta = max_channel_delta.max(delta);
+            squared_error += (delta as f64) * (delta as f64);
+        }
+        changed_pixels += changed as usize;
+    }
+
+    let rms_error = if total_pixels == 0 {
+        0.0
+    } else {
+        sqrt(squared_error / (total_pixels * 3) as f64) as f32
+    };
+    FrameDiff { changed_pixels, total_pixels, max_channel_delta, rms_error }
+}
+
+/// Square root by Newton's method (`f64::sqrt` needs `std`)
+fn sqrt(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut guess = if x > 1.0 { x / 2.0 } else { 1.0 };
+    for _ in 0..64 {
+        let next = (guess + x / guess) / 2.0;
+        if next >= guess {
+            break;
+        }
+        guess = next;
+    }
+    guess
+}
+
+/// Progress of the mode 2 OAM scan
+///
+/// One entry is handled every 2 T-cycles: the entry is read on the even
+/// cycle and checked against LY on the odd one, so entry `n` is evaluated
+/// during cycles `2n` and `2n + 1` of the line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct OamScanState {
+    /// OAM entry being scanned (0-39)
+    pub entry_index: u8,
+    /// T-cycles of the scan completed so far (0-80)
+    pub cycle_in_scan: u8,
+    /// Entry read on the last even cycle
+    pub entry: OamEntry,
+}
+
+/// T-cycles spent in mode 2
+const OAM_SCAN_CYCLES: u32 = 80;
+/// Sprites selected per line
+const MAX_LINE_SPRITES: usize = 10;
+/// Shortest mode 3: no scroll, sprites or window
+pub const MODE3_BASE_CYCLES: u32 = 172;
+/// Extra mode 3 T-cycles when the fetcher restarts for the window
+const WINDOW_PENALTY: u32 = 6;
+
+/// Mode 3 T-cycles lost fetching one sprite
+///
+/// The fetcher stalls until the current background tile is done, so the
+/// cost depends on where the sprite's left edge falls within a tile after
+/// fine scrolling: 6 cycles when aligned, down to 1 late in the tile.
+pub fn compute_sprite_fifo_penalty(sprite_x: u8, scx: u8) -> u32 {
+    let offset = sprite_x.wrapping_add(scx % 8) % 8;
+    6 - (offset as u32).min(5)
+}
+
 /// Pixel Processing Unit
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ppu {
-    /// Video RAM (8KB)
-    pub vram: [Byte; 0x2000],
+    /// Video RAM: bank 0 at 0x0000-0x1FFF, CGB bank 1 (tile data and BG
+    /// map attributes) at 0x2000-0x3FFF
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
+    pub vram: [Byte; VRAM_BANK_SIZE * VRAM_BANKS],
+    /// VRAM bank seen by `vram_read` and `vram_write` (VBK)
+    pub vram_bank: u8,
     /// Object Attribute Memory (40 sprites * 4 bytes)
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
     pub oam: [Byte; 160],
     /// Video buffer (160x144 pixels, ARGB format)
     pub video_buffer: Vec<u32>,
+    /// Per-pixel source and priority bits for the video buffer (see `PRIORITY_*`)
+    pub pixel_priority_buffer: Vec<u8>,
     /// Current frame number
     pub current_frame: u32,
     /// Ticks within current line
     pub line_ticks: u32,
     /// Window internal line counter
     pub window_line: u8,
-    /// VBlank interrupt requested
-    pub vblank_interrupt: bool,
     /// Sprites on current line (max 10)
     pub line_sprites: Vec<OamEntry>,
     /// Number of sprites on current line
     pub sprite_count: usize,
+    /// Mode 2 scan progress on the current line
+    pub oam_scan: OamScanState,
+    /// Length of the last completed mode 3 in T-cycles
+    pub mode3_duration: u32,
+    /// Mode 3 pixel pipeline
+    pub fifo: PixelFifoContext,
+    /// LCD was enabled at the last tick (to catch LCDC bit 7 edges)
+    pub lcd_on: bool,
+    /// Line 0 after the LCD is switched on: no OAM scan, mode 0 until mode 3
+    pub lcd_startup_line: bool,
+    /// The first frame after the LCD is switched on is not drawn
+    pub skip_frame: bool,
+    /// Output colors for background/window shades
+    pub bg_palette: DmgPalette,
+    /// Output colors for OBP0 sprite shades
+    pub obj0_palette: DmgPalette,
+    /// Output colors for OBP1 sprite shades
+    pub obj1_palette: DmgPalette,
+    /// Resolve colors through the CGB palette RAM instead of BGP/OBP0/OBP1
+    pub cgb_mode: bool,
+    /// CGB background and object palette RAM (synced from the bus)
+    pub cgb_palettes: CgbPalettes,
 }
 
 impl Default for Ppu {
@@ -94,45 +364,68 @@ impl Ppu {
     /// Create a new PPU
     pub fn new() -> Self {
         Self {
-            vram: [0; 0x2000],
+            vram: [0; VRAM_BANK_SIZE * VRAM_BANKS],
+            vram_bank: 0,
             oam: [0; 160],
             video_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            pixel_priority_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
             current_frame: 0,
             line_ticks: 0,
             window_line: 0,
-            vblank_interrupt: false,
-            line_sprites: Vec::with_capacity(10),
+            line_sprites: Vec::with_capacity(MAX_LINE_SPRITES),
             sprite_count: 0,
+            oam_scan: OamScanState::default(),
+            mode3_duration: MODE3_BASE_CYCLES,
+            fifo: PixelFifoContext::default(),
+            lcd_on: true,
+            lcd_startup_line: false,
+            skip_frame: false,
+            bg_palette: DmgPalette::GRAYSCALE,
+            obj0_palette: DmgPalette::GRAYSCALE,
+            obj1_palette: DmgPalette::GRAYSCALE,
+            cgb_mode: false,
+            cgb_palettes: CgbPalettes::new(),
         }
     }
 
     /// Initialize PPU
     pub fn init(&mut self) {
         self.vram.fill(0);
+        self.vram_bank = 0;
         self.oam.fill(0);
         self.video_buffer.fill(0);
+        self.pixel_priority_buffer.fill(0);
         self.current_frame = 0;
         self.line_ticks = 0;
         self.window_line = 0;
-        self.vblank_interrupt = false;
         self.line_sprites.clear();
         self.sprite_count = 0;
+        self.oam_scan = OamScanState::default();
+        self.mode3_duration = MODE3_BASE_CYCLES;
+        self.fifo = PixelFifoContext::default();
+        self.lcd_on = true;
+        self.lcd_startup_line = false;
+        self.skip_frame = false;
+        self.cgb_palettes = CgbPalettes::new();
+    }
+
+    /// Offset into `vram` of `address` in the selected bank, if it is in VRAM
+    fn vram_offset(&self, address: Word) -> Option<usize> {
+        let offset = address.checked_sub(0x8000)? as usize;
+        (offset < VRAM_BANK_SIZE).then(|| (self.vram_bank & 0x01) as usize * VRAM_BANK_SIZE + offset)
     }
 
-    /// Read from VRAM
+    /// Read from VRAM in the selected bank
     pub fn vram_read(&self, address: Word) -> Byte {
-        let offset = (address - 0x8000) as usize;
-        if offset < self.vram.len() {
-            self.vram[offset]
-        } else {
-            0xFF
+        match self.vram_offset(address) {
+            Some(offset) => self.vram[offset],
+            None => 0xFF,
         }
     }
 
-    /// Write to VRAM
+    /// Write to VRAM in the selected bank
     pub fn vram_write(&mut self, address: Word, value: Byte) {
-        let offset = (address - 0x8000) as usize;
-        if offset < self.vram.len() {
+        if let Some(offset) = self.vram_offset(address) {
             self.vram[offset] = value;
         }
     }
@@ -169,193 +462,360 @@ impl Ppu {
         }
     }
 
-    /// Tick the PPU by one T-cycle
-    pub fn tick(&mut self, lcd: &mut Lcd) {
+    /// Tick the PPU by one T-cycle, pushing VBlank and STAT events
+    pub fn tick(&mut self, lcd: &mut Lcd, events: &mut EventQueue) {
         if !lcd.lcd_enabled() {
+            if self.lcd_on {
+                self.lcd_disabled(lcd);
+            }
             return;
         }
+        if !self.lcd_on {
+            self.lcd_enabled(lcd, events);
+        }
 
         self.line_ticks += 1;
 
         match lcd.mode() {
-            PpuMode::OamScan => self.mode_oam_scan(lcd),
-            PpuMode::Transfer => self.mode_transfer(lcd),
-            PpuMode::HBlank => self.mode_hblank(lcd),
-            PpuMode::VBlank => self.mode_vblank(lcd),
+            PpuMode::OamScan => self.mode_oam_scan(lcd, events),
+            PpuMode::Transfer => self.mode_transfer(lcd, events),
+            PpuMode::HBlank => self.mode_hblank(lcd, events),
+            PpuMode::VBlank => self.mode_vblank(lcd, events),
         }
     }
 
-    /// OAM Scan mode (mode 2) - 80 T-cycles
-    fn mode_oam_scan(&mut self, lcd: &mut Lcd) {
-        if self.line_ticks >= 80 {
-            // Scan OAM for sprites on this line
-            self.scan_oam(lcd);
-            lcd.set_mode(PpuMode::Transfer);
+    /// LCDC bit 7 was cleared: stop at LY 0 in mode 0 with a blank screen
+    fn lcd_disabled(&mut self, lcd: &mut Lcd) {
+        self.lcd_on = false;
+        self.line_ticks = 0;
+        self.window_line = 0;
+        self.line_sprites.clear();
+        self.sprite_count = 0;
+        self.oam_scan = OamScanState::default();
+        self.fifo = PixelFifoContext::default();
+        lcd.ly = 0;
+        lcd.stat &= !0x03;
+
+        let blank = self.blank_argb();
+        self.video_buffer.fill(blank);
+        self.pixel_priority_buffer.fill(0);
+    }
+
+    /// LCDC bit 7 was set: restart from line 0
+    ///
+    /// The first line skips the OAM scan (STAT reads mode 0 until mode 3)
+    /// and is `LCD_ON_SHORT_LINE_TICKS` short. The frame is not drawn, so
+    /// the screen stays blank until the next one.
+    fn lcd_enabled(&mut self, lcd: &mut Lcd, events: &mut EventQueue) {
+        self.lcd_on = true;
+        self.lcd_startup_line = true;
+        self.skip_frame = true;
+        self.line_ticks = LCD_ON_SHORT_LINE_TICKS;
+        lcd.stat &= !0x03;
+        lcd.set_ly(0, events);
+    }
+
+    /// Color shown while the LCD is off (color 0, white)
+    fn blank_argb(&self) -> u32 {
+        if self.cgb_mode {
+            rgb555_to_argb(0x7FFF)
+        } else {
+            self.bg_palette.argb(0)
         }
     }
 
-    /// Pixel Transfer mode (mode 3) - variable length
-    fn mode_transfer(&mut self, lcd: &mut Lcd) {
-        // Simplified: assume fixed 172 T-cycles for transfer
-        if self.line_ticks >= 80 + 172 {
-            // Render the scanline
-            self.render_scanline(lcd);
-            lcd.set_mode(PpuMode::HBlank);
+    /// OAM Scan mode (mode 2) - 80 T-cycles, one scan step per T-cycle
+    fn mode_oam_scan(&mut self, lcd: &mut Lcd, events: &mut EventQueue) {
+        let cycle = self.line_ticks - 1;
+        if cycle == 0 {
+            self.line_sprites.clear();
+        }
+
+        if cycle < OAM_SCAN_CYCLES {
+            let index = (cycle / 2) as usize;
+            if cycle.is_multiple_of(2) {
+                self.oam_scan.entry = self.get_oam_entry(index);
+            } else if self.line_sprites.len() < MAX_LINE_SPRITES
+                && Self::sprite_on_line(&self.oam_scan.entry, lcd, lcd.ly)
+            {
+                self.line_sprites.push(self.oam_scan.entry);
+            }
+            self.oam_scan.entry_index = index as u8;
+            self.oam_scan.cycle_in_scan = (cycle + 1) as u8;
+        }
+
+        if self.line_ticks >= OAM_SCAN_CYCLES {
+            // Lower X draws on top; the stable sort keeps OAM order for ties
+            self.line_sprites.sort_by_key(|sprite| sprite.x);
+            self.sprite_count = self.line_sprites.len();
+            self.oam_scan = OamScanState::default();
+            self.start_transfer(lcd);
+            lcd.set_mode(PpuMode::Transfer, events);
+        }
+    }
+
+    /// Encode the video buffer as a 160x144 8-bit RGBA PNG
+    #[cfg(feature = "png-output")]
+    pub fn framebuffer_as_png(&self) -> Result<Vec<u8>, PpuError> {
+        crate::screenshot::encode_png(&self.video_buffer, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+            .map_err(|e| PpuError(e.to_string()))
+    }
+
+    /// Encode the video buffer as a 160x144 24-bit BMP
+    ///
+    /// Rows are stored bottom-up in BGR order; 160 * 3 bytes per row needs
+    /// no padding. Alpha is dropped.
+    pub fn framebuffer_as_bmp(&self) -> Vec<u8> {
+        let row_size = SCREEN_WIDTH * 3;
+        let image_size = row_size * SCREEN_HEIGHT;
+        let file_size = BMP_HEADER_SIZE + image_size;
+
+        let mut bmp = Vec::with_capacity(file_size);
+        // BITMAPFILEHEADER
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&(file_size as u32).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&(BMP_HEADER_SIZE as u32).to_le_bytes());
+        // BITMAPINFOHEADER
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&(SCREEN_WIDTH as i32).to_le_bytes());
+        bmp.extend_from_slice(&(SCREEN_HEIGHT as i32).to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes()); // Planes
+        bmp.extend_from_slice(&24u16.to_le_bytes()); // Bits per pixel
+        bmp.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+        bmp.extend_from_slice(&(image_size as u32).to_le_bytes());
+        bmp.extend_from_slice(&2835u32.to_le_bytes()); // 72 DPI
+        bmp.extend_from_slice(&2835u32.to_le_bytes());
+        bmp.extend_from_slice(&[0; 8]); // Palette colors used/important
+
+        for row in self.video_buffer.chunks_exact(SCREEN_WIDTH).rev() {
+            for &pixel in row {
+                bmp.extend_from_slice(&[pixel as u8, (pixel >> 8) as u8, (pixel >> 16) as u8]);
+            }
+        }
+        bmp
+    }
+
+    /// Predicted mode 3 length for the current line
+    ///
+    /// 172 T-cycles plus the fine scroll discard, a fetch penalty for each
+    /// selected sprite and a fetcher restart when the window is visible.
+    /// Needs the sprites chosen by the OAM scan. The pixel pipeline arrives
+    /// at the same length unless registers change during mode 3.
+    pub fn compute_mode3_duration(&self, lcd: &Lcd) -> u32 {
+        let sprite_penalty: u32 = self
+            .line_sprites
+            .iter()
+            .take(MAX_LINE_SPRITES)
+            .map(|sprite| compute_sprite_fifo_penalty(sprite.x, lcd.scx))
+            .sum();
+        let window_penalty = if Self::window_is_visible_on_scanline(lcd.ly, lcd) { WINDOW_PENALTY } else { 0 };
+        MODE3_BASE_CYCLES + sprite_penalty + (lcd.scx % 8) as u32 + window_penalty
+    }
+
+    /// Pixel Transfer mode (mode 3) - ends when the pipeline has output 160 pixels
+    fn mode_transfer(&mut self, lcd: &mut Lcd, events: &mut EventQueue) {
+        if self.transfer_tick(lcd) {
+            self.mode3_duration = self.line_ticks - OAM_SCAN_CYCLES;
+            lcd.set_mode(PpuMode::HBlank, events);
         }
     }
 
     /// HBlank mode (mode 0) - remainder of 456 T-cycles
-    fn mode_hblank(&mut self, lcd: &mut Lcd) {
+    fn mode_hblank(&mut self, lcd: &mut Lcd, events: &mut EventQueue) {
+        // The line after the LCD is switched on has no OAM scan or sprites
+        if self.lcd_startup_line {
+            if self.line_ticks >= OAM_SCAN_CYCLES {
+                self.lcd_startup_line = false;
+                self.line_sprites.clear();
+                self.sprite_count = 0;
+                self.start_transfer(lcd);
+                lcd.set_mode(PpuMode::Transfer, events);
+            }
+            return;
+        }
+
         if self.line_ticks >= TICKS_PER_LINE {
             self.line_ticks = 0;
-            lcd.inc_ly();
 
-            if lcd.ly >= SCREEN_HEIGHT as u8 {
-                // Enter VBlank
-                lcd.set_mode(PpuMode::VBlank);
-                self.vblank_interrupt = true;
+            if lcd.ly + 1 >= SCREEN_HEIGHT as u8 {
+                // Enter VBlank on the same T-cycle LY becomes 144: the LYC
+                // check sees the new LY first, then the mode switch raises
+                // the STAT mode-1 source (if enabled), then VBlank is requested.
+                lcd.inc_ly(events);
+                lcd.set_mode(PpuMode::VBlank, events);
+                events.push(HardwareEvent::VBlank);
                 self.current_frame += 1;
+                self.skip_frame = false;
             } else {
-                lcd.set_mode(PpuMode::OamScan);
+                // The new line is already in mode 2 when LYC=LY is checked
+                lcd.set_mode(PpuMode::OamScan, events);
+                lcd.inc_ly(events);
             }
         }
     }
 
     /// VBlank mode (mode 1) - 10 scanlines
-    fn mode_vblank(&mut self, lcd: &mut Lcd) {
+    fn mode_vblank(&mut self, lcd: &mut Lcd, events: &mut EventQueue) {
+        // Line 153 quirk: LY reads 0 a few cycles into the line, so an
+        // LYC=0 coincidence is raised while still in VBlank.
+        if lcd.ly == LINES_PER_FRAME - 1 && self.line_ticks == LY_153_RESET_TICKS {
+            lcd.set_ly(0, events);
+        }
+
         if self.line_ticks >= TICKS_PER_LINE {
             self.line_ticks = 0;
-            lcd.inc_ly();
 
-            // LY wraps to 0 inside inc_ly() after line 153.
-            // When that wrap happens, VBlank is complete.
             if lcd.ly == 0 {
-                lcd.set_ly(0);
-                lcd.set_mode(PpuMode::OamScan);
+                // VBlank is complete. LY is already 0, so only the mode-2
+                // STAT source is raised here.
+                lcd.set_mode(PpuMode::OamScan, events);
                 self.window_line = 0;
+            } else {
+                lcd.inc_ly(events);
             }
         }
     }
 
-    /// Scan OAM for sprites on current scanline
-    fn scan_oam(&mut self, lcd: &Lcd) {
-        self.line_sprites.clear();
-        self.sprite_count = 0;
+    /// Check if a sprite covers scanline `ly`
+    fn sprite_on_line(entry: &OamEntry, lcd: &Lcd, ly: u8) -> bool {
+        let ly = ly as i32;
+        let sprite_y = entry.y as i32 - 16;
+        ly >= sprite_y && ly < sprite_y + lcd.sprite_height() as i32
+    }
 
-        let ly = lcd.ly as i32;
-        let sprite_height = lcd.sprite_height() as i32;
+    /// Scan all of OAM at once for the (up to 10) sprites on scanline `ly`
+    ///
+    /// Sorted by X with OAM order kept for ties, as the PPU draws them. Used
+    /// for off-line rendering and debuggers; the PPU itself scans
+    /// incrementally in `mode_oam_scan` and this leaves its state untouched.
+    pub fn oam_scan_result_for_line(&self, ly: u8, lcd: &Lcd) -> Vec<OamEntry> {
+        let mut sprites = Vec::with_capacity(MAX_LINE_SPRITES);
 
         for i in 0..40 {
-            if self.sprite_count >= 10 {
+            if sprites.len() >= MAX_LINE_SPRITES {
                 break;
             }
 
             let entry = self.get_oam_entry(i);
-            let sprite_y = entry.y as i32 - 16;
-
-            // Check if sprite is on this scanline
-            if ly >= sprite_y && ly < sprite_y + sprite_height {
-                self.line_sprites.push(entry);
-                self.sprite_count += 1;
+            if Self::sprite_on_line(&entry, lcd, ly) {
+                sprites.push(entry);
             }
         }
 
         // Sort sprites by X position (lower X = higher priority)
         // For same X, earlier OAM index has priority (already in order)
-        self.line_sprites.sort_by(|a, b| a.x.cmp(&b.x));
+        sprites.sort_by_key(|sprite| sprite.x);
+        sprites
     }
 
-    /// Render a single scanline
-    fn render_scanline(&mut self, lcd: &Lcd) {
-        let ly = lcd.ly as usize;
-        if ly >= SCREEN_HEIGHT {
-            return;
-        }
-
-        for x in 0..SCREEN_WIDTH {
-            let mut color = 0u8;
-            let mut bg_color_id = 0u8;
-
-            // Render background
-            if lcd.bg_window_enabled() {
-                let (mapped, raw) = self.get_bg_pixel(lcd, x as u8, ly as u8);
-                color = mapped;
-                bg_color_id = raw;
-            }
+    /// Check if the window covers part of scanline `ly`
+    pub fn window_is_visible_on_scanline(ly: u8, lcd: &Lcd) -> bool {
+        lcd.window_enabled() && lcd.bg_window_enabled() && lcd.wy <= ly && lcd.wx <= 166
+    }
 
-            // Render window
-            if lcd.window_enabled() && lcd.bg_window_enabled() {
-                if let Some((mapped, raw)) = self.get_window_pixel(lcd, x as u8, ly as u8) {
-                    color = mapped;
-                    bg_color_id = raw;
-                }
-            }
+    /// Window column drawn at `screen_x`, or `None` left of WX-7 (or when WX > 166)
+    pub fn window_x_for_scanline_pixel(screen_x: u8, lcd: &Lcd) -> Option<u8> {
+        if lcd.wx > 166 {
+            return None;
+        }
+        (screen_x as u16 + 7).checked_sub(lcd.wx as u16).map(|win_x| win_x as u8)
+    }
 
-            // Render sprites
-            if lcd.sprites_enabled() {
-                if let Some((sprite_color, priority)) = self.get_sprite_pixel(lcd, x as u8, ly as u8) {
-                    // Sprite pixel is visible if:
-                    // - BG priority is false, OR
-                    // - BG color id is 0 (white/transparent for OBJ priority)
-                    if !priority || bg_color_id == 0 {
-                        color = sprite_color;
-                    }
-                }
-            }
+    /// Layer that produced the pixel at (x, y) in the last rendered frame
+    ///
+    /// Positions off the screen report `Background`.
+    pub fn pixel_source_at(&self, x: u8, y: u8) -> PixelSource {
+        let (x, y) = (x as usize, y as usize);
+        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+            return PixelSource::Background;
+        }
+        PixelSource::from_priority(self.pixel_priority_buffer[y * SCREEN_WIDTH + x])
+    }
 
-            // Convert color to ARGB
-            let argb = self.color_to_argb(color);
-            self.video_buffer[ly * SCREEN_WIDTH + x] = argb;
+    /// Output color of a background or window pixel
+    ///
+    /// `shade` is the color after BGP, used outside CGB mode. In CGB mode
+    /// the raw `color_id` indexes the BG palette named by the tile `attrs`.
+    fn bg_argb(&self, shade: u8, color_id: u8, attrs: u8) -> u32 {
+        if self.cgb_mode {
+            self.cgb_palettes.bg.argb(attrs & ATTR_PALETTE_MASK, color_id)
+        } else {
+            self.bg_palette.argb(shade)
         }
+    }
 
-        // Increment window line counter if window was visible
-        if lcd.window_enabled() && lcd.wy <= lcd.ly && lcd.wx <= 166 {
-            self.window_line += 1;
+    /// Output color of a sprite pixel (`shade` after OBP0/OBP1, raw `color_id`)
+    fn sprite_argb(&self, shade: u8, color_id: u8, sprite: &OamEntry) -> u32 {
+        if self.cgb_mode {
+            self.cgb_palettes.obj.argb(sprite.cgb_palette(), color_id)
+        } else if sprite.palette_number() {
+            self.obj1_palette.argb(shade)
+        } else {
+            self.obj0_palette.argb(shade)
         }
     }
 
-    /// Get background pixel color at position
-    fn get_bg_pixel(&self, lcd: &Lcd, x: u8, y: u8) -> (u8, u8) {
+    /// Get background pixel color at position: shade, raw color id and CGB tile attributes
+    fn get_bg_pixel(&self, lcd: &Lcd, x: u8, y: u8) -> (u8, u8, u8) {
         let scroll_x = lcd.scx.wrapping_add(x);
         let scroll_y = lcd.scy.wrapping_add(y);
 
         let tile_map = lcd.bg_tile_map();
         let tile_data = lcd.bg_tile_data();
 
-        let color_id = self.get_tile_color_id(tile_map, tile_data, scroll_x, scroll_y);
-        (lcd.bg_color(color_id), color_id)
+        let (color_id, attrs) = self.get_tile_color_id(tile_map, tile_data, scroll_x, scroll_y);
+        (lcd.bg_color(color_id), color_id, attrs)
     }
 
-    /// Get window pixel color at position (if visible)
-    fn get_window_pixel(&self, lcd: &Lcd, x: u8, y: u8) -> Option<(u8, u8)> {
-        // Window is visible if WX <= 166 and WY <= LY
-        if lcd.wx > 166 || lcd.wy > y {
-            return None;
-        }
-
-        let win_x = x as i16 - (lcd.wx as i16 - 7);
-        if win_x < 0 {
+    /// Get window pixel color at position (if visible), `window_line` rows into the window
+    fn get_window_pixel(&self, lcd: &Lcd, x: u8, y: u8, window_line: u8) -> Option<(u8, u8, u8)> {
+        if lcd.wy > y {
             return None;
         }
+        let win_x = Self::window_x_for_scanline_pixel(x, lcd)?;
 
         let tile_map = lcd.window_tile_map();
         let tile_data = lcd.bg_tile_data();
 
-        let color_id = self.get_tile_color_id(tile_map, tile_data, win_x as u8, self.window_line);
-        Some((lcd.bg_color(color_id), color_id))
+        let (color_id, attrs) = self.get_tile_color_id(tile_map, tile_data, win_x, window_line);
+        Some((lcd.bg_color(color_id), color_id, attrs))
     }
 
-    /// Get raw 2-bit tile color id from tile map
-    fn get_tile_color_id(&self, tile_map: u16, tile_data: u16, x: u8, y: u8) -> u8 {
+    /// Get raw 2-bit tile color id from tile map, with the tile's CGB attributes
+    ///
+    /// Attributes come from the same map position in VRAM bank 1 and are 0
+    /// outside CGB mode.
+    fn get_tile_color_id(&self, tile_map: u16, tile_data: u16, x: u8, y: u8) -> (u8, u8) {
         // Get tile coordinates
         let tile_x = (x / 8) as u16;
         let tile_y = (y / 8) as u16;
 
         // Get tile index from tile map
         let map_addr = tile_map + tile_y * 32 + tile_x;
-        let tile_index = self.vram[(map_addr - 0x8000) as usize];
+        let map_offset = (map_addr - 0x8000) as usize;
+        let tile_index = self.vram[map_offset];
+        let attrs = if self.cgb_mode { self.vram[VRAM_BANK_SIZE + map_offset] } else { 0 };
+
+        // Get pixel within tile
+        let mut pixel_x = 7 - (x % 8);
+        if bit(attrs, ATTR_X_FLIP) {
+            pixel_x = 7 - pixel_x;
+        }
 
-        // Get tile data address
+        let addr = Self::bg_tile_row_offset(tile_data, tile_index, attrs, y % 8);
+        if addr + 1 >= self.vram.len() {
+            return (0, attrs);
+        }
+
+        let lo = self.vram[addr];
+        let hi = self.vram[addr + 1];
+
+        (((hi >> pixel_x) & 1) << 1 | ((lo >> pixel_x) & 1), attrs)
+    }
+
+    /// VRAM offset of row `row` (0-7) of a BG/window tile, honoring the CGB bank and Y flip attributes
+    fn bg_tile_row_offset(tile_data: u16, tile_index: u8, attrs: u8, row: u8) -> usize {
         let tile_addr = if tile_data == 0x8000 {
             // Unsigned addressing
             tile_data + (tile_index as u16) * 16
@@ -364,29 +824,38 @@ impl Ppu {
             let signed_index = tile_index as i8 as i16;
             (0x9000i32 + (signed_index as i32) * 16) as u16
         };
+        let row = if bit(attrs, ATTR_Y_FLIP) { 7 - row } else { row };
+        let bank_offset = if bit(attrs, ATTR_VRAM_BANK) { VRAM_BANK_SIZE } else { 0 };
+        bank_offset + (tile_addr - 0x8000 + row as u16 * 2) as usize
+    }
 
-        // Get pixel within tile
-        let pixel_x = 7 - (x % 8);
-        let pixel_y = (y % 8) * 2;
-
-        let addr = (tile_addr - 0x8000 + pixel_y as u16) as usize;
-        if addr + 1 >= self.vram.len() {
-            return 0;
+    /// VRAM offset of the row of `sprite` drawn on line `y`
+    ///
+    /// Handles 8x16 sprites, Y flip and (in CGB mode) the sprite's VRAM bank.
+    fn sprite_row_offset(&self, sprite: &OamEntry, lcd: &Lcd, y: u8) -> usize {
+        let sprite_height = lcd.sprite_height();
+        let mut pixel_y = y.wrapping_sub(sprite.y.wrapping_sub(16)) % sprite_height;
+        if sprite.y_flip() {
+            pixel_y = sprite_height - 1 - pixel_y;
         }
 
-        let lo = self.vram[addr];
-        let hi = self.vram[addr + 1];
+        // Get tile index (mask bit 0 for 8x16 sprites)
+        let tile_index = if sprite_height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
 
-        ((hi >> pixel_x) & 1) << 1 | ((lo >> pixel_x) & 1)
+        let bank_offset = if self.cgb_mode && sprite.cgb_vram_bank() { VRAM_BANK_SIZE } else { 0 };
+        bank_offset + tile_index as usize * 16 + pixel_y as usize * 2
     }
 
-    /// Get sprite pixel at position (if any)
-    fn get_sprite_pixel(&self, lcd: &Lcd, x: u8, y: u8) -> Option<(u8, bool)> {
-        let sprite_height = lcd.sprite_height();
-
-        for sprite in &self.line_sprites {
+    /// Get sprite pixel at position (if any) from the sprites scanned for line `y`
+    ///
+    /// Returns the shade after OBP0/OBP1, the raw color id and the sprite.
+    fn get_sprite_pixel(&self, sprites: &[OamEntry], lcd: &Lcd, x: u8, y: u8) -> Option<(u8, u8, OamEntry)> {
+        for sprite in sprites {
             let sprite_x = sprite.x as i16 - 8;
-            let sprite_y = sprite.y as i16 - 16;
 
             // Check if pixel is within sprite bounds
             if (x as i16) < sprite_x || (x as i16) >= sprite_x + 8 {
@@ -394,27 +863,11 @@ impl Ppu {
             }
 
             let mut pixel_x = (x as i16 - sprite_x) as u8;
-            let mut pixel_y = (y as i16 - sprite_y) as u8;
-
-            // Handle flipping
             if sprite.x_flip() {
                 pixel_x = 7 - pixel_x;
             }
-            if sprite.y_flip() {
-                pixel_y = sprite_height - 1 - pixel_y;
-            }
-
-            // Get tile index (mask bit 0 for 8x16 sprites)
-            let tile_index = if sprite_height == 16 {
-                sprite.tile & 0xFE
-            } else {
-                sprite.tile
-            };
 
-            // Get tile data
-            let tile_addr = 0x8000u16 + (tile_index as u16) * 16 + (pixel_y as u16) * 2;
-            let addr = (tile_addr - 0x8000) as usize;
-            
+            let addr = self.sprite_row_offset(sprite, lcd, y);
             if addr + 1 >= self.vram.len() {
                 continue;
             }
@@ -436,27 +889,120 @@ impl Ppu {
                 lcd.sprite_color_0(color_bit)
             };
 
-            return Some((color, sprite.bg_priority()));
+            return Some((color, color_bit, *sprite));
         }
 
         None
     }
 
-    /// Convert 2-bit color to ARGB
-    fn color_to_argb(&self, color: u8) -> u32 {
-        // Neutral grayscale palette (no green tint)
-        match color & 0x03 {
-            0 => 0xFFFFFFFF, // White
-            1 => 0xFFAAAAAA, // Light gray
-            2 => 0xFF555555, // Dark gray
-            3 => 0xFF000000, // Black
-            _ => 0xFF000000,
+    /// Render only the sprite layer as a 160x144 ARGB buffer
+    ///
+    /// Pixels without a sprite are transparent (0x00000000). The LCDC enable
+    /// bits are ignored so the layer can be inspected while hidden.
+    pub fn render_sprites_only(&self, lcd: &Lcd) -> Vec<u32> {
+        let mut buffer = vec![0u32; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for y in 0..SCREEN_HEIGHT {
+            let sprites = self.oam_scan_result_for_line(y as u8, lcd);
+            if sprites.is_empty() {
+                continue;
+            }
+            for x in 0..SCREEN_WIDTH {
+                if let Some((shade, color_id, sprite)) = self.get_sprite_pixel(&sprites, lcd, x as u8, y as u8) {
+                    buffer[y * SCREEN_WIDTH + x] = self.sprite_argb(shade, color_id, &sprite);
+                }
+            }
         }
+        buffer
     }
 
-    /// Clear VBlank interrupt flag
-    pub fn clear_vblank_interrupt(&mut self) {
-        self.vblank_interrupt = false;
+    /// Render only the background layer (scrolled by SCX/SCY) as a 160x144 ARGB buffer
+    pub fn render_bg_only(&self, lcd: &Lcd) -> Vec<u32> {
+        let mut buffer = vec![0u32; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                let (shade, color_id, attrs) = self.get_bg_pixel(lcd, x as u8, y as u8);
+                buffer[y * SCREEN_WIDTH + x] = self.bg_argb(shade, color_id, attrs);
+            }
+        }
+        buffer
+    }
+
+    /// Render only the window layer as a 160x144 ARGB buffer
+    ///
+    /// Pixels outside the window (per WX/WY) are transparent.
+    pub fn render_window_only(&self, lcd: &Lcd) -> Vec<u32> {
+        let mut buffer = vec![0u32; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut window_line = 0u8;
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                if let Some((shade, color_id, attrs)) = self.get_window_pixel(lcd, x as u8, y as u8, window_line) {
+                    buffer[y * SCREEN_WIDTH + x] = self.bg_argb(shade, color_id, attrs);
+                }
+            }
+            if lcd.wy as usize <= y && lcd.wx <= 166 {
+                window_line = window_line.wrapping_add(1);
+            }
+        }
+        buffer
+    }
+
+    /// Draw tile `index` (counted from 0x8000 of bank 0; bank 1 starts at 512) with its top-left corner at (x, y)
+    fn draw_tile(&self, buffer: &mut [u32], width: usize, index: usize, x: usize, y: usize, palette: &DmgPalette) {
+        let tile = &self.vram[index * 16..index * 16 + 16];
+        for row in 0..8 {
+            let (lo, hi) = (tile[row * 2], tile[row * 2 + 1]);
+            for col in 0..8 {
+                let bit = 7 - col;
+                let shade = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
+                buffer[(y + row) * width + x + col] = palette.argb(shade);
+            }
+        }
+    }
+
+    /// Render all 384 tiles as a 128x192 ARGB sheet, 16 tiles per row
+    ///
+    /// Tiles are numbered from 0x8000, so rows 0-7 hold 0x8000-0x87FF, rows
+    /// 8-15 hold 0x8800-0x8FFF and rows 16-23 hold 0x9000-0x97FF. Shades are
+    /// drawn directly (BGP is not applied). `bank` picks the CGB VRAM bank;
+    /// only bit 0 is used.
+    pub fn generate_tileset_image(&self, palette: &DmgPalette, bank: u8) -> Vec<u32> {
+        let mut buffer = vec![0u32; TILESET_WIDTH * TILESET_HEIGHT];
+        let first = (bank & 0x01) as usize * VRAM_BANK_SIZE / 16;
+        for index in 0..TILESET_COLUMNS * TILESET_ROWS {
+            let (x, y) = (index % TILESET_COLUMNS * 8, index / TILESET_COLUMNS * 8);
+            self.draw_tile(&mut buffer, TILESET_WIDTH, first + index, x, y, palette);
+        }
+        buffer
+    }
+
+    /// Render the tile sheet with 1-pixel `grid_color` lines between tiles
+    ///
+    /// The image is `TILESET_GRID_WIDTH` x `TILESET_GRID_HEIGHT` (143x215);
+    /// tile pixels are never covered by the grid.
+    pub fn generate_tileset_image_with_grid(&self, palette: &DmgPalette, bank: u8, grid_color: u32) -> Vec<u32> {
+        let mut buffer = vec![grid_color; TILESET_GRID_WIDTH * TILESET_GRID_HEIGHT];
+        let first = (bank & 0x01) as usize * VRAM_BANK_SIZE / 16;
+        for index in 0..TILESET_COLUMNS * TILESET_ROWS {
+            let (x, y) = (index % TILESET_COLUMNS * 9, index / TILESET_COLUMNS * 9);
+            self.draw_tile(&mut buffer, TILESET_GRID_WIDTH, first + index, x, y, palette);
+        }
+        buffer
+    }
+
+    /// Render background, window and sprites side by side as a 480x144 ARGB buffer
+    pub fn render_composite_debug(&self, lcd: &Lcd) -> Vec<u32> {
+        let layers = [
+            self.render_bg_only(lcd),
+            self.render_window_only(lcd),
+            self.render_sprites_only(lcd),
+        ];
+        let mut buffer = Vec::with_capacity(SCREEN_WIDTH * 3 * SCREEN_HEIGHT);
+        for y in 0..SCREEN_HEIGHT {
+            for layer in &layers {
+                buffer.extend_from_slice(&layer[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH]);
+            }
+        }
+        buffer
     }
 }
 
@@ -464,10 +1010,80 @@ impl Ppu {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_diff_identical_frames() {
+        let frame = vec![0xFF555555; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let diff = diff_frames(&frame, &frame);
+        assert!(diff.is_identical());
+        assert_eq!(diff.total_pixels, SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(diff.max_channel_delta, 0);
+        assert_eq!(diff.rms_error, 0.0);
+    }
+
+    #[test]
+    fn test_diff_known_frames() {
+        let a = vec![0xFFFFFFFF; 4];
+        let mut b = a.clone();
+        b[1] = 0xFFFFFF00; // Blue off by 255
+        b[2] = 0x00FFFFFF; // Alpha only
+        let diff = diff_frames(&a, &b);
+        assert_eq!(diff.changed_pixels, 1);
+        assert_eq!(diff.max_channel_delta, 0xFF);
+        // sqrt(255^2 / 12)
+        assert!((diff.rms_error - 73.61).abs() < 0.01, "{}", diff.rms_error);
+        assert!(!diff.is_identical());
+        assert!(diff.similar_enough(1));
+        assert!(!diff.similar_enough(0));
+
+        // Length mismatch counts the extra pixels as changed
+        let diff = diff_frames(&a, &a[..3]);
+        assert_eq!((diff.changed_pixels, diff.total_pixels), (1, 4));
+    }
+
+    /// PPU whose frame has a red top-left and blue bottom-left pixel
+    fn marked_frame() -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.video_buffer.fill(0xFFFFFFFF);
+        ppu.video_buffer[0] = 0xFFFF0000;
+        ppu.video_buffer[(SCREEN_HEIGHT - 1) * SCREEN_WIDTH] = 0xFF0000FF;
+        ppu
+    }
+
+    #[cfg(feature = "png-output")]
+    #[test]
+    fn test_framebuffer_as_png() {
+        let png_data = marked_frame().framebuffer_as_png().unwrap();
+        assert_eq!(&png_data[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+
+        let decoder = png::Decoder::new(png_data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgba = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+        assert_eq!((info.width, info.height), (160, 144));
+        assert_eq!(&rgba[0..8], &[0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_framebuffer_as_bmp() {
+        let bmp = marked_frame().framebuffer_as_bmp();
+        assert_eq!(bmp.len(), 54 + 160 * 144 * 3);
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(u32::from_le_bytes([bmp[2], bmp[3], bmp[4], bmp[5]]) as usize, bmp.len());
+        assert_eq!(u32::from_le_bytes([bmp[10], bmp[11], bmp[12], bmp[13]]), 54);
+        assert_eq!(i32::from_le_bytes([bmp[18], bmp[19], bmp[20], bmp[21]]), 160);
+        assert_eq!(i32::from_le_bytes([bmp[22], bmp[23], bmp[24], bmp[25]]), 144);
+        assert_eq!(u16::from_le_bytes([bmp[28], bmp[29]]), 24);
+
+        // Bottom row comes first, in BGR order
+        assert_eq!(&bmp[54..60], &[0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF]);
+        let top_row = 54 + 143 * 160 * 3;
+        assert_eq!(&bmp[top_row..top_row + 3], &[0x00, 0x00, 0xFF]);
+    }
+
     #[test]
     fn test_ppu_new() {
         let ppu = Ppu::new();
-        assert_eq!(ppu.vram.len(), 0x2000);
+        assert_eq!(ppu.vram.len(), VRAM_BANK_SIZE * VRAM_BANKS);
         assert_eq!(ppu.oam.len(), 160);
         assert_eq!(ppu.video_buffer.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
     }
@@ -483,6 +1099,70 @@ mod tests {
         assert_eq!(ppu.vram_read(0x9FFF), 0x55);
     }
 
+    #[test]
+    fn test_vram_banks() {
+        let mut ppu = Ppu::new();
+        ppu.vram_write(0x8000, 0x11);
+        ppu.vram_bank = 1;
+        ppu.vram_write(0x8000, 0x22);
+
+        assert_eq!(ppu.vram_read(0x8000), 0x22);
+        ppu.vram_bank = 0;
+        assert_eq!(ppu.vram_read(0x8000), 0x11);
+        assert_eq!((ppu.vram[0], ppu.vram[VRAM_BANK_SIZE]), (0x11, 0x22));
+        assert_eq!(ppu.vram_read(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_cgb_bg_attributes() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lcdc = 0x93;
+        lcd.ly = 0;
+        ppu.cgb_mode = true;
+
+        // BG palette 2 color 1: red; everything else stays white
+        ppu.cgb_palettes.bg.write_index(0x80 | (2 * 8 + 2));
+        ppu.cgb_palettes.bg.write_data(0x1F, true);
+        ppu.cgb_palettes.bg.write_data(0x00, true);
+        const RED: u32 = 0xFFFF0000;
+        const WHITE: u32 = 0xFFFFFFFF;
+
+        // Tile 1 exists only in bank 1: leftmost pixel of row 0, rightmost of row 7
+        ppu.vram[VRAM_BANK_SIZE + 16] = 0x80;
+        ppu.vram[VRAM_BANK_SIZE + 16 + 14] = 0x01;
+        // Sprite tile 2 in bank 0: solid row 0
+        ppu.vram[32] = 0xFF;
+
+        let map = 0x1800;
+        ppu.vram[map..map + 4].copy_from_slice(&[1, 1, 1, 1]);
+        ppu.vram[VRAM_BANK_SIZE + map..VRAM_BANK_SIZE + map + 4].copy_from_slice(&[
+            0x02,        // bank 0 tile data: blank
+            0x2A,        // bank 1, X flip
+            0x4A,        // bank 1, Y flip
+            0x8A,        // bank 1, BG over sprites
+        ]);
+        ppu.line_sprites.push(OamEntry { y: 16, x: 32, tile: 2, flags: 0x00 });
+        ppu.render_scanline(&lcd);
+
+        assert_eq!(ppu.video_buffer[0], WHITE);
+        assert_eq!(ppu.video_buffer[8], WHITE);
+        assert_eq!(ppu.video_buffer[15], RED);
+        assert_eq!(ppu.video_buffer[16], WHITE);
+        assert_eq!(ppu.video_buffer[23], RED);
+
+        assert_eq!(ppu.video_buffer[24], RED);
+        assert_eq!(ppu.pixel_source_at(24, 0), PixelSource::Background);
+        assert_eq!(ppu.pixel_priority_buffer[24] & PRIORITY_BG_OVER_OBJ, PRIORITY_BG_OVER_OBJ);
+        assert_eq!(ppu.pixel_source_at(25, 0), PixelSource::Sprite);
+
+        // DMG mode ignores bank 1 entirely
+        ppu.cgb_mode = false;
+        ppu.render_scanline(&lcd);
+        assert!(ppu.pixel_priority_buffer[..SCREEN_WIDTH].iter().all(|&p| p & PRIORITY_BG_OVER_OBJ == 0));
+        assert_eq!(ppu.pixel_source_at(24, 0), PixelSource::Sprite);
+    }
+
     #[test]
     fn test_oam_read_write() {
         let mut ppu = Ppu::new();
@@ -515,11 +1195,538 @@ mod tests {
     }
 
     #[test]
-    fn test_color_to_argb() {
+    fn test_default_palette_argb() {
         let ppu = Ppu::new();
         
-        assert_eq!(ppu.color_to_argb(0), 0xFFFFFFFF);
-        assert_eq!(ppu.color_to_argb(3), 0xFF000000);
+        assert_eq!(ppu.bg_palette.argb(0), 0xFFFFFFFF);
+        assert_eq!(ppu.bg_palette.argb(3), 0xFF000000);
+    }
+
+    /// Place OAM entry `index` so it covers LY 0 (or not)
+    fn place_sprite(ppu: &mut Ppu, index: usize, on_line_0: bool, x: u8) {
+        let y = if on_line_0 { 16 } else { 100 };
+        ppu.oam[index * 4..index * 4 + 4].copy_from_slice(&[y, x, 0, 0]);
+    }
+
+    #[test]
+    fn test_oam_scan_checks_entry_0_in_cycles_0_and_1() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        let mut events = EventQueue::new();
+        place_sprite(&mut ppu, 0, true, 8);
+
+        // Cycle 0 reads entry 0
+        ppu.tick(&mut lcd, &mut events);
+        assert_eq!(ppu.oam_scan.entry_index, 0);
+        assert_eq!(ppu.oam_scan.cycle_in_scan, 1);
+        assert!(ppu.line_sprites.is_empty());
+
+        // Moving the sprite after the read does not change the result
+        place_sprite(&mut ppu, 0, false, 8);
+        // Cycle 1 evaluates it
+        ppu.tick(&mut lcd, &mut events);
+        assert_eq!(ppu.line_sprites.len(), 1);
+        assert_eq!(ppu.line_sprites[0].y, 16);
+
+        // Entry 1 is read on cycle 2, so a write before then is seen
+        place_sprite(&mut ppu, 1, true, 4);
+        ppu.tick(&mut lcd, &mut events);
+        assert_eq!(ppu.oam_scan.entry_index, 1);
+        ppu.tick(&mut lcd, &mut events);
+        assert_eq!(ppu.line_sprites.len(), 2);
+
+        // Entry 2 is already scanned by the time the write lands
+        for _ in 0..4 {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        place_sprite(&mut ppu, 2, true, 2);
+        while lcd.mode() == PpuMode::OamScan {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!(ppu.line_ticks, 80);
+        assert_eq!(ppu.sprite_count, 2);
+        // Sorted by X for drawing
+        assert_eq!(ppu.line_sprites[0].x, 4);
+        assert_eq!(ppu.oam_scan, OamScanState::default());
+    }
+
+    #[test]
+    fn test_oam_scan_limits_to_ten_sprites() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        let mut events = EventQueue::new();
+        for i in 0..40 {
+            place_sprite(&mut ppu, i, true, i as u8);
+        }
+        while lcd.mode() == PpuMode::OamScan {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!(ppu.sprite_count, 10);
+        assert!(ppu.line_sprites.iter().all(|sprite| sprite.x < 10));
+    }
+
+    #[test]
+    fn test_oam_scan_result_for_line() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        place_sprite(&mut ppu, 0, true, 30);
+        place_sprite(&mut ppu, 1, false, 5);
+        place_sprite(&mut ppu, 2, true, 12);
+        place_sprite(&mut ppu, 3, true, 30);
+        place_sprite(&mut ppu, 4, true, 30);
+        ppu.oam[4 * 4 + 2] = 0x42;
+
+        let sprites = ppu.oam_scan_result_for_line(0, &lcd);
+        let xs: Vec<u8> = sprites.iter().map(|sprite| sprite.x).collect();
+        assert_eq!(xs, [12, 30, 30, 30]);
+        // Ties keep OAM order
+        assert_eq!(sprites[3].tile, 0x42);
+        assert!(ppu.line_sprites.is_empty());
+
+        // The sprite at Y=100 covers lines 84..92, or 84..100 in 8x16 mode
+        assert_eq!(ppu.oam_scan_result_for_line(84, &lcd), [ppu.get_oam_entry(1)]);
+        assert!(ppu.oam_scan_result_for_line(95, &lcd).is_empty());
+        lcd.lcdc |= 0x04;
+        assert_eq!(ppu.oam_scan_result_for_line(95, &lcd).len(), 1);
+
+        for i in 0..40 {
+            place_sprite(&mut ppu, i, true, 40 - i as u8);
+        }
+        let sprites = ppu.oam_scan_result_for_line(0, &lcd);
+        assert_eq!(sprites.len(), MAX_LINE_SPRITES);
+        // The first ten in OAM order are selected, then sorted by X
+        assert_eq!(sprites[0].x, 31);
+        assert_eq!(sprites[9].x, 40);
+    }
+
+    #[test]
+    fn test_sprite_fifo_penalty() {
+        // Tile-aligned sprites cost the full 6 cycles
+        assert_eq!(compute_sprite_fifo_penalty(0, 0), 6);
+        assert_eq!(compute_sprite_fifo_penalty(8, 0), 6);
+        assert_eq!(compute_sprite_fifo_penalty(160, 0), 6);
+        // Later in the tile the stall shrinks, bottoming out at 1
+        for (x, expected) in [(1, 5), (2, 4), (3, 3), (4, 2), (5, 1), (6, 1), (7, 1), (13, 1)] {
+            assert_eq!(compute_sprite_fifo_penalty(x, 0), expected, "x={}", x);
+        }
+        // Fine scroll shifts the sprite within the tile; coarse scroll does not
+        assert_eq!(compute_sprite_fifo_penalty(0, 3), 3);
+        assert_eq!(compute_sprite_fifo_penalty(5, 3), 6);
+        assert_eq!(compute_sprite_fifo_penalty(5, 11), 6);
+        assert_eq!(compute_sprite_fifo_penalty(255, 1), 6);
+    }
+
+    #[test]
+    fn test_mode3_duration() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        assert_eq!(ppu.compute_mode3_duration(&lcd), 172);
+
+        // Fine scroll discards SCX % 8 pixels
+        lcd.scx = 0x0B;
+        assert_eq!(ppu.compute_mode3_duration(&lcd), 175);
+
+        // Sprites at X 8 and 13 land at tile offsets 3 and 0 with SCX 11
+        let sprite = |x| OamEntry { y: 16, x, tile: 0, flags: 0 };
+        ppu.line_sprites = vec![sprite(8), sprite(13)];
+        assert_eq!(ppu.compute_mode3_duration(&lcd), 175 + 3 + 6);
+
+        // The window adds a fetcher restart
+        lcd.lcdc |= 0x20;
+        lcd.wx = 7;
+        assert_eq!(ppu.compute_mode3_duration(&lcd), 175 + 3 + 6 + 6);
+        lcd.wy = 1;
+        assert_eq!(ppu.compute_mode3_duration(&lcd), 175 + 3 + 6);
+
+        // Ten aligned sprites add the most
+        lcd.scx = 0;
+        ppu.line_sprites = vec![sprite(8); 10];
+        assert_eq!(ppu.compute_mode3_duration(&lcd), 172 + 60);
+    }
+
+    #[test]
+    fn test_sprites_lengthen_mode3() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        let mut events = EventQueue::new();
+        lcd.lcdc |= 0x02; // Sprites on
+        place_sprite(&mut ppu, 0, true, 8);
+        place_sprite(&mut ppu, 1, true, 10);
+
+        while lcd.mode() != PpuMode::HBlank {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!(ppu.mode3_duration, 172 + 6 + 4);
+        assert_eq!(ppu.line_ticks, 80 + 172 + 6 + 4);
+    }
+
+    /// Run line 0 to HBlank and return the mode 3 length the pipeline took
+    fn measure_mode3(ppu: &mut Ppu, lcd: &mut Lcd) -> u32 {
+        let mut events = EventQueue::new();
+        while lcd.mode() != PpuMode::HBlank {
+            ppu.tick(lcd, &mut events);
+        }
+        ppu.mode3_duration
+    }
+
+    #[test]
+    fn test_mode3_length_follows_fine_scroll() {
+        for scx in 0..16u8 {
+            let mut ppu = Ppu::new();
+            let mut lcd = Lcd::new();
+            lcd.scx = scx;
+            let duration = measure_mode3(&mut ppu, &mut lcd);
+            assert_eq!(duration, MODE3_BASE_CYCLES + (scx & 7) as u32, "scx={}", scx);
+            assert_eq!(duration, ppu.compute_mode3_duration(&lcd));
+        }
+    }
+
+    #[test]
+    fn test_mode3_length_follows_sprite_count() {
+        for count in 0..=MAX_LINE_SPRITES {
+            let mut ppu = Ppu::new();
+            let mut lcd = Lcd::new();
+            lcd.lcdc |= 0x02; // Sprites on
+            // Tile-aligned sprites each stall the fetcher for 6 cycles
+            for i in 0..count {
+                place_sprite(&mut ppu, i, true, 8 + 16 * i as u8);
+            }
+            assert_eq!(measure_mode3(&mut ppu, &mut lcd), MODE3_BASE_CYCLES + 6 * count as u32, "count={}", count);
+        }
+
+        // With sprites disabled nothing is fetched
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        place_sprite(&mut ppu, 0, true, 8);
+        assert_eq!(measure_mode3(&mut ppu, &mut lcd), MODE3_BASE_CYCLES);
+    }
+
+    #[test]
+    fn test_mid_scanline_scx_change() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        let mut events = EventQueue::new();
+        // Tile 1 is solid color 3; only map column 12 uses it
+        ppu.vram[16..32].fill(0xFF);
+        ppu.vram[0x1800 + 12] = 1;
+        lcd.bgp = 0xE4;
+
+        while !(lcd.mode() == PpuMode::Transfer && ppu.fifo.x == 80) {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        // The next tile fetched is already column 12 once SCX moves on by a tile
+        lcd.scx = 8;
+        while lcd.mode() == PpuMode::Transfer {
+            ppu.tick(&mut lcd, &mut events);
+        }
+
+        let black = ppu.bg_palette.argb(3);
+        assert!(ppu.video_buffer[..88].iter().all(|&p| p != black));
+        assert!(ppu.video_buffer[88..96].iter().all(|&p| p == black));
+        assert!(ppu.video_buffer[96..SCREEN_WIDTH].iter().all(|&p| p != black));
+    }
+
+    /// Tick the PPU `ticks` times, recording (LY, line_ticks) of each STAT event
+    fn stat_event_ticks(ppu: &mut Ppu, lcd: &mut Lcd, ticks: u32) -> Vec<(u8, u32)> {
+        let mut events = EventQueue::new();
+        let mut fired = Vec::new();
+        for _ in 0..ticks {
+            ppu.tick(lcd, &mut events);
+            while let Some(event) = events.pop() {
+                if event == HardwareEvent::LcdStat {
+                    fired.push((lcd.ly, ppu.line_ticks));
+                }
+            }
+        }
+        fired
+    }
+
+    #[test]
+    fn test_stat_sources_fire_as_each_mode_begins() {
+        let two_lines = 2 * TICKS_PER_LINE - 1;
+        let setup = |sources: u8| {
+            let mut lcd = Lcd::new();
+            lcd.stat |= sources;
+            (Ppu::new(), lcd)
+        };
+
+        // Mode 2 starts the line (line 0 begins in mode 2 without an event)
+        let (mut ppu, mut lcd) = setup(0x20);
+        assert_eq!(stat_event_ticks(&mut ppu, &mut lcd, two_lines), [(1, 0)]);
+
+        // LYC is compared as the line starts
+        let (mut ppu, mut lcd) = setup(0x40);
+        lcd.lyc = 1;
+        assert_eq!(stat_event_ticks(&mut ppu, &mut lcd, two_lines), [(1, 0)]);
+
+        // Mode 0 follows a mode 3 stretched by fine scroll and a sprite on line 0
+        let (mut ppu, mut lcd) = setup(0x08);
+        lcd.lcdc |= 0x02;
+        lcd.scx = 3;
+        ppu.oam[0..4].copy_from_slice(&[16 - 7, 8, 0, 0]);
+        let hblank = OAM_SCAN_CYCLES + MODE3_BASE_CYCLES + 3;
+        assert_eq!(
+            stat_event_ticks(&mut ppu, &mut lcd, two_lines),
+            [(0, hblank + compute_sprite_fifo_penalty(8, 3)), (1, hblank)]
+        );
+
+        // Mode 1 starts line 144
+        let (mut ppu, mut lcd) = setup(0x10);
+        let to_vblank = SCREEN_HEIGHT as u32 * TICKS_PER_LINE;
+        assert_eq!(stat_event_ticks(&mut ppu, &mut lcd, to_vblank), [(144, 0)]);
+    }
+
+    #[test]
+    fn test_hblank_scx_write_moves_next_hblank_stat() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        let mut events = EventQueue::new();
+        lcd.stat |= 0x08;
+
+        while events.is_empty() {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!(ppu.line_ticks, OAM_SCAN_CYCLES + MODE3_BASE_CYCLES);
+
+        // A raster effect reprograms SCX during HBlank
+        lcd.scx = 7;
+        let fired = stat_event_ticks(&mut ppu, &mut lcd, TICKS_PER_LINE + 7);
+        assert_eq!(fired, [(1, OAM_SCAN_CYCLES + MODE3_BASE_CYCLES + 7)]);
+    }
+
+    #[test]
+    fn test_lcd_disable_and_reenable() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        let mut events = EventQueue::new();
+        lcd.bgp = 0xE4;
+        // Tile 0 is solid color 3, so a drawn frame is black
+        ppu.vram[..16].fill(0xFF);
+        for _ in 0..TICKS_PER_LINE * 5 + 100 {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!((lcd.ly, lcd.mode()), (5, PpuMode::Transfer));
+
+        lcd.lcdc &= !0x80;
+        for _ in 0..1000 {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!((lcd.ly, lcd.mode(), ppu.line_ticks), (0, PpuMode::HBlank, 0));
+        assert_eq!(ppu.window_line, 0);
+        assert!(ppu.video_buffer.iter().all(|&p| p == ppu.bg_palette.argb(0)));
+
+        // Line 0 stays in mode 0 instead of scanning OAM, and is 4 T-cycles short
+        lcd.lcdc |= 0x80;
+        events.clear();
+        ppu.tick(&mut lcd, &mut events);
+        assert_eq!((lcd.ly, lcd.mode()), (0, PpuMode::HBlank));
+        let mut ticks = 1;
+        while lcd.mode() == PpuMode::HBlank {
+            ppu.tick(&mut lcd, &mut events);
+            ticks += 1;
+        }
+        assert_eq!((ticks, lcd.mode()), (OAM_SCAN_CYCLES - LCD_ON_SHORT_LINE_TICKS, PpuMode::Transfer));
+        while lcd.ly == 0 {
+            ppu.tick(&mut lcd, &mut events);
+            ticks += 1;
+        }
+        assert_eq!(ticks, TICKS_PER_LINE - LCD_ON_SHORT_LINE_TICKS);
+        assert_eq!(lcd.mode(), PpuMode::OamScan);
+
+        // The first frame stays blank; the next one is drawn
+        let frame = ppu.current_frame;
+        while ppu.current_frame == frame {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert!(ppu.video_buffer.iter().all(|&p| p == ppu.bg_palette.argb(0)));
+        while ppu.current_frame == frame + 1 {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert!(ppu.video_buffer.iter().all(|&p| p == ppu.bg_palette.argb(3)));
+    }
+
+    #[test]
+    fn test_vblank_fires_at_ly_144() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.stat |= 0x10; // STAT mode-1 source
+        let mut events = EventQueue::new();
+
+        let mut ticks = 0u32;
+        while !events.iter().any(|&e| e == HardwareEvent::VBlank) {
+            assert!(lcd.ly < 144, "VBlank must fire when LY reaches 144");
+            ppu.tick(&mut lcd, &mut events);
+            ticks += 1;
+        }
+
+        assert_eq!(ticks, 144 * TICKS_PER_LINE);
+        assert_eq!(lcd.ly, 144);
+        assert_eq!(lcd.mode(), PpuMode::VBlank);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events.pop(), Some(HardwareEvent::LcdStat));
+        assert_eq!(ppu.current_frame, 1);
+    }
+
+    #[test]
+    fn test_stat_lyc0_and_mode2_after_vblank() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lyc = 0;
+        lcd.stat |= 0x60; // LYC and mode-2 STAT sources
+
+        // Run to the start of VBlank
+        let mut queue = EventQueue::new();
+        while lcd.mode() != PpuMode::VBlank {
+            ppu.tick(&mut lcd, &mut queue);
+        }
+        queue.clear();
+
+        let mut events = Vec::new();
+        let mut vblank_ticks = 0u32;
+        while lcd.mode() == PpuMode::VBlank {
+            ppu.tick(&mut lcd, &mut queue);
+            vblank_ticks += 1;
+            if queue.pop() == Some(HardwareEvent::LcdStat) {
+                events.push((lcd.ly, lcd.mode()));
+                assert!(queue.is_empty());
+            }
+        }
+
+        // LYC=0 matches early in line 153, then mode 2 starts line 0
+        assert_eq!(events, vec![(0, PpuMode::VBlank), (0, PpuMode::OamScan)]);
+        assert_eq!(vblank_ticks, 10 * TICKS_PER_LINE);
+        assert!(lcd.lyc_flag());
+    }
+
+    #[test]
+    fn test_lyc_fires_at_start_of_line_in_mode2() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lyc = 5;
+        lcd.stat |= 0x40; // LYC STAT source
+        let mut events = EventQueue::new();
+
+        while events.is_empty() {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!(events.pop(), Some(HardwareEvent::LcdStat));
+        assert_eq!(lcd.ly, 5);
+        assert_eq!(ppu.line_ticks, 0);
+        assert_eq!(lcd.mode(), PpuMode::OamScan);
+        assert!(lcd.lyc_flag());
+    }
+
+    #[test]
+    fn test_lyc_144_before_vblank() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lyc = 144;
+        lcd.stat |= 0x50; // LYC and mode-1 STAT sources
+        let mut events = EventQueue::new();
+
+        while events.is_empty() {
+            ppu.tick(&mut lcd, &mut events);
+        }
+        assert_eq!(lcd.ly, 144);
+        let order: Vec<_> = events.iter().copied().collect();
+        // LYC=144, then the mode-1 source, then VBlank
+        assert_eq!(order, [HardwareEvent::LcdStat, HardwareEvent::LcdStat, HardwareEvent::VBlank]);
+    }
+
+    #[test]
+    fn test_stat_interrupts_over_a_frame() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lyc = 0;
+        lcd.stat |= 0x40 | 0x10; // LYC and mode-1 STAT sources
+        let mut queue = EventQueue::new();
+
+        let mut stat = Vec::new();
+        let mut vblanks = 0;
+        for _ in 0..LINES_PER_FRAME as u32 * TICKS_PER_LINE {
+            ppu.tick(&mut lcd, &mut queue);
+            while let Some(event) = queue.pop() {
+                match event {
+                    HardwareEvent::LcdStat => stat.push((lcd.ly, lcd.mode(), ppu.line_ticks)),
+                    HardwareEvent::VBlank => vblanks += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        // Mode 1 at the start of line 144; LYC=0 early in line 153, while
+        // still in VBlank and before line 0's OAM scan
+        assert_eq!(vblanks, 1);
+        assert_eq!(
+            stat,
+            [(144, PpuMode::VBlank, 0), (0, PpuMode::VBlank, LY_153_RESET_TICKS)]
+        );
+        assert_eq!(lcd.ly, 0);
+        assert_eq!(lcd.mode(), PpuMode::OamScan);
+    }
+
+    #[test]
+    fn test_window_line_counts_only_drawn_lines() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lcdc = 0xF1; // LCD, window (map 0x9C00), unsigned tile data and BG on
+        lcd.bgp = 0xE4;
+        lcd.wy = 40;
+        lcd.wx = 200;
+        // Window tile 1: row 0 color 3, rows 1-7 color 1
+        ppu.vram[16..18].copy_from_slice(&[0xFF, 0xFF]);
+        for row in 1..8 {
+            ppu.vram[16 + row * 2] = 0xFF;
+        }
+        ppu.vram[0x1C00..0x2000].fill(1);
+
+        // Enabled from line 40 but off the right edge until line 60
+        for ly in 0..60 {
+            lcd.ly = ly;
+            ppu.render_scanline(&lcd);
+        }
+        assert_eq!(ppu.window_line, 0);
+
+        lcd.wx = 7;
+        lcd.ly = 60;
+        ppu.render_scanline(&lcd);
+        lcd.ly = 61;
+        ppu.render_scanline(&lcd);
+
+        // The first line the window shows is its tile row 0
+        let line = |ly: usize| &ppu.video_buffer[ly * SCREEN_WIDTH..(ly + 1) * SCREEN_WIDTH];
+        assert!(line(60).iter().all(|&p| p == ppu.bg_palette.argb(3)));
+        assert!(line(61).iter().all(|&p| p == ppu.bg_palette.argb(1)));
+        assert_eq!(ppu.pixel_source_at(0, 60), PixelSource::Window);
+        assert_eq!(ppu.window_line, 2);
+    }
+
+    #[test]
+    fn test_window_visibility_edges() {
+        let mut lcd = Lcd::new();
+        lcd.lcdc = 0xA1; // LCD, window and BG/window enabled
+        lcd.wy = 40;
+        lcd.wx = 7;
+
+        assert!(Ppu::window_is_visible_on_scanline(40, &lcd));
+        assert!(!Ppu::window_is_visible_on_scanline(39, &lcd));
+
+        // WX=7 places the window at the leftmost column
+        assert_eq!(Ppu::window_x_for_scanline_pixel(0, &lcd), Some(0));
+        assert_eq!(Ppu::window_x_for_scanline_pixel(159, &lcd), Some(159));
+
+        lcd.wx = 10;
+        assert_eq!(Ppu::window_x_for_scanline_pixel(2, &lcd), None);
+        assert_eq!(Ppu::window_x_for_scanline_pixel(3, &lcd), Some(0));
+
+        lcd.wx = 167;
+        assert!(!Ppu::window_is_visible_on_scanline(40, &lcd));
+        assert_eq!(Ppu::window_x_for_scanline_pixel(159, &lcd), None);
+
+        // LCDC bit 0 hides the window as well as the background
+        lcd.wx = 7;
+        lcd.lcdc = 0xA0;
+        assert!(!Ppu::window_is_visible_on_scanline(40, &lcd));
     }
 
     #[test]
@@ -555,4 +1762,144 @@ mod tests {
         ppu.render_scanline(&lcd);
         assert_eq!(ppu.video_buffer[0], 0xFFAAAAAA);
     }
+
+    #[test]
+    fn test_cgb_palettes_ignore_dmg_registers() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lcdc = 0x93;
+        lcd.ly = 0;
+        lcd.bgp = 0xFF;
+        lcd.obp0 = 0xFF;
+        ppu.cgb_mode = true;
+
+        // BG palette 0: color 0 blue, color 1 green
+        for (i, value) in [0x00, 0x7C, 0xE0, 0x03].into_iter().enumerate() {
+            ppu.cgb_palettes.bg.write_index(i as Byte);
+            ppu.cgb_palettes.bg.write_data(value, true);
+        }
+        // OBJ palette 5 color 1: red
+        ppu.cgb_palettes.obj.write_index(0x80 | (5 * 8 + 2));
+        ppu.cgb_palettes.obj.write_data(0x1F, true);
+        ppu.cgb_palettes.obj.write_data(0x00, true);
+
+        // Tile 1: first row color 1 on the left half
+        ppu.vram[16] = 0xF0;
+        ppu.vram[0x1801] = 1;
+        ppu.line_sprites.push(OamEntry { y: 16, x: 8, tile: 1, flags: 0x15 });
+        ppu.render_scanline(&lcd);
+
+        assert_eq!(ppu.video_buffer[0], 0xFFFF0000);
+        assert_eq!(ppu.video_buffer[4], 0xFF0000FF);
+        assert_eq!(ppu.video_buffer[8], 0xFF00FF00);
+        assert_eq!(ppu.video_buffer[12], 0xFF0000FF);
+    }
+
+    #[test]
+    fn test_pixel_priority_buffer() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.lcdc = 0xB3; // BG, OBJ and window on
+        lcd.ly = 0;
+        lcd.wx = 7 + 16; // Window from x = 16
+        lcd.wy = 0;
+
+        // Tile 1: first row has color id 1 in every pixel
+        ppu.vram[16] = 0xFF;
+        // BG map: tile 1 in column 0, tile 0 (color 0) in column 1
+        ppu.vram[0x1800] = 1;
+
+        // Sprite tile 2: first row solid color 1
+        ppu.vram[32] = 0xFF;
+        let sprite = |x, flags| OamEntry { y: 16, x, tile: 2, flags };
+        ppu.line_sprites = vec![
+            sprite(8, 0x80),  // x 0-7 over opaque BG, hidden by its BG flag
+            sprite(16, 0x80), // x 8-15 over BG color 0, so it shows
+            sprite(32, 0x00), // x 24-31 over the window, no BG flag
+        ];
+        ppu.render_scanline(&lcd);
+
+        assert_eq!(ppu.pixel_source_at(0, 0), PixelSource::Background);
+        assert_eq!(ppu.pixel_priority_buffer[0], PRIORITY_SPRITE_BG_FLAG);
+        assert_eq!(ppu.pixel_source_at(8, 0), PixelSource::Sprite);
+        assert_eq!(ppu.pixel_priority_buffer[8], 2 | PRIORITY_SPRITE_BG_FLAG);
+        assert_eq!(ppu.pixel_source_at(16, 0), PixelSource::Window);
+        assert_eq!(ppu.pixel_source_at(24, 0), PixelSource::Sprite);
+        assert_eq!(ppu.pixel_priority_buffer[24], 2);
+        assert_eq!(ppu.pixel_source_at(40, 0), PixelSource::Window);
+        assert_eq!(ppu.pixel_priority_buffer[40], 1);
+        assert_eq!(ppu.pixel_source_at(200, 0), PixelSource::Background);
+    }
+
+    #[test]
+    fn test_render_sprites_only() {
+        let mut ppu = Ppu::new();
+        let mut lcd = Lcd::new();
+        lcd.obp0 = 0xE4;
+
+        // Tile 1: solid color id 3
+        for byte in &mut ppu.vram[16..32] {
+            *byte = 0xFF;
+        }
+        // OAM entry 0: screen position (20, 30)
+        ppu.oam[0] = 30 + 16;
+        ppu.oam[1] = 20 + 8;
+        ppu.oam[2] = 1;
+        ppu.oam[3] = 0x00;
+
+        let before = ppu.video_buffer.clone();
+        let layer = ppu.render_sprites_only(&lcd);
+        assert_eq!(layer.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(layer[30 * SCREEN_WIDTH + 20], 0xFF000000);
+        assert_eq!(layer[37 * SCREEN_WIDTH + 27], 0xFF000000);
+        assert_eq!(layer[30 * SCREEN_WIDTH + 28], 0x00000000);
+        assert_eq!(layer[29 * SCREEN_WIDTH + 20], 0x00000000);
+        assert_eq!(layer.iter().filter(|&&p| p != 0).count(), 64);
+        assert_eq!(ppu.video_buffer, before);
+
+        let composite = ppu.render_composite_debug(&lcd);
+        assert_eq!(composite.len(), SCREEN_WIDTH * 3 * SCREEN_HEIGHT);
+        assert_eq!(composite[30 * SCREEN_WIDTH * 3 + SCREEN_WIDTH * 2 + 20], 0xFF000000);
+        // Window defaults to WY=0, WX=0 so it covers the whole screen
+        assert_eq!(composite[SCREEN_WIDTH], 0xFFFFFFFF);
+    }
+
+    #[test]
+    fn test_tileset_image() {
+        let mut ppu = Ppu::new();
+        // Tile 0, row 0: shades 3,2,1,0,0,0,0,0
+        ppu.vram[0] = 0b1010_0000;
+        ppu.vram[1] = 0b1100_0000;
+        // Tile 129 (0x8810), row 7: shade 3 in the last column
+        ppu.vram[129 * 16 + 14] = 0x01;
+        ppu.vram[129 * 16 + 15] = 0x01;
+
+        let palette = DmgPalette::GRAYSCALE;
+        let sheet = ppu.generate_tileset_image(&palette, 0);
+        assert_eq!(sheet.len(), 128 * 192);
+        assert_eq!(&sheet[0..4], &[palette.argb(3), palette.argb(2), palette.argb(1), palette.argb(0)]);
+        assert_eq!(sheet[TILESET_WIDTH], palette.argb(0));
+        // Tile 129 sits in row 8, column 1
+        assert_eq!(sheet[(8 * 8 + 7) * TILESET_WIDTH + 8 + 7], palette.argb(3));
+
+        let grid = ppu.generate_tileset_image_with_grid(&palette, 0, 0xFFFF0000);
+        assert_eq!(grid.len(), TILESET_GRID_WIDTH * TILESET_GRID_HEIGHT);
+        assert_eq!(grid[0], palette.argb(3));
+        assert_eq!(grid[8], 0xFFFF0000);
+        assert_eq!(grid[8 * TILESET_GRID_WIDTH], 0xFFFF0000);
+        assert_eq!(grid[(8 * 9 + 7) * TILESET_GRID_WIDTH + 9 + 7], palette.argb(3));
+    }
+
+    #[test]
+    fn test_tileset_image_bank_1() {
+        let mut ppu = Ppu::new();
+        // Tile 0 of bank 1 only, row 0: shade 3 in the first column
+        ppu.vram[VRAM_BANK_SIZE] = 0x80;
+        ppu.vram[VRAM_BANK_SIZE + 1] = 0x80;
+
+        let palette = DmgPalette::GRAYSCALE;
+        assert_eq!(ppu.generate_tileset_image(&palette, 0)[0], palette.argb(0));
+        assert_eq!(ppu.generate_tileset_image(&palette, 1)[0], palette.argb(3));
+        assert_eq!(ppu.generate_tileset_image_with_grid(&palette, 1, 0xFFFF0000)[0], palette.argb(3));
+    }
 }
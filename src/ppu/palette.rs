@@ -0,0 +1,156 @@
+//! CGB Color Palettes
+//!
+//! This module implements the CGB palette RAM: 8 background and 8 object
+//! palettes of 4 RGB555 colors each, written through BCPS/BCPD
+//! (0xFF68-0xFF69) and OCPS/OCPD (0xFF6A-0xFF6B).
+
+use crate::common::Byte;
+
+/// Palettes held by each palette RAM
+pub const CGB_PALETTE_COUNT: usize = 8;
+/// Bytes per palette RAM (8 palettes * 4 colors * 2 bytes)
+const PALETTE_RAM_SIZE: usize = CGB_PALETTE_COUNT * 4 * 2;
+/// Index register bit 7: advance the index after each data write
+const AUTO_INCREMENT: Byte = 0x80;
+/// Index register bits 0-5: byte addressed by the data register
+const INDEX_MASK: Byte = 0x3F;
+
+/// Convert an RGB555 color (red in bits 0-4) to ARGB8888
+///
+/// Each 5-bit channel is scaled to 8 bits by repeating its top bits, so
+/// 0x1F maps to 0xFF. No LCD color correction is applied.
+pub fn rgb555_to_argb(color: u16) -> u32 {
+    let scale = |channel: u16| {
+        let channel = (channel & 0x1F) as u32;
+        (channel << 3) | (channel >> 2)
+    };
+    0xFF000000 | (scale(color) << 16) | (scale(color >> 5) << 8) | scale(color >> 10)
+}
+
+/// One palette RAM and its index register
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct PaletteRam {
+    /// Colors as little-endian RGB555, 8 bytes per palette
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
+    data: [Byte; PALETTE_RAM_SIZE],
+    /// Index register (BCPS/OCPS): byte index and auto-increment flag
+    index: Byte,
+}
+
+impl Default for PaletteRam {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PaletteRam {
+    /// Create a palette RAM with every color white
+    pub fn new() -> Self {
+        Self {
+            data: [0xFF; PALETTE_RAM_SIZE],
+            index: 0,
+        }
+    }
+
+    /// Read the index register (bit 6 is unused and reads 1)
+    pub fn read_index(&self) -> Byte {
+        self.index | 0x40
+    }
+
+    /// Write the index register
+    pub fn write_index(&mut self, value: Byte) {
+        self.index = value & (AUTO_INCREMENT | INDEX_MASK);
+    }
+
+    /// Read the byte selected by the index register
+    pub fn read_data(&self) -> Byte {
+        self.data[(self.index & INDEX_MASK) as usize]
+    }
+
+    /// Write the byte selected by the index register
+    ///
+    /// With `store` false (palette RAM locked during mode 3) the byte is
+    /// dropped, but the index still auto-increments.
+    pub fn write_data(&mut self, value: Byte, store: bool) {
+        if store {
+            self.data[(self.index & INDEX_MASK) as usize] = value;
+        }
+        if self.index & AUTO_INCREMENT != 0 {
+            self.index = AUTO_INCREMENT | (self.index.wrapping_add(1) & INDEX_MASK);
+        }
+    }
+
+    /// RGB555 value of `color_id` (0-3) in `palette` (0-7)
+    pub fn color(&self, palette: u8, color_id: u8) -> u16 {
+        let offset = (palette as usize % CGB_PALETTE_COUNT) * 8 + (color_id & 0x03) as usize * 2;
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    /// ARGB8888 value of `color_id` (0-3) in `palette` (0-7)
+    #[inline]
+    pub fn argb(&self, palette: u8, color_id: u8) -> u32 {
+        rgb555_to_argb(self.color(palette, color_id))
+    }
+}
+
+/// Background and object palette RAM
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct CgbPalettes {
+    /// Background/window palettes (BCPS/BCPD)
+    pub bg: PaletteRam,
+    /// Object palettes (OCPS/OCPD)
+    pub obj: PaletteRam,
+}
+
+impl CgbPalettes {
+    /// Create both palette RAMs with every color white
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rgb555_to_argb() {
+        assert_eq!(rgb555_to_argb(0x0000), 0xFF000000);
+        assert_eq!(rgb555_to_argb(0x7FFF), 0xFFFFFFFF);
+        assert_eq!(rgb555_to_argb(0x001F), 0xFFFF0000);
+        assert_eq!(rgb555_to_argb(0x03E0), 0xFF00FF00);
+        assert_eq!(rgb555_to_argb(0x7C00), 0xFF0000FF);
+        assert_eq!(rgb555_to_argb(0x0010), 0xFF840000);
+    }
+
+    #[test]
+    fn test_palette_ram_auto_increment() {
+        let mut ram = PaletteRam::new();
+        ram.write_index(0x80 | 0x3E);
+        assert_eq!(ram.read_index(), 0xFE);
+
+        ram.write_data(0x1F, true);
+        ram.write_data(0x00, true);
+        assert_eq!(ram.read_index(), 0xC0);
+        assert_eq!(ram.color(7, 3), 0x001F);
+
+        // Without auto-increment the index stays put
+        ram.write_index(0x08);
+        ram.write_data(0xE0, true);
+        ram.write_data(0x03, true);
+        assert_eq!(ram.read_index(), 0x48);
+        assert_eq!(ram.read_data(), 0x03);
+        assert_eq!(ram.color(1, 0), 0xFF03);
+    }
+
+    #[test]
+    fn test_palette_ram_locked_write_still_increments() {
+        let mut ram = PaletteRam::new();
+        ram.write_index(0x80);
+        ram.write_data(0x00, false);
+        assert_eq!(ram.read_index(), 0xC1);
+        assert_eq!(ram.color(0, 0), 0xFFFF);
+    }
+}
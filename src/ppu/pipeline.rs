@@ -1,10 +1,392 @@
 //! Pixel Pipeline
 //!
-//! This module implements the pixel FIFO and fetch state machine.
-
-// TODO: Implement in task 15.4
-// - PixelFifo struct with push/pop operations
-// - FetchState enum (Tile, Data0, Data1, Idle, Push)
-// - PixelFifoContext struct
-// - Tile fetching from background/window tile map
-// - Sprite pixel mixing
+//! This module implements the pixel FIFO and fetch state machine that run
+//! during mode 3. The fetcher reads one tile row every 6 T-cycles and
+//! pushes 8 pixels once the background FIFO is empty; one pixel is shifted
+//! out to the LCD per T-cycle. Mode 3 ends when the 160th pixel is out, so
+//! its length follows from fine scroll discards, window restarts and sprite
+//! fetch stalls.
+
+use super::{
+    compute_sprite_fifo_penalty, OamEntry, PixelSource, Ppu, ATTR_PRIORITY, ATTR_X_FLIP, PRIORITY_BG_OVER_OBJ,
+    PRIORITY_SPRITE_BG_FLAG, SCREEN_WIDTH, VRAM_BANK_SIZE,
+};
+use crate::common::{bit, Byte};
+use crate::lcd::Lcd;
+
+/// Pixels held by a FIFO (one tile row)
+pub const FIFO_CAPACITY: usize = 8;
+/// T-cycles lost at the start of each line to the discarded first tile fetch
+const FIRST_FETCH_CYCLES: u32 = 6;
+/// T-cycles spent in each of the Tile, Data0 and Data1 fetcher steps
+const FETCH_STEP_CYCLES: u8 = 2;
+
+/// One pixel waiting in a FIFO
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct FifoPixel {
+    /// Raw 2-bit color id (0 is transparent for sprites)
+    pub color_id: u8,
+    /// CGB BG map attributes for background pixels, OAM flags for sprite pixels
+    pub attrs: u8,
+}
+
+/// Fixed-size pixel queue shifted out one pixel per T-cycle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct PixelFifo {
+    pixels: [FifoPixel; FIFO_CAPACITY],
+    head: u8,
+    len: u8,
+}
+
+impl PixelFifo {
+    /// Create an empty FIFO
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of queued pixels
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Check if no pixels are queued
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Drop every queued pixel
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Append a pixel; returns false if the FIFO is full
+    pub fn push(&mut self, pixel: FifoPixel) -> bool {
+        if self.len() == FIFO_CAPACITY {
+            return false;
+        }
+        let index = (self.head as usize + self.len()) % FIFO_CAPACITY;
+        self.pixels[index] = pixel;
+        self.len += 1;
+        true
+    }
+
+    /// Remove the oldest pixel
+    pub fn pop(&mut self) -> Option<FifoPixel> {
+        if self.is_empty() {
+            return None;
+        }
+        let pixel = self.pixels[self.head as usize];
+        self.head = (self.head + 1) % FIFO_CAPACITY as u8;
+        self.len -= 1;
+        Some(pixel)
+    }
+
+    /// Queued pixel `index` places from the front
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut FifoPixel> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(&mut self.pixels[(self.head as usize + index) % FIFO_CAPACITY])
+    }
+}
+
+/// Background fetcher step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub enum FetchState {
+    /// Read the tile number (and CGB attributes) from the tile map
+    #[default]
+    Tile,
+    /// Read the low bit plane of the tile row
+    Data0,
+    /// Read the high bit plane of the tile row
+    Data1,
+    /// Push the row into the background FIFO once it is empty
+    Push,
+}
+
+/// Mode 3 pipeline state for the current line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+pub struct PixelFifoContext {
+    /// Current fetcher step
+    pub fetch_state: FetchState,
+    /// T-cycles spent in the current step
+    pub fetch_ticks: u8,
+    /// Tile column being fetched, counted from the first tile of the line or window
+    pub fetch_x: u8,
+    /// Tile number read by the Tile step
+    pub tile_index: u8,
+    /// CGB BG map attributes read by the Tile step
+    pub tile_attrs: u8,
+    /// Low bit plane read by Data0
+    pub tile_lo: u8,
+    /// High bit plane read by Data1
+    pub tile_hi: u8,
+    /// Background/window pixels
+    pub bg_fifo: PixelFifo,
+    /// Sprite pixels, shifted out alongside the background
+    pub obj_fifo: PixelFifo,
+    /// Next screen column to output
+    pub x: u8,
+    /// Pixels still to drop before output (SCX fine scroll, or WX < 7)
+    pub discard: u8,
+    /// The fetcher has switched to the window on this line, so window pixels
+    /// are drawn and `window_line` advances when the line ends
+    pub window: bool,
+    /// T-cycles the whole pipeline is paused (first fetch, sprite fetches)
+    pub stall: u32,
+    /// Next entry of `line_sprites` waiting to be fetched
+    pub sprite_index: u8,
+}
+
+impl Ppu {
+    /// Reset the pipeline as mode 3 begins
+    pub(super) fn start_transfer(&mut self, lcd: &Lcd) {
+        self.fifo = PixelFifoContext {
+            discard: lcd.scx % 8,
+            stall: FIRST_FETCH_CYCLES,
+            ..PixelFifoContext::default()
+        };
+    }
+
+    /// Advance mode 3 by one T-cycle; returns true once the line is finished
+    pub(super) fn transfer_tick(&mut self, lcd: &Lcd) -> bool {
+        if self.fifo.stall > 0 {
+            self.fifo.stall -= 1;
+            return false;
+        }
+
+        self.fetcher_tick(lcd);
+        if self.fifo.bg_fifo.is_empty() {
+            return false;
+        }
+
+        // Fine scroll: the first pixels of the line are dropped
+        if self.fifo.discard > 0 {
+            self.fifo.bg_fifo.pop();
+            self.fifo.discard -= 1;
+            return false;
+        }
+
+        // Reaching the window restarts the fetcher on the window tile map
+        let x = self.fifo.x;
+        if !self.fifo.window && Self::window_is_visible_on_scanline(lcd.ly, lcd) {
+            if let Some(win_x) = Self::window_x_for_scanline_pixel(x, lcd) {
+                self.fifo.window = true;
+                self.fifo.bg_fifo.clear();
+                self.fifo.fetch_state = FetchState::Tile;
+                self.fifo.fetch_ticks = 0;
+                self.fifo.fetch_x = 0;
+                // With WX < 7 the window starts left of the screen
+                self.fifo.discard = win_x;
+                self.fetcher_tick(lcd);
+                return false;
+            }
+        }
+
+        // Sprites starting at this column are fetched while output stalls
+        let mut penalty = 0;
+        while let Some(&sprite) = self.line_sprites.get(self.fifo.sprite_index as usize) {
+            if sprite.x as u16 > x as u16 + 8 {
+                break;
+            }
+            self.fifo.sprite_index += 1;
+            if lcd.sprites_enabled() {
+                self.fetch_sprite(lcd, &sprite);
+                penalty += compute_sprite_fifo_penalty(sprite.x, lcd.scx);
+            }
+        }
+        if penalty > 0 {
+            self.fifo.stall = penalty - 1;
+            return false;
+        }
+
+        let bg = self.fifo.bg_fifo.pop().unwrap_or_default();
+        let obj = self.fifo.obj_fifo.pop();
+        self.output_pixel(lcd, bg, obj);
+
+        self.fifo.x += 1;
+        if self.fifo.x as usize == SCREEN_WIDTH {
+            if self.fifo.window {
+                self.window_line += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Run the pipeline over a whole line at once
+    #[cfg(test)]
+    pub(super) fn render_scanline(&mut self, lcd: &Lcd) {
+        if lcd.ly as usize >= super::SCREEN_HEIGHT {
+            return;
+        }
+        self.start_transfer(lcd);
+        while !self.transfer_tick(lcd) {}
+    }
+
+    /// Advance the background fetcher by one T-cycle
+    fn fetcher_tick(&mut self, lcd: &Lcd) {
+        let fifo = &mut self.fifo;
+        if fifo.fetch_state == FetchState::Push {
+            if fifo.bg_fifo.is_empty() {
+                let flip = bit(fifo.tile_attrs, ATTR_X_FLIP);
+                for i in 0..8 {
+                    let shift = if flip { i } else { 7 - i };
+                    let color_id = ((fifo.tile_hi >> shift) & 1) << 1 | ((fifo.tile_lo >> shift) & 1);
+                    fifo.bg_fifo.push(FifoPixel { color_id, attrs: fifo.tile_attrs });
+                }
+                fifo.fetch_x = fifo.fetch_x.wrapping_add(1);
+                fifo.fetch_state = FetchState::Tile;
+            }
+            return;
+        }
+
+        fifo.fetch_ticks += 1;
+        if fifo.fetch_ticks < FETCH_STEP_CYCLES {
+            return;
+        }
+        fifo.fetch_ticks = 0;
+
+        match fifo.fetch_state {
+            FetchState::Tile => {
+                let (map_offset, _) = self.fetch_map_position(lcd);
+                self.fifo.tile_index = self.vram[map_offset];
+                self.fifo.tile_attrs = if self.cgb_mode { self.vram[VRAM_BANK_SIZE + map_offset] } else { 0 };
+                self.fifo.fetch_state = FetchState::Data0;
+            }
+            FetchState::Data0 => {
+                self.fifo.tile_lo = self.vram[self.fetch_row_offset(lcd)];
+                self.fifo.fetch_state = FetchState::Data1;
+            }
+            FetchState::Data1 => {
+                self.fifo.tile_hi = self.vram[self.fetch_row_offset(lcd) + 1];
+                self.fifo.fetch_state = FetchState::Push;
+            }
+            FetchState::Push => {}
+        }
+    }
+
+    /// VRAM offset of the tile map entry being fetched, and the pixel row within its tile
+    fn fetch_map_position(&self, lcd: &Lcd) -> (usize, u8) {
+        let (map, map_x, y) = if self.fifo.window {
+            (lcd.window_tile_map(), self.fifo.fetch_x, self.window_line)
+        } else {
+            (lcd.bg_tile_map(), (lcd.scx / 8).wrapping_add(self.fifo.fetch_x), lcd.ly.wrapping_add(lcd.scy))
+        };
+        let map_addr = map + (y / 8) as u16 * 32 + (map_x % 32) as u16;
+        ((map_addr - 0x8000) as usize, y % 8)
+    }
+
+    /// VRAM offset of the low bit plane of the tile row being fetched
+    fn fetch_row_offset(&self, lcd: &Lcd) -> usize {
+        let (_, row) = self.fetch_map_position(lcd);
+        Self::bg_tile_row_offset(lcd.bg_tile_data(), self.fifo.tile_index, self.fifo.tile_attrs, row)
+    }
+
+    /// Merge a sprite's row into the sprite FIFO
+    ///
+    /// Slots already holding an opaque pixel keep it, so sprites fetched
+    /// earlier (lower X, then OAM order) stay on top. Columns left of the
+    /// current pixel are clipped.
+    fn fetch_sprite(&mut self, lcd: &Lcd, sprite: &OamEntry) {
+        let offset = self.sprite_row_offset(sprite, lcd, lcd.ly);
+        let (lo, hi) = (self.vram[offset], self.vram[offset + 1]);
+        let skip = (self.fifo.x as u16 + 8 - sprite.x as u16) as usize;
+
+        for column in skip..8 {
+            let shift = if sprite.x_flip() { column } else { 7 - column };
+            let pixel = FifoPixel {
+                color_id: ((hi >> shift) & 1) << 1 | ((lo >> shift) & 1),
+                attrs: sprite.flags,
+            };
+            let slot = column - skip;
+            match self.fifo.obj_fifo.get_mut(slot) {
+                Some(queued) if queued.color_id == 0 => *queued = pixel,
+                Some(_) => {}
+                None => {
+                    self.fifo.obj_fifo.push(pixel);
+                }
+            }
+        }
+    }
+
+    /// Mix a background and sprite pixel and write it at the current column
+    fn output_pixel(&mut self, lcd: &Lcd, bg: FifoPixel, obj: Option<FifoPixel>) {
+        if self.skip_frame {
+            return;
+        }
+        let mut bg_color_id = 0u8;
+        let mut bg_attrs = 0u8;
+        let mut argb = self.bg_argb(0, 0, 0);
+        let mut source = PixelSource::Background;
+        let mut priority_flags = 0u8;
+
+        if lcd.bg_window_enabled() {
+            bg_color_id = bg.color_id;
+            bg_attrs = bg.attrs;
+            argb = self.bg_argb(lcd.bg_color(bg.color_id), bg.color_id, bg.attrs);
+            if self.fifo.window {
+                source = PixelSource::Window;
+            }
+        }
+
+        let bg_over_obj = bit(bg_attrs, ATTR_PRIORITY);
+        if bg_over_obj {
+            priority_flags |= PRIORITY_BG_OVER_OBJ;
+        }
+
+        if let Some(obj) = obj.filter(|obj| obj.color_id != 0 && lcd.sprites_enabled()) {
+            let sprite = OamEntry { flags: obj.attrs, ..OamEntry::default() };
+            // Sprite pixel is visible if:
+            // - neither the sprite nor the BG tile (CGB) asks for BG priority, OR
+            // - BG color id is 0 (white/transparent for OBJ priority)
+            let priority = sprite.bg_priority();
+            if priority {
+                priority_flags |= PRIORITY_SPRITE_BG_FLAG;
+            }
+            if !(priority || bg_over_obj) || bg_color_id == 0 {
+                let shade = if sprite.palette_number() {
+                    lcd.sprite_color_1(obj.color_id)
+                } else {
+                    lcd.sprite_color_0(obj.color_id)
+                };
+                argb = self.sprite_argb(shade, obj.color_id, &sprite);
+                source = PixelSource::Sprite;
+            }
+        }
+
+        let index = lcd.ly as usize * SCREEN_WIDTH + self.fifo.x as usize;
+        self.video_buffer[index] = argb;
+        self.pixel_priority_buffer[index] = source as Byte | priority_flags;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_fifo_push_pop() {
+        let mut fifo = PixelFifo::new();
+        assert!(fifo.is_empty());
+        for color_id in 0..8 {
+            assert!(fifo.push(FifoPixel { color_id, attrs: 0 }));
+        }
+        assert!(!fifo.push(FifoPixel::default()));
+        assert_eq!(fifo.len(), FIFO_CAPACITY);
+
+        assert_eq!(fifo.pop().map(|p| p.color_id), Some(0));
+        assert!(fifo.push(FifoPixel { color_id: 8, attrs: 0 }));
+        fifo.get_mut(0).unwrap().attrs = 0x80;
+        assert_eq!(fifo.pop(), Some(FifoPixel { color_id: 1, attrs: 0x80 }));
+
+        let rest: Vec<u8> = core::iter::from_fn(|| fifo.pop()).map(|p| p.color_id).collect();
+        assert_eq!(rest, [2, 3, 4, 5, 6, 7, 8]);
+        assert!(fifo.get_mut(0).is_none());
+    }
+}
@@ -0,0 +1,335 @@
+//! Game Boy Printer
+//!
+//! This module emulates the Game Boy Printer as a serial device. Packets are
+//! parsed byte by byte and completed prints are decoded into pixel strips.
+
+use crate::serial::SerialDevice;
+use alloc::vec::Vec;
+
+/// Packet preamble bytes
+const MAGIC: [u8; 2] = [0x88, 0x33];
+/// Command: clear the image buffer
+const CMD_INIT: u8 = 0x01;
+/// Command: print the buffered image
+const CMD_PRINT: u8 = 0x02;
+/// Command: append image data
+const CMD_DATA: u8 = 0x04;
+/// Command: query status
+const CMD_STATUS: u8 = 0x0F;
+
+/// Response to the keepalive byte: printer connected
+const KEEPALIVE_RESPONSE: u8 = 0x81;
+/// Status bit 0: last packet had a bad checksum
+const STATUS_CHECKSUM_ERROR: u8 = 0x01;
+/// Status bit 3: image data waiting to be printed
+const STATUS_UNPROCESSED_DATA: u8 = 0x08;
+
+/// Printer paper width in tiles
+const WIDTH_TILES: usize = 20;
+/// Bytes per 8x8 2bpp tile
+const TILE_BYTES: usize = 16;
+/// Palette used when a print command specifies 0x00
+const DEFAULT_PALETTE: u8 = 0xE4;
+
+/// Position within the packet currently being received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PrinterState {
+    MagicA,
+    MagicB,
+    Command,
+    Compression,
+    LengthLo,
+    LengthHi,
+    Data,
+    ChecksumLo,
+    ChecksumHi,
+    KeepAlive,
+    Status,
+}
+
+/// Game Boy Printer attached to the serial port
+#[derive(Debug, Clone)]
+pub struct GbPrinter {
+    /// Packet parser state
+    state: PrinterState,
+    /// Command of the current packet
+    command: u8,
+    /// Current packet payload is RLE compressed
+    compressed: bool,
+    /// Payload length of the current packet
+    length: u16,
+    /// Payload of the current packet
+    payload: Vec<u8>,
+    /// Running checksum of the current packet
+    checksum: u16,
+    /// Checksum received from the Game Boy
+    received_checksum: u16,
+    /// Status byte returned at the end of each packet
+    status: u8,
+    /// Received image data (2bpp tiles, 20 tiles per row)
+    image_data: Vec<u8>,
+    /// Completed prints (one shade 0-3 per pixel, 160 pixels wide)
+    printed: Vec<Vec<u8>>,
+}
+
+impl GbPrinter {
+    /// Create a printer with an empty buffer
+    pub fn new() -> Self {
+        Self {
+            state: PrinterState::MagicA,
+            command: 0,
+            compressed: false,
+            length: 0,
+            payload: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            status: 0,
+            image_data: Vec::new(),
+            printed: Vec::new(),
+        }
+    }
+
+    /// Get every strip printed so far as 160-pixel-wide rows of shades (0-3)
+    pub fn get_printed_images(&self) -> Vec<Vec<u8>> {
+        self.printed.clone()
+    }
+
+    /// Feed one byte of the packet and return the byte shifted out in reply
+    fn receive(&mut self, byte: u8) -> u8 {
+        match self.state {
+            PrinterState::MagicA => {
+                if byte == MAGIC[0] {
+                    self.state = PrinterState::MagicB;
+                }
+            }
+            PrinterState::MagicB => {
+                self.state = if byte == MAGIC[1] {
+                    PrinterState::Command
+                } else {
+                    PrinterState::MagicA
+                };
+            }
+            PrinterState::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                self.payload.clear();
+                self.state = PrinterState::Compression;
+            }
+            PrinterState::Compression => {
+                self.compressed = byte & 0x01 != 0;
+                self.add_checksum(byte);
+                self.state = PrinterState::LengthLo;
+            }
+            PrinterState::LengthLo => {
+                self.length = byte as u16;
+                self.add_checksum(byte);
+                self.state = PrinterState::LengthHi;
+            }
+            PrinterState::LengthHi => {
+                self.length |= (byte as u16) << 8;
+                self.add_checksum(byte);
+                self.state = if self.length == 0 {
+                    PrinterState::ChecksumLo
+                } else {
+                    PrinterState::Data
+                };
+            }
+            PrinterState::Data => {
+                self.payload.push(byte);
+                self.add_checksum(byte);
+                if self.payload.len() == self.length as usize {
+                    self.state = PrinterState::ChecksumLo;
+                }
+            }
+            PrinterState::ChecksumLo => {
+                self.received_checksum = byte as u16;
+                self.state = PrinterState::ChecksumHi;
+            }
+            PrinterState::ChecksumHi => {
+                self.received_checksum |= (byte as u16) << 8;
+                self.state = PrinterState::KeepAlive;
+            }
+            PrinterState::KeepAlive => {
+                self.state = PrinterState::Status;
+                return KEEPALIVE_RESPONSE;
+            }
+            PrinterState::Status => {
+                self.state = PrinterState::MagicA;
+                self.finish_packet();
+                return self.status;
+            }
+        }
+        0x00
+    }
+
+    /// Accumulate a byte into the packet checksum
+    fn add_checksum(&mut self, byte: u8) {
+        self.checksum = self.checksum.wrapping_add(byte as u16);
+    }
+
+    /// Execute the command of a fully received packet
+    fn finish_packet(&mut self) {
+        if self.checksum != self.received_checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match self.command {
+            CMD_INIT => {
+                self.image_data.clear();
+                self.status = 0;
+            }
+            CMD_DATA => {
+                let payload = core::mem::take(&mut self.payload);
+                if self.compressed {
+                    decompress(&payload, &mut self.image_data);
+                } else {
+                    self.image_data.extend_from_slice(&payload);
+                }
+                if !self.image_data.is_empty() {
+                    self.status |= STATUS_UNPROCESSED_DATA;
+                }
+            }
+            CMD_PRINT => {
+                let palette = match self.payload.get(2) {
+                    Some(&0) | None => DEFAULT_PALETTE,
+                    Some(&palette) => palette,
+                };
+                let image = decode_tiles(&self.image_data, palette);
+                self.printed.push(image);
+                self.image_data.clear();
+                self.status &= !STATUS_UNPROCESSED_DATA;
+            }
+            CMD_STATUS => {}
+            _ => {}
+        }
+    }
+}
+
+impl Default for GbPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialDevice for GbPrinter {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.receive(byte)
+    }
+}
+
+/// Expand printer RLE data
+///
+/// A control byte with bit 7 clear is followed by `n + 1` literal bytes;
+/// with bit 7 set, the next byte repeats `(n & 0x7F) + 2` times.
+fn decompress(data: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < data.len() {
+        let control = data[i];
+        i += 1;
+        if control & 0x80 != 0 {
+            let Some(&value) = data.get(i) else { break };
+            i += 1;
+            let count = (control & 0x7F) as usize + 2;
+            out.extend(core::iter::repeat_n(value, count));
+        } else {
+            let end = (i + control as usize + 1).min(data.len());
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+}
+
+/// Convert 2bpp tile rows into one palette-mapped shade per pixel
+fn decode_tiles(data: &[u8], palette: u8) -> Vec<u8> {
+    let row_bytes = WIDTH_TILES * TILE_BYTES;
+    let width = WIDTH_TILES * 8;
+    let rows = data.len() / row_bytes;
+    let mut pixels = alloc::vec![0u8; rows * 8 * width];
+
+    for (tile_index, tile) in data.chunks_exact(TILE_BYTES).take(rows * WIDTH_TILES).enumerate() {
+        let tile_x = (tile_index % WIDTH_TILES) * 8;
+        let tile_y = (tile_index / WIDTH_TILES) * 8;
+        for line in 0..8 {
+            let lo = tile[line * 2];
+            let hi = tile[line * 2 + 1];
+            for px in 0..8 {
+                let color_id = ((hi >> (7 - px)) & 1) << 1 | ((lo >> (7 - px)) & 1);
+                let shade = (palette >> (color_id * 2)) & 0x03;
+                pixels[(tile_y + line) * width + tile_x + px] = shade;
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Build a complete packet (including the two trailing reply bytes)
+    fn packet(command: u8, compressed: bool, payload: &[u8]) -> Vec<u8> {
+        let len = payload.len() as u16;
+        let mut bytes = vec![0x88, 0x33, command, compressed as u8, len as u8, (len >> 8) as u8];
+        bytes.extend_from_slice(payload);
+        let checksum = bytes[2..].iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        bytes
+    }
+
+    /// Send a packet and return the keepalive and status replies
+    fn send(printer: &mut GbPrinter, bytes: &[u8]) -> (u8, u8) {
+        let replies: Vec<u8> = bytes.iter().map(|&b| printer.exchange(b)).collect();
+        (replies[replies.len() - 2], replies[replies.len() - 1])
+    }
+
+    #[test]
+    fn test_print_sequence() {
+        let mut printer = GbPrinter::new();
+        assert_eq!(send(&mut printer, &packet(CMD_INIT, false, &[])), (0x81, 0x00));
+
+        // One tile row: first tile solid color 3, the rest color 1
+        let mut row = vec![0xFF; TILE_BYTES];
+        for _ in 1..WIDTH_TILES {
+            row.extend_from_slice(&[0xFF, 0x00].repeat(8));
+        }
+        let (_, status) = send(&mut printer, &packet(CMD_DATA, false, &row));
+        assert_eq!(status & STATUS_UNPROCESSED_DATA, STATUS_UNPROCESSED_DATA);
+        send(&mut printer, &packet(CMD_DATA, false, &[]));
+
+        let (_, status) = send(&mut printer, &packet(CMD_PRINT, false, &[0x01, 0x13, 0xE4, 0x40]));
+        assert_eq!(status, 0x00);
+
+        let images = printer.get_printed_images();
+        assert_eq!(images.len(), 1);
+        let image = &images[0];
+        assert_eq!(image.len(), 160 * 8);
+        assert_eq!(image[0], 3);
+        assert_eq!(image[7 * 160 + 7], 3);
+        assert_eq!(image[8], 1);
+        assert_eq!(image[159], 1);
+    }
+
+    #[test]
+    fn test_compressed_data() {
+        let mut out = Vec::new();
+        decompress(&[0x81, 0xAA, 0x01, 0x11, 0x22], &mut out);
+        assert_eq!(out, vec![0xAA, 0xAA, 0xAA, 0x11, 0x22]);
+    }
+
+    #[test]
+    fn test_checksum_error_ignores_packet() {
+        let mut printer = GbPrinter::new();
+        let mut bytes = packet(CMD_DATA, false, &[0xFF; 4]);
+        let checksum_index = bytes.len() - 4;
+        bytes[checksum_index] ^= 0xFF;
+
+        let (keepalive, status) = send(&mut printer, &bytes);
+        assert_eq!(keepalive, 0x81);
+        assert_eq!(status & STATUS_CHECKSUM_ERROR, STATUS_CHECKSUM_ERROR);
+        assert!(printer.image_data.is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+//! Opcode Profiler
+//!
+//! Counts how often each opcode is executed, to find out which instructions
+//! a ROM leans on (and so which emulator paths are hot).
+
+use crate::cpu::instructions::{cb_instruction_by_opcode, instruction_by_opcode};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Execution counts per opcode
+///
+/// CB-prefixed instructions are counted in `cb_counts` by their second
+/// byte; the 0xCB prefix itself is not counted in `counts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeProfiler {
+    /// Counts for the main opcode table
+    pub counts: [u64; 256],
+    /// Counts for the CB-prefixed table
+    pub cb_counts: [u64; 256],
+}
+
+impl Default for OpcodeProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpcodeProfiler {
+    /// Create a profiler with every count at zero
+    pub fn new() -> Self {
+        Self {
+            counts: [0; 256],
+            cb_counts: [0; 256],
+        }
+    }
+
+    /// Count one execution of `opcode` (from the CB table if `is_cb`)
+    pub fn record(&mut self, opcode: u8, is_cb: bool) {
+        let table = if is_cb { &mut self.cb_counts } else { &mut self.counts };
+        table[opcode as usize] = table[opcode as usize].saturating_add(1);
+    }
+
+    /// Total instructions recorded
+    pub fn total(&self) -> u64 {
+        self.counts.iter().chain(self.cb_counts.iter()).sum()
+    }
+
+    /// The `n` most executed opcodes as `(opcode, is_cb, count)`
+    ///
+    /// Sorted by count descending, then main table before CB table, then by
+    /// opcode. Opcodes that never ran are left out.
+    pub fn top_n(&self, n: usize) -> Vec<(u8, bool, u64)> {
+        let mut entries: Vec<(u8, bool, u64)> = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(opcode, &count)| (opcode as u8, false, count))
+            .chain(self.cb_counts.iter().enumerate().map(|(opcode, &count)| (opcode as u8, true, count)))
+            .filter(|&(_, _, count)| count > 0)
+            .collect();
+        entries.sort_by(|a, b| b.2.cmp(&a.2).then(a.1.cmp(&b.1)).then(a.0.cmp(&b.0)));
+        entries.truncate(n);
+        entries
+    }
+
+    /// Every executed opcode as a Markdown table, most frequent first
+    pub fn as_markdown_table(&self) -> String {
+        let total = self.total();
+        let mut table = String::from("| Opcode | Instruction | Count | Share |\n|---|---|---:|---:|\n");
+        for (opcode, is_cb, count) in self.top_n(512) {
+            let (code, inst) = if is_cb {
+                (format!("CB {:02X}", opcode), cb_instruction_by_opcode(opcode))
+            } else {
+                (format!("{:02X}", opcode), instruction_by_opcode(opcode))
+            };
+            let share = count as f64 * 100.0 / total as f64;
+            let _ = writeln!(table, "| {} | {} | {} | {:.1}% |", code, inst, count, share);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_top_n() {
+        let mut profiler = OpcodeProfiler::new();
+        for _ in 0..3 {
+            profiler.record(0x00, false);
+        }
+        profiler.record(0x7C, true);
+        profiler.record(0x7C, true);
+        profiler.record(0x3E, false);
+        profiler.record(0x7C, false);
+
+        assert_eq!(profiler.total(), 7);
+        assert_eq!(
+            profiler.top_n(3),
+            [(0x00, false, 3), (0x7C, true, 2), (0x3E, false, 1)]
+        );
+        assert_eq!(profiler.top_n(10).len(), 4);
+        assert!(OpcodeProfiler::new().top_n(5).is_empty());
+    }
+
+    #[test]
+    fn test_as_markdown_table() {
+        let mut profiler = OpcodeProfiler::new();
+        profiler.record(0x00, false);
+        profiler.record(0x00, false);
+        profiler.record(0x00, false);
+        profiler.record(0x7C, true);
+
+        let table = profiler.as_markdown_table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[2], "| 00 | NOP | 3 | 75.0% |");
+        assert_eq!(lines[3], "| CB 7C | BIT 7, H | 1 | 25.0% |");
+    }
+}
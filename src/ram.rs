@@ -1,21 +1,34 @@
 //! RAM
 //!
 //! This module implements Work RAM (WRAM) and High RAM (HRAM) for the Game Boy.
+//! On CGB, WRAM has 8 banks of 4KB; bank 0 is fixed at 0xC000 and banks 1-7
+//! are selected at 0xD000 through SVBK (0xFF70).
 
 use crate::common::{Byte, Word};
 
-/// WRAM size: 8KB (0xC000-0xDFFF)
+/// WRAM address window: 8KB (0xC000-0xDFFF)
 const WRAM_SIZE: usize = 0x2000;
 
+/// WRAM bank size: 4KB
+const WRAM_BANK_SIZE: usize = 0x1000;
+
+/// Number of WRAM banks on CGB
+const WRAM_BANKS: usize = 8;
+
 /// HRAM size: 127 bytes (0xFF80-0xFFFE)
 const HRAM_SIZE: usize = 0x7F;
 
 /// RAM structure containing WRAM and HRAM
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ram {
-    /// Work RAM (8KB)
-    wram: [Byte; WRAM_SIZE],
+    /// Work RAM (8 banks of 4KB; DMG only uses banks 0-1)
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
+    wram: [Byte; WRAM_BANK_SIZE * WRAM_BANKS],
+    /// Bank mapped at 0xD000-0xDFFF (1-7)
+    wram_bank: u8,
     /// High RAM (127 bytes)
+    #[cfg_attr(feature = "save-state", serde(with = "crate::savestate::big_array"))]
     hram: [Byte; HRAM_SIZE],
 }
 
@@ -29,26 +42,64 @@ impl Ram {
     /// Create a new RAM instance with all memory zeroed
     pub fn new() -> Self {
         Self {
-            wram: [0; WRAM_SIZE],
+            wram: [0; WRAM_BANK_SIZE * WRAM_BANKS],
+            wram_bank: 1,
             hram: [0; HRAM_SIZE],
         }
     }
 
-    /// Read from WRAM (0xC000-0xDFFF)
-    pub fn wram_read(&self, address: Word) -> Byte {
+    /// Get the WRAM bank mapped at 0xD000-0xDFFF
+    pub fn wram_bank(&self) -> u8 {
+        self.wram_bank
+    }
+
+    /// Select the WRAM bank for 0xD000-0xDFFF (SVBK; 0 selects bank 1)
+    pub fn set_wram_bank(&mut self, bank: u8) {
+        self.wram_bank = (bank & 0x07).max(1);
+    }
+
+    /// Map a WRAM address to an index in the banked backing store
+    fn wram_index(&self, address: Word) -> Option<usize> {
         let offset = (address.wrapping_sub(0xC000)) as usize;
         if offset >= WRAM_SIZE {
+            return None;
+        }
+        if offset < WRAM_BANK_SIZE {
+            Some(offset)
+        } else {
+            Some(self.wram_bank as usize * WRAM_BANK_SIZE + (offset - WRAM_BANK_SIZE))
+        }
+    }
+
+    /// Read from WRAM (0xC000-0xDFFF)
+    pub fn wram_read(&self, address: Word) -> Byte {
+        match self.wram_index(address) {
+            Some(index) => self.wram[index],
             // Invalid address, return 0xFF
-            return 0xFF;
+            None => 0xFF,
         }
-        self.wram[offset]
     }
 
     /// Write to WRAM (0xC000-0xDFFF)
     pub fn wram_write(&mut self, address: Word, value: Byte) {
-        let offset = (address.wrapping_sub(0xC000)) as usize;
-        if offset < WRAM_SIZE {
-            self.wram[offset] = value;
+        if let Some(index) = self.wram_index(address) {
+            self.wram[index] = value;
+        }
+    }
+
+    /// Fill every WRAM bank with pseudo-random bytes
+    ///
+    /// Real WRAM powers up with garbage; a fixed seed keeps runs reproducible.
+    pub fn randomize_wram(&mut self, seed: u64) {
+        // splitmix64: every seed, including 0, gives a distinct stream
+        let mut state = seed;
+        for chunk in self.wram.chunks_mut(8) {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            let bytes = (z ^ (z >> 31)).to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
         }
     }
 
@@ -92,6 +143,27 @@ mod tests {
         assert_eq!(ram.wram_read(0xC100), 0x55);
     }
 
+    #[test]
+    fn test_wram_banking() {
+        let mut ram = Ram::new();
+        assert_eq!(ram.wram_bank(), 1);
+
+        ram.wram_write(0xC000, 0x11);
+        ram.wram_write(0xD000, 0x01);
+        ram.set_wram_bank(2);
+        assert_eq!(ram.wram_read(0xD000), 0x00);
+        ram.wram_write(0xD000, 0x02);
+        // Bank 0 is fixed
+        assert_eq!(ram.wram_read(0xC000), 0x11);
+
+        // Bank 0 selects bank 1
+        ram.set_wram_bank(0);
+        assert_eq!(ram.wram_bank(), 1);
+        assert_eq!(ram.wram_read(0xD000), 0x01);
+        ram.set_wram_bank(2);
+        assert_eq!(ram.wram_read(0xD000), 0x02);
+    }
+
     #[test]
     fn test_hram_read_write() {
         let mut ram = Ram::new();
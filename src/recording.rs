@@ -0,0 +1,160 @@
+//! Gameplay Recording
+//!
+//! This module captures PPU frames into an animated GIF using the
+//! four-shade DMG palette.
+
+use crate::common::argb_to_rgb888;
+use crate::error::EmulatorError;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// DMG shades in ARGB8888, indexed by GIF palette entry (matches the PPU output)
+const DMG_SHADES: [u32; 4] = [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000];
+
+/// Game Boy frame rate (Hz), used to derive the GIF frame delay
+const GB_FPS: u32 = 60;
+
+/// Records emulator frames to an animated GIF file
+pub struct GifRecorder {
+    /// GIF encoder (None once finished)
+    encoder: Option<gif::Encoder<BufWriter<File>>>,
+    /// Frame width in pixels
+    width: u16,
+    /// Frame height in pixels
+    height: u16,
+    /// Capture one of every `fps_divisor` frames
+    fps_divisor: u32,
+    /// Frames offered to the recorder so far
+    frame_counter: u32,
+    /// Frame delay in hundredths of a second
+    delay: u16,
+    /// Indexed pixel scratch buffer
+    indexed: Vec<u8>,
+}
+
+impl GifRecorder {
+    /// Create a looping GIF file
+    ///
+    /// `fps_divisor` of 2 keeps every other frame; 0 is treated as 1.
+    pub fn new(
+        path: impl AsRef<Path>,
+        width: u16,
+        height: u16,
+        fps_divisor: u32,
+    ) -> Result<GifRecorder, EmulatorError> {
+        let fps_divisor = fps_divisor.max(1);
+        let palette: Vec<u8> = DMG_SHADES
+            .iter()
+            .flat_map(|&c| argb_to_rgb888(c))
+            .collect();
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, width, height, &palette)
+            .map_err(|e| EmulatorError::Image(e.to_string()))?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| EmulatorError::Image(e.to_string()))?;
+
+        // Round to the nearest centisecond; browsers treat delays below 2 as slow
+        let delay = ((fps_divisor * 100 + GB_FPS / 2) / GB_FPS).clamp(2, u16::MAX as u32) as u16;
+
+        Ok(GifRecorder {
+            encoder: Some(encoder),
+            width,
+            height,
+            fps_divisor,
+            frame_counter: 0,
+            delay,
+            indexed: vec![0; width as usize * height as usize],
+        })
+    }
+
+    /// Offer an ARGB8888 frame; only every `fps_divisor`-th frame is written
+    pub fn add_frame(&mut self, pixels: &[u32]) -> Result<(), EmulatorError> {
+        let capture = self.frame_counter.is_multiple_of(self.fps_divisor);
+        self.frame_counter = self.frame_counter.wrapping_add(1);
+        if !capture {
+            return Ok(());
+        }
+
+        let encoder = match self.encoder.as_mut() {
+            Some(encoder) => encoder,
+            None => return Ok(()),
+        };
+
+        for (dst, &pixel) in self.indexed.iter_mut().zip(pixels) {
+            *dst = shade_index(pixel);
+        }
+
+        let mut frame = gif::Frame::from_indexed_pixels(self.width, self.height, &self.indexed[..], None);
+        frame.delay = self.delay;
+        encoder
+            .write_frame(&frame)
+            .map_err(|e| EmulatorError::Image(e.to_string()))
+    }
+
+    /// Write the GIF trailer and close the file
+    pub fn finish(mut self) -> Result<(), EmulatorError> {
+        if let Some(encoder) = self.encoder.take() {
+            let mut file = encoder.into_inner()?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Map an ARGB8888 pixel to the nearest DMG shade index by luminance
+fn shade_index(pixel: u32) -> u8 {
+    if let Some(index) = DMG_SHADES.iter().position(|&shade| shade == pixel) {
+        return index as u8;
+    }
+    let r = (pixel >> 16) & 0xFF;
+    let g = (pixel >> 8) & 0xFF;
+    let b = pixel & 0xFF;
+    let luma = (r * 299 + g * 587 + b * 114) / 1000;
+    // Darker pixels map to higher indices
+    (3 - (luma * 4 / 256).min(3)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shade_index() {
+        assert_eq!(shade_index(0xFFFFFFFF), 0);
+        assert_eq!(shade_index(0xFFAAAAAA), 1);
+        assert_eq!(shade_index(0xFF555555), 2);
+        assert_eq!(shade_index(0xFF000000), 3);
+        assert_eq!(shade_index(0xFF9BBC0F), 1);
+    }
+
+    #[test]
+    fn test_gif_recorder_header() {
+        let path = std::env::temp_dir().join(format!("rgbe_gif_{}.gif", std::process::id()));
+        let mut recorder = GifRecorder::new(&path, 4, 2, 2).unwrap();
+        for i in 0..10 {
+            recorder.add_frame(&[DMG_SHADES[i % 4]; 8]).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(&data[0..6], b"GIF89a");
+        assert_eq!(u16::from_le_bytes([data[6], data[7]]), 4);
+        assert_eq!(u16::from_le_bytes([data[8], data[9]]), 2);
+        assert_eq!(*data.last().unwrap(), 0x3B);
+
+        // Every other frame is kept: 5 image descriptors
+        let decoder = gif::DecodeOptions::new();
+        let mut reader = decoder.read_info(data.as_slice()).unwrap();
+        let mut frames = 0;
+        while let Some(frame) = reader.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 3);
+            frames += 1;
+        }
+        assert_eq!(frames, 5);
+    }
+}
@@ -0,0 +1,196 @@
+//! ROM Metadata Database
+//!
+//! This module caches header metadata for a ROM collection, keyed by the
+//! CRC32 of each image, so front ends can identify a ROM without parsing it.
+
+use crate::cart::{Cartridge, RomHeader};
+use crate::error::EmulatorError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// File extensions picked up by `scan_directory`
+const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+/// Header metadata for a single ROM image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomInfo {
+    /// Game title from the header
+    pub title: String,
+    /// Path the ROM was scanned from
+    pub path: String,
+    /// Size of the image in bytes
+    pub file_size: usize,
+    /// Cartridge type byte (0x147)
+    pub cart_type: u8,
+    /// Human-readable cartridge type
+    pub cart_type_name: String,
+    /// ROM size declared by the header, in bytes
+    pub rom_size: usize,
+    /// External RAM size declared by the header, in bytes
+    pub ram_size: usize,
+    /// CGB flag byte (0x143)
+    pub cgb_flag: u8,
+    /// Header checksum matches the header contents
+    pub checksum_valid: bool,
+}
+
+impl RomInfo {
+    /// Build metadata from a ROM image, or `None` if the header is missing
+    pub fn from_rom(rom: &[u8], path: &Path) -> Option<Self> {
+        let header = RomHeader::parse(rom)?;
+        Some(Self {
+            title: header.title.clone(),
+            path: path.display().to_string(),
+            file_size: rom.len(),
+            cart_type: header.cart_type,
+            cart_type_name: header.cart_type_name().to_string(),
+            rom_size: header.rom_size_bytes(),
+            ram_size: header.ram_size_bytes(),
+            cgb_flag: header.cgb_flag,
+            checksum_valid: Cartridge::validate_checksum(rom),
+        })
+    }
+}
+
+/// ROM metadata indexed by CRC32 of the image
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomDatabase {
+    entries: HashMap<u32, RomInfo>,
+}
+
+impl RomDatabase {
+    /// Create an empty database
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index every `.gb`/`.gbc` file in a directory (not recursive)
+    ///
+    /// Files too short to contain a header are skipped.
+    pub fn scan_directory(path: &Path) -> Result<RomDatabase, EmulatorError> {
+        let mut db = RomDatabase::new();
+        for entry in fs::read_dir(path)? {
+            let file_path = entry?.path();
+            let is_rom = file_path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ROM_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)));
+            if !is_rom || !file_path.is_file() {
+                continue;
+            }
+
+            let rom = fs::read(&file_path)?;
+            if let Some(info) = RomInfo::from_rom(&rom, &file_path) {
+                db.insert(crc32fast::hash(&rom), info);
+            }
+        }
+        Ok(db)
+    }
+
+    /// Load a database previously written by `save`
+    pub fn load(path: &Path) -> Result<RomDatabase, EmulatorError> {
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).map_err(|e| EmulatorError::Io(io::Error::from(e)))
+    }
+
+    /// Write the database as JSON
+    pub fn save(&self, path: &Path) -> Result<(), EmulatorError> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self).map_err(|e| EmulatorError::Io(io::Error::from(e)))
+    }
+
+    /// Add or replace the entry for a CRC32
+    pub fn insert(&mut self, crc32: u32, info: RomInfo) {
+        self.entries.insert(crc32, info);
+    }
+
+    /// Find the entry for a CRC32
+    pub fn lookup(&self, crc32: u32) -> Option<&RomInfo> {
+        self.entries.get(&crc32)
+    }
+
+    /// Find the entry for a ROM image
+    pub fn lookup_rom(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.lookup(crc32fast::hash(rom))
+    }
+
+    /// Number of indexed ROMs
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the database has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Create an empty scratch directory unique to a test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gbemu-romdb-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Build a 32KB ROM with a valid header
+    fn synthetic_rom(title: &str, cart_type: u8) -> Vec<u8> {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title.as_bytes());
+        rom[0x147] = cart_type;
+        rom[0x14D] = Cartridge::calculate_checksum(&rom);
+        rom
+    }
+
+    #[test]
+    fn test_scan_directory() {
+        let dir = temp_dir("scan");
+        let tetris = synthetic_rom("TETRIS", 0x00);
+        let zelda = synthetic_rom("ZELDA", 0x03);
+        fs::write(dir.join("tetris.gb"), &tetris).unwrap();
+        fs::write(dir.join("zelda.GBC"), &zelda).unwrap();
+        fs::write(dir.join("notes.txt"), b"not a rom").unwrap();
+        fs::write(dir.join("short.gb"), [0u8; 16]).unwrap();
+
+        let db = RomDatabase::scan_directory(&dir).unwrap();
+        assert_eq!(db.len(), 2);
+
+        let info = db.lookup(crc32fast::hash(&tetris)).unwrap();
+        assert_eq!(info.title, "TETRIS");
+        assert_eq!(info.cart_type, 0x00);
+        assert_eq!(info.rom_size, 0x8000);
+        assert!(info.checksum_valid);
+
+        let info = db.lookup_rom(&zelda).unwrap();
+        assert_eq!(info.title, "ZELDA");
+        assert_eq!(info.cart_type_name, "MBC1+RAM+BATTERY");
+        assert!(db.lookup(0xDEADBEEF).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let dir = temp_dir("roundtrip");
+        fs::write(dir.join("game.gb"), synthetic_rom("GAME", 0x01)).unwrap();
+        let db = RomDatabase::scan_directory(&dir).unwrap();
+
+        let db_path = dir.join("romdb.json");
+        db.save(&db_path).unwrap();
+        let loaded = RomDatabase::load(&db_path).unwrap();
+        assert_eq!(loaded, db);
+
+        fs::write(&db_path, b"{ not json").unwrap();
+        assert!(matches!(RomDatabase::load(&db_path), Err(EmulatorError::Io(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
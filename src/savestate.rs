@@ -0,0 +1,105 @@
+//! Save States
+//!
+//! This module defines the serialized snapshot behind `Emulator::save_state`
+//! and `Emulator::load_state`. States are JSON and tied to a ROM by the CRC32
+//! of its image; the ROM itself is never stored.
+
+use crate::apu::Apu;
+use crate::bus::Bus;
+use crate::cart::MbcState;
+use crate::cpu::Cpu;
+use crate::dma::{Dma, HdmaController};
+use crate::emu::EmulatorMode;
+use crate::error::EmulatorError;
+use crate::events::EventQueue;
+use crate::gamepad::Gamepad;
+use crate::lcd::Lcd;
+use crate::ppu::Ppu;
+use crate::timer::Timer;
+use serde::{Deserialize, Serialize};
+
+/// Format version written into every state; bumped when the layout changes
+pub const SAVE_STATE_VERSION: u32 = 1;
+
+/// Complete machine state at an instruction boundary
+///
+/// The memory-heavy components are boxed to keep decoding off the stack.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SaveState {
+    /// `SAVE_STATE_VERSION` of the writer
+    pub version: u32,
+    /// CRC32 of the cartridge ROM, if one was inserted
+    pub rom_hash: Option<u32>,
+    /// Active hardware mode
+    pub mode: EmulatorMode,
+    /// Total T-cycles executed
+    pub ticks: u64,
+    /// T-cycle count at the last reset
+    pub reset_ticks: u64,
+    pub cpu: Cpu,
+    pub ppu: Box<Ppu>,
+    pub apu: Box<Apu>,
+    pub timer: Timer,
+    pub dma: Dma,
+    pub hdma: HdmaController,
+    pub lcd: Lcd,
+    pub gamepad: Gamepad,
+    pub bus: Box<Bus>,
+    /// Cartridge banking registers and RAM
+    pub cart: Option<MbcState>,
+    /// Events raised but not yet turned into interrupts
+    pub events: EventQueue,
+    /// Boot ROM still overlaid on the cartridge
+    pub boot_rom_mapped: bool,
+}
+
+impl SaveState {
+    /// Serialize the state
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("save state serialization cannot fail")
+    }
+
+    /// Parse a state, rejecting other format versions
+    pub fn decode(data: &[u8]) -> Result<Self, EmulatorError> {
+        let state: SaveState = serde_json::from_slice(data)
+            .map_err(|e| EmulatorError::InvalidSaveState(e.to_string()))?;
+        if state.version != SAVE_STATE_VERSION {
+            return Err(EmulatorError::InvalidSaveState(format!(
+                "unsupported version {} (expected {})",
+                state.version, SAVE_STATE_VERSION
+            )));
+        }
+        Ok(state)
+    }
+}
+
+/// CRC32 identifying a ROM image in save states
+pub(crate) fn rom_hash(rom: &[u8]) -> u32 {
+    crc32fast::hash(rom)
+}
+
+/// Serde adapter for arrays longer than serde's built-in 32 elements
+pub(crate) mod big_array {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        array.as_slice().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        let items = Vec::<T>::deserialize(deserializer)?;
+        let len = items.len();
+        items
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(len, &"an array of the saved size"))
+    }
+}
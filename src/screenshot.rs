@@ -0,0 +1,53 @@
+//! Screenshot Encoding
+//!
+//! This module encodes the PPU video buffer (ARGB8888) as a PNG image.
+//! It is available with the `png-output` feature, which `screenshot` enables.
+
+use crate::error::EmulatorError;
+
+/// Convert an ARGB8888 pixel to RGBA byte order
+#[inline]
+pub fn argb_to_rgba(pixel: u32) -> [u8; 4] {
+    [
+        (pixel >> 16) as u8, // R
+        (pixel >> 8) as u8,  // G
+        pixel as u8,         // B
+        (pixel >> 24) as u8, // A
+    ]
+}
+
+/// Encode an ARGB8888 pixel buffer as an 8-bit RGBA PNG
+pub fn encode_png(pixels: &[u32], width: u32, height: u32) -> Result<Vec<u8>, EmulatorError> {
+    let rgba: Vec<u8> = pixels.iter().flat_map(|&p| argb_to_rgba(p)).collect();
+
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| EmulatorError::Image(e.to_string()))?;
+        writer
+            .write_image_data(&rgba)
+            .map_err(|e| EmulatorError::Image(e.to_string()))?;
+    }
+    Ok(png_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argb_to_rgba() {
+        assert_eq!(argb_to_rgba(0xFF112233), [0x11, 0x22, 0x33, 0xFF]);
+        assert_eq!(argb_to_rgba(0x80AABBCC), [0xAA, 0xBB, 0xCC, 0x80]);
+    }
+
+    #[test]
+    fn test_encode_png_signature() {
+        let png_data = encode_png(&[0xFFFFFFFF; 4], 2, 2).unwrap();
+        assert_eq!(&png_data[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+    }
+}
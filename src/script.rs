@@ -0,0 +1,243 @@
+//! Test Scripts
+//!
+//! This module runs simple line-oriented scripts against an emulator so
+//! ROM tests can be automated without writing Rust:
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! wait 60
+//! press start
+//! wait 2
+//! release start
+//! assert_a 0x42
+//! assert_mem 0xC000 0x99
+//! screenshot frame_63.png
+//! print_serial
+//! ```
+
+use crate::bus::MemoryBus;
+use crate::emu::Emulator;
+use crate::error::EmulatorError;
+use crate::gamepad::Button;
+use core::str::FromStr;
+
+/// One script instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptOp {
+    /// Run this many frames
+    WaitFrames(u32),
+    /// Hold a button
+    PressButton(Button),
+    /// Let go of a button
+    ReleaseButton(Button),
+    /// Fail unless register A has this value
+    AssertRegisterA(u8),
+    /// Fail unless the byte at the address has this value
+    AssertMemory(u16, u8),
+    /// Write a PNG of the current frame (needs the `screenshot` feature)
+    SaveScreenshot(String),
+    /// Add the text sent over the serial port so far to the script output
+    PrintSerial,
+}
+
+/// Parsed script, run with `Emulator::run_script`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    ops: Vec<ScriptOp>,
+}
+
+impl Script {
+    /// Operations in execution order
+    pub fn ops(&self) -> &[ScriptOp] {
+        &self.ops
+    }
+
+    /// Execute every operation in order, stopping at the first failure
+    ///
+    /// Returns the script output: one line per `print_serial`.
+    pub fn run(&self, emu: &mut Emulator) -> Result<String, EmulatorError> {
+        let mut output = String::new();
+        for op in &self.ops {
+            match op {
+                ScriptOp::WaitFrames(frames) => {
+                    for _ in 0..*frames {
+                        emu.run_frame();
+                    }
+                }
+                ScriptOp::PressButton(button) => emu.set_button(*button, true),
+                ScriptOp::ReleaseButton(button) => emu.set_button(*button, false),
+                ScriptOp::AssertRegisterA(expected) => {
+                    let a = emu.cpu.regs.a;
+                    if a != *expected {
+                        return Err(EmulatorError::ScriptAssertion(format!(
+                            "A is 0x{:02X}, expected 0x{:02X}",
+                            a, expected
+                        )));
+                    }
+                }
+                ScriptOp::AssertMemory(address, expected) => {
+                    let value = emu.bus.peek(*address);
+                    if value != *expected {
+                        return Err(EmulatorError::ScriptAssertion(format!(
+                            "[0x{:04X}] is 0x{:02X}, expected 0x{:02X}",
+                            address, value, expected
+                        )));
+                    }
+                }
+                ScriptOp::SaveScreenshot(_path) => {
+                    #[cfg(feature = "screenshot")]
+                    emu.save_screenshot(_path)?;
+                    #[cfg(not(feature = "screenshot"))]
+                    return Err(EmulatorError::NotSupported);
+                }
+                ScriptOp::PrintSerial => {
+                    output.push_str(emu.serial_output());
+                    output.push('\n');
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl FromStr for Script {
+    type Err = EmulatorError;
+
+    /// Parse one operation per line; `#` starts a comment
+    fn from_str(text: &str) -> Result<Script, EmulatorError> {
+        let mut ops = Vec::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let op = parse_op(line).map_err(|e| EmulatorError::InvalidScript(format!("line {}: {}", line_no + 1, e)))?;
+            ops.push(op);
+        }
+        Ok(Script { ops })
+    }
+}
+
+/// Parse a single non-empty line
+fn parse_op(line: &str) -> Result<ScriptOp, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let args: Vec<&str> = words.collect();
+
+    let op = match (command, args.as_slice()) {
+        ("wait", [frames]) => ScriptOp::WaitFrames(number(frames)?),
+        ("press", [button]) => ScriptOp::PressButton(button_name(button)?),
+        ("release", [button]) => ScriptOp::ReleaseButton(button_name(button)?),
+        ("assert_a", [value]) => ScriptOp::AssertRegisterA(number(value)?),
+        ("assert_mem", [address, value]) => ScriptOp::AssertMemory(number(address)?, number(value)?),
+        ("screenshot", [path]) => ScriptOp::SaveScreenshot(path.to_string()),
+        ("print_serial", []) => ScriptOp::PrintSerial,
+        ("wait" | "press" | "release" | "assert_a" | "assert_mem" | "screenshot" | "print_serial", _) => {
+            return Err(format!("wrong number of arguments for '{}'", command));
+        }
+        _ => return Err(format!("unknown command '{}'", command)),
+    };
+    Ok(op)
+}
+
+/// Parse `0x` hex or decimal into the target width
+fn number<T: TryFrom<u32>>(text: &str) -> Result<T, String> {
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("invalid number '{}'", text))?;
+    T::try_from(value).map_err(|_| format!("{} is out of range", text))
+}
+
+/// Look up a button name
+fn button_name(name: &str) -> Result<Button, String> {
+    Button::from_name(name).ok_or_else(|| format!("unknown button '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::asm;
+    use crate::emu::tests::test_emulator;
+
+    /// Loads A and WRAM, sends "HI" over serial, then spins
+    fn scripted_emulator() -> Emulator {
+        let program = asm::assemble_with_labels(
+            &[
+                "LD HL, 0xC000",
+                "LD (HL), 0x99",
+                "LD HL, 0xFF01",
+                "LD (HL), 0x48",
+                "LD HL, 0xFF02",
+                "LD (HL), 0x81",
+                "LD HL, 0xFF01",
+                "LD (HL), 0x49",
+                "LD HL, 0xFF02",
+                "LD (HL), 0x81",
+                "LD A, 0x42",
+                "DONE:",
+                "JR DONE",
+            ],
+            0x0100,
+        )
+        .unwrap();
+        test_emulator(&program)
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script: Script = "
+            # Boot and check state
+            wait 2
+            press START
+            assert_a 0x42
+            assert_mem 0xC000 153
+            release start
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(
+            script.ops(),
+            [
+                ScriptOp::WaitFrames(2),
+                ScriptOp::PressButton(Button::Start),
+                ScriptOp::AssertRegisterA(0x42),
+                ScriptOp::AssertMemory(0xC000, 0x99),
+                ScriptOp::ReleaseButton(Button::Start),
+            ]
+        );
+
+        for bad in ["jump 3", "wait", "press turbo", "assert_a 0x100", "assert_mem 0x10000 1"] {
+            assert!(matches!(bad.parse::<Script>(), Err(EmulatorError::InvalidScript(_))), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_run_script_in_sequence() {
+        let mut emu = scripted_emulator();
+        let script = Script::from_str("wait 2\npress START\nassert_a 0x42\nassert_mem 0xC000 0x99\nprint_serial").unwrap();
+        let output = script.run(&mut emu).unwrap();
+
+        assert_eq!(emu.current_frame(), 2);
+        assert!(emu.gamepad.is_pressed(Button::Start));
+        assert_eq!(output, "HI\n");
+    }
+
+    #[test]
+    fn test_print_serial_keeps_serial_output() {
+        let mut emu = scripted_emulator();
+        let output = Script::from_str("wait 2\nprint_serial\nprint_serial").unwrap().run(&mut emu).unwrap();
+        assert_eq!(output, "HI\nHI\n");
+        assert_eq!(emu.serial_output(), "HI");
+    }
+
+    #[test]
+    fn test_run_script_stops_at_failed_assertion() {
+        let mut emu = scripted_emulator();
+        let script = Script::from_str("wait 1\nassert_a 0x00\npress A").unwrap();
+        let err = emu.run_script(&script).unwrap_err();
+        assert!(matches!(err, EmulatorError::ScriptAssertion(_)), "{}", err);
+        assert!(!emu.gamepad.is_pressed(Button::A));
+    }
+}
@@ -0,0 +1,237 @@
+//! Serial Link
+//!
+//! This module connects two emulator instances through a virtual link cable,
+//! or one emulator to a peripheral such as the printer. Transfers complete
+//! instantly once the master (internal clock) has started a transfer and the
+//! partner is waiting on the external clock. With nothing plugged in, a
+//! master transfer completes at once and receives 0xFF.
+
+use crate::bus::SystemBus;
+use crate::cpu::InterruptType;
+use crate::emu::Emulator;
+use core::any::Any;
+
+/// SB - Serial transfer data (I/O offset)
+const SB: usize = 0x01;
+/// SC - Serial transfer control (I/O offset)
+const SC: usize = 0x02;
+/// SC bit 7: transfer requested / in progress
+const SC_TRANSFER: u8 = 0x80;
+/// SC bit 0: internal clock (this side is the master)
+const SC_INTERNAL_CLOCK: u8 = 0x01;
+
+/// Peripheral plugged into the serial port (clocked by the Game Boy)
+pub trait SerialDevice: Any {
+    /// Receive the byte sent by the Game Boy and return the byte sent back
+    fn exchange(&mut self, byte: u8) -> u8;
+}
+
+/// Which end of the cable started the pending transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkSide {
+    A,
+    B,
+}
+
+/// Link cable between two emulators
+pub struct SerialLink<'a> {
+    /// First emulator
+    a: &'a mut Emulator,
+    /// Second emulator
+    b: &'a mut Emulator,
+    /// Byte sent by the master, waiting for the partner to be ready
+    pending_byte: Option<u8>,
+    /// Side that started the pending transfer
+    master: LinkSide,
+}
+
+/// Connect two emulators with a link cable
+pub fn connect<'a>(a: &'a mut Emulator, b: &'a mut Emulator) -> SerialLink<'a> {
+    SerialLink {
+        a,
+        b,
+        pending_byte: None,
+        master: LinkSide::A,
+    }
+}
+
+impl SerialLink<'_> {
+    /// Step both emulators by one instruction and exchange serial bytes
+    ///
+    /// Returns false if either emulator has stopped.
+    pub fn tick_both(&mut self) -> bool {
+        // The cable, not the unconnected-port logic, completes transfers here
+        self.a.serial_linked = true;
+        self.b.serial_linked = true;
+        let a_running = self.a.step();
+        let b_running = self.b.step();
+        self.a.serial_linked = false;
+        self.b.serial_linked = false;
+
+        if self.pending_byte.is_none() {
+            if is_master_transfer(&self.a.bus) {
+                self.pending_byte = Some(self.a.bus.io_regs[SB]);
+                self.master = LinkSide::A;
+            } else if is_master_transfer(&self.b.bus) {
+                self.pending_byte = Some(self.b.bus.io_regs[SB]);
+                self.master = LinkSide::B;
+            }
+        }
+
+        if let Some(byte) = self.pending_byte {
+            let (master, partner) = match self.master {
+                LinkSide::A => (&mut *self.a, &mut *self.b),
+                LinkSide::B => (&mut *self.b, &mut *self.a),
+            };
+            if partner.bus.io_regs[SC] & SC_TRANSFER != 0 {
+                master.bus.io_regs[SB] = partner.bus.io_regs[SB];
+                partner.bus.io_regs[SB] = byte;
+                complete_transfer(&mut master.bus);
+                complete_transfer(&mut partner.bus);
+                master.record_serial_byte(byte);
+                self.pending_byte = None;
+            }
+        }
+
+        a_running && b_running
+    }
+
+    /// Check if a master transfer is waiting for the partner
+    pub fn has_pending_transfer(&self) -> bool {
+        self.pending_byte.is_some()
+    }
+}
+
+/// Complete a pending master transfer with the attached device, or with an
+/// unconnected port; returns the byte sent
+pub(crate) fn run_master_transfer<B: SystemBus>(bus: &mut B, device: Option<&mut dyn SerialDevice>) -> Option<u8> {
+    if !is_master_transfer(bus) {
+        return None;
+    }
+    let sent = bus.io_register(SB);
+    let received = device.map_or(0xFF, |device| device.exchange(sent));
+    bus.set_io_register(SB, received);
+    complete_transfer(bus);
+    Some(sent)
+}
+
+/// Check if SC requests a transfer on the internal clock
+fn is_master_transfer<B: SystemBus>(bus: &B) -> bool {
+    let sc = bus.io_register(SC);
+    sc & (SC_TRANSFER | SC_INTERNAL_CLOCK) == (SC_TRANSFER | SC_INTERNAL_CLOCK)
+}
+
+/// Clear the transfer flag and raise the serial interrupt
+fn complete_transfer<B: SystemBus>(bus: &mut B) {
+    let sc = bus.io_register(SC);
+    bus.set_io_register(SC, sc & !SC_TRANSFER);
+    let int_flags = bus.interrupt_flags();
+    bus.set_interrupt_flags(int_flags | InterruptType::Serial.bit());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::tests::test_emulator;
+
+    #[test]
+    fn test_serial_exchange() {
+        // JR -2: spin while the link does the work
+        let mut a = test_emulator(&[0x18, 0xFE]);
+        let mut b = test_emulator(&[0x18, 0xFE]);
+        a.bus.io_regs[SB] = 0x42;
+        a.bus.io_regs[SC] = 0x81;
+        b.bus.io_regs[SB] = 0x99;
+
+        let mut link = connect(&mut a, &mut b);
+        link.tick_both();
+        // Partner isn't listening yet
+        assert!(link.has_pending_transfer());
+
+        link.b.bus.io_regs[SC] = 0x80;
+        link.tick_both();
+        assert!(!link.has_pending_transfer());
+
+        assert_eq!(a.bus.io_regs[SB], 0x99);
+        assert_eq!(b.bus.io_regs[SB], 0x42);
+        assert_eq!(a.bus.io_regs[SC] & 0x80, 0);
+        assert_eq!(b.bus.io_regs[SC] & 0x80, 0);
+        assert_ne!(a.bus.int_flags & 0x08, 0);
+        assert_ne!(b.bus.int_flags & 0x08, 0);
+        assert_eq!(a.serial_output(), "B");
+        assert_eq!(b.serial_output(), "");
+        assert!(!a.serial_linked);
+    }
+
+    #[test]
+    fn test_unconnected_transfer_captures_output() {
+        // Send "OK" one byte at a time, then spin
+        let program = [
+            0x3E, b'O', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02,
+            0x3E, b'K', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02,
+            0x18, 0xFE,
+        ];
+        let mut emu = test_emulator(&program);
+        for _ in 0..4 {
+            emu.step();
+        }
+        assert_eq!(emu.serial_output(), "O");
+        assert_eq!(emu.bus.io_regs[SB], 0xFF);
+        assert_eq!(emu.bus.io_regs[SC] & SC_TRANSFER, 0);
+        assert_ne!(emu.bus.int_flags & 0x08, 0);
+
+        emu.run_frame();
+        assert_eq!(emu.serial_output(), "OK");
+        emu.clear_serial_output();
+        assert_eq!(emu.serial_output(), "");
+    }
+
+    #[test]
+    fn test_serial_output_is_bounded() {
+        let mut emu = test_emulator(&[]);
+        for i in 0..100_000u32 {
+            emu.record_serial_byte(if i % 3 == 0 { 0xE9 } else { b'a' + (i % 26) as u8 });
+        }
+        emu.record_serial_byte(b'!');
+        assert!(emu.serial_output().len() <= 64 * 1024 + 2);
+        assert!(emu.serial_output().ends_with('!'));
+
+        let taken = emu.take_serial_output();
+        assert!(!taken.is_empty());
+        assert_eq!(emu.serial_output(), "");
+    }
+
+    #[test]
+    fn test_external_clock_transfer_waits() {
+        // LD A,0x80; LDH (SC),A; JR -2
+        let mut emu = test_emulator(&[0x3E, 0x80, 0xE0, 0x02, 0x18, 0xFE]);
+        emu.run_frame();
+        assert_eq!(emu.serial_output(), "");
+        assert_ne!(emu.bus.io_regs[SC] & SC_TRANSFER, 0);
+    }
+
+    /// Device that answers every byte with its complement
+    struct Inverter;
+
+    impl SerialDevice for Inverter {
+        fn exchange(&mut self, byte: u8) -> u8 {
+            !byte
+        }
+    }
+
+    #[test]
+    fn test_serial_device_exchange() {
+        // LD A,0x5A; LDH (SB),A; LD A,0x81; LDH (SC),A; JR -2
+        let mut emu = test_emulator(&[0x3E, 0x5A, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE]);
+        emu.attach_serial_device(Box::new(Inverter));
+        for _ in 0..5 {
+            emu.step();
+        }
+
+        assert_eq!(emu.bus.io_regs[SB], 0xA5);
+        assert_eq!(emu.bus.io_regs[SC] & SC_TRANSFER, 0);
+        assert_ne!(emu.bus.int_flags & 0x08, 0);
+        assert_eq!(emu.serial_output(), "Z");
+        assert!(emu.serial_device::<Inverter>().is_some());
+    }
+}
@@ -0,0 +1,115 @@
+//! Test Utilities
+//!
+//! Helpers for testing components in isolation, most notably a `MemoryBus`
+//! mock for exercising the CPU without the real `Bus`. Available to this
+//! crate's tests and, with the `test-utils` feature, to downstream crates.
+
+use crate::bus::{MemoryBus, SystemBus};
+use crate::common::{Byte, Word};
+
+/// Flat 64KB memory bus that records every write
+///
+/// Addresses that were never written read as 0x00.
+#[derive(Debug, Clone)]
+pub struct MockBus {
+    /// Memory contents
+    memory: Box<[Byte]>,
+    /// Writes in the order they happened
+    write_log: Vec<(Word, Byte)>,
+}
+
+impl Default for MockBus {
+    fn default() -> Self {
+        Self {
+            memory: vec![0; 0x10000].into_boxed_slice(),
+            write_log: Vec::new(),
+        }
+    }
+}
+
+impl MockBus {
+    /// Create an empty bus
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a bus with `data` loaded starting at `addr`
+    pub fn with_data(addr: Word, data: &[Byte]) -> Self {
+        let mut bus = Self::new();
+        for (i, &byte) in data.iter().enumerate() {
+            bus.memory[addr.wrapping_add(i as Word) as usize] = byte;
+        }
+        bus
+    }
+
+    /// Get all writes since creation or the last `clear_write_log`
+    pub fn write_log(&self) -> &[(Word, Byte)] {
+        &self.write_log
+    }
+
+    /// Forget recorded writes (memory contents are kept)
+    pub fn clear_write_log(&mut self) {
+        self.write_log.clear();
+    }
+}
+
+impl MemoryBus for MockBus {
+    fn read(&self, address: Word) -> Byte {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        self.memory[address as usize] = value;
+        self.write_log.push((address, value));
+    }
+
+    fn speed_switch_armed(&self) -> bool {
+        self.read(0xFF4D) & 0x01 != 0
+    }
+}
+
+/// I/O registers and IE/IF live in plain memory; component syncs are not
+/// logged as writes
+impl SystemBus for MockBus {
+    fn io_register(&self, index: usize) -> Byte {
+        self.read(0xFF00 + index as Word)
+    }
+
+    fn set_io_register(&mut self, index: usize, value: Byte) {
+        self.memory[0xFF00 + index] = value;
+    }
+
+    fn interrupt_enable(&self) -> Byte {
+        self.read(0xFFFF)
+    }
+
+    fn interrupt_flags(&self) -> Byte {
+        self.read(0xFF0F)
+    }
+
+    fn set_interrupt_flags(&mut self, value: Byte) {
+        self.memory[0xFF0F] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_bus_with_data() {
+        let mut bus = MockBus::with_data(0xFFFF, &[0x12, 0x34]);
+        assert_eq!(bus.read(0xFFFF), 0x12);
+        assert_eq!(bus.read(0x0000), 0x34);
+        assert_eq!(bus.read(0x0001), 0x00);
+        assert!(bus.write_log().is_empty());
+
+        bus.write16(0xC000, 0xBEEF);
+        assert_eq!(bus.read16(0xC000), 0xBEEF);
+        assert_eq!(bus.write_log(), &[(0xC000, 0xEF), (0xC001, 0xBE)]);
+
+        bus.clear_write_log();
+        assert!(bus.write_log().is_empty());
+        assert_eq!(bus.read(0xC000), 0xEF);
+    }
+}
@@ -8,7 +8,9 @@
 //! - TMA (0xFF06): Timer modulo (reload value)
 //! - TAC (0xFF07): Timer control (enable and frequency select)
 
+use crate::apu::CPU_CLOCK;
 use crate::common::Byte;
+use crate::events::{EventQueue, HardwareEvent};
 
 /// Timer frequencies based on TAC bits 0-1
 /// Values are in T-cycles per TIMA increment
@@ -19,8 +21,46 @@ const TIMER_FREQUENCIES: [u16; 4] = [
     256,  // 11: 16384 Hz (CPU Clock / 256)
 ];
 
+/// T-cycles TIMA reads 0 after overflowing, and then spends reloading
+const TIMA_RELOAD_CYCLES: u8 = 4;
+
+/// Progress of a TIMA reload after an overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
+enum TimaReload {
+    /// No overflow in progress
+    #[default]
+    Idle,
+    /// TIMA overflowed and reads 0; reloads when the count reaches 0.
+    /// A TIMA write here cancels the reload and the interrupt.
+    Delay(u8),
+    /// TIMA was just loaded from TMA. TIMA writes are ignored and TMA
+    /// writes also go to TIMA until the count reaches 0.
+    Reloading(u8),
+}
+
+/// Snapshot of the timer state for debugging
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerState {
+    /// Full 16-bit internal divider (DIV is the upper 8 bits)
+    pub div_internal: u16,
+    /// TIMA register
+    pub tima: u8,
+    /// TMA register
+    pub tma: u8,
+    /// TAC register
+    pub tac: u8,
+    /// TAC bit 2: timer enabled
+    pub enabled: bool,
+    /// TIMA increment frequency selected by TAC
+    pub frequency_hz: u32,
+    /// TIMA overflowed and `TimerOverflow` is pushed once the reload delay ends
+    pub interrupt_requested: bool,
+}
+
 /// Game Boy Timer
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "save-state", derive(serde::Serialize, serde::Deserialize))]
 pub struct Timer {
     /// DIV register (0xFF04) - upper 8 bits of internal 16-bit counter
     div: u16,
@@ -30,8 +70,8 @@ pub struct Timer {
     tma: Byte,
     /// TAC register (0xFF07) - timer control
     tac: Byte,
-    /// Timer interrupt requested flag
-    pub interrupt_requested: bool,
+    /// Reload state after a TIMA overflow
+    reload: TimaReload,
 }
 
 impl Default for Timer {
@@ -48,7 +88,7 @@ impl Timer {
             tima: 0,
             tma: 0,
             tac: 0,
-            interrupt_requested: false,
+            reload: TimaReload::Idle,
         }
     }
 
@@ -58,7 +98,13 @@ impl Timer {
         self.tima = 0;
         self.tma = 0;
         self.tac = 0;
-        self.interrupt_requested = false;
+        self.reload = TimaReload::Idle;
+    }
+
+    /// Initialize timer to the CGB boot ROM skip state
+    pub fn init_cgb(&mut self) {
+        self.init();
+        self.div = 0x1EA0;
     }
 
     /// Read timer register
@@ -76,20 +122,37 @@ impl Timer {
     pub fn write(&mut self, address: u16, value: Byte) {
         match address {
             0xFF04 => {
-                // Writing any value to DIV resets it to 0
+                // Writing any value to DIV resets it to 0, which is a
+                // falling edge if the selected bit was set
+                let input = self.timer_input();
                 self.div = 0;
+                self.clock_on_falling_edge(input);
             }
             0xFF05 => {
-                // Write to TIMA
-                self.tima = value;
+                // Write to TIMA; ignored in the cycle it is reloaded
+                match self.reload {
+                    TimaReload::Reloading(_) => {}
+                    TimaReload::Delay(_) => {
+                        // Cancels the pending reload and interrupt
+                        self.reload = TimaReload::Idle;
+                        self.tima = value;
+                    }
+                    TimaReload::Idle => self.tima = value,
+                }
             }
             0xFF06 => {
-                // Write to TMA
+                // Write to TMA; also reaches TIMA in the cycle it is reloaded
                 self.tma = value;
+                if let TimaReload::Reloading(_) = self.reload {
+                    self.tima = value;
+                }
             }
             0xFF07 => {
-                // Write to TAC (only lower 3 bits are used)
+                // Write to TAC (only lower 3 bits are used); disabling the
+                // timer or selecting a clear bit is a falling edge
+                let input = self.timer_input();
                 self.tac = value & 0x07;
+                self.clock_on_falling_edge(input);
             }
             _ => {}
         }
@@ -105,47 +168,86 @@ impl Timer {
         TIMER_FREQUENCIES[(self.tac & 0x03) as usize]
     }
 
-    /// Tick the timer by one T-cycle
-    pub fn tick(&mut self) {
-        let prev_div = self.div;
-        self.div = self.div.wrapping_add(1);
+    /// DIV bit whose falling edge increments TIMA for the selected frequency
+    fn timer_bit(&self) -> u16 {
+        match self.timer_frequency() {
+            1024 => 9,
+            16 => 3,
+            64 => 5,
+            _ => 7,
+        }
+    }
 
-        // Check if timer is enabled
-        if !self.timer_enabled() {
+    /// Signal TIMA counts on: the selected DIV bit ANDed with the enable bit
+    fn timer_input(&self) -> bool {
+        self.timer_enabled() && (self.div >> self.timer_bit()) & 1 != 0
+    }
+
+    /// Increment TIMA if the timer input fell from `prev_input`
+    fn clock_on_falling_edge(&mut self, prev_input: bool) {
+        if !prev_input || self.timer_input() {
             return;
         }
 
-        // Get the bit position to check for falling edge
-        let freq = self.timer_frequency();
-        let bit_pos = match freq {
-            1024 => 9, // Check bit 9 of DIV
-            16 => 3,   // Check bit 3 of DIV
-            64 => 5,   // Check bit 5 of DIV
-            256 => 7,  // Check bit 7 of DIV
-            _ => return,
-        };
-
-        // Check for falling edge (bit was 1, now 0)
-        let prev_bit = (prev_div >> bit_pos) & 1;
-        let curr_bit = (self.div >> bit_pos) & 1;
+        let (new_tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = new_tima;
+        if overflow {
+            // TIMA overflow - reload from TMA and request interrupt after the delay
+            self.reload = TimaReload::Delay(TIMA_RELOAD_CYCLES);
+        }
+    }
 
-        if prev_bit == 1 && curr_bit == 0 {
-            // Increment TIMA
-            let (new_tima, overflow) = self.tima.overflowing_add(1);
-            
-            if overflow {
-                // TIMA overflow - reload from TMA and request interrupt
+    /// Tick the timer by one T-cycle, pushing `TimerOverflow` when TIMA wraps
+    ///
+    /// After an overflow TIMA reads 0 for `TIMA_RELOAD_CYCLES`; the reload
+    /// from TMA and the interrupt request happen together afterwards.
+    pub fn tick(&mut self, events: &mut EventQueue) {
+        self.reload = match self.reload {
+            TimaReload::Idle => TimaReload::Idle,
+            TimaReload::Delay(1) => {
                 self.tima = self.tma;
-                self.interrupt_requested = true;
-            } else {
-                self.tima = new_tima;
+                events.push(HardwareEvent::TimerOverflow);
+                TimaReload::Reloading(TIMA_RELOAD_CYCLES)
             }
+            TimaReload::Delay(cycles) => TimaReload::Delay(cycles - 1),
+            TimaReload::Reloading(1) => TimaReload::Idle,
+            TimaReload::Reloading(cycles) => TimaReload::Reloading(cycles - 1),
+        };
+
+        // Increment TIMA on the falling edge of the selected DIV bit
+        let input = self.timer_input();
+        self.div = self.div.wrapping_add(1);
+        self.clock_on_falling_edge(input);
+    }
+
+    /// Get the full 16-bit internal divider
+    pub fn div_internal(&self) -> u16 {
+        self.div
+    }
+
+    /// Get a snapshot of the timer state
+    pub fn inspect(&self) -> TimerState {
+        TimerState {
+            div_internal: self.div,
+            tima: self.tima,
+            tma: self.tma,
+            tac: self.tac,
+            enabled: self.timer_enabled(),
+            frequency_hz: CPU_CLOCK / self.timer_frequency() as u32,
+            interrupt_requested: matches!(self.reload, TimaReload::Delay(_)),
         }
     }
 
-    /// Clear the interrupt request flag
-    pub fn clear_interrupt(&mut self) {
-        self.interrupt_requested = false;
+    /// T-cycles until TIMA next increments (`u32::MAX` while disabled)
+    ///
+    /// TIMA increments on the falling edge of the selected DIV bit, i.e.
+    /// whenever the internal counter reaches a multiple of the period.
+    pub fn cycles_until_tima_tick(&self) -> u32 {
+        if !self.timer_enabled() {
+            return u32::MAX;
+        }
+        let period = self.timer_frequency() as u32;
+        period - (self.div as u32 % period)
     }
 }
 
@@ -160,7 +262,6 @@ mod tests {
         assert_eq!(timer.tima, 0);
         assert_eq!(timer.tma, 0);
         assert_eq!(timer.tac, 0);
-        assert!(!timer.interrupt_requested);
     }
 
     #[test]
@@ -170,6 +271,14 @@ mod tests {
         assert_eq!(timer.read(0xFF04), 0xAB);
     }
 
+    #[test]
+    fn test_init_cgb_div() {
+        let mut timer = Timer::new();
+        timer.init_cgb();
+        assert_eq!(timer.inspect().div_internal, 0x1EA0);
+        assert_eq!(timer.read(0xFF04), 0x1E);
+    }
+
     #[test]
     fn test_div_write_resets() {
         let mut timer = Timer::new();
@@ -197,13 +306,15 @@ mod tests {
         timer.div = 0;
         timer.tima = 0;
         timer.tac = 0x00; // Timer disabled
+        let mut events = EventQueue::new();
         
         // Tick many times - TIMA should not change
         for _ in 0..1000 {
-            timer.tick();
+            timer.tick(&mut events);
         }
         
         assert_eq!(timer.tima, 0);
+        assert!(events.is_empty());
     }
 
     #[test]
@@ -217,12 +328,236 @@ mod tests {
         // Tick until TIMA overflows
         // With freq 01, TIMA increments every 16 T-cycles
         // We need to trigger a falling edge on bit 3
+        let mut events = EventQueue::new();
         for _ in 0..16 {
-            timer.tick();
+            timer.tick(&mut events);
+        }
+
+        // TIMA reads 0 for four T-cycles before the reload
+        for _ in 0..TIMA_RELOAD_CYCLES {
+            assert_eq!(timer.read(0xFF05), 0);
+            assert!(events.is_empty());
+            timer.tick(&mut events);
+        }
+
+        // TIMA should have reloaded from TMA
+        assert_eq!(timer.tima, 0x42);
+        assert_eq!(events.pop(), Some(HardwareEvent::TimerOverflow));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_one_event_per_overflow() {
+        let mut timer = Timer::new();
+        timer.div = 0;
+        timer.tma = 0xFE;
+        timer.tima = 0xFE;
+        timer.tac = 0x05; // 16 T-cycles per increment
+
+        // TMA=0xFE: every second increment overflows
+        let mut events = EventQueue::new();
+        for _ in 0..16 * 10 + TIMA_RELOAD_CYCLES as u32 {
+            timer.tick(&mut events);
+        }
+        assert_eq!(events.len(), 5);
+        assert!(events.iter().all(|&e| e == HardwareEvent::TimerOverflow));
+    }
+
+    /// Timer at 16 T-cycles per increment, `cycles` before TIMA overflows
+    fn timer_before_overflow(tma: Byte, cycles: u16) -> Timer {
+        let mut timer = Timer::new();
+        timer.div = 16 - cycles;
+        timer.tima = 0xFF;
+        timer.tma = tma;
+        timer.tac = 0x05;
+        timer
+    }
+
+    #[test]
+    fn test_tima_write_during_delay_cancels_reload() {
+        let mut timer = timer_before_overflow(0x42, 1);
+        let mut events = EventQueue::new();
+        timer.tick(&mut events);
+        timer.tick(&mut events);
+        assert_eq!(timer.read(0xFF05), 0);
+
+        timer.write(0xFF05, 0x10);
+        for _ in 0..8 {
+            timer.tick(&mut events);
+        }
+        assert_eq!(timer.read(0xFF05), 0x10);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_tma_write_during_delay_is_reloaded() {
+        let mut timer = timer_before_overflow(0x42, 1);
+        let mut events = EventQueue::new();
+        timer.tick(&mut events);
+        timer.write(0xFF06, 0x80);
+        for _ in 0..TIMA_RELOAD_CYCLES {
+            timer.tick(&mut events);
+        }
+        assert_eq!(timer.read(0xFF05), 0x80);
+        assert_eq!(events.pop(), Some(HardwareEvent::TimerOverflow));
+    }
+
+    #[test]
+    fn test_writes_during_reload_cycle() {
+        let mut timer = timer_before_overflow(0x42, 1);
+        let mut events = EventQueue::new();
+        for _ in 0..=TIMA_RELOAD_CYCLES {
+            timer.tick(&mut events);
+        }
+        assert_eq!(timer.read(0xFF05), 0x42);
+
+        // TIMA writes are ignored and TMA writes pass through to TIMA
+        timer.write(0xFF05, 0x10);
+        assert_eq!(timer.read(0xFF05), 0x42);
+        timer.write(0xFF06, 0x99);
+        assert_eq!(timer.read(0xFF05), 0x99);
+
+        // Once the reload cycle is over, writes behave normally again
+        for _ in 0..TIMA_RELOAD_CYCLES {
+            timer.tick(&mut events);
+        }
+        timer.write(0xFF06, 0x55);
+        assert_eq!(timer.read(0xFF05), 0x99);
+        timer.write(0xFF05, 0x10);
+        assert_eq!(timer.read(0xFF05), 0x10);
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_div_write_glitch() {
+        let mut timer = Timer::new();
+        timer.tac = 0x05; // Bit 3
+
+        // Bit 3 set: the reset is a falling edge
+        timer.div = 0x0008;
+        timer.write(0xFF04, 0);
+        assert_eq!(timer.tima, 1);
+
+        // Bit 3 clear (other bits set): no increment
+        timer.div = 0xFFF7;
+        timer.write(0xFF04, 0);
+        assert_eq!(timer.tima, 1);
+
+        // Bit 9 at 4096 Hz
+        timer.tac = 0x04;
+        timer.div = 0x0200;
+        timer.write(0xFF04, 0);
+        assert_eq!(timer.tima, 2);
+
+        // Disabled timer ignores the edge
+        timer.tac = 0x00;
+        timer.div = 0xFFFF;
+        timer.write(0xFF04, 0);
+        assert_eq!(timer.tima, 2);
+    }
+
+    #[test]
+    fn test_div_write_glitch_overflow() {
+        let mut timer = Timer::new();
+        timer.tima = 0xFF;
+        timer.tma = 0x42;
+        timer.tac = 0x07; // Bit 7
+        timer.div = 0x0080;
+        timer.write(0xFF04, 0);
+        assert_eq!(timer.tima, 0);
+
+        let mut events = EventQueue::new();
+        for _ in 0..TIMA_RELOAD_CYCLES {
+            timer.tick(&mut events);
         }
-        
-        // TIMA should have overflowed and reloaded from TMA
         assert_eq!(timer.tima, 0x42);
-        assert!(timer.interrupt_requested);
+        assert_eq!(events.pop(), Some(HardwareEvent::TimerOverflow));
+    }
+
+    #[test]
+    fn test_tac_write_glitch() {
+        let mut timer = Timer::new();
+        timer.div = 0x0028; // Bits 3 and 5 set, bits 7 and 9 clear
+        timer.tac = 0x05; // Bit 3
+
+        // Switching to another set bit: no edge
+        timer.write(0xFF07, 0x06);
+        assert_eq!(timer.tima, 0);
+
+        // Switching from bit 5 to clear bit 7: falling edge
+        timer.write(0xFF07, 0x07);
+        assert_eq!(timer.tima, 1);
+
+        // Switching from clear bit 7 to set bit 3: rising edge only
+        timer.write(0xFF07, 0x05);
+        assert_eq!(timer.tima, 1);
+
+        // Disabling while bit 3 is set: falling edge
+        timer.write(0xFF07, 0x01);
+        assert_eq!(timer.tima, 2);
+
+        // Enabling while bit 3 is set: rising edge only
+        timer.write(0xFF07, 0x05);
+        assert_eq!(timer.tima, 2);
+
+        // Rewriting the same value: no edge
+        timer.write(0xFF07, 0x05);
+        assert_eq!(timer.tima, 2);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let mut timer = Timer::new();
+        timer.write(0xFF06, 0x34);
+        timer.write(0xFF07, 0x06);
+
+        let state = timer.inspect();
+        assert_eq!(state.div_internal, 0xABCC);
+        assert_eq!(state.tma, 0x34);
+        assert_eq!(state.tac, 0x06);
+        assert!(state.enabled);
+        assert_eq!(state.frequency_hz, 65536);
+        assert!(!state.interrupt_requested);
+
+        // Pending from the overflow until the reload pushes the event
+        let mut events = EventQueue::new();
+        timer.write(0xFF05, 0xFF);
+        timer.write(0xFF07, 0x05);
+        timer.div = 0x000F;
+        timer.tick(&mut events);
+        assert!(timer.inspect().interrupt_requested);
+        for _ in 0..TIMA_RELOAD_CYCLES {
+            timer.tick(&mut events);
+        }
+        assert!(!timer.inspect().interrupt_requested);
+        assert_eq!(events.pop(), Some(HardwareEvent::TimerOverflow));
+    }
+
+    #[test]
+    fn test_cycles_until_tima_tick() {
+        let mut timer = Timer::new();
+        assert_eq!(timer.cycles_until_tima_tick(), u32::MAX);
+
+        timer.div = 0;
+        timer.tac = 0x05; // 16 T-cycles
+        assert_eq!(timer.cycles_until_tima_tick(), 16);
+        timer.div = 5;
+        assert_eq!(timer.cycles_until_tima_tick(), 11);
+
+        timer.tac = 0x04; // 1024 T-cycles
+        timer.div = 0x03FF;
+        assert_eq!(timer.cycles_until_tima_tick(), 1);
+
+        // The countdown matches when TIMA actually increments
+        timer.div = 0x1234;
+        timer.tima = 0;
+        let cycles = timer.cycles_until_tima_tick();
+        let mut events = EventQueue::new();
+        for _ in 0..cycles - 1 {
+            timer.tick(&mut events);
+        }
+        assert_eq!(timer.tima, 0);
+        timer.tick(&mut events);
+        assert_eq!(timer.tima, 1);
     }
 }
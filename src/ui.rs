@@ -2,18 +2,23 @@
 //!
 //! This module implements the SDL2-based user interface for the emulator.
 
+use sdl2::controller::{Axis, GameController};
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::render::{Canvas, TextureCreator};
 use sdl2::video::{Window, WindowContext};
 use sdl2::audio::{AudioQueue, AudioSpecDesired};
 use sdl2::EventPump;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-use crate::apu::SAMPLE_RATE;
+use crate::apu::channels::ChannelState;
 use crate::emu::Emulator;
 use crate::gamepad::Button;
+use crate::memory_map;
 
 /// Game Boy screen dimensions
 pub const SCREEN_WIDTH: u32 = 160;
@@ -21,23 +26,170 @@ pub const SCREEN_HEIGHT: u32 = 144;
 /// Scale factor for the window
 pub const SCALE: u32 = 4;
 
+/// Pixel upscaling algorithm applied before the frame reaches SDL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+    /// Repeat each pixel `SCALE × SCALE` times
+    #[default]
+    Nearest,
+    /// Scale2x edge-smoothing upscaler (2x)
+    Scale2x,
+}
+
+impl ScaleMode {
+    /// Factor by which this mode enlarges the 160×144 frame
+    pub fn factor(self) -> u32 {
+        match self {
+            ScaleMode::Nearest => SCALE,
+            ScaleMode::Scale2x => 2,
+        }
+    }
+}
+
+/// Window title
+const WINDOW_TITLE: &str = "rgbe - Game Boy Emulator";
+
+/// Overlay glyphs are 3×5 pixels drawn in a 4×6 cell
+const GLYPH_ADVANCE: u32 = 4;
+const GLYPH_LINE_HEIGHT: u32 = 6;
+
+/// Overlay font glyphs: five rows, bit 2 is the leftmost column
+const GLYPHS: &[(u8, [u8; 5])] = &[
+    (b'0', [7, 5, 5, 5, 7]), (b'1', [2, 6, 2, 2, 7]), (b'2', [7, 1, 7, 4, 7]),
+    (b'3', [7, 1, 7, 1, 7]), (b'4', [5, 5, 7, 1, 1]), (b'5', [7, 4, 7, 1, 7]),
+    (b'6', [7, 4, 7, 5, 7]), (b'7', [7, 1, 1, 2, 2]), (b'8', [7, 5, 7, 5, 7]),
+    (b'9', [7, 5, 7, 1, 7]), (b'A', [2, 5, 7, 5, 5]), (b'B', [6, 5, 6, 5, 6]),
+    (b'C', [3, 4, 4, 4, 3]), (b'D', [6, 5, 5, 5, 6]), (b'E', [7, 4, 6, 4, 7]),
+    (b'F', [7, 4, 6, 4, 4]), (b'G', [3, 4, 5, 5, 3]), (b'H', [5, 5, 7, 5, 5]),
+    (b'I', [7, 2, 2, 2, 7]), (b'J', [1, 1, 1, 5, 2]), (b'K', [5, 5, 6, 5, 5]),
+    (b'L', [4, 4, 4, 4, 7]), (b'M', [5, 7, 7, 5, 5]), (b'N', [6, 5, 5, 5, 5]),
+    (b'O', [2, 5, 5, 5, 2]), (b'P', [6, 5, 6, 4, 4]), (b'Q', [2, 5, 5, 6, 3]),
+    (b'R', [6, 5, 6, 5, 5]), (b'S', [3, 4, 2, 1, 6]), (b'T', [7, 2, 2, 2, 2]),
+    (b'U', [5, 5, 5, 5, 7]), (b'V', [5, 5, 5, 5, 2]), (b'W', [5, 5, 7, 7, 5]),
+    (b'X', [5, 5, 2, 5, 5]), (b'Y', [5, 5, 2, 2, 2]), (b'Z', [7, 1, 2, 4, 7]),
+    (b':', [0, 2, 0, 2, 0]), (b'.', [0, 0, 0, 0, 2]), (b'-', [0, 0, 7, 0, 0]),
+    (b'%', [5, 1, 2, 4, 5]), (b'/', [1, 1, 2, 4, 4]),
+];
+
+/// Overlay font indexed by ASCII code (missing characters are blank)
+const FONT: [[u8; 5]; 128] = {
+    let mut font = [[0; 5]; 128];
+    let mut i = 0;
+    while i < GLYPHS.len() {
+        font[GLYPHS[i].0 as usize] = GLYPHS[i].1;
+        i += 1;
+    }
+    font
+};
+
+/// Overlay text colors
+const OVERLAY_TEXT: u32 = 0xFFFFFFFF;
+const OVERLAY_SHADOW: u32 = 0xFF000000;
+const OVERLAY_BAR_ON: u32 = 0xFF40C040;
+const OVERLAY_BAR_OFF: u32 = 0xFF606060;
+const OVERLAY_MARKER: u32 = 0xFFFFFF00;
+
+/// Analog stick deflection ignored around the centre
+const STICK_DEAD_ZONE: i16 = 8000;
+
+/// Default audio latency target in milliseconds
+const DEFAULT_AUDIO_LATENCY_MS: f32 = 50.0;
+/// Number of frames in the rolling latency average
+const LATENCY_WINDOW: usize = 30;
+/// Number of per-frame latency samples kept for `Ui::latency_history`
+const LATENCY_LOG_LENGTH: usize = 600;
+/// Bytes of queued audio per millisecond at `sample_rate` (stereo i16)
+fn audio_bytes_per_ms(sample_rate: u32) -> f32 {
+    (sample_rate * 4) as f32 / 1000.0
+}
+
+/// Tracks audio queue latency and decides how much audio to queue
+#[derive(Debug, Clone)]
+struct AudioLatencyMonitor {
+    /// Queued bytes for the most recent frames
+    history: VecDeque<u32>,
+    /// Latency target in milliseconds
+    target_ms: f32,
+    /// Bytes of queued audio per millisecond
+    bytes_per_ms: f32,
+    /// Rolling average latency after each frame
+    log: VecDeque<f32>,
+}
+
+impl AudioLatencyMonitor {
+    /// Create a monitor with the given target latency for a queue playing
+    /// `sample_rate` frames per second
+    fn new(target_ms: f32, sample_rate: u32) -> Self {
+        Self {
+            history: VecDeque::with_capacity(LATENCY_WINDOW),
+            target_ms,
+            bytes_per_ms: audio_bytes_per_ms(sample_rate),
+            log: VecDeque::with_capacity(LATENCY_LOG_LENGTH),
+        }
+    }
+
+    /// Record the queue size for this frame and return how many samples
+    /// to pull to top the queue up to the target
+    ///
+    /// Nothing is pulled while the queue is above the target, so it drains
+    /// back down at the playback rate.
+    fn update(&mut self, queued_bytes: u32) -> usize {
+        if self.history.len() == LATENCY_WINDOW {
+            self.history.pop_front();
+        }
+        self.history.push_back(queued_bytes);
+
+        let average = self.average_ms();
+        if self.log.len() == LATENCY_LOG_LENGTH {
+            self.log.pop_front();
+        }
+        self.log.push_back(average);
+
+        // Whole stereo frames: 4 bytes, 2 samples each
+        let target_bytes = (self.target_ms * self.bytes_per_ms) as u32;
+        (target_bytes.saturating_sub(queued_bytes) / 4 * 2) as usize
+    }
+
+    /// Rolling average latency in milliseconds
+    fn average_ms(&self) -> f32 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.history.iter().map(|&bytes| bytes as u64).sum();
+        total as f32 / self.history.len() as f32 / self.bytes_per_ms
+    }
+}
+
 /// SDL2 UI wrapper
 pub struct Ui {
     canvas: Canvas<Window>,
     event_pump: EventPump,
     texture_creator: TextureCreator<WindowContext>,
     audio_queue: Option<AudioQueue<i16>>,
+    /// Samples pulled from the emulator before queueing
+    audio_samples: Vec<i16>,
+    latency: AudioLatencyMonitor,
+    scale_mode: ScaleMode,
+    /// First attached game controller, kept open so its events arrive
+    _controller: Option<GameController>,
+    /// Last reported left stick position
+    stick: (i16, i16),
+    /// Draw the PPU/CPU status overlay (toggled with F1)
+    debug_overlay: bool,
 }
 
 impl Ui {
-    /// Create a new UI instance
-    pub fn new() -> Result<Self, String> {
+    /// Create a new UI instance playing audio at `sample_rate` Hz
+    ///
+    /// Pass the emulator's `apu.sample_rate()` so SDL plays samples at the
+    /// rate they are generated.
+    pub fn new(sample_rate: u32) -> Result<Self, String> {
         let sdl_context = sdl2::init()?;
         let video_subsystem = sdl_context.video()?;
 
         let window = video_subsystem
             .window(
-                "rgbe - Game Boy Emulator",
+                WINDOW_TITLE,
                 SCREEN_WIDTH * SCALE,
                 SCREEN_HEIGHT * SCALE,
             )
@@ -56,7 +208,7 @@ impl Ui {
         let audio_queue = match sdl_context.audio() {
             Ok(audio_subsystem) => {
                 let desired_spec = AudioSpecDesired {
-                    freq: Some(SAMPLE_RATE as i32),
+                    freq: Some(sample_rate as i32),
                     channels: Some(2),
                     samples: Some(1024),
                 };
@@ -77,6 +229,13 @@ impl Ui {
             }
         };
 
+        let controller = sdl_context.game_controller().ok().and_then(|subsystem| {
+            let count = subsystem.num_joysticks().ok()?;
+            (0..count)
+                .filter(|&id| subsystem.is_game_controller(id))
+                .find_map(|id| subsystem.open(id).ok())
+        });
+
         let texture_creator = canvas.texture_creator();
         let event_pump = sdl_context.event_pump()?;
 
@@ -85,18 +244,182 @@ impl Ui {
             event_pump,
             texture_creator,
             audio_queue,
+            audio_samples: Vec::new(),
+            latency: AudioLatencyMonitor::new(DEFAULT_AUDIO_LATENCY_MS, sample_rate),
+            scale_mode: ScaleMode::default(),
+            _controller: controller,
+            stick: (0, 0),
+            debug_overlay: false,
         })
     }
 
+    /// Select the upscaling algorithm (takes effect on the next `run`)
+    pub fn set_scale_mode(&mut self, mode: ScaleMode) {
+        self.scale_mode = mode;
+    }
+
+    /// Current upscaling algorithm
+    pub fn scale_mode(&self) -> ScaleMode {
+        self.scale_mode
+    }
+
+    /// Show or hide the status overlay (LY, PPU mode, PC, frame, FPS, latency)
+    pub fn show_debug_overlay(&mut self, enabled: bool) {
+        self.debug_overlay = enabled;
+    }
+
+    /// Draw `text` into a 160-pixel-wide frame with its top-left corner at (x, y)
+    ///
+    /// Lowercase letters are drawn as uppercase; characters without a glyph,
+    /// including all non-ASCII ones, leave a gap. Pixels outside the buffer
+    /// are clipped.
+    pub fn draw_text(buffer: &mut [u32], x: u32, y: u32, text: &str, color: u32) {
+        let width = SCREEN_WIDTH as usize;
+        let height = buffer.len() / width;
+        for (i, ch) in text.chars().enumerate() {
+            if !ch.is_ascii() {
+                continue;
+            }
+            let glyph = FONT[ch.to_ascii_uppercase() as usize];
+            let left = x as usize + i * GLYPH_ADVANCE as usize;
+            for (row, bits) in glyph.iter().enumerate() {
+                let py = y as usize + row;
+                for col in 0..3 {
+                    let px = left + col;
+                    if bits & (4 >> col) != 0 && px < width && py < height {
+                        buffer[py * width + px] = color;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy of the current frame with the status overlay drawn on top
+    fn overlay_frame(&self, emulator: &Emulator, fps: f64) -> Vec<u32> {
+        let mut frame = emulator.get_video_buffer().to_vec();
+        let lines = [
+            format!("LY:{:3} M{}", emulator.lcd.ly, emulator.lcd.mode() as u8),
+            format!("PC:{:04X}", emulator.cpu.regs.pc),
+            format!("FRAME:{}", emulator.current_frame()),
+            format!("FPS:{:.0}", fps),
+            format!("LAT:{:.0}MS", self.audio_latency_ms()),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            let y = 1 + i as u32 * GLYPH_LINE_HEIGHT;
+            Self::draw_text(&mut frame, 2, y + 1, line, OVERLAY_SHADOW);
+            Self::draw_text(&mut frame, 1, y, line, OVERLAY_TEXT);
+        }
+        Self::draw_channel_bars(&mut frame, &emulator.audio_channel_states());
+        frame
+    }
+
+    /// Draw one bar per sound channel in the bottom-left corner
+    ///
+    /// Bar height follows the volume (grey when the channel is off) and a
+    /// yellow marker sits at a height proportional to the channel period.
+    fn draw_channel_bars(frame: &mut [u32], channels: &[ChannelState; 4]) {
+        let width = SCREEN_WIDTH as usize;
+        let bottom = SCREEN_HEIGHT as usize - 2;
+        for (i, channel) in channels.iter().enumerate() {
+            // Channel 3 reports a 0-3 output level code
+            let level = match (i, channel.volume) {
+                (2, 0) => 0,
+                (2, code) => 16 >> code,
+                (_, volume) => volume as usize,
+            };
+            let color = if channel.enabled && channel.dac_enabled { OVERLAY_BAR_ON } else { OVERLAY_BAR_OFF };
+            let marker = bottom - (channel.frequency as usize & 0x7FF) / 64;
+            for x in 2 + i * 6..6 + i * 6 {
+                for y in bottom - level * 2..=bottom {
+                    frame[y * width + x] = color;
+                }
+                frame[marker * width + x] = OVERLAY_MARKER;
+            }
+        }
+    }
+
+    /// Enlarge a 160×144 frame by repeating each pixel `scale × scale` times
+    pub fn scale_video_buffer(src: &[u32], scale: u32) -> Vec<u32> {
+        let scale = scale.max(1) as usize;
+        let width = SCREEN_WIDTH as usize;
+        let height = SCREEN_HEIGHT as usize;
+        let mut dst = Vec::with_capacity(width * height * scale * scale);
+        for row in src.chunks_exact(width).take(height) {
+            let start = dst.len();
+            for &pixel in row {
+                dst.extend(std::iter::repeat_n(pixel, scale));
+            }
+            for _ in 1..scale {
+                dst.extend_from_within(start..start + width * scale);
+            }
+        }
+        dst
+    }
+
+    /// Enlarge a 160×144 frame to 320×288 with the Scale2x algorithm
+    ///
+    /// Each output 2×2 block copies a neighbour of the source pixel where two
+    /// adjacent neighbours agree, smoothing diagonal edges; pixels on the
+    /// border treat out-of-range neighbours as the pixel itself.
+    pub fn apply_scale2x(src: &[u32]) -> Vec<u32> {
+        let width = SCREEN_WIDTH as usize;
+        let height = SCREEN_HEIGHT as usize;
+        let out_width = width * 2;
+        let mut dst = vec![0u32; out_width * height * 2];
+        for y in 0..height {
+            for x in 0..width {
+                let p = src[y * width + x];
+                let a = if y > 0 { src[(y - 1) * width + x] } else { p };
+                let b = if x + 1 < width { src[y * width + x + 1] } else { p };
+                let c = if x > 0 { src[y * width + x - 1] } else { p };
+                let d = if y + 1 < height { src[(y + 1) * width + x] } else { p };
+
+                let (e0, e1, e2, e3) = if b != c && a != d {
+                    (
+                        if c == a { c } else { p },
+                        if a == b { b } else { p },
+                        if c == d { c } else { p },
+                        if d == b { b } else { p },
+                    )
+                } else {
+                    (p, p, p, p)
+                };
+
+                let top = y * 2 * out_width + x * 2;
+                dst[top] = e0;
+                dst[top + 1] = e1;
+                dst[top + out_width] = e2;
+                dst[top + out_width + 1] = e3;
+            }
+        }
+        dst
+    }
+
+    /// Rolling average audio latency in milliseconds
+    pub fn audio_latency_ms(&self) -> f32 {
+        self.latency.average_ms()
+    }
+
+    /// Set the audio latency the queue is steered towards (default 50ms)
+    pub fn set_audio_target_latency(&mut self, ms: f32) {
+        self.latency.target_ms = ms;
+    }
+
+    /// Rolling average latency recorded after each frame, oldest first
+    pub fn latency_history(&self) -> &VecDeque<f32> {
+        &self.latency.log
+    }
 
     /// Run the emulator with UI
     pub fn run(&mut self, emulator: &mut Emulator) -> Result<(), String> {
+        let scale_mode = self.scale_mode;
+        let texture_width = SCREEN_WIDTH * scale_mode.factor();
         let mut texture = self
             .texture_creator
             .create_texture_streaming(
                 PixelFormatEnum::ARGB8888,
-                SCREEN_WIDTH,
-                SCREEN_HEIGHT,
+                texture_width,
+                SCREEN_HEIGHT * scale_mode.factor(),
             )
             .map_err(|e| e.to_string())?;
 
@@ -105,6 +428,19 @@ impl Ui {
         // Cycles per frame: ~70224 T-cycles (456 * 154)
         const CYCLES_PER_FRAME: u32 = 70224;
 
+        // Count invalid opcodes instead of crashing; `I` shows the count in the title
+        let invalid_opcodes = Rc::new(Cell::new(0u32));
+        let counter = invalid_opcodes.clone();
+        emulator.set_invalid_opcode_handler(move |_, _| counter.set(counter.get() + 1));
+        let mut show_invalid_opcodes = false;
+
+        // FPS and emulation speed, refreshed once a second
+        let mut stats = String::new();
+        let mut stats_frames = 0u32;
+        let mut stats_start = Instant::now();
+        let mut fps = 0.0;
+        let mut shown_title = String::from(WINDOW_TITLE);
+
         'running: loop {
             let frame_start = Instant::now();
 
@@ -112,10 +448,43 @@ impl Ui {
             for event in self.event_pump.poll_iter() {
                 match event {
                     Event::Quit { .. } => break 'running,
-                    Event::KeyDown { keycode: Some(key), .. } => {
+                    Event::KeyDown { keycode: Some(key), keymod, repeat, .. } => {
                         if key == Keycode::Escape {
                             break 'running;
                         }
+                        if key == Keycode::R && !repeat && keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                            toggle_audio_recording(emulator);
+                            continue;
+                        }
+                        if key == Keycode::M && !repeat {
+                            print_memory_map(emulator);
+                            continue;
+                        }
+                        if key == Keycode::F1 && !repeat {
+                            self.debug_overlay = !self.debug_overlay;
+                            continue;
+                        }
+                        if key == Keycode::F5 && !repeat {
+                            emulator.resume();
+                            continue;
+                        }
+                        if key == Keycode::I && !repeat {
+                            show_invalid_opcodes = !show_invalid_opcodes;
+                            continue;
+                        }
+                        #[cfg(feature = "gif-recording")]
+                        if key == Keycode::G && !repeat && keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                            toggle_gif_recording(emulator);
+                            continue;
+                        }
+                        #[cfg(feature = "screenshot")]
+                        if key == Keycode::F12 {
+                            let path = format!("screenshot_{:08}.png", emulator.current_frame());
+                            match emulator.save_screenshot(&path) {
+                                Ok(()) => println!("Saved screenshot: {}", path),
+                                Err(e) => eprintln!("Failed to save screenshot: {}", e),
+                            }
+                        }
                         if let Some(button) = keycode_to_button(key) {
                             emulator.set_button(button, true);
                         }
@@ -125,36 +494,54 @@ impl Ui {
                             emulator.set_button(button, false);
                         }
                     }
+                    Event::ControllerAxisMotion { axis, value, .. } => {
+                        match axis {
+                            Axis::LeftX => self.stick.0 = value,
+                            Axis::LeftY => self.stick.1 = value,
+                            _ => continue,
+                        }
+                        emulator.set_analog_stick(self.stick.0, self.stick.1, STICK_DEAD_ZONE);
+                    }
                     _ => {}
                 }
             }
 
             // Run emulation for one frame worth of cycles
             let start_ticks = emulator.ctx.ticks;
-            while emulator.ctx.ticks - start_ticks < CYCLES_PER_FRAME as u64 {
+            let frame_cycles = (CYCLES_PER_FRAME as f32 * emulator.ctx.overclock_factor) as u64;
+            while emulator.ctx.ticks - start_ticks < frame_cycles && !emulator.is_paused() {
                 if !emulator.step() {
                     break 'running;
                 }
+                // Keep showing the frame so far while stopped for the debugger
+                if let Some(reason) = emulator.break_reason().filter(|_| emulator.is_paused()) {
+                    println!("Stopped at {:?} (PC={:04X}); press F5 to continue", reason, emulator.cpu.regs.pc);
+                }
             }
 
-            // Queue generated audio samples.
-            let audio = emulator.get_audio_buffer();
-            if !audio.is_empty() {
-                if let Some(audio_queue) = self.audio_queue.as_ref() {
-                    // Keep latency bounded under heavy load.
-                    let max_queued_bytes = (SAMPLE_RATE / 5) * 4;
-                    if audio_queue.size() > max_queued_bytes {
-                        audio_queue.clear();
-                    }
-                    if let Err(err) = audio_queue.queue_audio(audio) {
+            // Pull just enough samples to keep the queue at the latency
+            // target; the APU buffer drops the oldest ones if we fall behind.
+            if let Some(audio_queue) = self.audio_queue.as_ref() {
+                let wanted = self.latency.update(audio_queue.size());
+                self.audio_samples.resize(wanted, 0);
+                let count = emulator.read_audio_samples(&mut self.audio_samples);
+                if count > 0 {
+                    if let Err(err) = audio_queue.queue_audio(&self.audio_samples[..count]) {
                         eprintln!("Audio output disabled: {}", err);
                         self.audio_queue = None;
                     }
                 }
+            } else {
+                emulator.get_audio_buffer();
             }
 
-            // Update texture with video buffer
-            let video_buffer = emulator.get_video_buffer();
+            // Update texture with the upscaled video buffer
+            let overlay = self.debug_overlay.then(|| self.overlay_frame(emulator, fps));
+            let frame = overlay.as_deref().unwrap_or(emulator.get_video_buffer());
+            let video_buffer = match scale_mode {
+                ScaleMode::Nearest => Self::scale_video_buffer(frame, SCALE),
+                ScaleMode::Scale2x => Self::apply_scale2x(frame),
+            };
             texture
                 .update(
                     None,
@@ -164,10 +551,25 @@ impl Ui {
                             video_buffer.len() * 4,
                         )
                     },
-                    SCREEN_WIDTH as usize * 4,
+                    texture_width as usize * 4,
                 )
                 .map_err(|e| e.to_string())?;
 
+            // Keep the title's stats and invalid opcode count up to date
+            stats_frames += 1;
+            let stats_elapsed = stats_start.elapsed();
+            if stats_elapsed >= Duration::from_secs(1) {
+                fps = stats_frames as f64 / stats_elapsed.as_secs_f64();
+                stats = format!("{:.0} FPS, {:.2}x speed", fps, emulator.emulation_speed());
+                stats_frames = 0;
+                stats_start = Instant::now();
+            }
+            let title = window_title(&stats, show_invalid_opcodes.then(|| invalid_opcodes.get()));
+            if title != shown_title {
+                self.canvas.window_mut().set_title(&title).map_err(|e| e.to_string())?;
+                shown_title = title;
+            }
+
             // Render
             self.canvas.clear();
             self.canvas.copy(&texture, None, None)?;
@@ -180,10 +582,79 @@ impl Ui {
             }
         }
 
+        if let Err(e) = emulator.stop_audio_recording() {
+            eprintln!("Failed to finish audio recording: {}", e);
+        }
+        #[cfg(feature = "gif-recording")]
+        if let Err(e) = emulator.stop_gif_recording() {
+            eprintln!("Failed to finish GIF recording: {}", e);
+        }
+
         Ok(())
     }
 }
 
+/// Window title with the optional stats and invalid opcode count appended
+fn window_title(stats: &str, invalid_opcodes: Option<u32>) -> String {
+    let mut title = String::from(WINDOW_TITLE);
+    if !stats.is_empty() {
+        title.push_str(" - ");
+        title.push_str(stats);
+    }
+    if let Some(count) = invalid_opcodes {
+        title.push_str(&format!(" - {} invalid opcodes", count));
+    }
+    title
+}
+
+/// Start or stop WAV recording, naming the file after the current frame
+fn toggle_audio_recording(emulator: &mut Emulator) {
+    if emulator.is_recording_audio() {
+        match emulator.stop_audio_recording() {
+            Ok(()) => println!("Audio recording stopped"),
+            Err(e) => eprintln!("Failed to finish audio recording: {}", e),
+        }
+    } else {
+        let path = format!("recording_{:08}.wav", emulator.current_frame());
+        match emulator.start_audio_recording(&path) {
+            Ok(()) => println!("Recording audio to {}", path),
+            Err(e) => eprintln!("Failed to start audio recording: {}", e),
+        }
+    }
+}
+
+/// Start or stop GIF recording (every other frame), naming the file after the current frame
+#[cfg(feature = "gif-recording")]
+fn toggle_gif_recording(emulator: &mut Emulator) {
+    if emulator.is_recording_gif() {
+        match emulator.stop_gif_recording() {
+            Ok(()) => println!("GIF recording stopped"),
+            Err(e) => eprintln!("Failed to finish GIF recording: {}", e),
+        }
+    } else {
+        // A recording that failed mid-way reports its error here
+        if let Err(e) = emulator.stop_gif_recording() {
+            eprintln!("GIF recording stopped: {}", e);
+        }
+        let path = format!("recording_{:08}.gif", emulator.current_frame());
+        match emulator.start_gif_recording(&path, 2) {
+            Ok(()) => println!("Recording GIF to {}", path),
+            Err(e) => eprintln!("Failed to start GIF recording: {}", e),
+        }
+    }
+}
+
+/// Print the current memory map (with active banks) to stderr
+fn print_memory_map(emulator: &Emulator) {
+    for (start, end, region) in memory_map::full_map(&emulator.bus) {
+        if start == end {
+            eprintln!("{:04X}       {}", start, region);
+        } else {
+            eprintln!("{:04X}-{:04X}  {}", start, end, region);
+        }
+    }
+}
+
 /// Convert SDL2 keycode to Game Boy button
 fn keycode_to_button(keycode: Keycode) -> Option<Button> {
     match keycode {
@@ -198,3 +669,133 @@ fn keycode_to_button(keycode: Keycode) -> Option<Button> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apu::SAMPLE_RATE;
+
+    /// Queue size in bytes for `ms` milliseconds of audio
+    fn bytes_for_ms(ms: f32) -> u32 {
+        (ms * audio_bytes_per_ms(SAMPLE_RATE)) as u32
+    }
+
+    #[test]
+    fn test_latency_within_target() {
+        let mut monitor = AudioLatencyMonitor::new(50.0, SAMPLE_RATE);
+        for _ in 0..LATENCY_WINDOW {
+            assert_eq!(monitor.update(bytes_for_ms(50.0)), 0);
+        }
+        assert!((monitor.average_ms() - 50.0).abs() < 0.1);
+        assert_eq!(monitor.log.len(), LATENCY_WINDOW);
+    }
+
+    #[test]
+    fn test_latency_too_high_pulls_nothing() {
+        let mut monitor = AudioLatencyMonitor::new(50.0, SAMPLE_RATE);
+        assert_eq!(monitor.update(bytes_for_ms(200.0)), 0);
+        assert!(monitor.average_ms() > 150.0);
+    }
+
+    #[test]
+    fn test_latency_follows_sample_rate() {
+        let mut monitor = AudioLatencyMonitor::new(50.0, 48000);
+        // 50ms at 48 kHz is 9600 bytes
+        monitor.update(9600);
+        assert!((monitor.average_ms() - 50.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_latency_too_low_pulls_up_to_target() {
+        let mut monitor = AudioLatencyMonitor::new(50.0, 48000);
+        // 40ms short at 48 kHz is 1920 stereo frames
+        assert_eq!(monitor.update(1920), 3840);
+
+        monitor.target_ms = 10.0;
+        assert_eq!(monitor.update(1920), 0);
+    }
+
+    /// Black frame with a single white pixel at (x, y)
+    fn frame_with_pixel(x: usize, y: usize) -> Vec<u32> {
+        let mut frame = vec![0xFF000000; (SCREEN_WIDTH * SCREEN_HEIGHT) as usize];
+        frame[y * SCREEN_WIDTH as usize + x] = 0xFFFFFFFF;
+        frame
+    }
+
+    /// Positions of white pixels in a frame `width` pixels wide
+    fn white_pixels(frame: &[u32], width: usize) -> Vec<(usize, usize)> {
+        frame
+            .iter()
+            .enumerate()
+            .filter(|&(_, &p)| p == 0xFFFFFFFF)
+            .map(|(i, _)| (i % width, i / width))
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut buffer = vec![0u32; 160 * 144];
+        Ui::draw_text(&mut buffer, 10, 20, "1a", 0xFFFFFFFF);
+        let lit = |buffer: &[u32], x: usize, y: usize| buffer[y * 160 + x] == 0xFFFFFFFF;
+
+        // '1' rows: .#. / ##. / .#. / .#. / ###
+        let one = [[false, true, false], [true, true, false], [false, true, false], [false, true, false], [true, true, true]];
+        for (row, expected) in one.iter().enumerate() {
+            for (col, &on) in expected.iter().enumerate() {
+                assert_eq!(lit(&buffer, 10 + col, 20 + row), on, "'1' at {},{}", col, row);
+            }
+        }
+        // 'a' draws as 'A' one cell to the right: .#. / #.# / ### / #.# / #.#
+        assert!(lit(&buffer, 15, 20));
+        assert!(!lit(&buffer, 14, 20));
+        assert!(lit(&buffer, 14, 22) && lit(&buffer, 15, 22) && lit(&buffer, 16, 22));
+        assert!(!lit(&buffer, 15, 24));
+        // The gap column and the row below the glyphs stay clear
+        assert!((20..26).all(|y| !lit(&buffer, 13, y)));
+        assert_eq!(buffer.iter().filter(|&&p| p != 0).count(), 8 + 10);
+
+        // Text running off the frame is clipped
+        Ui::draw_text(&mut buffer, 158, 142, "88", 0xFF00FF00);
+        assert_eq!(buffer[142 * 160 + 159], 0xFF00FF00);
+
+        // A non-ASCII character leaves a single gap rather than a masked glyph
+        let mut buffer = vec![0u32; 160 * 144];
+        Ui::draw_text(&mut buffer, 0, 0, "\u{00C1}1", 0xFFFFFFFF);
+        assert!((0..4).all(|x| (0..5).all(|y| !lit(&buffer, x, y))));
+        assert!(lit(&buffer, 5, 0));
+    }
+
+    #[test]
+    fn test_window_title() {
+        assert_eq!(window_title("", None), WINDOW_TITLE);
+        assert_eq!(
+            window_title("60 FPS, 1.00x speed", Some(3)),
+            "rgbe - Game Boy Emulator - 60 FPS, 1.00x speed - 3 invalid opcodes"
+        );
+    }
+
+    #[test]
+    fn test_nearest_scale_2x() {
+        let scaled = Ui::scale_video_buffer(&frame_with_pixel(10, 5), 2);
+        assert_eq!(scaled.len(), 320 * 288);
+        assert_eq!(white_pixels(&scaled, 320), vec![(20, 10), (21, 10), (20, 11), (21, 11)]);
+    }
+
+    #[test]
+    fn test_scale2x_single_pixel() {
+        let scaled = Ui::apply_scale2x(&frame_with_pixel(10, 5));
+        assert_eq!(scaled.len(), 320 * 288);
+        assert_eq!(white_pixels(&scaled, 320), vec![(20, 10), (21, 10), (20, 11), (21, 11)]);
+    }
+
+    #[test]
+    fn test_scale2x_smooths_diagonal() {
+        // White above and to the left: the top-left corner of the black pixel turns white
+        let mut frame = frame_with_pixel(10, 4);
+        frame[5 * SCREEN_WIDTH as usize + 9] = 0xFFFFFFFF;
+        let scaled = Ui::apply_scale2x(&frame);
+        assert_eq!(scaled[10 * 320 + 20], 0xFFFFFFFF);
+        assert_eq!(scaled[10 * 320 + 21], 0xFF000000);
+        assert_eq!(scaled[11 * 320 + 20], 0xFF000000);
+    }
+}
@@ -0,0 +1,145 @@
+//! WebAssembly Bindings
+//!
+//! This module exposes the emulator to JavaScript through `wasm-bindgen`.
+//! Frames and samples are collected by buffering backends each frame and
+//! handed to the page on request. Build with `scripts/build-wasm.sh`; see
+//! `web/index.html` for a canvas front end.
+
+use crate::audio::AudioOutput;
+use crate::emu::Emulator;
+use crate::gamepad::Button;
+use crate::ppu::{VideoOutput, SCREEN_HEIGHT, SCREEN_WIDTH};
+use wasm_bindgen::prelude::*;
+
+/// Button codes accepted by `WasmEmulator::set_button`, in order
+const BUTTONS: [Button; 8] = [
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+    Button::Right,
+    Button::Left,
+    Button::Up,
+    Button::Down,
+];
+
+/// Keeps the most recent frame
+struct FrameBuffer {
+    pixels: Vec<u32>,
+}
+
+impl VideoOutput for FrameBuffer {
+    fn present_frame(&mut self, frame: &[u32]) {
+        self.pixels.clear();
+        self.pixels.extend_from_slice(frame);
+    }
+}
+
+/// Accumulates samples until the page drains them
+struct SampleBuffer {
+    samples: Vec<i16>,
+}
+
+impl AudioOutput for SampleBuffer {
+    fn write_samples(&mut self, samples: &[i16]) {
+        self.samples.extend_from_slice(samples);
+    }
+}
+
+/// Emulator handle for JavaScript
+#[wasm_bindgen]
+pub struct WasmEmulator {
+    emulator: Emulator,
+    video: FrameBuffer,
+    audio: SampleBuffer,
+}
+
+#[wasm_bindgen]
+impl WasmEmulator {
+    /// Load a ROM image; throws if the cartridge is not supported
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom_data: &[u8]) -> Result<WasmEmulator, String> {
+        let emulator = Emulator::from_bytes(rom_data.to_vec())?;
+        Ok(Self {
+            emulator,
+            video: FrameBuffer { pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT] },
+            audio: SampleBuffer { samples: Vec::new() },
+        })
+    }
+
+    /// Run one frame (~70224 T-cycles)
+    pub fn step_frame(&mut self) {
+        self.emulator.run_frame_to(&mut self.video, &mut self.audio);
+    }
+
+    /// Last completed frame as 160x144 ARGB8888 pixels
+    pub fn get_video_buffer(&self) -> Vec<u32> {
+        self.video.pixels.clone()
+    }
+
+    /// Interleaved stereo samples produced since the last call
+    pub fn get_audio_buffer(&mut self) -> Vec<i16> {
+        core::mem::take(&mut self.audio.samples)
+    }
+
+    /// Output sample rate in Hz
+    pub fn sample_rate(&self) -> u32 {
+        self.emulator.apu.sample_rate()
+    }
+
+    /// Press or release a button
+    ///
+    /// Codes: 0 A, 1 B, 2 Select, 3 Start, 4 Right, 5 Left, 6 Up, 7 Down.
+    /// Unknown codes are ignored.
+    pub fn set_button(&mut self, button: u8, pressed: bool) {
+        if let Some(&button) = BUTTONS.get(button as usize) {
+            self.emulator.set_button(button, pressed);
+        }
+    }
+
+    /// Check if the emulator is still running
+    pub fn is_running(&self) -> bool {
+        self.emulator.is_running()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blank 32KB ROM that spins at the entry point
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]); // JR -2
+        rom[0x134..0x138].copy_from_slice(b"WASM");
+        rom
+    }
+
+    #[test]
+    fn test_step_frame_fills_buffers() {
+        let mut emu = WasmEmulator::new(&rom()).unwrap();
+        assert!(emu.is_running());
+        emu.step_frame();
+
+        assert_eq!(emu.get_video_buffer().len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        let samples = emu.get_audio_buffer();
+        assert!(!samples.is_empty());
+        assert_eq!(samples.len() % 2, 0);
+        assert!(emu.get_audio_buffer().is_empty());
+    }
+
+    #[test]
+    fn test_button_codes() {
+        let mut emu = WasmEmulator::new(&rom()).unwrap();
+        emu.set_button(3, true);
+        assert!(emu.emulator.gamepad.is_pressed(Button::Start));
+        emu.set_button(7, true);
+        assert!(emu.emulator.gamepad.is_pressed(Button::Down));
+        emu.set_button(3, false);
+        assert!(!emu.emulator.gamepad.is_pressed(Button::Start));
+
+        // Out of range codes are ignored
+        emu.set_button(8, true);
+        assert!(WasmEmulator::new(&[0u8; 16]).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+//! APU Tests
+//!
+//! These tests program the APU through its registers, as a game would, and
+//! check channel output, register side effects and the mixed sample buffer.
+
+use gbemu::apu::channels::{Channel1, Channel4};
+use gbemu::apu::{Apu, CPU_CLOCK};
+
+/// Clock the frame sequencer `steps` times by toggling DIV bit 12
+fn clock_frame_sequencer(apu: &mut Apu, steps: u32) {
+    for _ in 0..steps {
+        apu.tick(1 << 12);
+        apu.tick(0);
+    }
+}
+
+/// Run the APU for `cycles` T-cycles with a free-running DIV
+fn run(apu: &mut Apu, cycles: u32) {
+    for div in 1..=cycles {
+        apu.tick(div as u16);
+    }
+}
+
+/// T-cycles per duty step of a triggered 50% duty channel 1
+///
+/// The 50% pattern goes low entering step 1 and high entering step 5, so
+/// the low phase spans exactly four steps.
+fn duty_step_period(ch: &mut Channel1) -> u32 {
+    while ch.output() != 0 {
+        ch.tick();
+    }
+    let mut ticks = 0;
+    while ch.output() == 0 {
+        ch.tick();
+        ticks += 1;
+    }
+    ticks / 4
+}
+
+/// Trigger channel 2 at full volume, 50% duty and the lowest frequency
+///
+/// The output stays high for the first 8192 T-cycles after the trigger.
+fn trigger_steady_ch2(apu: &mut Apu) {
+    apu.write(0xFF16, 0x80); // NR21: 50% duty
+    apu.write(0xFF17, 0xF0); // NR22: volume 15, no envelope
+    apu.write(0xFF18, 0x00); // NR23
+    apu.write(0xFF19, 0x80); // NR24: trigger, frequency 0
+}
+
+#[test]
+fn test_ch1_trigger_loads_volume_and_period() {
+    let mut apu = Apu::default();
+    apu.write(0xFF11, 0x80); // 50% duty
+    apu.write(0xFF12, 0xA0); // Initial volume 10
+    apu.write(0xFF13, 0xF8);
+    apu.write(0xFF14, 0x87); // Trigger, frequency 0x7F8
+
+    assert!(apu.ch1.enabled);
+    assert_eq!(apu.ch1.output(), 10);
+    assert_eq!(apu.read(0xFF26) & 0x01, 0x01);
+
+    // Period is (2048 - 0x7F8) * 4 = 32 T-cycles per duty step
+    for _ in 0..31 {
+        apu.ch1.tick();
+    }
+    assert_eq!(apu.ch1.output(), 10);
+    apu.ch1.tick();
+    assert_eq!(apu.ch1.output(), 0);
+    assert_eq!(duty_step_period(&mut apu.ch1), 32);
+}
+
+#[test]
+fn test_envelope_increase_and_decrease() {
+    let mut apu = Apu::default();
+    apu.write(0xFF11, 0x80);
+    apu.write(0xFF12, 0x09); // Volume 0, increase, period 1
+    apu.write(0xFF14, 0x80);
+    assert_eq!(apu.ch1.output(), 0);
+    for expected in 1..=3 {
+        apu.ch1.tick_envelope();
+        assert_eq!(apu.ch1.output(), expected);
+    }
+
+    apu.write(0xFF12, 0xF2); // Volume 15, decrease, period 2
+    apu.write(0xFF14, 0x80);
+    assert_eq!(apu.ch1.output(), 15);
+    apu.ch1.tick_envelope();
+    assert_eq!(apu.ch1.output(), 15);
+    apu.ch1.tick_envelope();
+    assert_eq!(apu.ch1.output(), 14);
+    for _ in 0..40 {
+        apu.ch1.tick_envelope();
+    }
+    assert_eq!(apu.ch1.output(), 0);
+}
+
+#[test]
+fn test_length_counter_disables_channel() {
+    let mut apu = Apu::default();
+    apu.write(0xFF11, 0x80 | 62); // Length 64 - 62 = 2
+    apu.write(0xFF12, 0xF0);
+    apu.write(0xFF14, 0xC0); // Trigger with length enabled
+
+    apu.ch1.tick_length();
+    assert_eq!(apu.read(0xFF26) & 0x01, 0x01);
+    apu.ch1.tick_length();
+    assert_eq!(apu.read(0xFF26) & 0x01, 0x00);
+    assert_eq!(apu.ch1.output(), 0);
+
+    // Without the length enable bit the counter is ignored
+    apu.write(0xFF11, 0x80 | 63);
+    apu.write(0xFF14, 0x80);
+    for _ in 0..10 {
+        apu.ch1.tick_length();
+    }
+    assert!(apu.ch1.enabled);
+}
+
+#[test]
+fn test_sweep_negate_lowers_frequency() {
+    let mut apu = Apu::default();
+    apu.write(0xFF10, 0x19); // Period 1, negate, shift 1
+    apu.write(0xFF11, 0x80);
+    apu.write(0xFF12, 0xF0);
+    apu.write(0xFF13, 0x00);
+    apu.write(0xFF14, 0x84); // Trigger, frequency 0x400
+    assert_eq!(duty_step_period(&mut apu.ch1), (2048 - 0x400) * 4);
+
+    // 0x400 - (0x400 >> 1) = 0x200
+    apu.ch1.tick_sweep();
+    assert!(apu.ch1.enabled);
+    assert_eq!(duty_step_period(&mut apu.ch1), (2048 - 0x200) * 4);
+
+    // 0x200 - (0x200 >> 1) = 0x100
+    apu.ch1.tick_sweep();
+    assert_eq!(duty_step_period(&mut apu.ch1), (2048 - 0x100) * 4);
+
+    // Without negate the same shift overflows 2047 and disables the channel
+    apu.write(0xFF10, 0x11);
+    apu.write(0xFF13, 0xF0);
+    apu.write(0xFF14, 0x87); // Frequency 0x7F0
+    assert!(!apu.ch1.enabled);
+}
+
+#[test]
+fn test_ch3_volume_codes() {
+    let mut apu = Apu::default();
+    apu.write(0xFF30, 0xF0); // First sample 15
+    apu.write(0xFF1A, 0x80); // DAC on
+
+    for (code, expected) in [(0u8, 0u8), (1, 15), (2, 7), (3, 3)] {
+        apu.write(0xFF1C, code << 5);
+        apu.write(0xFF1E, 0x80);
+        assert_eq!(apu.ch3.output(), expected, "volume code {}", code);
+    }
+}
+
+#[test]
+fn test_ch4_lfsr_width() {
+    /// Output bit after each LFSR step (divisor code 0: 8 T-cycles per step)
+    fn noise_sequence(ch: &mut Channel4, steps: usize) -> Vec<bool> {
+        (0..steps)
+            .map(|_| {
+                for _ in 0..8 {
+                    ch.tick();
+                }
+                ch.output() != 0
+            })
+            .collect()
+    }
+
+    let mut apu = Apu::default();
+    apu.write(0xFF21, 0xF0);
+    apu.write(0xFF22, 0x08); // 7-bit mode
+    apu.write(0xFF23, 0x80);
+    let short = noise_sequence(&mut apu.ch4, 600);
+    assert!((127..400).all(|i| short[i] == short[i + 127]));
+
+    apu.write(0xFF22, 0x00); // 15-bit mode
+    apu.write(0xFF23, 0x80);
+    let long = noise_sequence(&mut apu.ch4, 32767 + 200);
+    assert!((0..200).all(|i| long[i] == long[i + 32767]));
+    assert!((0..200).any(|i| long[i] != long[i + 127]));
+}
+
+#[test]
+fn test_nr52_status_follows_dac_enable() {
+    // (status bit, DAC register, DAC-on value, trigger register, trigger value)
+    let channels = [
+        (0x01, 0xFF12, 0xF0, 0xFF14, 0x80),
+        (0x02, 0xFF17, 0xF0, 0xFF19, 0x80),
+        (0x04, 0xFF1A, 0x80, 0xFF1E, 0x80),
+        (0x08, 0xFF21, 0xF0, 0xFF23, 0x80),
+    ];
+    for (bit, dac_reg, dac_on, trigger_reg, trigger) in channels {
+        let mut apu = Apu::default();
+
+        // Triggering with the DAC off leaves the channel stopped
+        apu.write(trigger_reg, trigger);
+        assert_eq!(apu.read(0xFF26) & bit, 0, "NR52 bit {:#04X}", bit);
+
+        apu.write(dac_reg, dac_on);
+        apu.write(trigger_reg, trigger);
+        assert_eq!(apu.read(0xFF26) & bit, bit, "NR52 bit {:#04X}", bit);
+
+        // Clearing the DAC bits stops the channel at once
+        apu.write(dac_reg, 0x00);
+        assert_eq!(apu.read(0xFF26) & bit, 0, "NR52 bit {:#04X}", bit);
+
+        // Turning the DAC back on does not restart it without a trigger
+        apu.write(dac_reg, dac_on);
+        assert_eq!(apu.read(0xFF26) & bit, 0, "NR52 bit {:#04X}", bit);
+        apu.write(trigger_reg, trigger);
+        assert_eq!(apu.read(0xFF26) & bit, bit, "NR52 bit {:#04X}", bit);
+    }
+}
+
+#[test]
+fn test_nr52_status_with_silent_envelope() {
+    let mut apu = Apu::default();
+    // Volume 0 with an increasing envelope keeps the DAC on
+    apu.write(0xFF12, 0x08);
+    apu.write(0xFF14, 0x80);
+    assert_eq!(apu.read(0xFF26) & 0x01, 0x01);
+    assert_eq!(apu.ch1.output(), 0);
+
+    // Volume 0 decreasing turns it off
+    apu.write(0xFF12, 0x07);
+    assert_eq!(apu.read(0xFF26) & 0x01, 0x00);
+
+    // A channel flagged enabled with its DAC off still reads as stopped
+    apu.ch1.enabled = true;
+    assert_eq!(apu.read(0xFF26) & 0x01, 0x00);
+}
+
+#[test]
+fn test_nr52_power_off_clears_registers() {
+    let mut apu = Apu::default();
+    apu.write(0xFF10, 0x7F);
+    apu.write(0xFF11, 0xC0);
+    apu.write(0xFF12, 0xF3);
+    apu.write(0xFF17, 0xF3);
+    apu.write(0xFF1A, 0x80);
+    apu.write(0xFF1C, 0x60);
+    apu.write(0xFF21, 0xF3);
+    apu.write(0xFF22, 0x5F);
+    trigger_steady_ch2(&mut apu);
+
+    apu.write(0xFF26, 0x00);
+    assert_eq!(apu.read(0xFF10), 0x80);
+    assert_eq!(apu.read(0xFF11), 0x3F);
+    assert_eq!(apu.read(0xFF12), 0x00);
+    assert_eq!(apu.read(0xFF17), 0x00);
+    assert_eq!(apu.read(0xFF1A), 0x7F);
+    assert_eq!(apu.read(0xFF1C), 0x9F);
+    assert_eq!(apu.read(0xFF21), 0x00);
+    assert_eq!(apu.read(0xFF22), 0x00);
+    assert_eq!(apu.read(0xFF24), 0x00);
+    assert_eq!(apu.read(0xFF25), 0x00);
+    assert_eq!(apu.read(0xFF26), 0x70);
+
+    // Registers ignore writes while powered off; wave RAM does not
+    apu.write(0xFF12, 0xF0);
+    apu.write(0xFF30, 0x12);
+    assert_eq!(apu.read(0xFF12), 0x00);
+    assert_eq!(apu.read(0xFF30), 0x12);
+}
+
+#[test]
+fn test_nr51_panning() {
+    let mut apu = Apu::default();
+    apu.write(0xFF24, 0x77);
+    apu.write(0xFF25, 0x02); // Channel 2 right only
+    trigger_steady_ch2(&mut apu);
+    run(&mut apu, 4000);
+
+    // Full DAC level at master volume 7, then decaying through the high-pass filter
+    let samples = apu.get_audio_buffer();
+    assert!(!samples.is_empty());
+    assert_eq!(samples[..2], [0, 8192]);
+    for (frame, next) in samples.chunks_exact(2).zip(samples.chunks_exact(2).skip(1)) {
+        assert_eq!(next[0], 0);
+        assert!(next[1] > 0 && next[1] <= frame[1]);
+    }
+
+    apu.write(0xFF25, 0x20); // Channel 2 left only
+    run(&mut apu, 1000);
+    let samples = apu.get_audio_buffer();
+    assert_eq!(samples[0], 8192);
+    for frame in samples.chunks_exact(2) {
+        assert!(frame[0] > 0);
+        assert!(frame[1] <= 0);
+    }
+}
+
+#[test]
+fn test_nr50_master_volume() {
+    let mut apu = Apu::default();
+    apu.write(0xFF25, 0x22); // Channel 2 both sides
+    apu.write(0xFF24, 0x70); // Left volume 7, right volume 0
+    trigger_steady_ch2(&mut apu);
+    run(&mut apu, 4000);
+
+    let samples = apu.get_audio_buffer();
+    assert!(!samples.is_empty());
+    // 1.0 * 8 / 8 * 8192 and 1.0 * 1 / 8 * 8192
+    assert_eq!(samples[..2], [8192, 1024]);
+    for frame in samples.chunks_exact(2) {
+        assert!((frame[0] - 8 * frame[1]).abs() <= 8);
+    }
+}
+
+#[test]
+fn test_muted_channel_excluded_from_mix() {
+    let mut apu = Apu::default();
+    apu.write(0xFF25, 0xD2); // Channel 2 right, channels 1, 3 and 4 left
+    apu.write(0xFF12, 0xF0); // Channel 1: volume 15
+    apu.write(0xFF14, 0x80);
+    trigger_steady_ch2(&mut apu);
+    apu.write(0xFF1A, 0x80); // Channel 3: DAC on, 100% output
+    apu.write(0xFF1C, 0x20);
+    apu.write(0xFF1E, 0x80);
+    apu.write(0xFF21, 0xF0); // Channel 4: volume 15
+    apu.write(0xFF23, 0x80);
+    assert_eq!(apu.read(0xFF26) & 0x0F, 0x0F);
+
+    let mut unmuted = apu.clone();
+    apu.set_channel_enabled(2, false);
+    assert!(!apu.channel_enabled(2));
+    assert!(apu.channel_enabled(1) && apu.channel_enabled(3) && apu.channel_enabled(4));
+    assert!(!apu.channel_enabled(0) && !apu.channel_enabled(5));
+    run(&mut apu, 4000);
+    run(&mut unmuted, 4000);
+
+    let muted_samples = apu.get_audio_buffer().to_vec();
+    let unmuted_samples = unmuted.get_audio_buffer();
+    assert_eq!(muted_samples.len(), unmuted_samples.len());
+    for (muted, unmuted) in muted_samples.chunks_exact(2).zip(unmuted_samples.chunks_exact(2)) {
+        assert_eq!(muted[0], unmuted[0]);
+        assert_eq!(muted[1], 0);
+        assert!(unmuted[1] > 0);
+    }
+    // Channel 2 is still running
+    assert_eq!(apu.read(0xFF26) & 0x0F, 0x0F);
+    assert_eq!(apu.ch2.output(), 15);
+
+    apu.set_channel_enabled(2, true);
+    run(&mut apu, 200);
+    assert!(apu.get_audio_buffer().chunks_exact(2).all(|frame| frame[1] > 0));
+}
+
+#[test]
+fn test_output_sample_rate() {
+    for rate in [48000, 44100, 32000] {
+        let mut apu = Apu::new(rate);
+        apu.write(0xFF25, 0x22);
+        trigger_steady_ch2(&mut apu);
+
+        // One emulated second, drained before the buffer fills
+        let mut frames = 0;
+        for _ in 0..CPU_CLOCK / 4096 {
+            run(&mut apu, 4096);
+            frames += apu.get_audio_buffer().len() / 2;
+        }
+        assert!(frames.abs_diff(rate as usize) <= 1, "{} Hz: {} frames", rate, frames);
+    }
+}
+
+#[test]
+fn test_sample_buffer_overwrites_oldest_when_full() {
+    let mut apu = Apu::default();
+    apu.write(0xFF25, 0x22);
+    trigger_steady_ch2(&mut apu);
+    let mut reference = apu.clone();
+    reference.set_sample_capacity(1 << 16);
+    apu.set_sample_capacity(64);
+    assert_eq!(apu.sample_capacity(), 64);
+
+    run(&mut apu, 20_000);
+    run(&mut reference, 20_000);
+
+    // The newest 32 frames are kept
+    let all = reference.get_audio_buffer();
+    assert!(all.len() > 64);
+    assert_eq!(apu.available_samples(), 64);
+    let mut out = [0; 40];
+    assert_eq!(apu.read_samples(&mut out), 40);
+    assert_eq!(out[..], all[all.len() - 64..all.len() - 24]);
+    assert_eq!(apu.read_samples(&mut out), 24);
+    assert_eq!(out[..24], all[all.len() - 24..]);
+    assert_eq!(apu.available_samples(), 0);
+}
+
+#[test]
+fn test_frame_sequencer_length_and_envelope_steps() {
+    let mut apu = Apu::default();
+    // Channel 1: envelope only (volume 15, decrease, period 1)
+    apu.write(0xFF11, 0x80);
+    apu.write(0xFF12, 0xF1);
+    apu.write(0xFF14, 0x80);
+    // Channel 2: length 4 with length enabled
+    apu.write(0xFF16, 0x80 | 60);
+    apu.write(0xFF17, 0xF0);
+    apu.write(0xFF19, 0xC0);
+
+    // Steps 0, 2 and 4 clock length; the envelope is untouched until step 7
+    clock_frame_sequencer(&mut apu, 6);
+    assert!(apu.ch2.enabled);
+    assert_eq!(apu.ch1.output(), 15);
+
+    // Step 6: fourth length clock
+    clock_frame_sequencer(&mut apu, 1);
+    assert!(!apu.ch2.enabled);
+    assert_eq!(apu.ch1.output(), 15);
+
+    // Step 7: envelope
+    clock_frame_sequencer(&mut apu, 1);
+    assert_eq!(apu.ch1.output(), 14);
+
+    // Only step 7 of the next round clocks the envelope again
+    clock_frame_sequencer(&mut apu, 7);
+    assert_eq!(apu.ch1.output(), 14);
+    clock_frame_sequencer(&mut apu, 1);
+    assert_eq!(apu.ch1.output(), 13);
+}
@@ -0,0 +1,145 @@
+//! OAM DMA Tests
+//!
+//! These tests drive the DMA controller through a ROM-less emulator whose
+//! WRAM is filled with known bytes, advancing components cycle by cycle.
+
+use gbemu::bus::MemoryBus;
+use gbemu::emu::Emulator;
+
+/// WRAM page used as the default DMA source
+const SOURCE_PAGE: u8 = 0xC1;
+/// Cycles before the first byte is copied
+const DMA_DELAY: u32 = 2;
+/// Bytes copied into OAM
+const OAM_SIZE: u32 = 160;
+
+/// Emulator running a blank 32KB ROM with distinct bytes in WRAM pages C1 and C2
+fn emulator() -> Emulator {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x134..0x138].copy_from_slice(b"DMA ");
+    let mut emu = Emulator::from_bytes(rom).unwrap();
+    for i in 0..0x100u16 {
+        emu.bus.write(0xC100 + i, i as u8 ^ 0x5A);
+        emu.bus.write(0xC200 + i, i as u8 ^ 0xA5);
+    }
+    emu
+}
+
+/// Expected OAM contents for a DMA from the given WRAM page
+fn source_bytes(emu: &Emulator, page: u8) -> Vec<u8> {
+    let base = (page as u16) << 8;
+    (0..OAM_SIZE as u16).map(|i| emu.bus.read(base + i)).collect()
+}
+
+/// Start a DMA by writing the DMA register
+fn start_dma(emu: &mut Emulator, page: u8) {
+    emu.bus.write(0xFF46, page);
+}
+
+#[test]
+fn test_dma_starts_after_delay() {
+    let mut emu = emulator();
+    let expected = source_bytes(&emu, SOURCE_PAGE);
+    start_dma(&mut emu, SOURCE_PAGE);
+
+    emu.advance_cycles(DMA_DELAY);
+    assert!(emu.dma.active);
+    assert_eq!(emu.dma.byte, 0);
+    assert_eq!(emu.bus.oam[0], 0x00);
+
+    emu.advance_cycles(1);
+    assert_eq!(emu.dma.byte, 1);
+    assert_eq!(emu.bus.oam[0], expected[0]);
+}
+
+#[test]
+fn test_only_hram_readable_during_dma() {
+    let mut emu = emulator();
+    emu.bus.write(0xFF80, 0x42);
+    emu.bus.write(0xFFFE, 0x24);
+    start_dma(&mut emu, SOURCE_PAGE);
+    emu.advance_cycles(DMA_DELAY + 10);
+    assert!(emu.bus.is_dma_active());
+
+    assert_eq!(emu.bus.read(0xFF80), 0x42);
+    assert_eq!(emu.bus.read(0xFFFE), 0x24);
+    for address in [0x0000, 0x0150, 0x8000, 0xA000, 0xC100, 0xE100, 0xFE00, 0xFF44, 0xFF46] {
+        assert_eq!(emu.bus.read(address), 0xFF, "read of {:04X} during DMA", address);
+    }
+
+    emu.advance_cycles(OAM_SIZE);
+    assert_eq!(emu.bus.read(0xC100), 0x5A);
+}
+
+#[test]
+fn test_dma_completes_after_162_cycles() {
+    let mut emu = emulator();
+    start_dma(&mut emu, SOURCE_PAGE);
+
+    emu.advance_cycles(DMA_DELAY + OAM_SIZE - 1);
+    assert!(emu.dma.active);
+    assert!(emu.bus.is_dma_active());
+
+    emu.advance_cycles(1);
+    assert!(!emu.dma.active);
+    assert!(!emu.bus.is_dma_active());
+}
+
+#[test]
+fn test_oam_matches_wram_source() {
+    let mut emu = emulator();
+    let expected = source_bytes(&emu, SOURCE_PAGE);
+    start_dma(&mut emu, SOURCE_PAGE);
+    emu.advance_cycles(DMA_DELAY + OAM_SIZE);
+
+    assert_eq!(emu.bus.oam.to_vec(), expected);
+    let oam: Vec<u8> = (0xFE00..0xFEA0).map(|a| emu.bus.read(a)).collect();
+    assert_eq!(oam, expected);
+}
+
+#[test]
+fn test_dma_restart_mid_transfer() {
+    let mut emu = emulator();
+    let expected = source_bytes(&emu, 0xC2);
+    start_dma(&mut emu, SOURCE_PAGE);
+    emu.advance_cycles(DMA_DELAY + 50);
+    assert_eq!(emu.dma.byte, 50);
+
+    start_dma(&mut emu, 0xC2);
+    emu.advance_cycles(1);
+    assert_eq!(emu.dma.value, 0xC2);
+    assert_eq!(emu.dma.byte, 0);
+    assert_eq!(emu.dma.delay, 1);
+
+    emu.advance_cycles(DMA_DELAY - 1 + OAM_SIZE);
+    assert!(!emu.dma.active);
+    assert_eq!(emu.bus.oam.to_vec(), expected);
+}
+
+#[test]
+fn test_dma_register_reads_last_source_page() {
+    let mut emu = emulator();
+    start_dma(&mut emu, SOURCE_PAGE);
+    emu.advance_cycles(DMA_DELAY + OAM_SIZE);
+
+    assert_eq!(emu.dma.read(), SOURCE_PAGE);
+    assert_eq!(emu.bus.read(0xFF46), SOURCE_PAGE);
+}
+
+#[test]
+fn test_echo_source_pages_remap_to_wram() {
+    let mut emu = emulator();
+    let expected = source_bytes(&emu, SOURCE_PAGE);
+    start_dma(&mut emu, SOURCE_PAGE + 0x20);
+    assert_eq!(emu.bus.read(0xFF46), SOURCE_PAGE + 0x20);
+    emu.advance_cycles(DMA_DELAY + OAM_SIZE);
+
+    assert_eq!(emu.bus.oam.to_vec(), expected);
+    assert_eq!(emu.bus.read(0xFF46), SOURCE_PAGE + 0x20);
+
+    // Pages that would otherwise hit OAM and I/O also read from WRAM
+    emu.dma.start(0xFE);
+    assert_eq!(emu.dma.source_address(), 0xDE00);
+    emu.dma.start(0xFF);
+    assert_eq!(emu.dma.source_address(), 0xDF00);
+}
@@ -0,0 +1,108 @@
+//! Frame Regression Tests
+//!
+//! These tests render a frame from an assembled ROM and compare it against
+//! a golden frame stored in `tests/golden`. Set `UPDATE_GOLDEN=1` to
+//! rewrite the golden files after an intended rendering change.
+
+use gbemu::bus::MemoryBus;
+use gbemu::cpu::asm;
+use gbemu::emu::Emulator;
+use gbemu::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Pixels allowed to differ from the golden frame
+const MAX_CHANGED_PIXELS: usize = 100;
+/// Frames run before capturing
+const SETTLE_FRAMES: u32 = 3;
+
+/// Background of alternating blank and checkered tiles
+const TILE_PATTERN: &[&str] = &[
+    "LD HL, 0xFF40",
+    "LD (HL), 0x00",    // LCD off while VRAM is written
+    "LD HL, 0x8010",    // Tile 1: 1-pixel checkerboard in color 3
+    "LD B, 4",
+    "TILE:",
+    "LD (HL), 0xAA",
+    "INC HL",
+    "LD (HL), 0xAA",
+    "INC HL",
+    "LD (HL), 0x55",
+    "INC HL",
+    "LD (HL), 0x55",
+    "INC HL",
+    "DEC B",
+    "JR NZ, TILE",
+    "LD HL, 0x9800",    // Every other map entry uses tile 1
+    "LD B, 0",
+    "MAP:",
+    "LD (HL), 1",
+    "INC HL",
+    "INC HL",
+    "DEC B",
+    "JR NZ, MAP",
+    "LD HL, 0xFF40",
+    "LD (HL), 0x91",    // LCD and background on
+    "DONE:",
+    "JR DONE",
+];
+
+/// Path of a golden frame
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.bin", name))
+}
+
+/// Run a program from the cartridge entry point and return the emulator
+fn render(program: &[&str]) -> Emulator {
+    let code = asm::assemble_with_labels(program, 0x0100).unwrap();
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    rom[0x134..0x13B].copy_from_slice(b"GOLDEN ");
+    let mut emu = Emulator::from_bytes(rom).unwrap();
+    for _ in 0..SETTLE_FRAMES {
+        emu.run_frame();
+    }
+    emu
+}
+
+/// Load a golden frame (little-endian ARGB8888), writing it first when
+/// `UPDATE_GOLDEN` is set
+fn golden_frame(name: &str, current: &[u32]) -> Vec<u32> {
+    let path = golden_path(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        let bytes: Vec<u8> = current.iter().flat_map(|p| p.to_le_bytes()).collect();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, bytes).unwrap();
+    }
+    let bytes = fs::read(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", path.display(), e));
+    bytes.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+}
+
+#[test]
+fn test_tile_pattern_matches_golden() {
+    let emu = render(TILE_PATTERN);
+    let frame = emu.get_video_buffer();
+    assert_eq!(frame.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    // Guard against a blank golden frame
+    assert!(frame.iter().any(|&p| p != frame[0]));
+
+    let golden = golden_frame("tile_pattern", frame);
+    let diff = emu.compare_frame(&golden);
+    assert!(diff.similar_enough(MAX_CHANGED_PIXELS), "{:?}", diff);
+}
+
+#[test]
+fn test_modified_frame_fails_comparison() {
+    let mut emu = render(TILE_PATTERN);
+    let golden = emu.get_video_buffer().to_vec();
+    assert!(emu.compare_frame(&golden).is_identical());
+
+    // Disabling the background blanks the checkered tiles
+    emu.bus.write(0xFF40, 0x80);
+    emu.run_frame();
+    let diff = emu.compare_frame(&golden);
+    assert!(!diff.similar_enough(MAX_CHANGED_PIXELS), "{:?}", diff);
+    assert_eq!(diff.max_channel_delta, 0xFF);
+}
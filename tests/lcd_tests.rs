@@ -0,0 +1,240 @@
+//! LCD Tests
+//!
+//! These tests drive the LCD registers the way the PPU does, stepping
+//! through the modes of every scanline with `set_mode` and `inc_ly`, and
+//! check the STAT events raised along the way.
+
+use gbemu::events::{EventQueue, HardwareEvent};
+use gbemu::lcd::{Lcd, PpuMode};
+
+/// Visible scanlines per frame
+const VISIBLE_LINES: u8 = 144;
+/// Scanlines per frame, including VBlank
+const LINES_PER_FRAME: u32 = 154;
+
+/// Drain the queue, recording the LY and mode each STAT event fired at
+fn drain(lcd: &Lcd, events: &mut EventQueue, fired: &mut Vec<(u8, PpuMode)>) {
+    let before = fired.len();
+    while let Some(event) = events.pop() {
+        assert_eq!(event, HardwareEvent::LcdStat);
+        fired.push((lcd.ly, lcd.mode()));
+    }
+    assert!(fired.len() - before <= 1, "one transition raised several STAT events");
+}
+
+/// Run one frame of mode transitions starting from LY=0, returning the STAT events
+fn run_frame(lcd: &mut Lcd) -> Vec<(u8, PpuMode)> {
+    let mut events = EventQueue::new();
+    let mut fired = Vec::new();
+
+    for _ in 0..VISIBLE_LINES {
+        for mode in [PpuMode::OamScan, PpuMode::Transfer, PpuMode::HBlank] {
+            lcd.set_mode(mode, &mut events);
+            drain(lcd, &mut events, &mut fired);
+        }
+        lcd.inc_ly(&mut events);
+        drain(lcd, &mut events, &mut fired);
+    }
+
+    assert_eq!(lcd.ly, VISIBLE_LINES);
+    lcd.set_mode(PpuMode::VBlank, &mut events);
+    drain(lcd, &mut events, &mut fired);
+    for _ in VISIBLE_LINES as u32..LINES_PER_FRAME {
+        lcd.inc_ly(&mut events);
+        drain(lcd, &mut events, &mut fired);
+    }
+
+    fired
+}
+
+/// LCD with only the given STAT interrupt sources enabled
+fn lcd_with_sources(sources: u8) -> Lcd {
+    let mut lcd = Lcd::new();
+    lcd.write(0xFF41, sources, &mut EventQueue::new());
+    lcd
+}
+
+#[test]
+fn test_no_stat_events_without_sources() {
+    let mut lcd = lcd_with_sources(0x00);
+    assert!(run_frame(&mut lcd).is_empty());
+}
+
+#[test]
+fn test_hblank_source_fires_every_visible_line() {
+    let mut lcd = lcd_with_sources(0x08);
+    let fired = run_frame(&mut lcd);
+
+    assert_eq!(fired.len(), VISIBLE_LINES as usize);
+    for (line, &(ly, mode)) in fired.iter().enumerate() {
+        assert_eq!((ly, mode), (line as u8, PpuMode::HBlank));
+    }
+}
+
+#[test]
+fn test_vblank_source_fires_once_at_line_144() {
+    let mut lcd = lcd_with_sources(0x10);
+    assert_eq!(run_frame(&mut lcd), [(144, PpuMode::VBlank)]);
+}
+
+#[test]
+fn test_oam_source_fires_every_visible_line() {
+    let mut lcd = lcd_with_sources(0x20);
+    let fired = run_frame(&mut lcd);
+
+    assert_eq!(fired.len(), VISIBLE_LINES as usize);
+    for (line, &(ly, mode)) in fired.iter().enumerate() {
+        assert_eq!((ly, mode), (line as u8, PpuMode::OamScan));
+    }
+}
+
+#[test]
+fn test_lyc_fires_once_per_frame() {
+    let mut lcd = lcd_with_sources(0x40);
+    lcd.write(0xFF45, 50, &mut EventQueue::new());
+
+    for _ in 0..3 {
+        assert_eq!(run_frame(&mut lcd), [(50, PpuMode::HBlank)]);
+    }
+}
+
+#[test]
+fn test_lyc_in_vblank_and_on_wrap() {
+    let mut lcd = lcd_with_sources(0x40);
+    lcd.write(0xFF45, 150, &mut EventQueue::new());
+    assert_eq!(run_frame(&mut lcd), [(150, PpuMode::VBlank)]);
+
+    lcd.write(0xFF45, 0, &mut EventQueue::new());
+    assert_eq!(run_frame(&mut lcd), [(0, PpuMode::VBlank)]);
+}
+
+#[test]
+fn test_lyc_flag_tracks_ly() {
+    let mut lcd = Lcd::new();
+    let mut events = EventQueue::new();
+    lcd.write(0xFF45, 10, &mut events);
+
+    for _ in 0..LINES_PER_FRAME {
+        assert_eq!(lcd.lyc_flag(), lcd.ly == 10);
+        lcd.inc_ly(&mut events);
+    }
+    assert!(events.is_empty());
+}
+
+#[test]
+fn test_ly_144_enters_vblank_and_154_wraps() {
+    let mut lcd = Lcd::new();
+    run_frame(&mut lcd);
+
+    // The frame ends in VBlank with LY wrapped back to 0
+    assert_eq!(lcd.ly, 0);
+    assert_eq!(lcd.mode(), PpuMode::VBlank);
+
+    let mut events = EventQueue::new();
+    lcd.set_ly(153, &mut events);
+    lcd.inc_ly(&mut events);
+    assert_eq!(lcd.ly, 0);
+    assert_eq!(lcd.read(0xFF44), 0);
+}
+
+#[test]
+fn test_all_sources_fire_once_per_transition() {
+    let mut lcd = lcd_with_sources(0x78);
+    lcd.write(0xFF45, 100, &mut EventQueue::new());
+    let fired = run_frame(&mut lcd);
+
+    // Mode 2 and mode 0 on every visible line, mode 1 once and LYC once;
+    // `drain` checks no single transition raised more than one event
+    let visible = VISIBLE_LINES as usize;
+    assert_eq!(fired.len(), 2 * visible + 2);
+    assert_eq!(fired.iter().filter(|e| e.1 == PpuMode::OamScan).count(), visible);
+    assert!(fired.contains(&(100, PpuMode::HBlank)));
+    assert!(fired.contains(&(144, PpuMode::VBlank)));
+    assert!(!fired.iter().any(|e| e.1 == PpuMode::Transfer));
+}
+
+#[test]
+fn test_stat_low_bits_are_read_only() {
+    let mut lcd = Lcd::new();
+    let mut events = EventQueue::new();
+    lcd.write(0xFF45, 7, &mut events);
+    lcd.set_ly(7, &mut events);
+    lcd.set_mode(PpuMode::VBlank, &mut events);
+
+    for value in [0x00, 0x07, 0xFF, 0x7A] {
+        lcd.write(0xFF41, value, &mut events);
+        assert_eq!(lcd.mode(), PpuMode::VBlank);
+        assert!(lcd.lyc_flag());
+        assert_eq!(lcd.read(0xFF41) & 0x07, 0x05);
+        assert_eq!(lcd.read(0xFF41) & 0x78, value & 0x78);
+    }
+}
+
+#[test]
+fn test_stat_bit_7_always_reads_set() {
+    let mut lcd = Lcd::new();
+    let mut events = EventQueue::new();
+
+    for value in [0x00, 0x78, 0x7F, 0xFF] {
+        lcd.write(0xFF41, value, &mut events);
+        for mode in [PpuMode::HBlank, PpuMode::VBlank, PpuMode::OamScan, PpuMode::Transfer] {
+            lcd.set_mode(mode, &mut events);
+            assert_eq!(lcd.read(0xFF41) & 0x80, 0x80);
+        }
+    }
+}
+
+#[test]
+fn test_lcdc_bits_are_independent() {
+    let mut lcd = Lcd::new();
+    let mut events = EventQueue::new();
+
+    for bit in 0..8 {
+        lcd.write(0xFF40, 1 << bit, &mut events);
+        assert_eq!(lcd.read(0xFF40), 1 << bit);
+        assert_eq!(lcd.bg_window_enabled(), bit == 0);
+        assert_eq!(lcd.sprites_enabled(), bit == 1);
+        assert_eq!(lcd.sprite_height(), if bit == 2 { 16 } else { 8 });
+        assert_eq!(lcd.bg_tile_map(), if bit == 3 { 0x9C00 } else { 0x9800 });
+        assert_eq!(lcd.bg_tile_data(), if bit == 4 { 0x8000 } else { 0x8800 });
+        assert_eq!(lcd.window_enabled(), bit == 5);
+        assert_eq!(lcd.window_tile_map(), if bit == 6 { 0x9C00 } else { 0x9800 });
+        assert_eq!(lcd.lcd_enabled(), bit == 7);
+    }
+}
+
+#[test]
+fn test_palettes_map_all_color_ids() {
+    let mut lcd = Lcd::new();
+    let mut events = EventQueue::new();
+    lcd.write(0xFF47, 0b00_01_10_11, &mut events);
+    lcd.write(0xFF48, 0b11_10_01_00, &mut events);
+    lcd.write(0xFF49, 0b01_11_00_10, &mut events);
+
+    let bgp: Vec<u8> = (0..4).map(|id| lcd.bg_color(id)).collect();
+    let obp0: Vec<u8> = (0..4).map(|id| lcd.sprite_color_0(id)).collect();
+    let obp1: Vec<u8> = (0..4).map(|id| lcd.sprite_color_1(id)).collect();
+    assert_eq!(bgp, [3, 2, 1, 0]);
+    assert_eq!(obp0, [0, 1, 2, 3]);
+    assert_eq!(obp1, [2, 0, 3, 1]);
+}
+
+#[test]
+fn test_disabling_lcd_resets_mode_and_ly() {
+    let mut lcd = Lcd::new();
+    let mut events = EventQueue::new();
+    lcd.set_ly(80, &mut events);
+    lcd.set_mode(PpuMode::Transfer, &mut events);
+
+    // Rewriting LCDC with the display still on leaves the position alone
+    lcd.write(0xFF40, 0x93, &mut events);
+    assert_eq!(lcd.ly, 80);
+    assert_eq!(lcd.mode(), PpuMode::Transfer);
+
+    lcd.write(0xFF40, 0x13, &mut events);
+    assert!(!lcd.lcd_enabled());
+    assert_eq!(lcd.read(0xFF44), 0);
+    assert_eq!(lcd.mode(), PpuMode::HBlank);
+    assert_eq!(lcd.read(0xFF41) & 0x03, 0);
+    assert!(events.is_empty());
+}